@@ -1,37 +1,110 @@
-#import bevy_pbr::mesh_vertex_output    MeshVertexOutput
+#import bevy_pbr::mesh_bindings         mesh
+#import bevy_pbr::mesh_functions        as mesh_functions
 #import bevy_pbr::mesh_view_bindings    view
 #import bevy_pbr::pbr_types             STANDARD_MATERIAL_FLAGS_DOUBLE_SIDED_BIT
 #import bevy_core_pipeline::tonemapping tone_mapping
 #import bevy_pbr::pbr_functions as fns
+#import mcrs::voxel_vertex as voxel_vertex
 
 @group(1) @binding(0)
 var my_array_texture: texture_2d_array<f32>;
 @group(1) @binding(1)
 var my_array_texture_sampler: sampler;
+@group(1) @binding(2)
+var<uniform> underwater: vec4<f32>; // x 1 with the camera in water, y seconds, z surface height
+@group(1) @binding(3)
+var<uniform> light_bounce: vec4<f32>; // rgb bounce color, w strength
+
+const GLOW_BIT: u32 = 0x10000u; // set on the layer of glowing blocks, see `voxel::GLOW_BIT`
+const BOUNCE_SHIFT: u32 = 12u; // see `voxel::light::BOUNCE_SHIFT`
+const MAX_LIGHT: f32 = 15.0; // see `voxel::MAX_LIGHT`
+const CAUSTICS_RANGE: f32 = 16.0; // blocks from the camera that catch caustics
+const CAUSTICS_STRENGTH: f32 = 0.6;
+
+struct Vertex {
+    @location(0) packed: vec2<u32>, // see `voxel::ATTRIBUTE_PACKED_VERTEX`
+    @location(1) light: u32,        // see `voxel::ATTRIBUTE_VOXEL_LIGHT`
+};
+
+struct VoxelVertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) world_position: vec4<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) @interpolate(flat) layer: u32,
+    @location(4) block_light: vec3<f32>, // blended between the corners of the face
+    @location(5) bounce_light: vec3<f32>, // bounced onto the face by the surfaces around
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VoxelVertexOutput {
+    var out: VoxelVertexOutput;
+    let position = voxel_vertex::unpack_position(vertex.packed);
+    out.world_position =
+        mesh_functions::mesh_position_local_to_world(mesh.model, vec4<f32>(position, 1.0));
+    out.position = mesh_functions::mesh_position_world_to_clip(out.world_position);
+    out.world_normal =
+        mesh_functions::mesh_normal_local_to_world(voxel_vertex::unpack_normal(vertex.packed));
+    out.uv = voxel_vertex::unpack_uv(vertex.packed);
+    out.layer = voxel_vertex::unpack_layer(vertex.packed);
+    out.block_light = voxel_vertex::unpack_light(vertex.light) / MAX_LIGHT;
+    out.bounce_light = voxel_vertex::unpack_light(vertex.light >> BOUNCE_SHIFT) / MAX_LIGHT;
+    return out;
+}
+
+// Bright lines where two sets of crossing waves meet, drifting over time
+fn caustics(p: vec2<f32>, time: f32) -> f32 {
+    let a = sin(p.x * 1.7 + time * 1.3) + sin(p.y * 2.1 - time * 1.1)
+        + sin((p.x + p.y) * 1.3 + time * 0.7);
+    let b = sin(p.x * 2.3 - time * 0.9) + sin(p.y * 1.5 + time * 1.7)
+        + sin((p.x - p.y) * 1.9 - time * 1.2);
+    return pow(clamp(1.0 - abs(a - b) / 3.0, 0.0, 1.0), 6.0);
+}
 
 @fragment
 fn fragment(
     @builtin(front_facing) is_front: bool,
-    mesh: MeshVertexOutput,
+    in: VoxelVertexOutput,
 ) -> @location(0) vec4<f32> {
-    var layer = 1;
-    if mesh.world_position.y > 80.0 {
-        layer = 0;
-    }
-
     // Prepare a 'processed' StandardMaterial by sampling all textures to resolve
     // the material members
     var pbr_input: fns::PbrInput = fns::pbr_input_new();
 
-    pbr_input.material.base_color = textureSample(my_array_texture, my_array_texture_sampler, mesh.uv, layer);
-#ifdef VERTEX_COLORS
-    pbr_input.material.base_color = pbr_input.material.base_color * mesh.color;
-#endif
+    let layer = in.layer & (GLOW_BIT - 1u);
+    pbr_input.material.base_color = textureSample(my_array_texture, my_array_texture_sampler, in.uv, layer);
+    // alpha test for glass and leaves, opaque layers are fully opaque
+    if pbr_input.material.base_color.a < 0.5 {
+        discard;
+    }
+
+    // one bounce of block light off the surfaces around, traced when the chunk was meshed
+    pbr_input.material.emissive = vec4<f32>(
+        pbr_input.material.base_color.rgb * light_bounce.rgb * light_bounce.w * in.bounce_light,
+        1.0,
+    );
+    // the colored light of lamps and fire nearby
+    pbr_input.material.emissive = vec4<f32>(
+        pbr_input.material.emissive.rgb + pbr_input.material.base_color.rgb * in.block_light,
+        1.0,
+    );
+    // caustics on the submerged surfaces around a camera in water, brightest on the ones facing up
+    if underwater.x > 0.0 && in.world_position.y < underwater.z {
+        let distance = length(in.world_position.xyz - view.world_position);
+        let fade = 1.0 - smoothstep(CAUSTICS_RANGE * 0.5, CAUSTICS_RANGE, distance);
+        let facing_up = 0.3 + 0.7 * max(normalize(in.world_normal).y, 0.0);
+        let light = caustics(in.world_position.xz, underwater.y) * fade * facing_up;
+        let caustic = pbr_input.material.base_color.rgb * light * CAUSTICS_STRENGTH;
+        pbr_input.material.emissive = vec4<f32>(pbr_input.material.emissive.rgb + caustic, 1.0);
+    }
+    // glowing blocks light themselves
+    if (in.layer & GLOW_BIT) != 0u {
+        pbr_input.material.emissive = vec4<f32>(pbr_input.material.base_color.rgb, 1.0);
+    }
 
-    pbr_input.frag_coord = mesh.position;
-    pbr_input.world_position = mesh.world_position;
+    pbr_input.frag_coord = in.position;
+    pbr_input.world_position = in.world_position;
     pbr_input.world_normal = fns::prepare_world_normal(
-        mesh.world_normal,
+        in.world_normal,
         (pbr_input.material.flags & STANDARD_MATERIAL_FLAGS_DOUBLE_SIDED_BIT) != 0u,
         is_front,
     );
@@ -40,16 +113,11 @@ fn fragment(
 
     pbr_input.N = fns::apply_normal_mapping(
         pbr_input.material.flags,
-        mesh.world_normal,
-#ifdef VERTEX_TANGENTS
-#ifdef STANDARDMATERIAL_NORMAL_MAP
-        mesh.world_tangent,
-#endif
-#endif
-        mesh.uv,
+        in.world_normal,
+        in.uv,
         view.mip_bias,
     );
-    pbr_input.V = fns::calculate_view(mesh.world_position, pbr_input.is_orthographic);
+    pbr_input.V = fns::calculate_view(in.world_position, pbr_input.is_orthographic);
 
     return tone_mapping(fns::pbr(pbr_input), view.color_grading);
-}
\ No newline at end of file
+}
@@ -0,0 +1,52 @@
+#import bevy_pbr::prepass_bindings
+#import bevy_pbr::mesh_functions as mesh_functions
+#import bevy_pbr::mesh_bindings mesh
+#import mcrs::voxel_vertex as voxel_vertex
+
+// Depth, normal and shadow passes of the voxel meshes: bevy's prepass vertex shader,
+// reading the packed vertices. The fragment stage is bevy's
+
+struct Vertex {
+    @location(0) packed: vec2<u32>,
+};
+
+// matches bevy_pbr's prepass VertexOutput
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+#ifdef NORMAL_PREPASS
+    @location(1) world_normal: vec3<f32>,
+#endif
+#ifdef MOTION_VECTOR_PREPASS
+    @location(3) world_position: vec4<f32>,
+    @location(4) previous_world_position: vec4<f32>,
+#endif
+#ifdef DEPTH_CLAMP_ORTHO
+    @location(5) clip_position_unclamped: vec4<f32>,
+#endif
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    let position = vec4<f32>(voxel_vertex::unpack_position(vertex.packed), 1.0);
+
+    out.clip_position = mesh_functions::mesh_position_local_to_clip(mesh.model, position);
+#ifdef DEPTH_CLAMP_ORTHO
+    out.clip_position_unclamped = out.clip_position;
+    out.clip_position.z = min(out.clip_position.z, 1.0);
+#endif
+
+#ifdef NORMAL_PREPASS
+    out.world_normal = mesh_functions::mesh_normal_local_to_world(
+        voxel_vertex::unpack_normal(vertex.packed),
+    );
+#endif
+
+#ifdef MOTION_VECTOR_PREPASS
+    out.world_position = mesh_functions::mesh_position_local_to_world(mesh.model, position);
+    out.previous_world_position =
+        mesh_functions::mesh_position_local_to_world(mesh.previous_model, position);
+#endif
+
+    return out;
+}
@@ -0,0 +1,71 @@
+#import bevy_pbr::mesh_bindings      mesh
+#import bevy_pbr::mesh_functions     as mesh_functions
+
+// Faces of the experimental instanced renderer, see `src/instanced.rs`
+
+@group(2) @binding(0)
+var face_texture: texture_2d_array<f32>;
+@group(2) @binding(1)
+var face_sampler: sampler;
+
+const GLOW_BIT: u32 = 0x10000u; // see `voxel::GLOW_BIT`
+const SUN: vec3<f32> = vec3<f32>(0.3, 0.8, 0.5);
+
+struct Vertex {
+    @location(0) corner: vec3<f32>, // of a unit quad in xy
+    @location(3) face: vec2<u32>,   // see `voxel::face_instances`
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) layer: u32,
+    @location(2) light: f32,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let voxel = vec3<f32>(
+        f32(vertex.face.x & 0xfu),
+        f32((vertex.face.x >> 8u) & 0xffu),
+        f32((vertex.face.x >> 4u) & 0xfu),
+    );
+    let direction = (vertex.face.x >> 16u) & 0x7u; // +x, +y, +z, -x, -y, -z
+    let axis = direction % 3u;
+    let positive = direction < 3u;
+
+    // the quad on the two other axes, mirrored on the negative faces so they face out too
+    var corner = vertex.corner.xy;
+    if !positive {
+        corner = corner.yx;
+    }
+    var local = vec3<f32>(0.0);
+    local[axis] = select(0.0, 1.0, positive);
+    local[(axis + 1u) % 3u] = corner.x;
+    local[(axis + 2u) % 3u] = corner.y;
+
+    var out: VertexOutput;
+    out.clip_position =
+        mesh_functions::mesh_position_local_to_clip(mesh.model, vec4<f32>(voxel + local, 1.0));
+    out.uv = vec2<f32>(local.x, local.z);
+    if axis == 0u {
+        out.uv = vec2<f32>(local.z, 1.0 - local.y);
+    } else if axis == 2u {
+        out.uv = vec2<f32>(local.x, 1.0 - local.y);
+    }
+    out.layer = vertex.face.y;
+
+    var normal = vec3<f32>(0.0);
+    normal[axis] = select(-1.0, 1.0, positive);
+    out.light = 0.55 + 0.45 * max(dot(normal, normalize(SUN)), 0.0);
+    if (out.layer & GLOW_BIT) != 0u {
+        out.light = 1.0;
+    }
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(face_texture, face_sampler, in.uv, in.layer & (GLOW_BIT - 1u));
+    return vec4<f32>(color.rgb * in.light, 1.0);
+}
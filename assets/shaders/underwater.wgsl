@@ -0,0 +1,32 @@
+// Full-screen pass while the camera is in water, see `underwater::UnderwaterPlugin`
+#import bevy_core_pipeline::fullscreen_vertex_shader FullscreenVertexOutput
+
+@group(0) @binding(0)
+var screen_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var screen_sampler: sampler;
+
+struct UnderwaterPass {
+    time: f32, // seconds, for the waves
+    _webgl2_padding: vec3<f32>,
+};
+@group(0) @binding(2)
+var<uniform> settings: UnderwaterPass;
+
+const WAVE: f32 = 0.004; // of the screen, how far the waves shift the picture
+const WATER_TINT: vec3<f32> = vec3<f32>(0.55, 0.8, 1.0); // graded toward blue-green
+const WATER_SATURATION: f32 = 0.6;
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    // the picture waves as if seen through moving water
+    let uv = in.uv + WAVE * vec2<f32>(
+        sin(in.uv.y * 24.0 + settings.time * 2.0),
+        cos(in.uv.x * 18.0 + settings.time * 1.7),
+    );
+    let uv_in_screen = clamp(uv, vec2<f32>(0.0), vec2<f32>(1.0));
+    let color = textureSample(screen_texture, screen_sampler, uv_in_screen);
+    // washed out a little and graded toward the blue-green of the water
+    let luma = dot(color.rgb, vec3<f32>(0.299, 0.587, 0.114));
+    return vec4<f32>(mix(vec3<f32>(luma), color.rgb, WATER_SATURATION) * WATER_TINT, color.a);
+}
@@ -0,0 +1,45 @@
+#define_import_path mcrs::voxel_vertex
+
+// Decoding of `voxel::ATTRIBUTE_PACKED_VERTEX`, see `voxel::pack_vertex`
+
+const POSITION_STEPS: f32 = 16.0;
+const POSITION_BIAS: f32 = 1.0;
+
+fn unpack_position(packed: vec2<u32>) -> vec3<f32> {
+    let x = packed.x & 0x1ffu;
+    let z = (packed.x >> 9u) & 0x1ffu;
+    let y = packed.x >> 18u;
+    return vec3<f32>(f32(x), f32(y), f32(z)) / POSITION_STEPS - POSITION_BIAS;
+}
+
+// index into `voxel::NORMALS`: +x, +y, +z, -x, -y, -z
+fn unpack_normal(packed: vec2<u32>) -> vec3<f32> {
+    let index = (packed.y >> 17u) & 0x7u;
+    var normal = vec3<f32>(0.0);
+    normal[index % 3u] = select(1.0, -1.0, index >= 3u);
+    return normal;
+}
+
+// index into `voxel::UVS`: (1, 0), (0, 0), (0, 1), (1, 1)
+fn unpack_uv(packed: vec2<u32>) -> vec2<f32> {
+    let corner = (packed.y >> 20u) & 0x3u;
+    return vec2<f32>(
+        select(0.0, 1.0, corner == 0u || corner == 3u),
+        select(0.0, 1.0, corner >= 2u),
+    );
+}
+
+// texture layer, with the glow bit
+fn unpack_layer(packed: vec2<u32>) -> u32 {
+    return packed.y & 0x1ffffu;
+}
+
+// block light, 4 bits per channel, see `voxel::ATTRIBUTE_VOXEL_LIGHT`. The light bounced onto
+// the face is packed the same way above it, at `voxel::light::BOUNCE_SHIFT`
+fn unpack_light(light: u32) -> vec3<f32> {
+    return vec3<f32>(
+        f32(light & 0xfu),
+        f32((light >> 4u) & 0xfu),
+        f32((light >> 8u) & 0xfu),
+    );
+}
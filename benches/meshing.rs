@@ -0,0 +1,84 @@
+//! Mesher benchmarks, `cargo bench --bench meshing`, on the chunks it sees most and the
+//! one it handles worst: empty, full, the terrain surface and a 3d checkerboard.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use mcrs::{
+    combine_meshes, greedy_meshing, BlockId, ChunkData, ChunkIndex, ChunkLight, ChunkNeighbourhood,
+    WorldGenSettings, AIR, CHUNK_LIMIT_Y, STONE,
+};
+
+fn filled(fill: impl Fn(usize, usize, usize) -> BlockId) -> ChunkData {
+    let mut chunk = ChunkData::default();
+    for (x, plane) in chunk.voxels.iter_mut().enumerate() {
+        for (y, row) in plane.iter_mut().enumerate() {
+            for (z, voxel) in row.iter_mut().enumerate() {
+                *voxel = fill(x, y, z);
+            }
+        }
+    }
+    chunk.states.clear();
+    chunk
+}
+
+/// The generated chunk at the origin with both ground and air in it
+fn surface() -> ChunkData {
+    let settings = WorldGenSettings::default();
+    (0..CHUNK_LIMIT_Y as i32)
+        .map(|y| ChunkData::new(ChunkIndex { x: 0, y, z: 0 }, &settings))
+        .find(|chunk| {
+            let voxels = chunk.voxels.iter().flatten().flatten();
+            voxels.clone().any(|&voxel| voxel == AIR) && voxels.clone().any(|&voxel| voxel != AIR)
+        })
+        .expect("the origin column has a surface")
+}
+
+fn chunks() -> [(&'static str, ChunkData); 4] {
+    [
+        ("empty", filled(|_, _, _| AIR)),
+        ("full", filled(|_, _, _| STONE)),
+        ("surface", surface()),
+        (
+            "checkerboard",
+            filled(|x, y, z| if (x + y + z) % 2 == 0 { STONE } else { AIR }),
+        ),
+    ]
+}
+
+fn generation(c: &mut Criterion) {
+    let settings = WorldGenSettings::default();
+    let surface = surface().index;
+    c.bench_function("ChunkData::new surface", |b| {
+        b.iter(|| ChunkData::new(black_box(surface), &settings))
+    });
+    let sky = ChunkIndex {
+        y: CHUNK_LIMIT_Y as i32 - 1,
+        ..surface
+    };
+    c.bench_function("ChunkData::new sky", |b| {
+        b.iter(|| ChunkData::new(black_box(sky), &settings))
+    });
+}
+
+fn meshing(c: &mut Criterion) {
+    for (name, chunk) in chunks() {
+        let light = ChunkLight::of(&ChunkNeighbourhood::lone(&chunk));
+        c.bench_function(&format!("greedy_meshing {}", name), |b| {
+            b.iter(|| greedy_meshing(black_box(&chunk), &light, false))
+        });
+    }
+}
+
+fn combining(c: &mut Criterion) {
+    // a full column of chunks, like `combine_sub_meshes` builds
+    for (name, chunk) in chunks() {
+        let light = ChunkLight::of(&ChunkNeighbourhood::lone(&chunk));
+        let mesh = greedy_meshing(&chunk, &light, false);
+        let column = vec![mesh; CHUNK_LIMIT_Y];
+        c.bench_function(&format!("combine_meshes {}", name), |b| {
+            b.iter(|| combine_meshes(black_box(&column)))
+        });
+    }
+}
+
+criterion_group!(benches, generation, meshing, combining);
+criterion_main!(benches);
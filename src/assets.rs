@@ -0,0 +1,177 @@
+//! Built-in stand-ins for the files under `assets/`, so a missing texture or
+//! font shows up as an on-screen error instead of a stuck or broken game.
+
+use bevy::{
+    asset::LoadState,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+const FALLBACK_FONT: &[u8] = include_bytes!("../assets/fonts/FiraSans-Bold.ttf");
+const FALLBACK_TEXTURE_SIZE: u32 = 16;
+/// Average color of each layer of `textures/array_texture.png`: grass, dirt, snow, stone, glass,
+/// leaves, farmland, the four stages of wheat, fire and log
+pub(crate) const LAYER_COLORS: [[u8; 3]; 28] = [
+    [86, 140, 52],
+    [120, 84, 48],
+    [230, 236, 242],
+    [128, 122, 116],
+    [196, 220, 228],
+    [48, 110, 36],
+    [58, 37, 20],
+    [51, 141, 31],
+    [73, 153, 33],
+    [114, 159, 39],
+    [202, 169, 59],
+    [242, 135, 43],
+    [77, 55, 33],
+    [185, 76, 65],
+    [217, 205, 158],
+    [125, 116, 112],
+    [84, 6, 5],
+    [232, 36, 26],
+    [118, 107, 97],
+    [122, 106, 97],
+    [132, 128, 124],
+    [111, 85, 54],
+    [217, 186, 109],
+    [138, 99, 56],
+    [133, 94, 54],
+    [110, 100, 92],
+    [40, 92, 196],
+    [170, 88, 77],
+];
+
+/// Assets that failed to load and were replaced by a built-in fallback
+#[derive(Resource, Default)]
+pub struct AssetStatus {
+    pub missing: Vec<String>,
+}
+
+impl AssetStatus {
+    pub fn report_missing(&mut self, path: impl Into<String>) {
+        let path = path.into();
+        if !self.missing.contains(&path) {
+            error!("Asset {} is missing, using a built-in fallback", path);
+            self.missing.push(path);
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct FallbackFont {
+    pub handle: Handle<Font>,
+}
+
+#[derive(Component)]
+pub struct AssetErrorBanner;
+
+/// Rgba pixels of a flat, lightly checkered `size` by `size` layer
+pub(crate) fn fallback_layer(color: [u8; 3], size: u32) -> Vec<u8> {
+    let checker = (size / 4).max(1);
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let shade = if (x / checker + y / checker).is_multiple_of(2) {
+                0
+            } else {
+                16
+            };
+            data.extend(color.iter().map(|c| c.saturating_sub(shade)));
+            data.push(255);
+        }
+    }
+    data
+}
+
+/// Stacked 2d image with one flat, lightly checkered layer per block texture
+pub fn fallback_array_texture() -> Image {
+    let size = FALLBACK_TEXTURE_SIZE;
+    let layers = LAYER_COLORS.len() as u32;
+    let mut data = Vec::with_capacity((size * size * layers * 4) as usize);
+    for color in LAYER_COLORS.iter() {
+        data.extend(fallback_layer(*color, size));
+    }
+    Image::new(
+        Extent3d {
+            width: size,
+            height: size * layers,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+pub fn setup_fallback_assets(mut commands: Commands, mut fonts: ResMut<Assets<Font>>) {
+    let font = Font::try_from_bytes(FALLBACK_FONT.to_vec()).expect("embedded font is valid");
+    commands.insert_resource(FallbackFont {
+        handle: fonts.add(font),
+    });
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 20.0,
+                color: Color::RED,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(5.0),
+            left: Val::Px(5.0),
+            ..default()
+        }),
+        AssetErrorBanner,
+    ));
+}
+
+/// Swap fonts that failed to load for the embedded one
+pub fn use_fallback_fonts(
+    asset_server: Res<AssetServer>,
+    fallback_font: Res<FallbackFont>,
+    mut asset_status: ResMut<AssetStatus>,
+    mut text_query: Query<&mut Text>,
+) {
+    for mut text in text_query.iter_mut() {
+        let failed = |font: &Handle<Font>| {
+            *font != fallback_font.handle && asset_server.get_load_state(font) == LoadState::Failed
+        };
+        if !text
+            .sections
+            .iter()
+            .any(|section| failed(&section.style.font))
+        {
+            continue;
+        }
+        for section in text.sections.iter_mut() {
+            if failed(&section.style.font) {
+                if let Some(path) = asset_server.get_handle_path(&section.style.font) {
+                    asset_status.report_missing(path.path().display().to_string());
+                }
+                section.style.font = fallback_font.handle.clone();
+            }
+        }
+    }
+}
+
+pub fn show_asset_errors(
+    asset_status: Res<AssetStatus>,
+    fallback_font: Res<FallbackFont>,
+    mut banner_query: Query<&mut Text, With<AssetErrorBanner>>,
+) {
+    if !asset_status.is_changed() {
+        return;
+    }
+    for mut text in banner_query.iter_mut() {
+        text.sections[0].style.font = fallback_font.handle.clone();
+        text.sections[0].value = if asset_status.missing.is_empty() {
+            String::new()
+        } else {
+            format!("Missing assets: {}", asset_status.missing.join(", "))
+        };
+    }
+}
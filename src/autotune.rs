@@ -0,0 +1,195 @@
+use bevy::{
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    ecs::system::SystemParam,
+    prelude::*,
+};
+use bevy_inspector_egui::prelude::*;
+
+use crate::{voxel::VoxelSettings, GraphicsSettings};
+
+const SAMPLE_PERIOD: f32 = 2.0; // seconds of frames judged at once
+const STEP_UP_PERIODS: u8 = 3; // fast periods in a row before raising quality again
+const NOTICE_DURATION: f32 = 3.0;
+
+/// Bounds the auto quality preset stays within
+#[derive(Reflect, Resource, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub struct AutoTuneSettings {
+    pub target_fps: f32,
+    pub min_sight_range: u8,
+    pub max_sight_range: u8,
+    pub min_mesh_budget: u16,
+    pub max_mesh_budget: u16,
+}
+
+impl Default for AutoTuneSettings {
+    fn default() -> Self {
+        AutoTuneSettings {
+            target_fps: 60.0,
+            min_sight_range: 4,
+            max_sight_range: 16,
+            min_mesh_budget: 1,
+            max_mesh_budget: 8,
+        }
+    }
+}
+
+/// The sight range and mesh budget in use: the ones from the settings, lowered or raised by
+/// the auto quality preset while it's on. Kept apart from `VoxelSettings` so tuning is never saved
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct TunedQuality {
+    pub sight_range: u8,
+    pub column_mesh_budget: u16,
+}
+
+impl TunedQuality {
+    fn from_settings(voxel_settings: &VoxelSettings) -> Self {
+        TunedQuality {
+            sight_range: voxel_settings.sight_range,
+            column_mesh_budget: voxel_settings.column_mesh_budget,
+        }
+    }
+}
+
+impl Default for TunedQuality {
+    fn default() -> Self {
+        TunedQuality::from_settings(&VoxelSettings::default())
+    }
+}
+
+#[derive(Resource)]
+pub struct AutoTune {
+    timer: Timer,
+    fast_periods: u8,
+    shadows: bool,
+    notice_shown_at: Option<f32>,
+}
+
+impl Default for AutoTune {
+    fn default() -> Self {
+        AutoTune {
+            timer: Timer::from_seconds(SAMPLE_PERIOD, TimerMode::Repeating),
+            fast_periods: 0,
+            shadows: true,
+            notice_shown_at: None,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct AutoTuneNotice;
+
+/// The settings the auto quality preset starts from and stays within
+#[derive(SystemParam)]
+pub struct QualitySettings<'w> {
+    graphics: Res<'w, GraphicsSettings>,
+    bounds: Res<'w, AutoTuneSettings>,
+    voxel: Res<'w, VoxelSettings>,
+}
+
+pub fn setup_auto_tune_notice(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 20.0,
+                color: Color::NONE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(30.0), // above the missing assets banner
+            left: Val::Px(5.0),
+            ..default()
+        }),
+        AutoTuneNotice,
+    ));
+}
+
+/// Lower the cheapest settings first when the frame rate drops below the target,
+/// and raise them back in reverse order once it has been comfortably above for a while.
+/// Starts over from the settings whenever they change, and follows them while it's off
+pub fn auto_tune(
+    time: Res<Time>,
+    diagnostics: Res<DiagnosticsStore>,
+    settings: QualitySettings,
+    mut auto_tune: ResMut<AutoTune>,
+    mut tuned: ResMut<TunedQuality>,
+    mut light_query: Query<&mut PointLight>,
+    mut notice_query: Query<&mut Text, With<AutoTuneNotice>>,
+) {
+    if let (Ok(mut text), Some(shown_at)) =
+        (notice_query.get_single_mut(), auto_tune.notice_shown_at)
+    {
+        let remaining = NOTICE_DURATION - (time.raw_elapsed_seconds() - shown_at);
+        text.sections[0].style.color = Color::ORANGE.with_a(remaining.clamp(0.0, 1.0));
+    }
+
+    if settings.voxel.is_changed() || !settings.graphics.auto_quality {
+        let from_settings = TunedQuality::from_settings(&settings.voxel);
+        if *tuned != from_settings {
+            *tuned = from_settings;
+        }
+    }
+
+    if !settings.graphics.auto_quality || !auto_tune.timer.tick(time.raw_delta()).just_finished() {
+        return;
+    }
+    let Some(fps) = diagnostics
+        .get(FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.average())
+    else {
+        return;
+    };
+    let fps = fps as f32;
+    let bounds = &*settings.bounds;
+    let budget = tuned.column_mesh_budget;
+    let sight_range = tuned.sight_range;
+
+    let notice = if fps < bounds.target_fps * 0.9 {
+        auto_tune.fast_periods = 0;
+        let step = if budget > bounds.min_mesh_budget {
+            tuned.column_mesh_budget = (budget / 2).max(bounds.min_mesh_budget);
+            Some(format!("mesh budget to {}", tuned.column_mesh_budget))
+        } else if auto_tune.shadows {
+            auto_tune.shadows = false;
+            Some("shadows off".to_string())
+        } else if sight_range > bounds.min_sight_range {
+            tuned.sight_range = sight_range - 1;
+            Some(format!("sight range to {}", tuned.sight_range))
+        } else {
+            None
+        };
+        step.map(|step| format!("Auto quality: lowered {step} ({fps:.0} fps)"))
+    } else if fps > bounds.target_fps * 1.2 {
+        auto_tune.fast_periods += 1;
+        if auto_tune.fast_periods < STEP_UP_PERIODS {
+            return;
+        }
+        auto_tune.fast_periods = 0;
+        if sight_range < bounds.max_sight_range {
+            tuned.sight_range = sight_range + 1;
+        } else if !auto_tune.shadows {
+            auto_tune.shadows = true;
+        } else if budget < bounds.max_mesh_budget {
+            tuned.column_mesh_budget = (budget * 2).max(1).min(bounds.max_mesh_budget);
+        }
+        None
+    } else {
+        auto_tune.fast_periods = 0;
+        None
+    };
+
+    let shadows = auto_tune.shadows;
+    for mut light in light_query.iter_mut() {
+        if light.shadows_enabled != shadows {
+            light.shadows_enabled = shadows;
+        }
+    }
+
+    if let (Some(notice), Ok(mut text)) = (notice, notice_query.get_single_mut()) {
+        text.sections[0].value = notice;
+        auto_tune.notice_shown_at = Some(time.raw_elapsed_seconds());
+    }
+}
@@ -0,0 +1,36 @@
+//! Block entities: what a block keeps beyond its id and state, like the
+//! contents of a furnace or the text of a sign. They're stored with the chunk
+//! of their block and saved and loaded along with it. Placing a block that has
+//! one makes a new one, and replacing the block hands the old one out in a
+//! `BlockEntityRemoved`.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    furnace::Furnace,
+    sign::Sign,
+    voxel::{BlockId, VoxelPos, FURNACE, SIGN},
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BlockEntity {
+    Furnace(Furnace),
+    Sign(Sign),
+}
+
+/// The block entity a newly placed `block` starts with, if it has one
+pub fn new_entity(block: BlockId) -> Option<BlockEntity> {
+    match block {
+        FURNACE => Some(BlockEntity::Furnace(Furnace::default())),
+        SIGN => Some(BlockEntity::Sign(Sign::default())),
+        _ => None,
+    }
+}
+
+/// The block entity of a block that was just replaced, sent once the new block is in the world
+#[derive(Event, Debug, Clone)]
+pub struct BlockEntityRemoved {
+    pub position: VoxelPos,
+    pub entity: BlockEntity,
+}
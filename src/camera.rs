@@ -0,0 +1,127 @@
+//! Effects on top of the smooth camera controller: the base field of view,
+//! a wider one while sprinting, and the view bobbing up and down with each
+//! step while walking.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_inspector_egui::prelude::*;
+use serde::{Deserialize, Serialize};
+use smooth_bevy_cameras::controllers::fps::FpsCameraController;
+
+use crate::{
+    input::{InputAction, PlayerInput},
+    player::{CameraMode, Player},
+    MouseSettings,
+};
+
+const BOB_STRIDE: f32 = 2.4; // blocks walked per stride, a step with each foot
+const BOB_FULL_SPEED: f32 = 4.0; // blocks per second at which the bobbing is strongest
+const FOV_EASING: f32 = 8.0; // how quickly the sprint fov comes and goes, per second
+
+#[derive(Reflect, Resource, InspectorOptions, Serialize, Deserialize)]
+#[reflect(Resource, InspectorOptions)]
+#[serde(default)]
+pub struct CameraSettings {
+    pub fov: f32,      // vertical, in degrees
+    sprint_fov: f32,   // added to the fov while sprinting, in degrees
+    sprint_speed: f32, // multiplies the movement speed while sprinting
+    pub view_bobbing: bool,
+    bob_height: f32,     // in blocks, the sideways sway is half of it
+    pub cinematic: bool, // the free camera glides along a heavily smoothed path
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        CameraSettings {
+            fov: 45.0,
+            sprint_fov: 10.0,
+            sprint_speed: 1.6,
+            view_bobbing: true,
+            bob_height: 0.05,
+            cinematic: false,
+        }
+    }
+}
+
+/// Where the effects are at, carried from frame to frame
+#[derive(Default)]
+pub struct CameraEffects {
+    last_position: Option<Vec3>,
+    bob_phase: f32,  // radians, a full turn per stride
+    sprint_fov: f32, // degrees currently added to the fov
+}
+
+/// The settings the effects follow, and whether the camera is in third person
+#[derive(SystemParam)]
+pub struct CameraOptions<'w> {
+    mouse: Res<'w, MouseSettings>,
+    settings: Res<'w, CameraSettings>,
+    mode: Res<'w, CameraMode>,
+}
+
+/// Sprint while holding the sprint action, widening the fov, and bob the view while walking.
+/// Runs after the player was moved under the camera, so the bobbing doesn't move the player
+pub fn camera_effects(
+    time: Res<Time>,
+    player_input: Res<PlayerInput>,
+    options: CameraOptions,
+    player_query: Query<&Transform, (With<Player>, Without<FpsCameraController>)>,
+    mut camera_query: Query<(&mut Transform, &mut Projection, &mut FpsCameraController)>,
+    mut effects: Local<CameraEffects>,
+) {
+    let (Ok(player), Ok((mut transform, mut projection, mut controller))) =
+        (player_query.get_single(), camera_query.get_single_mut())
+    else {
+        return;
+    };
+    let dt = time.delta_seconds();
+    let moved = effects
+        .last_position
+        .replace(player.translation)
+        .map_or(Vec3::ZERO, |last| player.translation - last);
+    let walked = Vec2::new(moved.x, moved.z).length();
+    let speed = if dt > 0.0 { walked / dt } else { 0.0 };
+
+    let sprinting =
+        !options.mouse.ui_mode && player_input.actions.pressed(InputAction::Sprint) && walked > 0.0;
+    let translate_sensitivity = if sprinting {
+        options.mouse.speed * options.settings.sprint_speed
+    } else {
+        options.mouse.speed
+    };
+    if controller.enabled && controller.translate_sensitivity != translate_sensitivity {
+        controller.translate_sensitivity = translate_sensitivity;
+    }
+
+    let target_fov = if sprinting {
+        options.settings.sprint_fov
+    } else {
+        0.0
+    };
+    effects.sprint_fov += (target_fov - effects.sprint_fov) * (FOV_EASING * dt).min(1.0);
+    if (target_fov - effects.sprint_fov).abs() < 0.01 {
+        // settle, so the projection isn't touched every frame
+        effects.sprint_fov = target_fov;
+    }
+    let fov = (options.settings.fov + effects.sprint_fov).to_radians();
+    let changed = matches!(
+        projection.as_ref(),
+        Projection::Perspective(perspective) if perspective.fov != fov
+    );
+    if changed {
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            perspective.fov = fov;
+        }
+    }
+
+    if !options.settings.view_bobbing || options.mode.third_person {
+        return;
+    }
+    effects.bob_phase =
+        (effects.bob_phase + walked / BOB_STRIDE * std::f32::consts::TAU) % std::f32::consts::TAU;
+    let strength = (speed / BOB_FULL_SPEED).min(1.0) * options.settings.bob_height;
+    // lowest at each footfall, swaying from one foot to the other in between
+    let up = -effects.bob_phase.cos().abs() * strength;
+    let side = effects.bob_phase.sin() * strength / 2.0;
+    let offset = Vec3::Y * up + transform.right() * side;
+    transform.translation += offset;
+}
@@ -0,0 +1,104 @@
+//! Chat between players: lines typed into the console without a slash go out
+//! to the other players through the network layer, and the lines they send
+//! come back in the same way. The latest lines show over the game for a while,
+//! the console keeps them all.
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::console::Console;
+
+pub const CHAT_LENGTH_LIMIT: usize = 256; // characters in a line
+const HISTORY_LINES: usize = 100;
+const SHOWN_LINES: usize = 8;
+const SHOWN_SECONDS: f64 = 10.0; // lines fade from the screen after that long
+pub(crate) const LOCAL_NAME: &str = "You"; // how our own lines are shown
+
+/// A chat line we typed, to be broadcast by the network layer
+#[derive(Event, Debug, Clone)]
+pub struct ChatSent {
+    pub text: String,
+}
+
+/// A chat line from another player, sent in by the network layer
+#[derive(Event, Debug, Clone)]
+pub struct ChatReceived {
+    pub player: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChatLine {
+    pub player: String,
+    pub text: String,
+    pub at: f64, // elapsed seconds when it was sent or received
+}
+
+impl ChatLine {
+    pub fn format(&self) -> String {
+        format!("<{}> {}", self.player, self.text)
+    }
+}
+
+/// The latest chat lines, oldest first
+#[derive(Resource, Default)]
+pub struct ChatHistory {
+    pub lines: VecDeque<ChatLine>,
+}
+
+/// Keep the lines we sent and received, in the order they came
+pub fn record_chat(
+    time: Res<Time>,
+    mut sent_events: EventReader<ChatSent>,
+    mut received_events: EventReader<ChatReceived>,
+    mut history: ResMut<ChatHistory>,
+) {
+    let at = time.elapsed_seconds_f64();
+    let sent = sent_events.iter().map(|event| ChatLine {
+        player: LOCAL_NAME.to_string(),
+        text: event.text.clone(),
+        at,
+    });
+    let received = received_events.iter().map(|event| ChatLine {
+        player: event.player.clone(),
+        text: event.text.chars().take(CHAT_LENGTH_LIMIT).collect(),
+        at,
+    });
+    history.lines.extend(sent.chain(received));
+    let overflow = history.lines.len().saturating_sub(HISTORY_LINES);
+    history.lines.drain(..overflow);
+}
+
+/// The latest lines in the bottom left corner, while the console with all of them is closed
+pub fn chat_overlay(
+    mut contexts: EguiContexts,
+    time: Res<Time>,
+    console: Res<Console>,
+    history: Res<ChatHistory>,
+) {
+    let now = time.elapsed_seconds_f64();
+    let shown: Vec<&ChatLine> = history
+        .lines
+        .iter()
+        .rev()
+        .take(SHOWN_LINES)
+        .take_while(|line| now - line.at < SHOWN_SECONDS)
+        .collect();
+    if console.open || shown.is_empty() {
+        return;
+    }
+    egui::Area::new("chat")
+        .anchor(egui::Align2::LEFT_BOTTOM, egui::vec2(10.0, -60.0))
+        .interactable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            for line in shown.iter().rev() {
+                ui.label(
+                    egui::RichText::new(line.format())
+                        .color(egui::Color32::WHITE)
+                        .background_color(egui::Color32::from_black_alpha(128)),
+                );
+            }
+        });
+}
@@ -0,0 +1,161 @@
+//! Summaries of chunk and column state kept on their entities, so the world
+//! inspector shows what each chunk holds, how far along it is and what meshing
+//! it costs.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::voxel::{
+    self, BlockId, Chunk, ChunkColumn, ChunkData, ChunkIndex, ColumnMesh, TransparentPass,
+    VoxelData, VoxelMeshes, AIR, DIRT, GRASS, LEAVES, SNOW, STONE, STONE_SLAB, STONE_STAIRS,
+};
+
+/// How far along a chunk is, from spawned to drawn
+#[derive(Reflect, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkStage {
+    #[default]
+    Pending, // spawned, waiting for its data
+    Generated, // has data, its column mesh isn't up to date
+    Meshed,
+}
+
+#[derive(Component, Reflect, Default, PartialEq)]
+#[reflect(Component)]
+pub struct ChunkSummary {
+    pub biome: String,
+    pub stage: ChunkStage,
+    pub modified: bool, // edited since it was last saved
+    pub solid_blocks: u32,
+    pub entities: u32,   // other entities positioned inside the chunk
+    pub dirty: bool,     // waiting to be meshed again
+    pub vertices: u32,   // of its part of the column mesh
+    pub meshing_ms: f32, // how long it took to light and mesh last time
+}
+
+#[derive(Component, Reflect, Default, PartialEq)]
+#[reflect(Component)]
+pub struct ColumnMeshStats {
+    pub vertices: u32,
+    pub triangles: u32,
+    pub transparent_triangles: u32,
+    pub dirty: bool,
+    pub remesh_ms: f32, // how long its last update took, meshing and upload
+}
+
+/// Biome named after the most common block with air above it, and the number of solid blocks
+fn classify_chunk(chunk: &ChunkData) -> (&'static str, u32) {
+    let mut solid_blocks = 0;
+    let mut surface: HashMap<BlockId, u32> = HashMap::new();
+    for x in 0..voxel::CHUNK_SIZE {
+        for z in 0..voxel::CHUNK_SIZE {
+            for y in 0..voxel::CHUNK_SIZE {
+                let block = chunk.voxels[x][y][z];
+                if block == AIR {
+                    continue;
+                }
+                solid_blocks += 1;
+                if y + 1 == voxel::CHUNK_SIZE || chunk.voxels[x][y + 1][z] == AIR {
+                    *surface.entry(block).or_default() += 1;
+                }
+            }
+        }
+    }
+    let name = match surface.into_iter().max_by_key(|(_, count)| *count) {
+        Some((GRASS | DIRT, _)) => "grassland",
+        Some((SNOW, _)) => "snowfield",
+        Some((STONE | STONE_SLAB | STONE_STAIRS, _)) => "rocky",
+        Some((LEAVES, _)) => "forest",
+        Some(_) => "built",
+        None if solid_blocks > 0 => "underground",
+        None => "sky",
+    };
+    (name, solid_blocks)
+}
+
+/// Entities counted as inside a chunk: anything placed in the world but the chunks, their
+/// meshes and the UI
+type OtherEntities = (
+    Without<Chunk>,
+    Without<ColumnMesh>,
+    Without<TransparentPass>,
+    Without<Node>,
+);
+
+/// Refresh the summaries on chunk and column entities, the block counts only when a chunk
+/// moves between stages since that's when its data or mesh changed
+pub fn update_chunk_summaries(
+    voxel_data: Res<VoxelData>,
+    voxel_meshes: Res<VoxelMeshes>,
+    meshes: Res<Assets<Mesh>>,
+    column_query: Query<&ColumnMesh>,
+    mut chunk_query: Query<(&Chunk, &mut ChunkSummary)>,
+    mut stats_query: Query<(&ColumnMesh, &mut ColumnMeshStats)>,
+    others_query: Query<&GlobalTransform, OtherEntities>,
+) {
+    let mut entities: HashMap<ChunkIndex, u32> = HashMap::new();
+    for transform in others_query.iter() {
+        *entities
+            .entry(voxel::get_chunk_index(&transform.translation()))
+            .or_default() += 1;
+    }
+
+    for (chunk, mut summary) in chunk_query.iter_mut() {
+        let index = chunk.index;
+        let column_mesh = voxel_meshes
+            .columns
+            .get(&ChunkColumn {
+                x: index.x,
+                z: index.z,
+            })
+            .and_then(|entity| column_query.get(*entity).ok());
+        let meshed = column_mesh.is_some_and(|column_mesh| !column_mesh.dirty);
+        let sub_mesh = column_mesh.and_then(|column_mesh| column_mesh.sub_meshes.get(&index.y));
+        let stage = match voxel_data.chunk(index) {
+            None => ChunkStage::Pending,
+            Some(_) if !meshed => ChunkStage::Generated,
+            Some(_) => ChunkStage::Meshed,
+        };
+
+        let (biome, solid_blocks) = match voxel_data.chunk(index) {
+            Some(chunk_data) if stage != summary.stage => {
+                let (biome, solid_blocks) = classify_chunk(chunk_data);
+                (biome.to_string(), solid_blocks)
+            }
+            Some(_) => (summary.biome.clone(), summary.solid_blocks),
+            None => (String::new(), 0),
+        };
+        summary.set_if_neq(ChunkSummary {
+            biome,
+            stage,
+            modified: voxel_data.is_modified(index),
+            solid_blocks,
+            entities: entities.get(&index).copied().unwrap_or_default(),
+            dirty: column_mesh
+                .is_some_and(|column_mesh| column_mesh.dirty_chunks.contains(&index.y)),
+            vertices: sub_mesh.map_or(0, |sub_mesh| sub_mesh.vertices()),
+            meshing_ms: sub_mesh.map_or(0.0, |sub_mesh| sub_mesh.meshing_ms),
+        });
+    }
+
+    for (column_mesh, mut stats) in stats_query.iter_mut() {
+        let triangles = |handle: &Handle<Mesh>| {
+            meshes
+                .get(handle)
+                .and_then(|mesh| mesh.indices())
+                .map_or(0, |indices| indices.len() as u32 / 3)
+        };
+        let vertices = [&column_mesh.mesh, &column_mesh.transparent_mesh]
+            .into_iter()
+            .filter_map(|handle| meshes.get(handle))
+            .map(|mesh| mesh.count_vertices() as u32)
+            .sum();
+        stats.set_if_neq(ColumnMeshStats {
+            vertices,
+            triangles: triangles(&column_mesh.mesh),
+            transparent_triangles: triangles(&column_mesh.transparent_mesh),
+            dirty: column_mesh.dirty,
+            remesh_ms: column_mesh.remesh_ms,
+        });
+    }
+}
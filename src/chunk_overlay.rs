@@ -0,0 +1,109 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use smooth_bevy_cameras::controllers::fps::FpsCameraController;
+
+use crate::{
+    input::{InputAction, PlayerInput},
+    voxel::{
+        self, ChunkMeshesUpdateQueue, ColumnMesh, VoxelData, VoxelPos, CHUNK_SIZE, HEIGHT_LIMIT,
+    },
+    DebugSettings, MemoryUsage, StatsText,
+};
+
+const BOUNDARY_RANGE: i32 = 2; // chunk columns outlined around the camera in each direction
+const STATS_SECTION: usize = 12; // last section of the stats text the overlay writes to
+
+pub fn toggle_chunk_overlay(
+    player_input: Res<PlayerInput>,
+    mut debug_settings: ResMut<DebugSettings>,
+) {
+    if player_input.actions.just_pressed(InputAction::ChunkOverlay) {
+        debug_settings.chunk_overlay = !debug_settings.chunk_overlay;
+    }
+}
+
+/// What the streaming stats are counted from
+#[derive(SystemParam)]
+pub struct StreamingStats<'w, 's> {
+    voxel_data: Res<'w, VoxelData>,
+    chunk_meshes_update_queue: Res<'w, ChunkMeshesUpdateQueue>,
+    memory_usage: Res<'w, MemoryUsage>,
+    meshes: Res<'w, Assets<Mesh>>,
+    column_query: Query<'w, 's, &'static ColumnMesh>,
+}
+
+/// Outline the chunks around the camera and list streaming stats under the fps counter
+pub fn chunk_overlay(
+    debug_settings: Res<DebugSettings>,
+    stats: StreamingStats,
+    fps_camera_query: Query<&GlobalTransform, With<FpsCameraController>>,
+    mut text_query: Query<&mut Text, With<StatsText>>,
+    mut gizmos: Gizmos,
+) {
+    let Ok(mut text) = text_query.get_single_mut() else {
+        return;
+    };
+    if !debug_settings.chunk_overlay {
+        if !text.sections[STATS_SECTION].value.is_empty() {
+            text.sections[STATS_SECTION].value.clear();
+        }
+        return;
+    }
+    let Ok(transform) = fps_camera_query.get_single() else {
+        return;
+    };
+    let position = transform.translation();
+    let camera_chunk = voxel::get_chunk_index(&position);
+    let size = CHUNK_SIZE as f32;
+
+    // vertical lines on the column corners, and the chunk the camera is in
+    for x in -BOUNDARY_RANGE..=BOUNDARY_RANGE + 1 {
+        for z in -BOUNDARY_RANGE..=BOUNDARY_RANGE + 1 {
+            let corner_x = (camera_chunk.x + x) as f32 * size;
+            let corner_z = (camera_chunk.z + z) as f32 * size;
+            gizmos.line(
+                Vec3::new(corner_x, 0.0, corner_z),
+                Vec3::new(corner_x, HEIGHT_LIMIT as f32, corner_z),
+                Color::YELLOW,
+            );
+        }
+    }
+    let chunk_origin = camera_chunk.origin().as_vec3();
+    gizmos.cuboid(
+        Transform::from_translation(chunk_origin + Vec3::splat(size / 2.0))
+            .with_scale(Vec3::splat(size)),
+        Color::CYAN,
+    );
+
+    let StreamingStats {
+        voxel_data,
+        chunk_meshes_update_queue,
+        memory_usage,
+        meshes,
+        column_query,
+    } = &stats;
+    let dirty_columns = column_query.iter().filter(|column| column.dirty).count();
+    let triangles: usize = column_query
+        .iter()
+        .flat_map(|column| [&column.mesh, &column.transparent_mesh])
+        .filter_map(|mesh| meshes.get(mesh))
+        .filter_map(|mesh| mesh.indices())
+        .map(|indices| indices.len() / 3)
+        .sum();
+    let voxel_position = VoxelPos::from_world(position).0;
+
+    text.sections[STATS_SECTION].value = format!(
+        "\nChunk: {} {} {}\nVoxel: {} {} {}\nLoaded chunks: {}\nMesh jobs: {} queued, {} dirty\n\
+         Triangles: {}\nMemory: {}",
+        camera_chunk.x,
+        camera_chunk.y,
+        camera_chunk.z,
+        voxel_position.x,
+        voxel_position.y,
+        voxel_position.z,
+        voxel_data.chunk_count(),
+        chunk_meshes_update_queue.len(),
+        dirty_columns,
+        triangles,
+        memory_usage.summary(),
+    );
+}
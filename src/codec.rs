@@ -0,0 +1,443 @@
+//! Binary encoding of voxel data shared by saves and the network protocol.
+//!
+//! Every encoded blob starts with a small header (magic + format version) so
+//! older data can be migrated when the layout changes. Chunk voxels are run
+//! length encoded, since terrain is mostly long runs of air and stone.
+//! Version 2 added voxel states, version 3 block entities and version 4 the
+//! entities stored with a chunk, older chunks load without them. Version 5
+//! added states and block entities to schematics.
+
+use std::{collections::BTreeMap, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    block_entity::BlockEntity,
+    schematic::Schematic,
+    stored_entity::StoredEntity,
+    voxel::{
+        BlockId, BlockState, ChunkData, ChunkIndex, VoxelData, VoxelLocalIndex, AIR, CHUNK_SIZE,
+    },
+};
+
+const MAGIC: [u8; 4] = *b"MCRS";
+pub const FORMAT_VERSION: u16 = 5;
+
+const VOXELS_PER_CHUNK: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+
+#[derive(Debug)]
+pub enum DecodeError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    InvalidChunk(String),
+    Bincode(bincode::Error),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::BadMagic => write!(f, "not an mcrs blob"),
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {}", version)
+            }
+            DecodeError::InvalidChunk(reason) => write!(f, "invalid chunk: {}", reason),
+            DecodeError::Bincode(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<bincode::Error> for DecodeError {
+    fn from(err: bincode::Error) -> Self {
+        DecodeError::Bincode(err)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    magic: [u8; 4],
+    version: u16,
+}
+
+/// On-disk/on-wire form of a chunk, voxels stored as (block, run length) pairs
+#[derive(Serialize, Deserialize)]
+pub struct RleChunk {
+    level: u32,
+    index: ChunkIndex,
+    runs: Vec<(u8, u16)>,
+    states: Vec<(u16, BlockState)>, // packed local index and state of the voxels with one
+    entities: Vec<(u16, BlockEntity)>,
+    stored: Vec<StoredEntity>,
+}
+
+/// A chunk in format version 1, from before voxels had states
+#[derive(Deserialize)]
+struct RleChunkV1 {
+    level: u32,
+    index: ChunkIndex,
+    runs: Vec<(u8, u16)>,
+}
+
+impl From<RleChunkV1> for RleChunk {
+    fn from(chunk: RleChunkV1) -> Self {
+        RleChunk {
+            level: chunk.level,
+            index: chunk.index,
+            runs: chunk.runs,
+            states: Vec::new(),
+            entities: Vec::new(),
+            stored: Vec::new(),
+        }
+    }
+}
+
+/// A chunk in format version 2, from before block entities
+#[derive(Deserialize)]
+struct RleChunkV2 {
+    level: u32,
+    index: ChunkIndex,
+    runs: Vec<(u8, u16)>,
+    states: Vec<(u16, BlockState)>,
+}
+
+impl From<RleChunkV2> for RleChunk {
+    fn from(chunk: RleChunkV2) -> Self {
+        RleChunk {
+            level: chunk.level,
+            index: chunk.index,
+            runs: chunk.runs,
+            states: chunk.states,
+            entities: Vec::new(),
+            stored: Vec::new(),
+        }
+    }
+}
+
+/// A chunk in format version 3, from before entities were stored with it
+#[derive(Deserialize)]
+struct RleChunkV3 {
+    level: u32,
+    index: ChunkIndex,
+    runs: Vec<(u8, u16)>,
+    states: Vec<(u16, BlockState)>,
+    entities: Vec<(u16, BlockEntity)>,
+}
+
+impl From<RleChunkV3> for RleChunk {
+    fn from(chunk: RleChunkV3) -> Self {
+        RleChunk {
+            level: chunk.level,
+            index: chunk.index,
+            runs: chunk.runs,
+            states: chunk.states,
+            entities: chunk.entities,
+            stored: Vec::new(),
+        }
+    }
+}
+
+impl From<ChunkData> for RleChunk {
+    fn from(chunk: ChunkData) -> Self {
+        let mut runs: Vec<(u8, u16)> = Vec::new();
+        for column in chunk.voxels.iter() {
+            for row in column.iter() {
+                for &block in row.iter() {
+                    match runs.last_mut() {
+                        Some((last, count)) if *last == block.0 => *count += 1,
+                        _ => runs.push((block.0, 1)),
+                    }
+                }
+            }
+        }
+        RleChunk {
+            level: chunk.level,
+            index: chunk.index,
+            runs,
+            states: chunk.states.into_iter().collect(),
+            entities: chunk.entities.into_iter().collect(),
+            stored: chunk.stored,
+        }
+    }
+}
+
+impl TryFrom<RleChunk> for ChunkData {
+    type Error = String;
+
+    fn try_from(rle: RleChunk) -> Result<Self, Self::Error> {
+        let total: usize = rle.runs.iter().map(|(_, count)| *count as usize).sum();
+        if total != VOXELS_PER_CHUNK {
+            return Err(format!(
+                "{} voxels in chunk {:?}, expected {}",
+                total, rle.index, VOXELS_PER_CHUNK
+            ));
+        }
+
+        let mut voxels = [[[AIR; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+        let mut blocks = rle
+            .runs
+            .iter()
+            .flat_map(|(block, count)| std::iter::repeat_n(*block, *count as usize));
+        for column in voxels.iter_mut() {
+            for row in column.iter_mut() {
+                for voxel in row.iter_mut() {
+                    *voxel = BlockId(blocks.next().unwrap_or_default());
+                }
+            }
+        }
+        let mut chunk = ChunkData {
+            level: rle.level,
+            index: rle.index,
+            voxels,
+            states: BTreeMap::new(),
+            entities: BTreeMap::new(),
+            stored: rle.stored,
+        };
+        for (packed, state) in rle.states {
+            let local = VoxelLocalIndex::from_packed(packed)
+                .ok_or_else(|| format!("state of voxel {} in chunk {:?}", packed, rle.index))?;
+            chunk.set_state(local, state);
+        }
+        for (packed, entity) in rle.entities {
+            let local = VoxelLocalIndex::from_packed(packed)
+                .ok_or_else(|| format!("entity of voxel {} in chunk {:?}", packed, rle.index))?;
+            chunk.set_entity(local, Some(entity));
+        }
+        Ok(chunk)
+    }
+}
+
+fn encode<T: Serialize>(value: &T) -> Vec<u8> {
+    let mut bytes = bincode::serialize(&Header {
+        magic: MAGIC,
+        version: FORMAT_VERSION,
+    })
+    .expect("header always serializes");
+    bincode::serialize_into(&mut bytes, value).expect("voxel data always serializes");
+    bytes
+}
+
+/// Check the header and return the format version and the payload after it
+fn read_header(bytes: &[u8]) -> Result<(u16, &[u8]), DecodeError> {
+    let header: Header = bincode::deserialize(bytes)?;
+    if header.magic != MAGIC {
+        return Err(DecodeError::BadMagic);
+    }
+    let header_size = bincode::serialized_size(&header)? as usize;
+    Ok((header.version, &bytes[header_size..]))
+}
+
+pub fn encode_chunk(chunk: &ChunkData) -> Vec<u8> {
+    encode(chunk)
+}
+
+pub fn decode_chunk(bytes: &[u8]) -> Result<ChunkData, DecodeError> {
+    let (version, payload) = read_header(bytes)?;
+    match version {
+        1 => upgrade(bincode::deserialize::<RleChunkV1>(payload)?),
+        2 => upgrade(bincode::deserialize::<RleChunkV2>(payload)?),
+        3 => upgrade(bincode::deserialize::<RleChunkV3>(payload)?),
+        4 | 5 => bincode::deserialize(payload).map_err(invalid_chunk),
+        version => Err(DecodeError::UnsupportedVersion(version)),
+    }
+}
+
+pub fn encode_voxel_data(voxel_data: &VoxelData) -> Vec<u8> {
+    let chunks: Vec<&ChunkData> = voxel_data.iter_chunks().collect();
+    encode(&chunks)
+}
+
+pub fn decode_voxel_data(bytes: &[u8]) -> Result<VoxelData, DecodeError> {
+    let (version, payload) = read_header(bytes)?;
+    let chunks: Vec<ChunkData> = match version {
+        1 => {
+            let chunks: Vec<RleChunkV1> = bincode::deserialize(payload)?;
+            chunks.into_iter().map(upgrade).collect::<Result<_, _>>()?
+        }
+        2 => {
+            let chunks: Vec<RleChunkV2> = bincode::deserialize(payload)?;
+            chunks.into_iter().map(upgrade).collect::<Result<_, _>>()?
+        }
+        3 => {
+            let chunks: Vec<RleChunkV3> = bincode::deserialize(payload)?;
+            chunks.into_iter().map(upgrade).collect::<Result<_, _>>()?
+        }
+        4 | 5 => bincode::deserialize(payload).map_err(invalid_chunk)?,
+        version => return Err(DecodeError::UnsupportedVersion(version)),
+    };
+    let mut voxel_data = VoxelData::default();
+    for chunk in chunks {
+        voxel_data.insert_chunk(chunk);
+    }
+    Ok(voxel_data)
+}
+
+pub fn encode_schematic(schematic: &Schematic) -> Vec<u8> {
+    encode(schematic)
+}
+
+pub fn decode_schematic(bytes: &[u8]) -> Result<Schematic, DecodeError> {
+    let (version, payload) = read_header(bytes)?;
+    match version {
+        // before version 5 schematics held only their blocks
+        1..=4 => {
+            let (size, palette, blocks) = bincode::deserialize(payload)?;
+            Ok(Schematic {
+                size,
+                palette,
+                blocks,
+                states: Vec::new(),
+                entities: Vec::new(),
+            })
+        }
+        5 => Ok(bincode::deserialize(payload)?),
+        version => Err(DecodeError::UnsupportedVersion(version)),
+    }
+}
+
+/// Load a chunk of an older format version
+fn upgrade(chunk: impl Into<RleChunk>) -> Result<ChunkData, DecodeError> {
+    ChunkData::try_from(chunk.into()).map_err(DecodeError::InvalidChunk)
+}
+
+/// `try_from` failures surface as custom bincode errors, report them as invalid chunks
+fn invalid_chunk(err: bincode::Error) -> DecodeError {
+    match *err {
+        bincode::ErrorKind::Custom(reason) => DecodeError::InvalidChunk(reason),
+        _ => DecodeError::Bincode(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        sign::Sign,
+        voxel::{SIGN, STONE},
+    };
+
+    /// A chunk with a few blocks, a state and a block entity
+    fn sample_chunk() -> ChunkData {
+        let mut chunk = ChunkData::air(ChunkIndex { x: -2, y: 3, z: 7 });
+        let sign = VoxelLocalIndex { x: 1, y: 2, z: 3 };
+        for y in 0..4 {
+            chunk.set_block(VoxelLocalIndex { x: 5, y, z: 5 }, STONE);
+        }
+        chunk.set_block(sign, SIGN);
+        chunk.set_state(sign, 2);
+        chunk.set_entity(
+            sign,
+            Some(BlockEntity::Sign(Sign {
+                text: "Home".to_string(),
+            })),
+        );
+        chunk
+    }
+
+    fn assert_same_chunk(decoded: &ChunkData, chunk: &ChunkData) {
+        assert_eq!(decoded.index, chunk.index);
+        assert_eq!(decoded.voxels, chunk.voxels);
+        assert_eq!(decoded.states, chunk.states);
+        assert_eq!(decoded.entities, chunk.entities);
+    }
+
+    /// `payload` behind a header of format `version`, like an older build wrote it
+    fn encode_as<T: Serialize>(version: u16, payload: &T) -> Vec<u8> {
+        let mut bytes = bincode::serialize(&Header {
+            magic: MAGIC,
+            version,
+        })
+        .unwrap();
+        bincode::serialize_into(&mut bytes, payload).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn chunks_and_voxel_data_round_trip() {
+        let chunk = sample_chunk();
+        let bytes = encode_chunk(&chunk);
+        assert_same_chunk(&decode_chunk(&bytes).unwrap(), &chunk);
+
+        let mut voxel_data = VoxelData::air([ChunkIndex { x: 0, y: 0, z: 0 }]);
+        voxel_data.insert_chunk(chunk.clone());
+        let decoded = decode_voxel_data(&encode_voxel_data(&voxel_data)).unwrap();
+        assert_eq!(decoded.chunk_count(), 2);
+        assert_same_chunk(decoded.chunk(chunk.index).unwrap(), &chunk);
+    }
+
+    #[test]
+    fn bad_headers_are_rejected() {
+        let mut bytes = encode_chunk(&sample_chunk());
+        bytes[..4].copy_from_slice(b"NOPE");
+        assert!(matches!(decode_chunk(&bytes), Err(DecodeError::BadMagic)));
+
+        let newer = encode_as(FORMAT_VERSION + 1, &RleChunk::from(sample_chunk()));
+        assert!(matches!(
+            decode_chunk(&newer),
+            Err(DecodeError::UnsupportedVersion(version)) if version == FORMAT_VERSION + 1
+        ));
+        assert!(matches!(
+            decode_voxel_data(&newer),
+            Err(DecodeError::UnsupportedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn truncated_and_corrupt_chunks_are_rejected() {
+        let bytes = encode_chunk(&sample_chunk());
+        assert!(matches!(
+            decode_chunk(&bytes[..bytes.len() / 2]),
+            Err(DecodeError::Bincode(_))
+        ));
+        assert!(matches!(
+            decode_chunk(&bytes[..3]),
+            Err(DecodeError::Bincode(_))
+        ));
+
+        // runs that don't fill the chunk
+        let mut short = RleChunk::from(sample_chunk());
+        short.runs.truncate(1);
+        assert!(matches!(
+            decode_chunk(&encode_as(FORMAT_VERSION, &short)),
+            Err(DecodeError::InvalidChunk(_))
+        ));
+        // a state past the last voxel
+        let mut stray = RleChunk::from(sample_chunk());
+        stray.states.push((VOXELS_PER_CHUNK as u16, 1));
+        assert!(matches!(
+            decode_chunk(&encode_as(FORMAT_VERSION, &stray)),
+            Err(DecodeError::InvalidChunk(_))
+        ));
+    }
+
+    #[test]
+    fn older_versions_are_migrated() {
+        let chunk = sample_chunk();
+        let rle = RleChunk::from(chunk.clone());
+        let v1 = (rle.level, rle.index, rle.runs.clone());
+        let v2 = (rle.level, rle.index, rle.runs.clone(), rle.states.clone());
+        let v3 = (
+            rle.level,
+            rle.index,
+            rle.runs.clone(),
+            rle.states.clone(),
+            rle.entities.clone(),
+        );
+
+        let from_v1 = decode_chunk(&encode_as(1, &v1)).unwrap();
+        assert_eq!(from_v1.voxels, chunk.voxels);
+        assert!(from_v1.states.is_empty() && from_v1.entities.is_empty());
+        let from_v2 = decode_chunk(&encode_as(2, &v2)).unwrap();
+        assert_eq!(from_v2.states, chunk.states);
+        assert!(from_v2.entities.is_empty());
+        assert_same_chunk(&decode_chunk(&encode_as(3, &v3)).unwrap(), &chunk);
+
+        let voxel_data = decode_voxel_data(&encode_as(2, &vec![v2])).unwrap();
+        assert_eq!(voxel_data.chunk(chunk.index).unwrap().states, chunk.states);
+
+        let blocks_only = ([1u32, 1, 2], vec![AIR, STONE], vec![0u8, 1]);
+        let schematic = decode_schematic(&encode_as(4, &blocks_only)).unwrap();
+        assert_eq!(schematic.blocks, blocks_only.2);
+        assert!(schematic.states.is_empty() && schematic.entities.is_empty());
+    }
+}
@@ -0,0 +1,386 @@
+//! Slash command parsing and execution, independent of where the command
+//! came from (the in-game console now, remote players later).
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    daytime::{WorldTime, HOURS_PER_DAY},
+    edit::EditTools,
+    game_mode::GameMode,
+    journal::{JournalTools, RollbackScope},
+    repair::repair_world,
+    save::WorldSave,
+    schematic::Schematic,
+    server::LOCAL_CLIENT,
+    stress::{self, StressTest, StressTools, PREGEN_SIGHT_RANGE},
+    teleport::{Destination, PlayerTravel},
+    voxel::{self, BlockId, BlockTag, VoxelModifyQueue, VoxelPos, WorldGenSettings},
+    world::VoxelWorld,
+};
+
+const MAX_FILL_VOLUME: usize = 32 * 32 * 32;
+
+/// Names of the built-in commands, the others are left to scripts
+pub const COMMANDS: [&str; 21] = [
+    "tp", "spawn", "set", "fill", "replace", "hollow", "sphere", "time", "seed", "gamemode",
+    "rollback", "repair", "regen", "tag", "stress", "export", "paste", "claim", "unclaim",
+    "claims", "help",
+];
+
+const HELP: &str = "/tp x y z, /spawn, /set x y z block, /fill x1 y1 z1 x2 y2 z2 block, \
+                    /set block, /replace from to, /hollow, /sphere block radius, /time set hours, /seed, /gamemode mode, /rollback seconds [radius], /repair, \
+                    /regen, /tag #name, /stress checkerboard|pregen|items, \
+                    /export x1 y1 z1 x2 y2 z2 name, /paste x y z name, \
+                    /claim name [x1 y1 z1 x2 y2 z2], /unclaim name, /claims";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Teleport(Vec3),
+    Spawn, // teleport to the world's spawn point
+    Set(VoxelPos, BlockId),
+    Fill(VoxelPos, VoxelPos, BlockId),
+    SetSelection(BlockId),
+    Replace(BlockId, BlockId), // inside the selection
+    Hollow,
+    Sphere(BlockId, u32), // block and radius, around the player
+    TimeSet(f32),
+    Seed,
+    GameMode(GameMode),
+    Rollback(f64, Option<i32>), // seconds back, radius in chunks around the player
+    Repair,
+    Regen,         // generate the unedited chunks again with the current settings
+    Tag(BlockTag), // list the blocks carrying a tag
+    Stress(StressTest),
+    Export(VoxelPos, VoxelPos, String), // save a cuboid as a schematic
+    Paste(VoxelPos, String),            // schematic placed with its minimum corner there
+    Claim(String, Option<(VoxelPos, VoxelPos)>), // the selection if there are no corners
+    Unclaim(String),
+    Claims,
+    Help,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let mut words = line.trim().trim_start_matches('/').split_whitespace();
+        let name = words.next().ok_or("empty command")?;
+        let args: Vec<&str> = words.collect();
+
+        let command = match (name, args.as_slice()) {
+            ("tp", [x, y, z]) => Command::Teleport(parse_position(x, y, z)?),
+            ("spawn", []) => Command::Spawn,
+            ("set", [x, y, z, block]) => Command::Set(parse_voxel(x, y, z)?, parse(block)?),
+            ("fill", [x1, y1, z1, x2, y2, z2, block]) => Command::Fill(
+                parse_voxel(x1, y1, z1)?,
+                parse_voxel(x2, y2, z2)?,
+                parse(block)?,
+            ),
+            ("set", [block]) => Command::SetSelection(parse(block)?),
+            ("replace", [from, to]) => Command::Replace(parse(from)?, parse(to)?),
+            ("hollow", []) => Command::Hollow,
+            ("sphere", [block, radius]) => Command::Sphere(parse(block)?, parse(radius)?),
+            ("time", ["set", hours]) => Command::TimeSet(parse(hours)?),
+            ("seed", []) => Command::Seed,
+            ("gamemode", [mode]) => Command::GameMode(
+                GameMode::from_name(mode).ok_or(format!("unknown game mode '{}'", mode))?,
+            ),
+            ("rollback", [seconds]) => Command::Rollback(parse(seconds)?, None),
+            ("rollback", [seconds, radius]) => {
+                Command::Rollback(parse(seconds)?, Some(parse(radius)?))
+            }
+            ("repair", []) => Command::Repair,
+            ("regen", []) => Command::Regen,
+            ("tag", [name]) => Command::Tag(
+                BlockTag::from_name(name).ok_or(format!("unknown block tag '{}'", name))?,
+            ),
+            ("stress", [name]) => Command::Stress(
+                StressTest::from_name(name).ok_or(format!("unknown stress test '{}'", name))?,
+            ),
+            ("export", [x1, y1, z1, x2, y2, z2, name]) => Command::Export(
+                parse_voxel(x1, y1, z1)?,
+                parse_voxel(x2, y2, z2)?,
+                name.to_string(),
+            ),
+            ("paste", [x, y, z, name]) => Command::Paste(parse_voxel(x, y, z)?, name.to_string()),
+            ("claim", [name]) => Command::Claim(name.to_string(), None),
+            ("claim", [name, x1, y1, z1, x2, y2, z2]) => Command::Claim(
+                name.to_string(),
+                Some((parse_voxel(x1, y1, z1)?, parse_voxel(x2, y2, z2)?)),
+            ),
+            ("unclaim", [name]) => Command::Unclaim(name.to_string()),
+            ("claims", []) => Command::Claims,
+            ("help", []) => Command::Help,
+            (name, _) if COMMANDS.contains(&name) => {
+                return Err(format!("wrong arguments for /{}, usage: {}", name, HELP))
+            }
+            _ => return Err(format!("unknown command /{}", name)),
+        };
+        Ok(command)
+    }
+}
+
+fn parse<T: std::str::FromStr>(word: &str) -> Result<T, String> {
+    word.parse()
+        .map_err(|_| format!("invalid number '{}'", word))
+}
+
+fn parse_position(x: &str, y: &str, z: &str) -> Result<Vec3, String> {
+    Ok(Vec3::new(parse(x)?, parse(y)?, parse(z)?))
+}
+
+fn parse_voxel(x: &str, y: &str, z: &str) -> Result<VoxelPos, String> {
+    Ok(VoxelPos::new(parse(x)?, parse(y)?, parse(z)?))
+}
+
+fn too_many_blocks(volume: usize) -> String {
+    format!(
+        "{} blocks is too many, the limit is {}",
+        volume, MAX_FILL_VOLUME
+    )
+}
+
+/// Number of blocks in the cuboid between two corners, if it's within `MAX_FILL_VOLUME`
+fn check_volume(from: VoxelPos, to: VoxelPos) -> Result<usize, String> {
+    let size = (from.0.max(to.0) - from.0.min(to.0) + IVec3::ONE).as_uvec3();
+    let volume = size.x as usize * size.y as usize * size.z as usize;
+    if volume > MAX_FILL_VOLUME {
+        return Err(too_many_blocks(volume));
+    }
+    Ok(volume)
+}
+
+/// Bulk edits are a creative mode tool
+fn require_creative(game_mode: &GameMode) -> Result<(), String> {
+    if *game_mode != GameMode::Creative {
+        return Err("bulk edits need creative mode, /gamemode creative".to_string());
+    }
+    Ok(())
+}
+
+/// A command line to run, from the console or a remote player
+#[derive(Event, Debug, Clone)]
+pub struct CommandRequest {
+    pub line: String,
+}
+
+/// Feedback for whoever issued a command
+#[derive(Event, Debug, Clone)]
+pub struct CommandResponse {
+    pub message: String,
+}
+
+/// The world and the ways commands change it: right away, through the edit queue, or by rolling
+/// it back with the journal, with its saved chunks
+#[derive(SystemParam)]
+pub struct WorldEdits<'w> {
+    world: VoxelWorld<'w>,
+    world_save: ResMut<'w, WorldSave>,
+    modify_queue: ResMut<'w, VoxelModifyQueue>,
+    journal: JournalTools<'w>,
+}
+
+/// The time of day, game mode and generator settings, which commands show and change
+#[derive(SystemParam)]
+pub struct WorldRules<'w> {
+    world_time: ResMut<'w, WorldTime>,
+    game_mode: ResMut<'w, GameMode>,
+    world_gen_settings: ResMut<'w, WorldGenSettings>,
+}
+
+pub fn run_commands(
+    mut requests: EventReader<CommandRequest>,
+    mut responses: EventWriter<CommandResponse>,
+    mut edits: WorldEdits,
+    mut rules: WorldRules,
+    mut player_travel: PlayerTravel,
+    mut edit_tools: EditTools,
+    mut stress_tools: StressTools,
+) {
+    for request in requests.iter() {
+        let name = request
+            .line
+            .trim()
+            .trim_start_matches('/')
+            .split_whitespace()
+            .next();
+        if !name.is_some_and(|name| COMMANDS.contains(&name)) {
+            continue; // a script's, or unknown, see `script_commands`
+        }
+        let result = Command::parse(&request.line).and_then(|command| match command {
+            Command::Teleport(position) => {
+                player_travel.teleport(Destination::Position(position));
+                Ok(format!(
+                    "Teleporting to {} once the ground there is loaded",
+                    position
+                ))
+            }
+            Command::Spawn => {
+                player_travel.teleport(Destination::Spawn);
+                Ok("Teleporting to the spawn point once the ground there is loaded".to_string())
+            }
+            Command::Set(position, block) => {
+                edits.modify_queue.queue.push((position, block));
+                Ok(format!("Set {} to {}", position.0, block))
+            }
+            Command::Fill(from, to, block) => {
+                let volume = check_volume(from, to)?;
+                let min = from.0.min(to.0);
+                let max = from.0.max(to.0);
+                for x in min.x..=max.x {
+                    for y in min.y..=max.y {
+                        for z in min.z..=max.z {
+                            edits
+                                .modify_queue
+                                .queue
+                                .push((VoxelPos::new(x, y, z), block));
+                        }
+                    }
+                }
+                Ok(format!("Filled {} blocks", volume))
+            }
+            Command::SetSelection(block) => {
+                require_creative(&rules.game_mode)?;
+                let queued = edit_tools.set(block)?;
+                Ok(format!("Setting {} blocks to {}", queued, block))
+            }
+            Command::Replace(from, to) => {
+                require_creative(&rules.game_mode)?;
+                let queued = edit_tools.replace(edits.world.data(), from, to)?;
+                Ok(format!(
+                    "Replacing {} blocks of {} with {}",
+                    queued, from, to
+                ))
+            }
+            Command::Hollow => {
+                require_creative(&rules.game_mode)?;
+                let queued = edit_tools.hollow()?;
+                Ok(format!("Clearing {} blocks inside the selection", queued))
+            }
+            Command::Sphere(block, radius) => {
+                require_creative(&rules.game_mode)?;
+                let center = VoxelPos(player_travel.eye()?.floor().as_ivec3());
+                let queued = edit_tools.sphere(center, radius, block)?;
+                Ok(format!("Placing a sphere of {} blocks", queued))
+            }
+            Command::TimeSet(hours) => {
+                rules.world_time.time_of_day = hours.rem_euclid(HOURS_PER_DAY);
+                Ok(format!("Time set to {:.1}h", rules.world_time.time_of_day))
+            }
+            Command::Seed => Ok(format!("Seed: {}", rules.world_gen_settings.seed)),
+            Command::GameMode(mode) => {
+                *rules.game_mode = mode;
+                Ok(format!("Game mode set to {:?}", mode))
+            }
+            Command::Rollback(seconds, radius) => {
+                let scope = match radius {
+                    Some(radius) => RollbackScope::Region {
+                        center: voxel::get_chunk_index(&player_travel.eye()?),
+                        radius,
+                    },
+                    None => RollbackScope::World,
+                };
+                let restored = edits.journal.rollback(
+                    seconds,
+                    scope,
+                    &mut edits.world,
+                    &rules.world_gen_settings,
+                )?;
+                Ok(format!("Rolled back {} chunks by {}s", restored, seconds))
+            }
+            Command::Repair => {
+                let report = repair_world(&mut edits.world, &mut edits.world_save);
+                Ok(report.summary())
+            }
+            Command::Regen => {
+                // `regenerate_world` does it, as for the inspector's button
+                rules.world_gen_settings.regenerate.pressed = true;
+                Ok("Regenerating the world with the current settings".to_string())
+            }
+            Command::Tag(tag) => {
+                let blocks: Vec<String> = voxel::blocks_with_tag(tag)
+                    .map(|block| block.to_string())
+                    .collect();
+                Ok(format!("{}: {}", tag.name(), blocks.join(", ")))
+            }
+            Command::Stress(test) => {
+                stress_tools.debug_settings.chunk_overlay = true;
+                match test {
+                    StressTest::Checkerboard => {
+                        let center = voxel::get_chunk_index(&player_travel.eye()?);
+                        let changed = stress::checkerboard(&mut edits.world, center);
+                        Ok(format!("Checkerboarded {} chunks", changed))
+                    }
+                    StressTest::Pregen => {
+                        // skips the server clamp, until the sight range setting changes again
+                        stress_tools.granted_sight_range.sight_range = Some(PREGEN_SIGHT_RANGE);
+                        Ok(format!("Loading chunks {} around", PREGEN_SIGHT_RANGE))
+                    }
+                    StressTest::Items => {
+                        let center = VoxelPos::from_world(player_travel.eye()?);
+                        let dropped = stress_tools.drop_items(center);
+                        Ok(format!("Dropped {} items", dropped))
+                    }
+                }
+            }
+            Command::Export(from, to, name) => {
+                check_volume(from, to)?;
+                let schematic = Schematic::capture(edits.world.data(), from, to);
+                let location = schematic.save(&name)?;
+                Ok(format!(
+                    "Exported {} blocks to {}",
+                    schematic.volume(),
+                    location
+                ))
+            }
+            Command::Paste(origin, name) => {
+                let schematic = Schematic::load(&name)?;
+                if schematic.volume() > MAX_FILL_VOLUME {
+                    return Err(too_many_blocks(schematic.volume()));
+                }
+                let pasted = schematic.paste(origin, &mut edits.modify_queue);
+                Ok(format!("Pasted {} blocks of {}", pasted, name))
+            }
+            Command::Claim(name, corners) => {
+                // commands come from the console, so the claim is the local player's
+                let claim = match corners {
+                    Some((first, second)) => {
+                        edit_tools
+                            .claims()
+                            .claim(LOCAL_CLIENT, &name, first, second)?
+                    }
+                    None => edit_tools.claim_selection(LOCAL_CLIENT, &name)?,
+                };
+                Ok(format!(
+                    "Claimed '{}' from {} to {}",
+                    claim.name, claim.min, claim.max
+                ))
+            }
+            Command::Unclaim(name) => {
+                edit_tools.claims().unclaim(LOCAL_CLIENT, &name)?;
+                Ok(format!("Gave up the claim '{}'", name))
+            }
+            Command::Claims => {
+                let claims: Vec<String> = edit_tools
+                    .claims()
+                    .claims
+                    .iter()
+                    .map(|claim| {
+                        format!(
+                            "{} ({} to {}, client {})",
+                            claim.name, claim.min, claim.max, claim.owner
+                        )
+                    })
+                    .collect();
+                if claims.is_empty() {
+                    Ok("No claims".to_string())
+                } else {
+                    Ok(format!("Claims: {}", claims.join(", ")))
+                }
+            }
+            Command::Help => Ok(HELP.to_string()),
+        });
+
+        responses.send(CommandResponse {
+            message: result.unwrap_or_else(|err| format!("Error: {}", err)),
+        });
+    }
+}
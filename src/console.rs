@@ -0,0 +1,110 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_egui::{egui, EguiContexts};
+use smooth_bevy_cameras::controllers::fps::FpsCameraController;
+
+use crate::{
+    chat::{ChatReceived, ChatSent, CHAT_LENGTH_LIMIT, LOCAL_NAME},
+    command::{CommandRequest, CommandResponse},
+    input::{InputAction, PlayerInput},
+    MouseSettings,
+};
+
+const LOG_LINES: usize = 50;
+
+#[derive(Resource, Default)]
+pub struct Console {
+    pub open: bool,
+    input: String,
+    log: Vec<String>,
+}
+
+pub fn console_closed(console: Res<Console>) -> bool {
+    !console.open
+}
+
+/// What goes through the console: commands and their responses, and chat
+#[derive(SystemParam)]
+pub struct ConsoleEvents<'w, 's> {
+    requests: EventWriter<'w, CommandRequest>,
+    responses: EventReader<'w, 's, CommandResponse>,
+    chat_sent: EventWriter<'w, ChatSent>,
+    chat_received: EventReader<'w, 's, ChatReceived>,
+}
+
+/// T or / opens the console, Enter runs the typed command or says the typed chat line,
+/// Esc closes it
+pub fn console(
+    mut contexts: EguiContexts,
+    keyboard_input: Res<Input<KeyCode>>,
+    player_input: Res<PlayerInput>,
+    mut console: ResMut<Console>,
+    mut events: ConsoleEvents,
+    ms: Res<MouseSettings>,
+    mut fps_camera_query: Query<&mut FpsCameraController>,
+) {
+    for response in events.responses.iter() {
+        console.log.push(response.message.clone());
+    }
+    for event in events.chat_received.iter() {
+        console
+            .log
+            .push(format!("<{}> {}", event.player, event.text));
+    }
+    let overflow = console.log.len().saturating_sub(LOG_LINES);
+    console.log.drain(..overflow);
+
+    let was_open = console.open;
+    if !console.open {
+        if player_input.actions.just_pressed(InputAction::Console) {
+            console.open = true;
+        } else if player_input.actions.just_pressed(InputAction::Command) {
+            console.open = true;
+            console.input = "/".to_string();
+        }
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        console.open = false;
+        console.input.clear();
+    }
+    if console.open != was_open {
+        // keep the camera from moving while typing
+        if let Ok(mut fps_camera) = fps_camera_query.get_single_mut() {
+            fps_camera.enabled = !console.open && !ms.ui_mode;
+        }
+    }
+    if !console.open || !was_open {
+        return;
+    }
+
+    let console = console.as_mut();
+    let mut submitted = false;
+    egui::TopBottomPanel::top("console").show(contexts.ctx_mut(), |ui| {
+        egui::ScrollArea::vertical()
+            .max_height(200.0)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                for line in console.log.iter() {
+                    ui.label(line);
+                }
+            });
+        let input =
+            ui.add(egui::TextEdit::singleline(&mut console.input).desired_width(f32::INFINITY));
+        input.request_focus();
+        submitted = input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+    });
+
+    if submitted {
+        let line = std::mem::take(&mut console.input);
+        if line.starts_with('/') {
+            console.log.push(format!("> {}", line));
+            events.requests.send(CommandRequest { line });
+        } else if !line.trim().is_empty() {
+            let text: String = line.trim().chars().take(CHAT_LENGTH_LIMIT).collect();
+            console.log.push(format!("<{}> {}", LOCAL_NAME, text));
+            events.chat_sent.send(ChatSent { text });
+        }
+        console.open = false;
+        if let Ok(mut fps_camera) = fps_camera_query.get_single_mut() {
+            fps_camera.enabled = !ms.ui_mode;
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! Crash reports: a panic hook writes what the world looked like when the game
+//! went down, and the next launch offers to reopen it in safe mode.
+
+use std::{backtrace::Backtrace, collections::VecDeque, fmt::Write as _, sync::Mutex};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use smooth_bevy_cameras::controllers::fps::FpsCameraController;
+
+use crate::{
+    storage::{self, Folder},
+    voxel::{ChunkMeshesUpdateQueue, VoxelData, VoxelModifyQueue, VoxelSettings, WorldGenSettings},
+    DebugSettings, GraphicsSettings,
+};
+
+const RECENT_SYSTEMS: usize = 16;
+const SAFE_SIGHT_RANGE: u8 = 4;
+
+/// World state copied every frame, so the panic hook can report it without the ECS
+struct CrashContext {
+    seed: u32,
+    player: Vec3,
+    loaded_chunks: usize,
+    chunk_mesh_queue: usize,
+    voxel_modify_queue: usize,
+    recent_systems: VecDeque<&'static str>,
+}
+
+static CONTEXT: Mutex<CrashContext> = Mutex::new(CrashContext {
+    seed: 0,
+    player: Vec3::ZERO,
+    loaded_chunks: 0,
+    chunk_mesh_queue: 0,
+    voxel_modify_queue: 0,
+    recent_systems: VecDeque::new(),
+});
+
+/// Left behind by a crash and removed once the player has answered the safe mode prompt
+const MARKER_FILE: &str = "last_crash";
+
+/// Remember that a voxel system is running, for the crash report
+pub(crate) fn note_system(name: &'static str) {
+    if let Ok(mut context) = CONTEXT.lock() {
+        if context.recent_systems.len() == RECENT_SYSTEMS {
+            context.recent_systems.pop_front();
+        }
+        context.recent_systems.push_back(name);
+    }
+}
+
+/// Write a crash report on panic, then carry on with the previous hook
+pub fn install_crash_reporter() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        write_crash_report(&info.to_string());
+        previous(info);
+    }));
+}
+
+fn write_crash_report(panic: &str) {
+    let mut report = format!("mcrs crash report\n\n{panic}\n\n");
+    // the panic may have happened while the context was locked
+    match CONTEXT.try_lock() {
+        Ok(context) => {
+            let _ = write!(
+                report,
+                "seed: {}\nplayer position: {}\nloaded chunks: {}\nchunk mesh queue: {}\nvoxel modify queue: {}\nlast voxel systems: {}\n",
+                context.seed,
+                context.player,
+                context.loaded_chunks,
+                context.chunk_mesh_queue,
+                context.voxel_modify_queue,
+                context.recent_systems.iter().copied().collect::<Vec<_>>().join(", "),
+            );
+        }
+        Err(_) => report.push_str("world state unavailable\n"),
+    }
+    let _ = write!(report, "\nbacktrace:\n{}\n", Backtrace::force_capture());
+
+    let file = format!("crash-{}.txt", storage::unix_time().as_secs());
+    if storage::write(Folder::Data, &file, report.as_bytes()).is_ok() {
+        let location = storage::describe(Folder::Data, &file);
+        eprintln!("Crash report written to {}", location);
+        let _ = storage::write(Folder::Data, MARKER_FILE, location.as_bytes());
+    }
+}
+
+pub fn update_crash_context(
+    world_gen_settings: Res<WorldGenSettings>,
+    voxel_data: Res<VoxelData>,
+    chunk_meshes_update_queue: Res<ChunkMeshesUpdateQueue>,
+    voxel_modify_queue: Res<VoxelModifyQueue>,
+    fps_camera_query: Query<&GlobalTransform, With<FpsCameraController>>,
+) {
+    if let Ok(mut context) = CONTEXT.lock() {
+        context.seed = world_gen_settings.seed;
+        if let Ok(transform) = fps_camera_query.get_single() {
+            context.player = transform.translation();
+        }
+        context.loaded_chunks = voxel_data.chunk_count();
+        context.chunk_mesh_queue = chunk_meshes_update_queue.len();
+        context.voxel_modify_queue = voxel_modify_queue.queue.len();
+    }
+}
+
+/// Report left by the previous run's crash, if the player hasn't answered the prompt yet
+#[derive(Resource, Default)]
+pub struct LastCrash {
+    pub report: Option<String>,
+}
+
+pub fn check_last_crash(mut last_crash: ResMut<LastCrash>) {
+    last_crash.report = storage::read(Folder::Data, MARKER_FILE)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok());
+}
+
+/// The settings safe mode turned down, as they were before. Safe mode lasts the session, the
+/// settings file keeps these unless the player changed them since
+#[derive(Resource, Default)]
+pub struct SafeMode {
+    active: bool,
+    sight_range: u8,
+    wireframe: bool,
+    chunk_overlay: bool,
+    light_bounce: bool,
+    auto_quality: bool,
+}
+
+impl SafeMode {
+    /// Turn the settings down, remembering them as they were
+    fn enter(
+        &mut self,
+        voxel_settings: &mut VoxelSettings,
+        debug_settings: &mut DebugSettings,
+        graphics_settings: &mut GraphicsSettings,
+    ) {
+        *self = SafeMode {
+            active: true,
+            sight_range: voxel_settings.sight_range,
+            wireframe: debug_settings.wireframe,
+            chunk_overlay: debug_settings.chunk_overlay,
+            light_bounce: graphics_settings.light_bounce,
+            auto_quality: graphics_settings.auto_quality,
+        };
+        voxel_settings.sight_range = voxel_settings.sight_range.min(SAFE_SIGHT_RANGE);
+        debug_settings.wireframe = false;
+        debug_settings.chunk_overlay = false;
+        graphics_settings.light_bounce = false;
+        graphics_settings.auto_quality = true;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// The settings as they're saved: the ones still at their safe mode value go back to
+    /// what they were before
+    pub fn saved(
+        &self,
+        voxel_settings: &VoxelSettings,
+        debug_settings: &DebugSettings,
+        graphics_settings: &GraphicsSettings,
+    ) -> (VoxelSettings, DebugSettings, GraphicsSettings) {
+        let mut voxel = voxel_settings.clone();
+        let mut debug = debug_settings.clone();
+        let mut graphics = graphics_settings.clone();
+        if voxel.sight_range == self.sight_range.min(SAFE_SIGHT_RANGE) {
+            voxel.sight_range = self.sight_range;
+        }
+        debug.wireframe |= self.wireframe;
+        debug.chunk_overlay |= self.chunk_overlay;
+        graphics.light_bounce |= self.light_bounce;
+        graphics.auto_quality &= self.auto_quality;
+        (voxel, debug, graphics)
+    }
+}
+
+pub fn crash_prompt(
+    mut contexts: EguiContexts,
+    mut last_crash: ResMut<LastCrash>,
+    mut safe_mode: ResMut<SafeMode>,
+    mut voxel_settings: ResMut<VoxelSettings>,
+    mut debug_settings: ResMut<DebugSettings>,
+    mut graphics_settings: ResMut<GraphicsSettings>,
+) {
+    let Some(report) = last_crash.report.as_ref() else {
+        return;
+    };
+    let mut answered = false;
+    egui::Window::new("The game crashed last time")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!("A crash report was saved to {report}"));
+            ui.horizontal(|ui| {
+                if ui.button("Reopen in safe mode").clicked() {
+                    safe_mode.enter(
+                        &mut voxel_settings,
+                        &mut debug_settings,
+                        &mut graphics_settings,
+                    );
+                    answered = true;
+                }
+                answered |= ui.button("Continue normally").clicked();
+            });
+        });
+
+    if answered {
+        last_crash.report = None;
+        let _ = storage::remove(Folder::Data, MARKER_FILE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn safe_mode_is_left_out_of_the_saved_settings() {
+        let mut voxel_settings = VoxelSettings {
+            sight_range: 12,
+            ..default()
+        };
+        let mut debug_settings = DebugSettings {
+            chunk_overlay: true,
+            ..default()
+        };
+        let mut graphics_settings = GraphicsSettings {
+            light_bounce: true,
+            auto_quality: false,
+            ..default()
+        };
+        let mut safe_mode = SafeMode::default();
+        safe_mode.enter(
+            &mut voxel_settings,
+            &mut debug_settings,
+            &mut graphics_settings,
+        );
+        assert_eq!(voxel_settings.sight_range, SAFE_SIGHT_RANGE);
+        assert!(!debug_settings.chunk_overlay && !graphics_settings.light_bounce);
+
+        // what the player changed during the session is saved as they left it
+        debug_settings.wireframe = true;
+        let (voxel, debug, graphics) =
+            safe_mode.saved(&voxel_settings, &debug_settings, &graphics_settings);
+        assert_eq!(voxel.sight_range, 12);
+        assert!(debug.chunk_overlay && debug.wireframe);
+        assert!(graphics.light_bounce && !graphics.auto_quality);
+    }
+}
@@ -0,0 +1,43 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use bevy::prelude::*;
+
+pub const HOURS_PER_DAY: f32 = 24.0;
+
+#[derive(Resource)]
+pub struct WorldTime {
+    pub time_of_day: f32, // in hours, 0 is midnight
+    pub day_length: f32,  // real seconds per in-game day
+}
+
+impl Default for WorldTime {
+    fn default() -> Self {
+        WorldTime {
+            time_of_day: 9.0,
+            day_length: 1200.0,
+        }
+    }
+}
+
+impl WorldTime {
+    /// Between dusk and dawn, when hostile mobs roam the surface
+    pub fn is_night(&self) -> bool {
+        !(6.0..18.0).contains(&self.time_of_day)
+    }
+}
+
+pub fn advance_time(time: Res<Time>, mut world_time: ResMut<WorldTime>) {
+    let hours = time.delta_seconds() / world_time.day_length * HOURS_PER_DAY;
+    world_time.time_of_day = (world_time.time_of_day + hours) % HOURS_PER_DAY;
+}
+
+/// Point the sun straight down at noon and below the horizon at night
+pub fn update_sun(
+    world_time: Res<WorldTime>,
+    mut sun_query: Query<&mut Transform, With<DirectionalLight>>,
+) {
+    let angle = world_time.time_of_day / HOURS_PER_DAY * TAU;
+    for mut transform in sun_query.iter_mut() {
+        transform.rotation = Quat::from_rotation_x(FRAC_PI_2 - angle);
+    }
+}
@@ -0,0 +1,184 @@
+//! The debug screen, like Minecraft's F3 screen: where the player stands and
+//! which way they face, the block they look at, the biome and the light at
+//! their feet, what's loaded, memory use and the world's seed. It's toggled on
+//! its own, apart from the inspector and the chunk overlay.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    chunk_info::ChunkSummary,
+    input::{InputAction, PlayerInput},
+    player::EYE_HEIGHT,
+    voxel::{self, ChunkEntities, LightColor, VoxelPos, WorldGenSettings},
+    CrosshairTarget, DebugSettings, MemoryUsage,
+};
+
+const LIGHT_INTERVAL: f32 = 0.25; // seconds between samples of the light at the feet
+const COMPASS: [&str; 8] = [
+    "north",
+    "northeast",
+    "east",
+    "southeast",
+    "south",
+    "southwest",
+    "west",
+    "northwest",
+];
+
+#[derive(Component)]
+pub struct DebugScreenText;
+
+pub fn setup_debug_screen(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 18.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(5.0),
+            right: Val::Px(5.0),
+            ..default()
+        })
+        .with_background_color(Color::rgba(0.0, 0.0, 0.0, 0.4)),
+        DebugScreenText,
+    ));
+}
+
+pub fn toggle_debug_screen(
+    player_input: Res<PlayerInput>,
+    mut debug_settings: ResMut<DebugSettings>,
+) {
+    if player_input.actions.just_pressed(InputAction::DebugScreen) {
+        debug_settings.debug_screen = !debug_settings.debug_screen;
+    }
+}
+
+/// Heading in degrees clockwise from north and the compass point it's closest to, -z being
+/// north and +x east like in Minecraft
+fn heading(forward: Vec3) -> (f32, &'static str) {
+    let degrees = forward.x.atan2(-forward.z).to_degrees().rem_euclid(360.0);
+    let point = ((degrees + 22.5) / 45.0) as usize % COMPASS.len();
+    (degrees, COMPASS[point])
+}
+
+/// What the debug screen tells about the world: the seed, the biomes, what's loaded and the
+/// memory it takes
+#[derive(SystemParam)]
+pub struct WorldStats<'w, 's> {
+    world_gen_settings: Res<'w, WorldGenSettings>,
+    memory_usage: Res<'w, MemoryUsage>,
+    chunk_entities: Res<'w, ChunkEntities>,
+    summary_query: Query<'w, 's, &'static ChunkSummary>,
+    entity_query: Query<'w, 's, ()>,
+}
+
+/// Fill in the debug screen while it's shown. The light at the feet takes a flood fill, so
+/// it's sampled a few times a second
+pub fn update_debug_screen(
+    time: Res<Time>,
+    debug_settings: Res<DebugSettings>,
+    crosshair: CrosshairTarget,
+    world_stats: WorldStats,
+    mut text_query: Query<(&mut Text, &mut Visibility), With<DebugScreenText>>,
+    mut feet_light: Local<(f32, LightColor)>, // seconds to the next sample, last sample
+) {
+    let Ok((mut text, mut visibility)) = text_query.get_single_mut() else {
+        return;
+    };
+    let shown = if debug_settings.debug_screen {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    if *visibility != shown {
+        *visibility = shown;
+    }
+    let Some((eye, forward)) = crosshair.view() else {
+        return;
+    };
+    if !debug_settings.debug_screen {
+        return;
+    }
+
+    let voxel_data = &crosshair.voxel_data;
+    let feet = eye - Vec3::Y * EYE_HEIGHT;
+    let feet_voxel = VoxelPos::from_world(feet);
+    let chunk = feet_voxel.chunk();
+    feet_light.0 -= time.delta_seconds();
+    if feet_light.0 <= 0.0 {
+        *feet_light = (LIGHT_INTERVAL, voxel_data.block_light(feet_voxel));
+    }
+
+    let (degrees, compass_point) = heading(forward);
+    let pitch = forward.y.clamp(-1.0, 1.0).asin().to_degrees();
+    let target = crosshair.get().map_or("none".to_string(), |(position, _)| {
+        let block = voxel_data.get_block(position);
+        format!(
+            "{} (id {}, state {}) at {} {} {}",
+            voxel::block_name(block),
+            block.0,
+            voxel_data.get_state(position),
+            position.0.x,
+            position.0.y,
+            position.0.z,
+        )
+    });
+    let WorldStats {
+        world_gen_settings,
+        memory_usage,
+        chunk_entities,
+        summary_query,
+        entity_query,
+    } = &world_stats;
+    let biome = chunk_entities
+        .chunks
+        .get(&chunk)
+        .and_then(|entity| summary_query.get(*entity).ok())
+        .map_or("unknown", |summary| summary.biome.as_str());
+    let [red, green, blue] = feet_light.1;
+
+    text.sections[0].value = format!(
+        "XYZ: {:.3} / {:.3} / {:.3}\nBlock: {} {} {}\nChunk: {} {} {}\n\
+         Facing: {} ({:.1} / {:.1})\nTarget: {}\nBiome: {}\nBlock light: {} {} {}\n\
+         Chunks: {} loaded\nEntities: {}\nMemory: {}\nSeed: {}",
+        feet.x,
+        feet.y,
+        feet.z,
+        feet_voxel.0.x,
+        feet_voxel.0.y,
+        feet_voxel.0.z,
+        chunk.x,
+        chunk.y,
+        chunk.z,
+        compass_point,
+        degrees,
+        pitch,
+        target,
+        biome,
+        red,
+        green,
+        blue,
+        voxel_data.chunk_count(),
+        entity_query.iter().count(),
+        memory_usage.summary(),
+        world_gen_settings.seed,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headings_name_the_nearest_compass_point() {
+        assert_eq!(heading(Vec3::NEG_Z), (0.0, "north"));
+        assert_eq!(heading(Vec3::X), (90.0, "east"));
+        let (degrees, point) = heading(Vec3::new(-1.0, 0.5, 1.0));
+        assert_eq!((degrees.round(), point), (225.0, "southwest"));
+    }
+}
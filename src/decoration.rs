@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+
+use crate::{
+    region::splitmix64,
+    voxel::{
+        self, BlockId, BlockTag, ChunkData, ChunkIndex, VoxelLocalIndex, VoxelPos,
+        WorldGenSettings, CHUNK_LIMIT_Y,
+    },
+};
+
+/// A decoration stage run on every freshly generated chunk, e.g. ores or structures
+pub trait ChunkDecorator: Send + Sync + 'static {
+    /// Stages run in ascending order, ties keep their registration order
+    fn order(&self) -> i32 {
+        0
+    }
+
+    /// How many chunks away from its anchor chunk a feature may reach
+    fn reach(&self) -> u8 {
+        0
+    }
+
+    /// Place the features anchored in `ctx.anchor()`, only writes inside the generated chunk land
+    fn decorate(&self, ctx: &mut DecorationContext);
+}
+
+/// View of the chunk being decorated, from the point of view of one anchor chunk
+pub struct DecorationContext<'a> {
+    chunk: &'a mut ChunkData,
+    anchor: ChunkIndex,
+    settings: &'a WorldGenSettings,
+}
+
+impl DecorationContext<'_> {
+    pub fn anchor(&self) -> ChunkIndex {
+        self.anchor
+    }
+
+    /// World position of the anchor chunk's lowest corner
+    pub fn anchor_origin(&self) -> IVec3 {
+        self.anchor.origin()
+    }
+
+    /// Random seed for the anchor, the same whichever neighbour chunk is being decorated
+    pub fn seed(&self) -> u64 {
+        let coords = ((self.anchor.x as u32 as u64) << 32) | self.anchor.z as u32 as u64;
+        splitmix64(
+            ((self.settings.seed as u64) << 32)
+                ^ splitmix64(coords ^ ((self.anchor.y as u64) << 48)),
+        )
+    }
+
+    pub fn settings(&self) -> &WorldGenSettings {
+        self.settings
+    }
+
+    /// Land height of a world column, before erosion
+    pub fn surface_height(&self, world_x: i32, world_z: i32) -> i32 {
+        voxel::surface_height(world_x, world_z, self.settings)
+    }
+
+    /// Block at a world position, `None` outside the chunk being decorated
+    pub fn get(&self, position: IVec3) -> Option<BlockId> {
+        let local = self.local(position)?;
+        Some(self.chunk.voxels[local.x as usize][local.y as usize][local.z as usize])
+    }
+
+    /// Whether the block at a world position carries `tag`, e.g. ground a tree can grow on
+    pub fn has_tag(&self, position: IVec3, tag: BlockTag) -> bool {
+        self.get(position)
+            .is_some_and(|block| voxel::has_tag(block, tag))
+    }
+
+    /// Set a block at a world position, ignored outside the chunk being decorated
+    pub fn set(&mut self, position: IVec3, block: BlockId) {
+        if let Some(local) = self.local(position) {
+            self.chunk.voxels[local.x as usize][local.y as usize][local.z as usize] = block;
+        }
+    }
+
+    fn local(&self, position: IVec3) -> Option<VoxelLocalIndex> {
+        let (index, local) = VoxelPos(position).split();
+        (index == self.chunk.index).then_some(local)
+    }
+}
+
+/// Decoration stages registered with `App::add_chunk_decorator`
+#[derive(Resource, Default, Clone)]
+pub struct ChunkDecorators {
+    stages: Vec<Arc<dyn ChunkDecorator>>,
+}
+
+impl ChunkDecorators {
+    pub fn add(&mut self, decorator: impl ChunkDecorator) {
+        self.stages.push(Arc::new(decorator));
+        self.stages.sort_by_key(|stage| stage.order());
+    }
+
+    /// Run every stage for each anchor chunk whose features can reach `chunk`
+    pub(crate) fn decorate(&self, chunk: &mut ChunkData, settings: &WorldGenSettings) {
+        for stage in self.stages.iter() {
+            let reach = stage.reach() as i32;
+            for y in -reach..=reach {
+                let anchor_y = chunk.index.y + y;
+                if !(0..CHUNK_LIMIT_Y as i32).contains(&anchor_y) {
+                    continue;
+                }
+                for x in -reach..=reach {
+                    for z in -reach..=reach {
+                        let anchor = ChunkIndex {
+                            x: chunk.index.x + x,
+                            y: anchor_y,
+                            z: chunk.index.z + z,
+                        };
+                        stage.decorate(&mut DecorationContext {
+                            chunk: &mut *chunk,
+                            anchor,
+                            settings,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub trait AddChunkDecorator {
+    /// Register a decoration stage, so plugins can add ores or structures to generated chunks
+    fn add_chunk_decorator(&mut self, decorator: impl ChunkDecorator) -> &mut Self;
+}
+
+impl AddChunkDecorator for App {
+    fn add_chunk_decorator(&mut self, decorator: impl ChunkDecorator) -> &mut Self {
+        self.init_resource::<ChunkDecorators>();
+        self.world.resource_mut::<ChunkDecorators>().add(decorator);
+        self
+    }
+}
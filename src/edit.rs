@@ -0,0 +1,214 @@
+//! WorldEdit style tools for creative mode: two corners picked with the
+//! bracket keys, and bulk edits of the selection queued up and fed to the
+//! voxel modify queue a batch per frame, so a big edit doesn't stall a frame.
+
+use std::collections::VecDeque;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    command::CommandResponse,
+    game_mode::GameMode,
+    input::{InputAction, PlayerInput},
+    protection::{Claim, Claims},
+    voxel::{BlockId, VoxelData, VoxelModifyQueue, VoxelPos, VoxelSettings, AIR},
+    CrosshairTarget,
+};
+
+pub const MAX_EDIT_VOLUME: usize = 64 * 64 * 64;
+pub const MAX_SPHERE_RADIUS: u32 = 31; // the sphere's bounding cube stays within `MAX_EDIT_VOLUME`
+
+/// The two corners of the cuboid bulk edits apply to
+#[derive(Resource, Default, Debug)]
+pub struct Selection {
+    pub first: Option<VoxelPos>,
+    pub second: Option<VoxelPos>,
+}
+
+impl Selection {
+    /// Minimum and maximum corner, both included
+    pub fn bounds(&self) -> Result<(IVec3, IVec3), String> {
+        let (Some(first), Some(second)) = (self.first, self.second) else {
+            return Err("select two corners with [ and ] first".to_string());
+        };
+        Ok((first.0.min(second.0), first.0.max(second.0)))
+    }
+}
+
+/// Edits waiting to be handed to the voxel modify queue
+#[derive(Resource, Default)]
+pub struct BulkEdits {
+    queue: VecDeque<(VoxelPos, BlockId)>,
+}
+
+impl BulkEdits {
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+/// Selection, bulk edit queue and claims, for the commands that edit or claim the selection
+#[derive(SystemParam)]
+pub struct EditTools<'w> {
+    selection: Res<'w, Selection>,
+    bulk_edits: ResMut<'w, BulkEdits>,
+    claims: ResMut<'w, Claims>,
+}
+
+fn cuboid_volume(min: IVec3, max: IVec3) -> usize {
+    let size = (max - min + IVec3::ONE).as_uvec3();
+    size.x as usize * size.y as usize * size.z as usize
+}
+
+fn cuboid(min: IVec3, max: IVec3) -> impl Iterator<Item = VoxelPos> {
+    (min.x..=max.x).flat_map(move |x| {
+        (min.y..=max.y).flat_map(move |y| (min.z..=max.z).map(move |z| VoxelPos::new(x, y, z)))
+    })
+}
+
+impl EditTools<'_> {
+    /// Bounds of the selection, if it's small enough to edit
+    fn selected(&self) -> Result<(IVec3, IVec3), String> {
+        let (min, max) = self.selection.bounds()?;
+        let volume = cuboid_volume(min, max);
+        if volume > MAX_EDIT_VOLUME {
+            return Err(format!(
+                "{} blocks selected, the limit is {}",
+                volume, MAX_EDIT_VOLUME
+            ));
+        }
+        Ok((min, max))
+    }
+
+    fn queue(&mut self, edits: impl Iterator<Item = (VoxelPos, BlockId)>) -> usize {
+        let before = self.bulk_edits.queue.len();
+        self.bulk_edits.queue.extend(edits);
+        self.bulk_edits.queue.len() - before
+    }
+
+    /// Fill the selection with a block, returns the blocks queued
+    pub fn set(&mut self, block: BlockId) -> Result<usize, String> {
+        let (min, max) = self.selected()?;
+        Ok(self.queue(cuboid(min, max).map(|position| (position, block))))
+    }
+
+    /// Swap one block for another inside the selection
+    pub fn replace(
+        &mut self,
+        voxel_data: &VoxelData,
+        from: BlockId,
+        to: BlockId,
+    ) -> Result<usize, String> {
+        let (min, max) = self.selected()?;
+        let edits = cuboid(min, max)
+            .filter(|position| voxel_data.get_block(*position) == from)
+            .map(|position| (position, to));
+        Ok(self.queue(edits))
+    }
+
+    /// Clear the inside of the selection, keeping its outer walls
+    pub fn hollow(&mut self) -> Result<usize, String> {
+        let (min, max) = self.selected()?;
+        if (max - min).min_element() < 2 {
+            return Ok(0);
+        }
+        Ok(self.queue(cuboid(min + IVec3::ONE, max - IVec3::ONE).map(|position| (position, AIR))))
+    }
+
+    /// Claim the selection for `owner`
+    pub fn claim_selection(&mut self, owner: u32, name: &str) -> Result<&Claim, String> {
+        let (min, max) = self.selection.bounds()?;
+        self.claims.claim(owner, name, VoxelPos(min), VoxelPos(max))
+    }
+
+    pub fn claims(&mut self) -> &mut Claims {
+        &mut self.claims
+    }
+
+    /// Solid sphere of a block around `center`, independent of the selection
+    pub fn sphere(
+        &mut self,
+        center: VoxelPos,
+        radius: u32,
+        block: BlockId,
+    ) -> Result<usize, String> {
+        if radius > MAX_SPHERE_RADIUS {
+            return Err(format!(
+                "radius {} is too big, the limit is {}",
+                radius, MAX_SPHERE_RADIUS
+            ));
+        }
+        let r = radius as i32;
+        let edits = cuboid(center.0 - IVec3::splat(r), center.0 + IVec3::splat(r))
+            .filter(|position| (position.0 - center.0).length_squared() <= r * r)
+            .map(|position| (position, block));
+        Ok(self.queue(edits))
+    }
+}
+
+/// [ and ] pick the targeted block as the first and second corner, in creative mode
+pub fn select_corners(
+    player_input: Res<PlayerInput>,
+    game_mode: Res<GameMode>,
+    crosshair: CrosshairTarget,
+    mut selection: ResMut<Selection>,
+    mut responses: EventWriter<CommandResponse>,
+) {
+    if *game_mode != GameMode::Creative {
+        return;
+    }
+    let actions = &player_input.actions;
+    let first = actions.just_pressed(InputAction::SelectFirstCorner);
+    if !first && !actions.just_pressed(InputAction::SelectSecondCorner) {
+        return;
+    }
+    let Some((target, _)) = crosshair.get() else {
+        return;
+    };
+    let corner = if first {
+        &mut selection.first
+    } else {
+        &mut selection.second
+    };
+    *corner = Some(target);
+    responses.send(CommandResponse {
+        message: format!(
+            "{} corner set to {}",
+            if first { "First" } else { "Second" },
+            target.0
+        ),
+    });
+}
+
+pub fn draw_selection(selection: Res<Selection>, mut gizmos: Gizmos) {
+    let Ok((min, max)) = selection.bounds() else {
+        return;
+    };
+    let size = (max - min + IVec3::ONE).as_vec3();
+    gizmos.cuboid(
+        Transform::from_translation(min.as_vec3() + size / 2.0).with_scale(size),
+        Color::ORANGE,
+    );
+}
+
+/// Move up to `bulk_edit_budget` queued bulk edits into the voxel modify queue
+pub fn apply_bulk_edits(
+    voxel_settings: Res<VoxelSettings>,
+    mut bulk_edits: ResMut<BulkEdits>,
+    mut voxel_modify_queue: ResMut<VoxelModifyQueue>,
+) {
+    if bulk_edits.is_empty() {
+        return;
+    }
+    let batch = bulk_edits
+        .queue
+        .len()
+        .min(voxel_settings.bulk_edit_budget as usize);
+    voxel_modify_queue
+        .queue
+        .extend(bulk_edits.queue.drain(..batch));
+}
@@ -0,0 +1,167 @@
+//! Falling blocks: sand and gravel with nothing under them come loose and fall
+//! as entities, then settle back into the world as blocks where they land, or
+//! drop as items when something already took the spot. A stack comes down all
+//! at once, each block coming loose with the one under it.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::{
+    protection::{Claims, Editor},
+    voxel::{self, BlockId, VoxelData, VoxelModifyQueue, VoxelPos, AIR},
+    BlockBroken, VoxelMaterial,
+};
+
+const GRAVITY: f32 = 20.0;
+const TERMINAL_VELOCITY: f32 = 30.0; // under a block per frame, so nothing falls through the ground
+
+/// Blocks taken out of the world by the last edits, waiting to be spawned falling
+#[derive(Resource, Default)]
+pub struct LooseBlocks {
+    blocks: Vec<(VoxelPos, BlockId, Option<Editor>)>,
+}
+
+impl LooseBlocks {
+    /// Take the block at `position` out of the world if it falls and has nothing under it. It
+    /// comes loose for the `editor` of the edit under it, if that edit was guarded
+    pub(crate) fn loosen(
+        &mut self,
+        voxel_data: &VoxelData,
+        voxel_modify_queue: &mut VoxelModifyQueue,
+        position: VoxelPos,
+        editor: Option<Editor>,
+    ) {
+        let block = voxel_data.get_block(position);
+        let below = VoxelPos(position.0 - IVec3::Y);
+        // the edit taking it out comes later in the queue, so it may be seen twice before that
+        let loose = voxel::falls(block)
+            && voxel_data.is_loaded(below.split().0)
+            && voxel_data.get_block(below) == AIR
+            && !self
+                .blocks
+                .iter()
+                .any(|&(p, b, _)| (p, b) == (position, block));
+        if !loose {
+            return;
+        }
+        match editor {
+            Some(editor) => voxel_modify_queue.guarded.push((position, AIR, editor)),
+            None => voxel_modify_queue.queue.push((position, AIR)),
+        }
+        self.blocks.push((position, block, editor));
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct FallingBlock {
+    block: BlockId,
+    velocity: f32,          // downwards, blocks per second
+    editor: Option<Editor>, // it lands for, the one it came loose for
+}
+
+impl FallingBlock {
+    pub fn block(&self) -> BlockId {
+        self.block
+    }
+
+    pub fn velocity(&self) -> f32 {
+        self.velocity
+    }
+}
+
+/// Spawn `block` falling at `velocity` with its center at `translation`
+pub(crate) fn spawn_falling_block(
+    commands: &mut Commands,
+    mesh: Handle<Mesh>,
+    voxel_material: &VoxelMaterial,
+    (block, editor): (BlockId, Option<Editor>),
+    velocity: f32,
+    translation: Vec3,
+) {
+    commands.spawn((
+        MaterialMeshBundle {
+            mesh,
+            material: voxel_material.material.clone(),
+            transform: Transform::from_translation(translation),
+            ..default()
+        },
+        FallingBlock {
+            block,
+            velocity,
+            editor,
+        },
+        Name::new("Falling block"),
+    ));
+}
+
+pub fn spawn_falling_blocks(
+    mut commands: Commands,
+    mut loose_blocks: ResMut<LooseBlocks>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    voxel_material: Res<VoxelMaterial>,
+    mut block_meshes: Local<HashMap<BlockId, Handle<Mesh>>>,
+) {
+    if loose_blocks.blocks.is_empty() {
+        return;
+    }
+    for (position, block, editor) in loose_blocks.blocks.drain(..) {
+        let mesh = block_meshes
+            .entry(block)
+            .or_insert_with(|| meshes.add(voxel::block_mesh(block).into()))
+            .clone();
+        let translation = position.to_world() + Vec3::splat(0.5);
+        spawn_falling_block(
+            &mut commands,
+            mesh,
+            &voxel_material,
+            (block, editor),
+            0.0,
+            translation,
+        );
+    }
+}
+
+/// Fall until there's a block underneath, then turn back into a block on top of it, or drop
+/// as an item where it isn't allowed to land
+pub fn update_falling_blocks(
+    mut commands: Commands,
+    time: Res<Time>,
+    claims: Res<Claims>,
+    voxel_data: Res<VoxelData>,
+    mut voxel_modify_queue: ResMut<VoxelModifyQueue>,
+    mut broken_events: EventWriter<BlockBroken>,
+    mut falling_query: Query<(Entity, &mut FallingBlock, &mut Transform)>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut falling, mut transform) in falling_query.iter_mut() {
+        if !voxel_data.is_loaded(voxel::get_chunk_index(&transform.translation)) {
+            continue;
+        }
+        falling.velocity = (falling.velocity + GRAVITY * dt).min(TERMINAL_VELOCITY);
+        let next = transform.translation - Vec3::Y * falling.velocity * dt;
+        let bottom = VoxelPos::from_world(next - Vec3::Y * 0.5);
+        if !voxel::is_solid(voxel_data.get_block(bottom)) {
+            transform.translation = next;
+            continue;
+        }
+        let landed = VoxelPos(bottom.0 + IVec3::Y);
+        let allowed = falling
+            .editor
+            .is_none_or(|editor| claims.allows(editor, landed));
+        if voxel_data.get_block(landed) == AIR && allowed {
+            match falling.editor {
+                Some(editor) => voxel_modify_queue
+                    .guarded
+                    .push((landed, falling.block, editor)),
+                None => voxel_modify_queue.queue.push((landed, falling.block)),
+            }
+        } else {
+            broken_events.send(BlockBroken {
+                position: landed,
+                block: falling.block,
+            });
+        }
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,81 @@
+//! Farming: the hoe tills grass and dirt into farmland, wearing a little with
+//! each use, seeds planted on it grow into wheat a stage at a time on random
+//! ticks, and ripe wheat is eaten to fill up on food.
+
+use bevy::prelude::*;
+use smooth_bevy_cameras::controllers::fps::FpsCameraController;
+
+use crate::{
+    game_mode::GameMode,
+    hunger::Hunger,
+    input::{InputAction, PlayerInput},
+    item::{HeldItem, PlayerItems},
+    random_tick::TickContext,
+    region::splitmix64,
+    tool::Tool,
+    voxel::{
+        self, BlockId, BlockTag, VoxelModifyQueue, VoxelPos, AIR, FARMLAND, RIPE_WHEAT, WHEAT,
+    },
+    CrosshairTarget,
+};
+
+const WHEAT_FOOD: u32 = 5; // half drumsticks a ripe wheat fills
+
+/// Till, plant or eat with the place button, depending on the held item
+pub fn use_held_item(
+    crosshair: CrosshairTarget,
+    player_input: Res<PlayerInput>,
+    held_item: Res<HeldItem>,
+    mut items: PlayerItems,
+    mut voxel_modify_queue: ResMut<VoxelModifyQueue>,
+    mut hunger_query: Query<&mut Hunger, With<FpsCameraController>>,
+) {
+    let game_mode = *items.game_mode;
+    if !player_input.actions.just_pressed(InputAction::Place) || game_mode == GameMode::Spectator {
+        return;
+    }
+    let Ok(mut hunger) = hunger_query.get_single_mut() else {
+        return;
+    };
+    if *held_item == HeldItem::Food {
+        // only hunger in survival makes eating worth it
+        let survival = game_mode == GameMode::Survival;
+        if survival && hunger.food < hunger.max && items.take(RIPE_WHEAT, 1) {
+            hunger.eat(WHEAT_FOOD);
+        }
+        return;
+    }
+    let Some((target, _)) = crosshair.get() else {
+        return;
+    };
+    let voxel_data = &crosshair.voxel_data;
+    let above = VoxelPos(target.0 + IVec3::Y);
+    if voxel_data.get_block(above) != AIR {
+        return;
+    }
+    let block = voxel_data.get_block(target);
+    match *held_item {
+        HeldItem::Hoe if voxel::has_tag(block, BlockTag::DirtLike) && items.use_tool(Tool::Hoe) => {
+            voxel_modify_queue.queue.push((target, FARMLAND));
+        }
+        HeldItem::Seeds if block == FARMLAND && items.take(WHEAT, 1) => {
+            voxel_modify_queue.queue.push((above, WHEAT));
+        }
+        _ => {}
+    }
+}
+
+/// Random number for a block, different every `salt`
+pub(crate) fn position_roll(position: VoxelPos, salt: u64) -> u64 {
+    let IVec3 { x, y, z } = position.0;
+    let coords = ((x as u32 as u64) << 40) ^ ((y as u32 as u64) << 20) ^ z as u32 as u64;
+    splitmix64(salt ^ splitmix64(coords))
+}
+
+/// Crops on farmland grow a stage every random tick that picks them
+pub(crate) fn grow_crop(ctx: &mut TickContext, position: VoxelPos, block: BlockId) {
+    let below = VoxelPos(position.0 - IVec3::Y);
+    if ctx.get(below) == FARMLAND {
+        ctx.set(position, block.offset(1));
+    }
+}
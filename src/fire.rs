@@ -0,0 +1,252 @@
+//! Fire: lit with flint and steel, which wears like the hoe, it spreads onto
+//! the flammable blocks around it on random ticks and burns them away, going
+//! out once there's nothing left to burn. Fires close to the player glow with
+//! a few point lights and throw up sparks.
+
+use bevy::{
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+};
+
+use crate::{
+    game_mode::GameMode,
+    input::{InputAction, PlayerInput},
+    item::{HeldItem, PlayerItems},
+    player::Player,
+    random_tick::TickContext,
+    region::splitmix64,
+    tnt::LightTnt,
+    tool::Tool,
+    voxel::{
+        self, BlockTag, VoxelData, VoxelLocalIndex, VoxelModifyQueue, VoxelPos, AIR, FIRE, TNT,
+    },
+    CrosshairTarget,
+};
+
+const SPREAD_CHANCE: u64 = 2; // one in this many flammable neighbours catches fire on a tick
+const BURN_OUT_CHANCE: u64 = 3; // one in this many ticks puts out a fire that still has fuel
+const EFFECT_RADIUS: i32 = 2; // chunks around the player whose fires glow and spark
+const MAX_LIGHTS: usize = 4; // the nearest fires get a light each
+const LIGHT_COLOR: Color = Color::rgb(1.0, 0.6, 0.25);
+const LIGHT_INTENSITY: f32 = 600.0;
+const LIGHT_RANGE: f32 = 10.0;
+const SPARK_RATE: f32 = 3.0; // sparks per second from each fire
+const SPARK_LIFETIME: f32 = 1.2; // seconds
+const SPARK_SPEED: f32 = 1.5; // blocks per second, upwards
+const SPARK_SIZE: f32 = 0.08;
+
+const NEIGHBOURS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+/// Random tick of a fire: catch onto the flammable blocks around it, or go out
+pub(crate) fn spread_fire(ctx: &mut TickContext, position: VoxelPos) {
+    let mut fuel = false;
+    for offset in NEIGHBOURS {
+        let neighbour = VoxelPos(position.0 + offset);
+        if !voxel::has_tag(ctx.get(neighbour), BlockTag::Flammable) {
+            continue;
+        }
+        fuel = true;
+        if ctx.random().is_multiple_of(SPREAD_CHANCE) {
+            ctx.set(neighbour, FIRE);
+        }
+    }
+    if !fuel || ctx.random().is_multiple_of(BURN_OUT_CHANCE) {
+        ctx.set(position, AIR);
+    }
+}
+
+/// Set fire to the air in front of the block looked at while holding flint and steel,
+/// or light it if it's TNT
+pub fn ignite(
+    crosshair: CrosshairTarget,
+    player_input: Res<PlayerInput>,
+    held_item: Res<HeldItem>,
+    mut items: PlayerItems,
+    mut voxel_modify_queue: ResMut<VoxelModifyQueue>,
+    mut light_events: EventWriter<LightTnt>,
+) {
+    if !player_input.actions.just_pressed(InputAction::Place)
+        || *held_item != HeldItem::FlintAndSteel
+        || *items.game_mode == GameMode::Spectator
+    {
+        return;
+    }
+    let Some((target, previous)) = crosshair.get() else {
+        return;
+    };
+    let voxel_data = &crosshair.voxel_data;
+    let block = voxel_data.get_block(target);
+    if voxel::on_use(block).is_some() {
+        return;
+    }
+    let lit = block == TNT || voxel_data.get_block(previous) == AIR;
+    if !lit || !items.use_tool(Tool::FlintAndSteel) {
+        return;
+    }
+    if block == TNT {
+        light_events.send(LightTnt::new(target));
+    } else {
+        voxel_modify_queue.queue.push((previous, FIRE));
+    }
+}
+
+/// Fires around the player, nearest first
+#[derive(Resource, Default)]
+pub struct NearbyFires {
+    positions: Vec<VoxelPos>,
+}
+
+#[derive(Component)]
+pub struct FireLight;
+
+#[derive(Component)]
+pub struct Spark {
+    age: f32,
+}
+
+#[derive(Resource)]
+pub struct SparkAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+pub fn setup_fire(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for _ in 0..MAX_LIGHTS {
+        commands.spawn((
+            PointLightBundle {
+                point_light: PointLight {
+                    color: LIGHT_COLOR,
+                    intensity: LIGHT_INTENSITY,
+                    range: LIGHT_RANGE,
+                    ..default()
+                },
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            FireLight,
+            Name::new("Fire light"),
+        ));
+    }
+    commands.insert_resource(SparkAssets {
+        mesh: meshes.add(shape::Cube::new(SPARK_SIZE).into()),
+        material: materials.add(StandardMaterial {
+            base_color: LIGHT_COLOR,
+            unlit: true,
+            ..default()
+        }),
+    });
+}
+
+/// Look for fires in the chunks around the player, a scan is too slow to run every frame
+pub fn find_nearby_fires(
+    voxel_data: Res<VoxelData>,
+    player_query: Query<&Transform, With<Player>>,
+    mut nearby_fires: ResMut<NearbyFires>,
+) {
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+    let center = voxel::get_chunk_index(&player.translation);
+    let mut positions = Vec::new();
+    for chunk in voxel_data.iter_chunks() {
+        let index = chunk.index;
+        let near = (index.x - center.x).abs() <= EFFECT_RADIUS
+            && (index.y - center.y).abs() <= EFFECT_RADIUS
+            && (index.z - center.z).abs() <= EFFECT_RADIUS;
+        if !near {
+            continue;
+        }
+        for (x, plane) in chunk.voxels.iter().enumerate() {
+            for (y, row) in plane.iter().enumerate() {
+                for (z, block) in row.iter().enumerate() {
+                    if *block != FIRE {
+                        continue;
+                    }
+                    let local = VoxelLocalIndex {
+                        x: x as u8,
+                        y: y as u8,
+                        z: z as u8,
+                    };
+                    positions.push(VoxelPos::from_parts(index, local));
+                }
+            }
+        }
+    }
+    let distance = |position: &VoxelPos| position.to_world().distance_squared(player.translation);
+    positions.sort_by(|a, b| distance(a).total_cmp(&distance(b)));
+    if positions != nearby_fires.positions {
+        nearby_fires.positions = positions;
+    }
+}
+
+/// Put the lights on the nearest fires and throw sparks up from all of them
+pub fn update_fire_effects(
+    mut commands: Commands,
+    time: Res<Time>,
+    nearby_fires: Res<NearbyFires>,
+    spark_assets: Res<SparkAssets>,
+    mut light_query: Query<(&mut Transform, &mut Visibility), With<FireLight>>,
+    mut spark_query: Query<(Entity, &mut Spark, &mut Transform), Without<FireLight>>,
+    mut state: Local<u64>,
+) {
+    let mut fires = nearby_fires.positions.iter();
+    for (mut transform, mut visibility) in light_query.iter_mut() {
+        let shown = match fires.next() {
+            Some(fire) => {
+                let center = fire.to_world() + Vec3::splat(0.5);
+                if transform.translation != center {
+                    transform.translation = center;
+                }
+                Visibility::Inherited
+            }
+            None => Visibility::Hidden,
+        };
+        if *visibility != shown {
+            *visibility = shown;
+        }
+    }
+
+    let dt = time.delta_seconds();
+    for (entity, mut spark, mut transform) in spark_query.iter_mut() {
+        spark.age += dt;
+        if spark.age > SPARK_LIFETIME {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        transform.translation.y += SPARK_SPEED * dt;
+        transform.scale = Vec3::splat(1.0 - spark.age / SPARK_LIFETIME);
+    }
+
+    let mut random = || {
+        *state = splitmix64(*state);
+        (*state >> 40) as f32 / (1u64 << 24) as f32
+    };
+    for fire in nearby_fires.positions.iter() {
+        if random() >= SPARK_RATE * dt {
+            continue;
+        }
+        let offset = Vec3::new(random(), random() * 0.5, random());
+        commands.spawn((
+            PbrBundle {
+                mesh: spark_assets.mesh.clone(),
+                material: spark_assets.material.clone(),
+                transform: Transform::from_translation(fire.to_world() + offset),
+                ..default()
+            },
+            Spark { age: 0.0 },
+            NotShadowCaster,
+            NotShadowReceiver,
+        ));
+    }
+}
@@ -0,0 +1,77 @@
+//! The free camera, for taking pictures: the player's body stays where it
+//! stood while the camera flies through blocks as a spectator, with the HUD
+//! hidden. In cinematic mode the camera glides along a heavily smoothed path.
+
+use bevy::prelude::*;
+use smooth_bevy_cameras::{controllers::fps::FpsCameraController, LookTransform, Smoother};
+
+use crate::{
+    camera::CameraSettings,
+    game_mode::GameMode,
+    input::{InputAction, PlayerInput},
+};
+
+const CINEMATIC_SMOOTHING: f32 = 0.98; // lag weight of the camera in cinematic mode
+
+/// Where the body was left, None while the camera is the player's eye
+#[derive(Resource, Default)]
+pub struct FreeCamera {
+    body: Option<LeftBody>,
+}
+
+struct LeftBody {
+    eye: Vec3,
+    target: Vec3,
+    game_mode: GameMode, // the camera flies as a spectator until it's back
+}
+
+impl FreeCamera {
+    pub fn active(&self) -> bool {
+        self.body.is_some()
+    }
+}
+
+/// Detach the camera from the body or put it back in, `update_hud` hides the HUD while it's
+/// detached
+pub fn toggle_free_camera(
+    player_input: Res<PlayerInput>,
+    camera_settings: Res<CameraSettings>,
+    mut game_mode: ResMut<GameMode>,
+    mut free_camera: ResMut<FreeCamera>,
+    mut camera_query: Query<(&FpsCameraController, &mut LookTransform, &mut Smoother)>,
+) {
+    let Ok((controller, mut look, mut smoother)) = camera_query.get_single_mut() else {
+        return;
+    };
+    let mut returned = false;
+    if player_input.actions.just_pressed(InputAction::FreeCamera) {
+        match free_camera.body.take() {
+            Some(body) => {
+                look.eye = body.eye;
+                look.target = body.target;
+                if *game_mode == GameMode::Spectator {
+                    *game_mode = body.game_mode;
+                }
+                returned = true;
+            }
+            None => {
+                free_camera.body = Some(LeftBody {
+                    eye: look.eye,
+                    target: look.target,
+                    game_mode: *game_mode,
+                });
+                *game_mode = GameMode::Spectator;
+            }
+        }
+    }
+
+    let active = free_camera.active();
+    let lag_weight = if returned {
+        0.0 // straight back into the body instead of flying there
+    } else if active && camera_settings.cinematic {
+        CINEMATIC_SMOOTHING
+    } else {
+        controller.smoothing_weight
+    };
+    smoother.set_lag_weight(lag_weight);
+}
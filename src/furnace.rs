@@ -0,0 +1,357 @@
+//! Furnaces: fuel burning in a furnace smelts the items put in it one at a
+//! time, sand into glass. They burn and smelt in every loaded chunk whether
+//! or not their window is open, and drop what's in them when they're mined.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+use smooth_bevy_cameras::controllers::fps::FpsCameraController;
+
+use crate::{
+    block_entity::{BlockEntity, BlockEntityRemoved},
+    input::{InputAction, PlayerInput},
+    interact::OpenBlock,
+    item::{spawn_item, Inventory, ItemMeshes},
+    voxel::{
+        self, BlockId, BlockTag, VoxelData, VoxelPos, VoxelSettings, FURNACE, GLASS, LOG, SAND,
+    },
+    InputMode, VoxelMaterial,
+};
+
+pub const SMELT_TICK: f32 = 0.25; // seconds between smelting ticks
+const SMELT_TIME: f32 = 5.0; // seconds to smelt one item
+const LOG_BURN_TIME: f32 = 15.0;
+const BURN_TIME: f32 = 3.0; // of other flammable blocks
+const STACK_LIMIT: u32 = 64; // items in a furnace slot
+
+/// An item and how many of it, or nothing
+pub type Slot = Option<(BlockId, u32)>;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Furnace {
+    pub input: Slot,
+    pub fuel: Slot,
+    pub output: Slot,
+    burn_left: f32, // seconds the lit fuel still burns
+    burn_time: f32, // seconds the lit fuel burns in all
+    progress: f32,  // seconds the next item has been smelting
+}
+
+/// What `item` smelts into
+fn smelted(item: BlockId) -> Option<BlockId> {
+    match item {
+        SAND => Some(GLASS),
+        _ => None,
+    }
+}
+
+/// Seconds `item` burns for as fuel
+fn burn_time(item: BlockId) -> Option<f32> {
+    match item {
+        LOG => Some(LOG_BURN_TIME),
+        _ if voxel::has_tag(item, BlockTag::Flammable) => Some(BURN_TIME),
+        _ => None,
+    }
+}
+
+/// Whether `count` more of `item` fit in `slot`
+fn fits(slot: Slot, item: BlockId, count: u32) -> bool {
+    match slot {
+        None => count <= STACK_LIMIT,
+        Some((held, held_count)) => held == item && held_count + count <= STACK_LIMIT,
+    }
+}
+
+fn take_one(slot: &mut Slot) {
+    if let Some((_, count)) = slot {
+        *count -= 1;
+        if *count == 0 {
+            *slot = None;
+        }
+    }
+}
+
+fn put(slot: &mut Slot, item: BlockId, count: u32) {
+    match slot {
+        Some((_, held)) => *held += count,
+        None => *slot = Some((item, count)),
+    }
+}
+
+impl Furnace {
+    /// Burn the fuel and smelt the input for `dt` seconds
+    pub fn tick(&mut self, dt: f32) {
+        let smelting = self
+            .input
+            .and_then(|(item, _)| smelted(item))
+            .filter(|result| fits(self.output, *result, 1));
+        // fresh fuel is only lit when there's something to smelt
+        if self.burn_left <= 0.0 && smelting.is_some() {
+            if let Some(time) = self.fuel.and_then(|(item, _)| burn_time(item)) {
+                take_one(&mut self.fuel);
+                self.burn_left = time;
+                self.burn_time = time;
+            }
+        }
+        if self.burn_left <= 0.0 {
+            self.progress = 0.0;
+            return;
+        }
+        self.burn_left = (self.burn_left - dt).max(0.0);
+        let Some(result) = smelting else {
+            self.progress = 0.0;
+            return;
+        };
+        self.progress += dt;
+        if self.progress >= SMELT_TIME {
+            self.progress = 0.0;
+            take_one(&mut self.input);
+            put(&mut self.output, result, 1);
+        }
+    }
+
+    /// What's left of the lit fuel, from 1 when it was lit to 0 when it's out
+    pub fn flame(&self) -> f32 {
+        if self.burn_time > 0.0 {
+            self.burn_left / self.burn_time
+        } else {
+            0.0
+        }
+    }
+
+    /// How far the next item is smelted, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        self.progress / SMELT_TIME
+    }
+
+    /// Everything in the slots
+    pub fn contents(&self) -> impl Iterator<Item = (BlockId, u32)> {
+        [self.input, self.fuel, self.output].into_iter().flatten()
+    }
+}
+
+/// Burn and smelt in the furnaces of every loaded chunk, runs every `SMELT_TICK`
+pub fn smelt(mut voxel_data: ResMut<VoxelData>) {
+    let mut changed = Vec::new();
+    for chunk in voxel_data.iter_chunks_mut() {
+        for entity in chunk.entities.values_mut() {
+            if let BlockEntity::Furnace(furnace) = entity {
+                let before = furnace.clone();
+                furnace.tick(SMELT_TICK);
+                if *furnace != before {
+                    changed.push(chunk.index);
+                }
+            }
+        }
+    }
+    for index in changed {
+        voxel_data.mark_modified(index);
+    }
+}
+
+/// Drop the contents of mined furnaces
+pub fn drop_furnace_contents(
+    mut commands: Commands,
+    mut removed_events: EventReader<BlockEntityRemoved>,
+    mut item_meshes: ResMut<ItemMeshes>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    voxel_material: Res<VoxelMaterial>,
+) {
+    for event in removed_events.iter() {
+        let BlockEntity::Furnace(furnace) = &event.entity else {
+            continue;
+        };
+        for stack in furnace.contents() {
+            spawn_item(
+                &mut commands,
+                &mut item_meshes,
+                &mut meshes,
+                &voxel_material,
+                stack,
+                event.position,
+            );
+        }
+    }
+}
+
+/// The furnace whose window is open
+#[derive(Resource, Default)]
+pub struct FurnaceWindow {
+    pub open: Option<VoxelPos>,
+}
+
+pub fn furnace_closed(furnace_window: Res<FurnaceWindow>) -> bool {
+    furnace_window.open.is_none()
+}
+
+/// What moving a stack between the inventory and a furnace slot does
+enum Transfer {
+    Take(fn(&mut Furnace) -> &mut Slot),
+    Put(BlockId, fn(&mut Furnace) -> &mut Slot),
+}
+
+/// The furnaces and the inventory, that items move between
+#[derive(SystemParam)]
+pub struct FurnaceSlots<'w> {
+    voxel_data: ResMut<'w, VoxelData>,
+    inventory: ResMut<'w, Inventory>,
+}
+
+/// How far the player reaches from the camera
+#[derive(SystemParam)]
+pub struct PlayerReach<'w, 's> {
+    voxel_settings: Res<'w, VoxelSettings>,
+    camera_query: Query<'w, 's, &'static GlobalTransform, With<FpsCameraController>>,
+}
+
+impl PlayerReach<'_, '_> {
+    /// Whether the block at `position` is within a block of the interact distance, None
+    /// without a camera
+    fn reaches(&self, position: VoxelPos) -> Option<bool> {
+        let transform = self.camera_query.get_single().ok()?;
+        let center = position.to_world() + Vec3::splat(0.5);
+        Some(
+            transform.translation().distance(center) <= self.voxel_settings.interact_distance + 1.0,
+        )
+    }
+}
+
+/// The window of a used furnace: its slots with the items in the inventory that go in them.
+/// It closes with Esc, or once the furnace is gone or out of reach
+pub fn furnace_window(
+    mut contexts: EguiContexts,
+    mut open_events: EventReader<OpenBlock>,
+    player_input: Res<PlayerInput>,
+    mut furnace_window: ResMut<FurnaceWindow>,
+    mut slots: FurnaceSlots,
+    reach: PlayerReach,
+    mut input_mode: InputMode,
+) {
+    let FurnaceSlots {
+        voxel_data,
+        inventory,
+    } = &mut slots;
+    let was_open = furnace_window.open.is_some();
+    for event in open_events.iter() {
+        if voxel_data.get_block(event.position) == FURNACE {
+            furnace_window.open = Some(event.position);
+        }
+    }
+    let Some(position) = furnace_window.open else {
+        return;
+    };
+    let Some(in_reach) = reach.reaches(position) else {
+        return;
+    };
+    let furnace = match voxel_data.get_entity(position) {
+        Some(BlockEntity::Furnace(furnace)) => Some(furnace.clone()),
+        _ => None,
+    };
+    let mut open = furnace.is_some()
+        && in_reach
+        && (!was_open || !player_input.actions.just_pressed(InputAction::Menu));
+
+    let mut transfer = None;
+    if let Some(furnace) = furnace.as_ref().filter(|_| open) {
+        let show_slot = |ui: &mut egui::Ui, label: &str, slot: Slot| -> bool {
+            ui.horizontal(|ui| {
+                ui.label(label);
+                match slot {
+                    Some((item, count)) => {
+                        ui.label(format!("{} x{}", voxel::block_name(item), count));
+                        ui.button("Take").clicked()
+                    }
+                    None => {
+                        ui.label("-");
+                        false
+                    }
+                }
+            })
+            .inner
+        };
+        egui::Window::new("Furnace")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(contexts.ctx_mut(), |ui| {
+                if show_slot(ui, "Input", furnace.input) {
+                    transfer = Some(Transfer::Take(|furnace| &mut furnace.input));
+                }
+                ui.add(egui::ProgressBar::new(furnace.progress()).text("Smelting"));
+                if show_slot(ui, "Fuel", furnace.fuel) {
+                    transfer = Some(Transfer::Take(|furnace| &mut furnace.fuel));
+                }
+                ui.add(egui::ProgressBar::new(furnace.flame()).text("Fire"));
+                if show_slot(ui, "Output", furnace.output) {
+                    transfer = Some(Transfer::Take(|furnace| &mut furnace.output));
+                }
+                ui.separator();
+                ui.label("Inventory");
+                for (item, count) in inventory.iter() {
+                    let name = voxel::block_name(item);
+                    if smelted(item).is_some()
+                        && ui.button(format!("Smelt {} x{}", name, count)).clicked()
+                    {
+                        transfer = Some(Transfer::Put(item, |furnace| &mut furnace.input));
+                    }
+                    if burn_time(item).is_some()
+                        && ui.button(format!("Burn {} x{}", name, count)).clicked()
+                    {
+                        transfer = Some(Transfer::Put(item, |furnace| &mut furnace.fuel));
+                    }
+                }
+            });
+    }
+
+    // the whole stack moves, as much of it as fits in the slot
+    let furnace = match transfer {
+        Some(_) => voxel_data.entity_mut(position),
+        None => None,
+    };
+    if let (Some(transfer), Some(BlockEntity::Furnace(furnace))) = (transfer, furnace) {
+        match transfer {
+            Transfer::Take(slot) => {
+                if let Some((item, count)) = slot(furnace).take() {
+                    inventory.add(item, count);
+                }
+            }
+            Transfer::Put(item, slot) => {
+                let slot = slot(furnace);
+                let room = STACK_LIMIT - slot.map_or(0, |(_, count)| count);
+                let count = inventory.count(item).min(room);
+                if count > 0 && fits(*slot, item, count) && inventory.take(item, count) {
+                    put(slot, item, count);
+                }
+            }
+        }
+    }
+
+    if !open {
+        furnace_window.open = None;
+    }
+    // the cursor is free while the window is open
+    if open != input_mode.ms.ui_mode && (open || was_open) {
+        input_mode.set_ui_mode(open);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_log_smelts_three_sand() {
+        let mut furnace = Furnace {
+            input: Some((SAND, 5)),
+            fuel: Some((LOG, 1)),
+            ..default()
+        };
+        for _ in 0..(60.0 / SMELT_TICK) as u32 {
+            furnace.tick(SMELT_TICK);
+        }
+        assert_eq!(furnace.output, Some((GLASS, 3)));
+        assert_eq!(furnace.input, Some((SAND, 2)));
+        assert_eq!(furnace.fuel, None);
+        assert_eq!(furnace.flame(), 0.0);
+    }
+}
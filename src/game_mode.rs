@@ -0,0 +1,25 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Creative flies, places endless blocks and mines them instantly; survival walks,
+/// places from the inventory, takes time to mine and has health and hunger
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+    #[default]
+    Creative,
+    Survival,
+    Spectator,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 3] = [GameMode::Creative, GameMode::Survival, GameMode::Spectator];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "creative" | "c" | "1" => Some(GameMode::Creative),
+            "survival" | "s" | "0" => Some(GameMode::Survival),
+            "spectator" | "sp" | "3" => Some(GameMode::Spectator),
+            _ => None,
+        }
+    }
+}
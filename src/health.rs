@@ -0,0 +1,233 @@
+//! Player health in survival mode: hurt by hostile mobs and by hitting the
+//! ground too fast, shown as hearts at the bottom of the screen. Dying brings
+//! up the death screen, and respawning puts the player back on the last bed
+//! they used, or at the spawn point without one, with full health.
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_egui::{egui, EguiContexts};
+use smooth_bevy_cameras::controllers::fps::FpsCameraController;
+
+use crate::{
+    apply_input_mode,
+    game_mode::GameMode,
+    hunger::Hunger,
+    player::Player,
+    save::WorldSlot,
+    teleport::{Destination, PendingTeleport},
+    voxel::{self, VoxelData, VoxelPos},
+    MouseSettings,
+};
+
+pub const MAX_HEALTH: u32 = 20; // in half hearts
+const HEARTS: u32 = MAX_HEALTH / 2;
+const SAFE_FALL_SPEED: f32 = 12.0; // blocks per second hitting the ground without damage
+const TELEPORT_SPEED: f32 = 100.0; // moving down faster than this is a teleport, not a fall
+const FALL_SPEED_PER_DAMAGE: f32 = 2.0; // every this many blocks per second above safe hurts
+const HEART_SIZE: f32 = 16.0;
+
+#[derive(Component, Debug)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Health {
+            current: MAX_HEALTH,
+            max: MAX_HEALTH,
+        }
+    }
+}
+
+/// Damage dealt to the player, in half hearts
+#[derive(Event, Debug, Clone)]
+pub struct Damage {
+    pub amount: u32,
+    pub cause: &'static str,
+}
+
+/// How the player died, while the death screen is up
+#[derive(Resource, Default)]
+pub struct DeathScreen {
+    cause: Option<&'static str>,
+}
+
+pub fn death_screen_closed(death_screen: Res<DeathScreen>) -> bool {
+    death_screen.cause.is_none()
+}
+
+#[derive(Component)]
+pub struct HeartsHud;
+
+#[derive(Component)]
+pub struct Heart(u32); // position in the row, from the left
+
+pub fn setup_hearts(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(10.0),
+                    width: Val::Percent(100.),
+                    justify_content: JustifyContent::Center,
+                    column_gap: Val::Px(4.0),
+                    ..default()
+                },
+                ..default()
+            },
+            HeartsHud,
+        ))
+        .with_children(|parent| {
+            for index in 0..HEARTS {
+                parent.spawn((
+                    NodeBundle {
+                        style: Style {
+                            width: Val::Px(HEART_SIZE),
+                            height: Val::Px(HEART_SIZE),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    Heart(index),
+                ));
+            }
+        });
+}
+
+/// Fill the hearts from the player's health, hidden outside survival where nothing hurts
+pub fn update_hearts(
+    game_mode: Res<GameMode>,
+    health_query: Query<&Health>,
+    mut hud_query: Query<&mut Visibility, With<HeartsHud>>,
+    mut heart_query: Query<(&Heart, &mut BackgroundColor)>,
+) {
+    let Ok(mut visibility) = hud_query.get_single_mut() else {
+        return;
+    };
+    let shown = if *game_mode == GameMode::Survival {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    if *visibility != shown {
+        *visibility = shown;
+    }
+    let Ok(health) = health_query.get_single() else {
+        return;
+    };
+    for (heart, mut background) in heart_query.iter_mut() {
+        let color = match health.current.saturating_sub(heart.0 * 2) {
+            0 => Color::rgba(0.2, 0.0, 0.0, 0.6),
+            1 => Color::rgb(0.6, 0.1, 0.1), // half a heart
+            _ => Color::RED,
+        };
+        if background.0 != color {
+            background.0 = color;
+        }
+    }
+}
+
+/// Hurt the player for hitting the ground faster than `SAFE_FALL_SPEED`
+pub fn fall_damage(
+    time: Res<Time>,
+    voxel_data: Res<VoxelData>,
+    player_query: Query<&Transform, With<Player>>,
+    mut damage_events: EventWriter<Damage>,
+    mut last_position: Local<Option<Vec3>>,
+    mut grounded: Local<bool>,
+) {
+    let Ok(transform) = player_query.get_single() else {
+        return;
+    };
+    let position = transform.translation;
+    let fall_speed = match last_position.replace(position) {
+        Some(last) if time.delta_seconds() > 0.0 => (last.y - position.y) / time.delta_seconds(),
+        _ => 0.0,
+    };
+    let on_ground = voxel::is_solid(voxel_data.get_block(VoxelPos::from_world(position)));
+    if on_ground && !*grounded && fall_speed > SAFE_FALL_SPEED && fall_speed < TELEPORT_SPEED {
+        damage_events.send(Damage {
+            amount: ((fall_speed - SAFE_FALL_SPEED) / FALL_SPEED_PER_DAMAGE).ceil() as u32,
+            cause: "fell too hard",
+        });
+    }
+    *grounded = on_ground;
+}
+
+/// Take the damage in survival, dying brings up the death screen. Nothing hurts the player
+/// until they respawn
+pub fn apply_damage(
+    game_mode: Res<GameMode>,
+    mut death_screen: ResMut<DeathScreen>,
+    mut damage_events: EventReader<Damage>,
+    mut health_query: Query<&mut Health, With<FpsCameraController>>,
+) {
+    let Ok(mut health) = health_query.get_single_mut() else {
+        return;
+    };
+    for damage in damage_events.iter() {
+        if *game_mode != GameMode::Survival || health.current == 0 {
+            continue;
+        }
+        health.current = health.current.saturating_sub(damage.amount);
+        if health.current == 0 {
+            death_screen.cause = Some(damage.cause);
+        }
+    }
+}
+
+/// The death screen, saying how the player died, with the cursor free to click respawn.
+/// Respawning goes to the bed, fed again and with full health
+pub fn death_screen(
+    mut contexts: EguiContexts,
+    world_slot: Res<WorldSlot>,
+    mut death_screen: ResMut<DeathScreen>,
+    mut pending_teleport: ResMut<PendingTeleport>,
+    mut ms: ResMut<MouseSettings>,
+    mut primary_query: Query<&mut Window, With<PrimaryWindow>>,
+    mut player_query: Query<(&mut Health, &mut Hunger, &mut FpsCameraController)>,
+) {
+    let Some(cause) = death_screen.cause else {
+        return;
+    };
+    let Ok((mut health, mut hunger, mut fps_camera)) = player_query.get_single_mut() else {
+        return;
+    };
+    if !ms.ui_mode {
+        ms.ui_mode = true;
+        apply_input_mode(
+            &ms,
+            &mut fps_camera,
+            primary_query.get_single_mut().ok().as_deref_mut(),
+        );
+    }
+
+    let mut respawn = false;
+    egui::Window::new("You died")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!("You {}", cause));
+            respawn = ui.button("Respawn").clicked();
+        });
+    if !respawn {
+        return;
+    }
+    pending_teleport.request(
+        world_slot
+            .bed()
+            .map_or(Destination::Spawn, Destination::Bed),
+    );
+    health.current = health.max;
+    *hunger = Hunger::default();
+    death_screen.cause = None;
+    ms.ui_mode = false;
+    apply_input_mode(
+        &ms,
+        &mut fps_camera,
+        primary_query.get_single_mut().ok().as_deref_mut(),
+    );
+}
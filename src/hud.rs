@@ -0,0 +1,209 @@
+//! The HUD drawn over the world: the crosshair in the middle of the screen, the
+//! scale the whole HUD is drawn at and whether it's shown at all. The crosshair
+//! sits in a node covering the window, centered by layout rather than by
+//! offsets, so it stays in the middle however the window is resized.
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use bevy_inspector_egui::prelude::*;
+use serde::{Deserialize, Serialize};
+use smooth_bevy_cameras::controllers::fps::FpsCameraController;
+
+use crate::{
+    free_camera::FreeCamera,
+    input::{InputAction, PlayerInput},
+    player::CameraMode,
+    target_voxel, voxel, MouseSettings,
+};
+
+const CROSSHAIR_TEXTURE_SIZE: u32 = 15; // pixels, odd so the lines have a middle
+const CROSSHAIR_COLOR: Color = Color::WHITE; // tints the white of the texture
+const OUT_OF_REACH_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.4);
+const REACH_LOOKAHEAD: f32 = 2.0; // blocks within this multiple of the interact distance show as out of reach
+
+#[derive(Reflect, Resource, InspectorOptions, Serialize, Deserialize)]
+#[reflect(Resource, InspectorOptions)]
+#[serde(default)]
+pub struct HudSettings {
+    pub shown: bool, // toggled with F1, menus still show while it's hidden
+    #[inspector(min = 0.5, max = 3.0)]
+    scale: f32, // of everything drawn with bevy's ui, text included
+    crosshair: bool,
+    crosshair_size: f32, // in pixels before the scale
+}
+
+impl Default for HudSettings {
+    fn default() -> Self {
+        HudSettings {
+            shown: true,
+            scale: 1.0,
+            crosshair: true,
+            crosshair_size: CROSSHAIR_TEXTURE_SIZE as f32,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Crosshair;
+
+/// Rgba pixels of the crosshair: a white cross with a dark outline, so it shows on bright
+/// and dark blocks alike
+fn crosshair_pixels(size: u32) -> Vec<u8> {
+    let middle = size / 2;
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let (dx, dy) = (x.abs_diff(middle), y.abs_diff(middle));
+            let inner = (1..size - 1).contains(&x) && (1..size - 1).contains(&y);
+            let pixel = if (dx == 0 || dy == 0) && inner {
+                [255, 255, 255, 255]
+            } else if dx <= 1 || dy <= 1 {
+                [0, 0, 0, 150]
+            } else {
+                [0, 0, 0, 0]
+            };
+            data.extend(pixel);
+        }
+    }
+    data
+}
+
+pub fn setup_hud(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let size = CROSSHAIR_TEXTURE_SIZE;
+    let texture = images.add(Image::new(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        crosshair_pixels(size),
+        TextureFormat::Rgba8UnormSrgb,
+    ));
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                ImageBundle {
+                    image: UiImage::new(texture),
+                    background_color: BackgroundColor(CROSSHAIR_COLOR),
+                    ..default()
+                },
+                Crosshair,
+            ));
+        });
+}
+
+pub fn toggle_hud(player_input: Res<PlayerInput>, mut hud_settings: ResMut<HudSettings>) {
+    if player_input.actions.just_pressed(InputAction::ToggleHud) {
+        hud_settings.shown = !hud_settings.shown;
+    }
+}
+
+/// Apply the scale, show or hide the HUD, hidden with the free camera too, and size the
+/// crosshair, left out while the cursor is free in ui mode
+pub fn update_hud(
+    hud_settings: Res<HudSettings>,
+    ms: Res<MouseSettings>,
+    free_camera: Res<FreeCamera>,
+    mut ui_scale: ResMut<UiScale>,
+    mut camera_query: Query<&mut UiCameraConfig>,
+    mut crosshair_query: Query<(&mut Style, &mut Visibility), With<Crosshair>>,
+) {
+    let scale = hud_settings.scale.clamp(0.5, 3.0) as f64;
+    if ui_scale.scale != scale {
+        ui_scale.scale = scale;
+    }
+    if let Ok(mut ui_config) = camera_query.get_single_mut() {
+        let show_ui = !free_camera.active() && (hud_settings.shown || ms.ui_mode);
+        if ui_config.show_ui != show_ui {
+            ui_config.show_ui = show_ui;
+        }
+    }
+
+    let Ok((mut style, mut visibility)) = crosshair_query.get_single_mut() else {
+        return;
+    };
+    let shown = if hud_settings.crosshair && !ms.ui_mode {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    if *visibility != shown {
+        *visibility = shown;
+    }
+    let size = Val::Px(hud_settings.crosshair_size.max(1.0));
+    if style.width != size {
+        style.width = size;
+        style.height = size;
+    }
+}
+
+/// Fade the crosshair while it points at a block just beyond the interact distance
+pub fn update_crosshair(
+    voxel_data: Res<voxel::VoxelData>,
+    voxel_settings: Res<voxel::VoxelSettings>,
+    camera_mode: Res<CameraMode>,
+    fps_camera_query: Query<&GlobalTransform, With<FpsCameraController>>,
+    mut crosshair_query: Query<&mut BackgroundColor, With<Crosshair>>,
+) {
+    let (Ok(transform), Ok(mut background)) = (
+        fps_camera_query.get_single(),
+        crosshair_query.get_single_mut(),
+    ) else {
+        return;
+    };
+    let target = |range| {
+        target_voxel(
+            &voxel_data,
+            camera_mode.eye(transform),
+            transform.forward(),
+            range,
+        )
+    };
+    let reach = voxel_settings.interact_distance;
+    let out_of_reach = target(reach).is_none() && target(reach * REACH_LOOKAHEAD).is_some();
+    let color = if out_of_reach {
+        OUT_OF_REACH_COLOR
+    } else {
+        CROSSHAIR_COLOR
+    };
+    if background.0 != color {
+        background.0 = color;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crosshair_is_an_outlined_cross() {
+        let size = CROSSHAIR_TEXTURE_SIZE;
+        let pixels = crosshair_pixels(size);
+        let pixel = |x: u32, y: u32| {
+            let start = ((y * size + x) * 4) as usize;
+            &pixels[start..start + 4]
+        };
+        let middle = size / 2;
+        assert_eq!(pixel(middle, middle), [255, 255, 255, 255]);
+        assert_eq!(pixel(middle, 1), [255, 255, 255, 255]);
+        // outlined along the lines and at their ends
+        assert_eq!(pixel(middle + 1, 1)[3], 150);
+        assert_eq!(pixel(middle, 0)[3], 150);
+        // and clear between the arms
+        assert_eq!(pixel(0, 0)[3], 0);
+    }
+}
@@ -0,0 +1,154 @@
+//! Hunger in survival mode: food runs low over time, faster while sprinting,
+//! and shows as a row of drumsticks above the hearts. Well fed the player
+//! slowly heals, starving hurts.
+
+use bevy::prelude::*;
+
+use crate::{
+    game_mode::GameMode,
+    health::{Damage, Health},
+    input::{InputAction, PlayerInput},
+};
+
+pub const MAX_FOOD: u32 = 20; // in half drumsticks
+const DRUMSTICKS: u32 = MAX_FOOD / 2;
+const DRAIN_INTERVAL: f32 = 40.0; // seconds standing still before half a drumstick is gone
+const SPRINT_DRAIN: f32 = 4.0; // times faster food runs low while sprinting
+const WELL_FED: u32 = 18; // food from which the player heals
+const TICK_INTERVAL: f32 = 4.0; // seconds between half hearts healed or lost to starving
+const DRUMSTICK_SIZE: f32 = 16.0;
+
+#[derive(Component, Debug)]
+pub struct Hunger {
+    pub food: u32,
+    pub max: u32,
+    exhaustion: f32, // towards the next half drumstick lost, in seconds of `DRAIN_INTERVAL`
+    tick: f32,       // seconds since the last half heart healed or lost
+}
+
+impl Default for Hunger {
+    fn default() -> Self {
+        Hunger {
+            food: MAX_FOOD,
+            max: MAX_FOOD,
+            exhaustion: 0.0,
+            tick: 0.0,
+        }
+    }
+}
+
+impl Hunger {
+    pub fn eat(&mut self, food: u32) {
+        self.food = (self.food + food).min(self.max);
+    }
+}
+
+#[derive(Component)]
+pub struct FoodHud;
+
+#[derive(Component)]
+pub struct Drumstick(u32); // position in the row, from the left
+
+pub fn setup_food(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(14.0 + DRUMSTICK_SIZE), // just above the hearts
+                    width: Val::Percent(100.),
+                    justify_content: JustifyContent::Center,
+                    column_gap: Val::Px(4.0),
+                    ..default()
+                },
+                ..default()
+            },
+            FoodHud,
+        ))
+        .with_children(|parent| {
+            for index in 0..DRUMSTICKS {
+                parent.spawn((
+                    NodeBundle {
+                        style: Style {
+                            width: Val::Px(DRUMSTICK_SIZE),
+                            height: Val::Px(DRUMSTICK_SIZE),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    Drumstick(index),
+                ));
+            }
+        });
+}
+
+/// Fill the drumsticks from the player's food, hidden outside survival
+pub fn update_food(
+    game_mode: Res<GameMode>,
+    hunger_query: Query<&Hunger>,
+    mut hud_query: Query<&mut Visibility, With<FoodHud>>,
+    mut drumstick_query: Query<(&Drumstick, &mut BackgroundColor)>,
+) {
+    let Ok(mut visibility) = hud_query.get_single_mut() else {
+        return;
+    };
+    let shown = if *game_mode == GameMode::Survival {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    if *visibility != shown {
+        *visibility = shown;
+    }
+    let Ok(hunger) = hunger_query.get_single() else {
+        return;
+    };
+    for (drumstick, mut background) in drumstick_query.iter_mut() {
+        let color = match hunger.food.saturating_sub(drumstick.0 * 2) {
+            0 => Color::rgba(0.2, 0.1, 0.0, 0.6),
+            1 => Color::rgb(0.5, 0.3, 0.1), // half a drumstick
+            _ => Color::rgb(0.8, 0.5, 0.2),
+        };
+        if background.0 != color {
+            background.0 = color;
+        }
+    }
+}
+
+/// Use up food in survival, then heal the well fed player or hurt the starving one
+pub fn update_hunger(
+    time: Res<Time>,
+    game_mode: Res<GameMode>,
+    player_input: Res<PlayerInput>,
+    mut damage_events: EventWriter<Damage>,
+    mut player_query: Query<(&mut Hunger, &mut Health)>,
+) {
+    let Ok((mut hunger, mut health)) = player_query.get_single_mut() else {
+        return;
+    };
+    if *game_mode != GameMode::Survival {
+        return;
+    }
+    let dt = time.delta_seconds();
+    let sprinting =
+        player_input.movement != Vec3::ZERO && player_input.actions.pressed(InputAction::Sprint);
+    hunger.exhaustion += if sprinting { dt * SPRINT_DRAIN } else { dt };
+    if hunger.exhaustion >= DRAIN_INTERVAL {
+        hunger.exhaustion -= DRAIN_INTERVAL;
+        hunger.food = hunger.food.saturating_sub(1);
+    }
+
+    hunger.tick += dt;
+    if hunger.tick < TICK_INTERVAL {
+        return;
+    }
+    hunger.tick = 0.0;
+    if hunger.food == 0 {
+        damage_events.send(Damage {
+            amount: 1,
+            cause: "starved",
+        });
+    } else if hunger.food >= WELL_FED && health.current < health.max {
+        health.current += 1;
+    }
+}
@@ -0,0 +1,60 @@
+use bevy::{
+    prelude::*,
+    utils::Duration,
+    window::WindowFocused,
+    winit::{UpdateMode, WinitSettings},
+};
+use bevy_inspector_egui::{prelude::ReflectInspectorOptions, InspectorOptions};
+
+/// How the game behaves while the window is in the background
+#[derive(Reflect, Resource, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub struct IdleSettings {
+    pub background_fps: f32,        // frame cap while unfocused
+    pub pause_when_unfocused: bool, // stop game time in single player
+}
+
+impl Default for IdleSettings {
+    fn default() -> Self {
+        IdleSettings {
+            background_fps: 10.0,
+            pause_when_unfocused: false,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct WindowIdle {
+    pub idle: bool,
+}
+
+/// Run condition for work that can wait until the window is focused again,
+/// like prefetching chunks
+pub fn window_active(window_idle: Res<WindowIdle>) -> bool {
+    !window_idle.idle
+}
+
+pub fn update_idle(
+    idle_settings: Res<IdleSettings>,
+    mut focus_events: EventReader<WindowFocused>,
+    mut window_idle: ResMut<WindowIdle>,
+    mut winit_settings: ResMut<WinitSettings>,
+    mut time: ResMut<Time>,
+) {
+    if idle_settings.is_changed() {
+        winit_settings.unfocused_mode = UpdateMode::ReactiveLowPower {
+            max_wait: Duration::from_secs_f32(1.0 / idle_settings.background_fps.max(1.0)),
+        };
+    }
+
+    let Some(focus) = focus_events.iter().last() else {
+        return;
+    };
+    window_idle.idle = !focus.focused;
+
+    if window_idle.idle && idle_settings.pause_when_unfocused {
+        time.pause();
+    } else if time.is_paused() {
+        time.unpause();
+    }
+}
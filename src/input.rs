@@ -0,0 +1,354 @@
+//! Player input as actions, read from the keyboard and mouse and from the
+//! first connected gamepad alike. Gameplay reads `PlayerInput` instead of the
+//! devices, and the camera controller is driven from it too.
+
+use std::{collections::BTreeMap, fmt};
+
+use bevy::{ecs::system::SystemParam, input::mouse::MouseMotion, prelude::*};
+use bevy_egui::EguiContexts;
+use serde::{Deserialize, Serialize};
+use smooth_bevy_cameras::controllers::fps::{ControlEvent, FpsCameraController};
+
+use crate::{
+    game_mode::GameMode,
+    swimming::{Underwater, SWIM_SPEED},
+};
+
+const GAMEPAD_LOOK_SPEED: f32 = 8.0; // stick fully tilted, in the units of mouse motion per frame
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum InputAction {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    Jump,    // jumps when walking, moves up while flying
+    Descend, // moves down while flying
+    Sprint,
+    Break,
+    Place,
+    NextItem,   // cycles what the place button uses
+    PickBlock,  // holds the block looked at
+    RepairTool, // mends the held tool
+    SelectFirstCorner,
+    SelectSecondCorner,
+    ToggleUiMode,
+    Console,
+    Command, // the console, started with a slash
+    Menu,
+    DropWaypoint,
+    NextWaypoint,
+    ShareWaypoint,
+    Screenshot,
+    ChunkOverlay,
+    CameraView,
+    FreeCamera,
+    DebugScreen,
+    ToggleHud,
+}
+
+impl InputAction {
+    pub const ALL: [InputAction; 27] = [
+        InputAction::MoveForward,
+        InputAction::MoveBack,
+        InputAction::MoveLeft,
+        InputAction::MoveRight,
+        InputAction::Jump,
+        InputAction::Descend,
+        InputAction::Sprint,
+        InputAction::Break,
+        InputAction::Place,
+        InputAction::NextItem,
+        InputAction::PickBlock,
+        InputAction::RepairTool,
+        InputAction::SelectFirstCorner,
+        InputAction::SelectSecondCorner,
+        InputAction::ToggleUiMode,
+        InputAction::Console,
+        InputAction::Command,
+        InputAction::Menu,
+        InputAction::DropWaypoint,
+        InputAction::NextWaypoint,
+        InputAction::ShareWaypoint,
+        InputAction::Screenshot,
+        InputAction::ChunkOverlay,
+        InputAction::CameraView,
+        InputAction::FreeCamera,
+        InputAction::DebugScreen,
+        InputAction::ToggleHud,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            InputAction::MoveForward => "Move forward",
+            InputAction::MoveBack => "Move back",
+            InputAction::MoveLeft => "Move left",
+            InputAction::MoveRight => "Move right",
+            InputAction::Jump => "Jump / fly up",
+            InputAction::Descend => "Fly down",
+            InputAction::Sprint => "Sprint",
+            InputAction::Break => "Mine",
+            InputAction::Place => "Place / use",
+            InputAction::NextItem => "Next held item",
+            InputAction::PickBlock => "Pick block",
+            InputAction::RepairTool => "Repair tool",
+            InputAction::SelectFirstCorner => "Select first corner",
+            InputAction::SelectSecondCorner => "Select second corner",
+            InputAction::ToggleUiMode => "Toggle UI mode",
+            InputAction::Console => "Console",
+            InputAction::Command => "Command",
+            InputAction::Menu => "Menu",
+            InputAction::DropWaypoint => "Drop waypoint",
+            InputAction::NextWaypoint => "Next waypoint",
+            InputAction::ShareWaypoint => "Share waypoint",
+            InputAction::Screenshot => "Screenshot",
+            InputAction::ChunkOverlay => "Chunk overlay",
+            InputAction::CameraView => "Camera view",
+            InputAction::FreeCamera => "Free camera",
+            InputAction::DebugScreen => "Debug screen",
+            InputAction::ToggleHud => "Hide HUD",
+        }
+    }
+
+    fn default_binding(self) -> Binding {
+        match self {
+            InputAction::MoveForward => Binding::Key(KeyCode::W),
+            InputAction::MoveBack => Binding::Key(KeyCode::S),
+            InputAction::MoveLeft => Binding::Key(KeyCode::A),
+            InputAction::MoveRight => Binding::Key(KeyCode::D),
+            InputAction::Jump => Binding::Key(KeyCode::Space),
+            InputAction::Descend => Binding::Key(KeyCode::ShiftLeft),
+            InputAction::Sprint => Binding::Key(KeyCode::ControlLeft),
+            InputAction::Break => Binding::Mouse(MouseButton::Left),
+            InputAction::Place => Binding::Mouse(MouseButton::Right),
+            InputAction::NextItem => Binding::Key(KeyCode::Q),
+            InputAction::PickBlock => Binding::Mouse(MouseButton::Middle),
+            InputAction::RepairTool => Binding::Key(KeyCode::R),
+            InputAction::SelectFirstCorner => Binding::Key(KeyCode::BracketLeft),
+            InputAction::SelectSecondCorner => Binding::Key(KeyCode::BracketRight),
+            InputAction::ToggleUiMode => Binding::Key(KeyCode::Grave),
+            InputAction::Console => Binding::Key(KeyCode::T),
+            InputAction::Command => Binding::Key(KeyCode::Slash),
+            InputAction::Menu => Binding::Key(KeyCode::Escape),
+            InputAction::DropWaypoint => Binding::Key(KeyCode::B),
+            InputAction::NextWaypoint => Binding::Key(KeyCode::N),
+            InputAction::ShareWaypoint => Binding::Key(KeyCode::V),
+            InputAction::Screenshot => Binding::Key(KeyCode::F2),
+            InputAction::ChunkOverlay => Binding::Key(KeyCode::F3),
+            InputAction::CameraView => Binding::Key(KeyCode::F5),
+            InputAction::FreeCamera => Binding::Key(KeyCode::F4),
+            InputAction::DebugScreen => Binding::Key(KeyCode::F6),
+            InputAction::ToggleHud => Binding::Key(KeyCode::F1),
+        }
+    }
+
+    /// Gamepad buttons aren't rebindable, the sticks move and look
+    fn gamepad_button(self) -> Option<GamepadButtonType> {
+        match self {
+            InputAction::Break => Some(GamepadButtonType::RightTrigger2),
+            InputAction::Place => Some(GamepadButtonType::LeftTrigger2),
+            InputAction::NextItem => Some(GamepadButtonType::RightTrigger),
+            InputAction::Jump => Some(GamepadButtonType::South),
+            InputAction::Descend => Some(GamepadButtonType::East),
+            InputAction::Sprint => Some(GamepadButtonType::LeftThumb),
+            InputAction::ToggleUiMode => Some(GamepadButtonType::Select),
+            InputAction::Menu => Some(GamepadButtonType::Start),
+            InputAction::CameraView => Some(GamepadButtonType::North),
+            _ => None,
+        }
+    }
+}
+
+/// A key or mouse button an action is bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Binding::Key(KeyCode::Grave) => write!(f, "~"),
+            Binding::Key(KeyCode::BracketLeft) => write!(f, "["),
+            Binding::Key(KeyCode::BracketRight) => write!(f, "]"),
+            Binding::Key(KeyCode::Slash) => write!(f, "/"),
+            Binding::Key(KeyCode::Escape) => write!(f, "Esc"),
+            Binding::Key(key) => write!(f, "{:?}", key),
+            Binding::Mouse(MouseButton::Left) => write!(f, "LMB"),
+            Binding::Mouse(MouseButton::Right) => write!(f, "RMB"),
+            Binding::Mouse(MouseButton::Middle) => write!(f, "MMB"),
+            Binding::Mouse(MouseButton::Other(button)) => write!(f, "Mouse {}", button),
+        }
+    }
+}
+
+/// The key or mouse button bound to each action, saved with the settings.
+/// Actions missing from the settings file keep their default binding
+#[derive(Resource, Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    bindings: BTreeMap<InputAction, Binding>,
+}
+
+impl KeyBindings {
+    pub fn get(&self, action: InputAction) -> Binding {
+        self.bindings
+            .get(&action)
+            .copied()
+            .unwrap_or_else(|| action.default_binding())
+    }
+
+    pub fn set(&mut self, action: InputAction, binding: Binding) {
+        if binding == action.default_binding() {
+            self.bindings.remove(&action);
+        } else {
+            self.bindings.insert(action, binding);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.bindings.clear();
+    }
+
+    /// Whether another action is bound to the same key
+    pub fn conflicts(&self, action: InputAction) -> bool {
+        let binding = self.get(action);
+        InputAction::ALL
+            .iter()
+            .any(|other| *other != action && self.get(*other) == binding)
+    }
+}
+
+/// This frame's input, whichever device it came from
+#[derive(Resource, Default)]
+pub struct PlayerInput {
+    pub actions: Input<InputAction>,
+    pub movement: Vec3, // x right, y up, z forward
+    pub look: Vec2,     // in the units of mouse motion, y down
+}
+
+/// The first connected gamepad's buttons and sticks
+#[derive(SystemParam)]
+pub struct GamepadInput<'w> {
+    gamepads: Res<'w, Gamepads>,
+    buttons: Res<'w, Input<GamepadButton>>,
+    axes: Res<'w, Axis<GamepadAxis>>,
+}
+
+impl GamepadInput<'_> {
+    fn first(&self) -> Option<Gamepad> {
+        self.gamepads.iter().next()
+    }
+
+    fn pressed(&self, gamepad: Gamepad, button: GamepadButtonType) -> bool {
+        self.buttons.pressed(GamepadButton::new(gamepad, button))
+    }
+
+    /// How far a stick is tilted along its two axes
+    fn stick(&self, gamepad: Gamepad, x: GamepadAxisType, y: GamepadAxisType) -> Vec2 {
+        let axis = |axis| {
+            self.axes
+                .get(GamepadAxis::new(gamepad, axis))
+                .unwrap_or_default()
+        };
+        Vec2::new(axis(x), axis(y))
+    }
+}
+
+/// Gather the devices' state into `PlayerInput`, runs right after Bevy updated the devices
+pub fn read_player_input(
+    key_bindings: Res<KeyBindings>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    gamepads: GamepadInput,
+    mut player_input: ResMut<PlayerInput>,
+    mut contexts: EguiContexts,
+) {
+    let gamepad = gamepads.first();
+    // clicks on a window are for the window, not the world behind it, and so is typing
+    let ctx = contexts.ctx_mut();
+    let over_window = ctx.is_pointer_over_area();
+    let typing = ctx.wants_keyboard_input();
+    player_input.actions.clear();
+    for action in InputAction::ALL {
+        let pressed = match key_bindings.get(action) {
+            Binding::Key(key) => keyboard_input.pressed(key) && !typing,
+            Binding::Mouse(button) => mouse_input.pressed(button) && !over_window,
+        } || gamepad
+            .zip(action.gamepad_button())
+            .is_some_and(|(gamepad, button)| gamepads.pressed(gamepad, button));
+        if pressed && !player_input.actions.pressed(action) {
+            player_input.actions.press(action);
+        } else if !pressed && player_input.actions.pressed(action) {
+            player_input.actions.release(action);
+        }
+    }
+
+    let mut movement = Vec3::ZERO;
+    for (action, direction) in [
+        (InputAction::MoveForward, Vec3::Z),
+        (InputAction::MoveBack, Vec3::NEG_Z),
+        (InputAction::MoveRight, Vec3::X),
+        (InputAction::MoveLeft, Vec3::NEG_X),
+        (InputAction::Jump, Vec3::Y),
+        (InputAction::Descend, Vec3::NEG_Y),
+    ] {
+        if player_input.actions.pressed(action) {
+            movement += direction;
+        }
+    }
+    let mut look: Vec2 = mouse_motion.iter().map(|motion| motion.delta).sum();
+
+    if let Some(gamepad) = gamepad {
+        let left = gamepads.stick(
+            gamepad,
+            GamepadAxisType::LeftStickX,
+            GamepadAxisType::LeftStickY,
+        );
+        let right = gamepads.stick(
+            gamepad,
+            GamepadAxisType::RightStickX,
+            GamepadAxisType::RightStickY,
+        );
+        movement += Vec3::new(left.x, 0.0, left.y);
+        look += Vec2::new(right.x, -right.y) * GAMEPAD_LOOK_SPEED;
+    }
+    player_input.movement = movement;
+    player_input.look = look;
+}
+
+/// Feed the input to the camera controller, in place of its own keyboard and mouse reading
+pub fn drive_camera(
+    player_input: Res<PlayerInput>,
+    game_mode: Res<GameMode>,
+    underwater: Res<Underwater>,
+    fps_camera_query: Query<&FpsCameraController>,
+    mut control_events: EventWriter<ControlEvent>,
+) {
+    let Some(controller) = fps_camera_query
+        .iter()
+        .find(|controller| controller.enabled)
+    else {
+        return;
+    };
+    control_events.send(ControlEvent::Rotate(
+        controller.mouse_rotate_sensitivity * player_input.look,
+    ));
+    let mut movement = player_input.movement;
+    if *game_mode == GameMode::Survival {
+        // walking, `player_gravity` moves the player up and down
+        movement.y = 0.0;
+    }
+    if underwater.0 && *game_mode != GameMode::Spectator {
+        movement *= SWIM_SPEED;
+    }
+    if movement != Vec3::ZERO {
+        // the controller's eye moves along x to the left
+        let movement = movement * Vec3::new(-1.0, 1.0, 1.0);
+        control_events.send(ControlEvent::TranslateEye(
+            controller.translate_sensitivity * movement,
+        ));
+    }
+}
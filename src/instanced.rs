@@ -0,0 +1,498 @@
+//! Experimental terrain renderer, behind the `instanced-faces` feature, to compare against
+//! the meshed columns: each column uploads one instance per visible face of its opaque
+//! cubes and draws them all with a single instanced draw of a quad. `FaceInstancing`
+//! switches between the two, from the inspector. Glass, leaves and shaped blocks are
+//! only drawn by the meshes, so they're missing while the instanced faces are shown.
+
+use bevy::prelude::*;
+
+/// Adds the instanced face renderer when built with the `instanced-faces` feature
+#[derive(Default)]
+pub struct InstancedFacesPlugin;
+
+impl Plugin for InstancedFacesPlugin {
+    fn build(&self, _app: &mut App) {
+        #[cfg(feature = "instanced-faces")]
+        faces::build(_app);
+    }
+
+    fn finish(&self, _app: &mut App) {
+        #[cfg(feature = "instanced-faces")]
+        faces::finish(_app);
+    }
+}
+
+#[cfg(feature = "instanced-faces")]
+mod faces {
+    use bevy::{
+        core::cast_slice,
+        core_pipeline::core_3d::Opaque3d,
+        ecs::{
+            query::ROQueryItem,
+            system::{
+                lifetimeless::{Read, SRes},
+                SystemParamItem,
+            },
+        },
+        pbr::{MeshPipeline, MeshPipelineKey, MeshUniform, SetMeshBindGroup, SetMeshViewBindGroup},
+        prelude::*,
+        render::{
+            mesh::{GpuBufferInfo, Indices, MeshVertexBufferLayout},
+            primitives::Aabb,
+            render_asset::RenderAssets,
+            render_phase::{
+                AddRenderCommand, DrawFunctions, PhaseItem, RenderCommand, RenderCommandResult,
+                RenderPhase, SetItemPipeline, TrackedRenderPass,
+            },
+            render_resource::*,
+            renderer::RenderDevice,
+            view::{ExtractedView, VisibleEntities},
+            Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+        },
+        utils::HashMap,
+    };
+
+    use crate::{
+        update_column_meshes,
+        voxel::{self, ChunkIndex, ColumnMesh, VoxelData, CHUNK_SIZE, HEIGHT_LIMIT},
+        LoadingTexture,
+    };
+
+    const SHADER: &str = "shaders/face_instances.wgsl";
+
+    /// Which renderer draws the terrain
+    #[derive(Reflect, Resource)]
+    #[reflect(Resource)]
+    pub struct FaceInstancing {
+        pub enabled: bool, // the instanced faces rather than the column meshes
+        #[reflect(ignore)]
+        quad: Handle<Mesh>,
+    }
+
+    /// The faces of a column, see `voxel::face_instances`
+    #[derive(Component)]
+    struct FaceInstances(Vec<[u32; 2]>);
+
+    /// On a column, the entity drawing its faces
+    #[derive(Component)]
+    struct FaceColumn(Entity);
+
+    /// On the entity drawing a column's faces, the column
+    #[derive(Component)]
+    struct FacesOf(Entity);
+
+    pub(super) fn build(app: &mut App) {
+        app.register_type::<FaceInstancing>()
+            .add_systems(Startup, setup_face_instancing)
+            .add_systems(Update, update_face_instances.after(update_column_meshes))
+            .add_systems(Update, despawn_orphan_faces);
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .add_render_command::<Opaque3d, DrawFaces>()
+            .init_resource::<SpecializedMeshPipelines<FacePipeline>>()
+            .init_resource::<FaceBuffers>()
+            .add_systems(ExtractSchedule, extract_face_instances)
+            .add_systems(
+                Render,
+                (
+                    prepare_face_buffers.in_set(RenderSet::Prepare),
+                    queue_faces.in_set(RenderSet::Queue),
+                ),
+            );
+    }
+
+    pub(super) fn finish(app: &mut App) {
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.init_resource::<FacePipeline>();
+        }
+    }
+
+    fn setup_face_instancing(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+        // a unit quad in xy, the shader turns it to the face
+        let mut quad = Mesh::new(PrimitiveTopology::TriangleList);
+        quad.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [0.0, 1.0, 0.0],
+            ],
+        );
+        quad.set_indices(Some(Indices::U32(vec![0, 1, 2, 0, 2, 3])));
+        commands.insert_resource(FaceInstancing {
+            enabled: true,
+            quad: meshes.add(quad),
+        });
+    }
+
+    fn visibility(shown: bool) -> Visibility {
+        if shown {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        }
+    }
+
+    /// Collect the faces of the columns that were meshed, and show either the faces or the
+    /// column meshes
+    fn update_face_instances(
+        mut commands: Commands,
+        face_instancing: Res<FaceInstancing>,
+        voxel_data: Res<VoxelData>,
+        mut columns: Query<(
+            Entity,
+            Ref<ColumnMesh>,
+            &mut Visibility,
+            Option<&FaceColumn>,
+        )>,
+        mut faces: Query<&mut Visibility, (With<FacesOf>, Without<ColumnMesh>)>,
+    ) {
+        let switched = face_instancing.is_changed();
+        let column_visibility = visibility(!face_instancing.enabled);
+        let face_visibility = visibility(face_instancing.enabled);
+        for (entity, column_mesh, mut shown, face_column) in columns.iter_mut() {
+            if !column_mesh.is_changed() && !switched {
+                continue;
+            }
+            // meshing the column puts its bundle back, visibility included
+            if *shown != column_visibility {
+                *shown = column_visibility;
+            }
+            if !column_mesh.is_changed() || column_mesh.dirty {
+                continue;
+            }
+            let column = column_mesh.column;
+            let instances: Vec<[u32; 2]> = column_mesh
+                .sub_meshes
+                .keys()
+                .filter(|y| !column_mesh.hidden.contains(y))
+                .filter_map(|&y| {
+                    voxel_data.chunks.get(&ChunkIndex {
+                        x: column.x,
+                        y,
+                        z: column.z,
+                    })
+                })
+                .flat_map(voxel::face_instances)
+                .collect();
+            match face_column {
+                Some(FaceColumn(face_entity)) => {
+                    commands
+                        .entity(*face_entity)
+                        .insert(FaceInstances(instances));
+                }
+                None => {
+                    let origin = ChunkIndex {
+                        x: column.x,
+                        y: 0,
+                        z: column.z,
+                    }
+                    .origin();
+                    let face_entity = commands
+                        .spawn((
+                            SpatialBundle {
+                                transform: Transform::from_translation(origin.as_vec3()),
+                                visibility: face_visibility,
+                                ..default()
+                            },
+                            face_instancing.quad.clone(),
+                            FaceInstances(instances),
+                            Aabb::from_min_max(
+                                Vec3::ZERO,
+                                Vec3::new(
+                                    CHUNK_SIZE as f32,
+                                    HEIGHT_LIMIT as f32,
+                                    CHUNK_SIZE as f32,
+                                ),
+                            ),
+                            FacesOf(entity),
+                        ))
+                        .id();
+                    commands.entity(entity).insert(FaceColumn(face_entity));
+                }
+            }
+        }
+        if switched {
+            for mut shown in faces.iter_mut() {
+                *shown = face_visibility;
+            }
+        }
+    }
+
+    fn despawn_orphan_faces(
+        mut commands: Commands,
+        faces: Query<(Entity, &FacesOf)>,
+        columns: Query<(), With<ColumnMesh>>,
+    ) {
+        for (entity, FacesOf(column)) in faces.iter() {
+            if !columns.contains(*column) {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+
+    /// Render world side: the instance buffers by entity, and the array texture's bind group
+    #[derive(Resource, Default)]
+    struct FaceBuffers {
+        pending: Vec<(Entity, Vec<[u32; 2]>)>, // faces changed since the last upload
+        buffers: HashMap<Entity, (Buffer, u32)>, // with the number of faces
+        texture: Option<Handle<Image>>,
+        texture_bind_group: Option<BindGroup>,
+    }
+
+    /// Only the faces that changed are copied out of the main world
+    fn extract_face_instances(
+        mut face_buffers: ResMut<FaceBuffers>,
+        loading_texture: Extract<Option<Res<LoadingTexture>>>,
+        faces: Extract<Query<(Entity, Ref<FaceInstances>)>>,
+    ) {
+        face_buffers.texture = loading_texture
+            .as_ref()
+            .filter(|loading_texture| loading_texture.is_loaded)
+            .map(|loading_texture| loading_texture.handle.clone());
+        face_buffers
+            .buffers
+            .retain(|entity, _| faces.contains(*entity));
+        for (entity, instances) in faces.iter() {
+            if instances.is_changed() {
+                face_buffers.pending.push((entity, instances.0.clone()));
+            }
+        }
+    }
+
+    fn prepare_face_buffers(
+        mut face_buffers: ResMut<FaceBuffers>,
+        render_device: Res<RenderDevice>,
+    ) {
+        for (entity, instances) in std::mem::take(&mut face_buffers.pending) {
+            if instances.is_empty() {
+                face_buffers.buffers.remove(&entity);
+                continue;
+            }
+            let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+                label: Some("face instances"),
+                contents: cast_slice(&instances),
+                usage: BufferUsages::VERTEX,
+            });
+            face_buffers
+                .buffers
+                .insert(entity, (buffer, instances.len() as u32));
+        }
+    }
+
+    #[derive(Resource)]
+    struct FacePipeline {
+        shader: Handle<Shader>,
+        mesh_pipeline: MeshPipeline,
+        texture_layout: BindGroupLayout,
+    }
+
+    impl FromWorld for FacePipeline {
+        fn from_world(world: &mut World) -> Self {
+            let render_device = world.resource::<RenderDevice>();
+            let texture_layout =
+                render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                    label: Some("face texture layout"),
+                    entries: &[
+                        BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: ShaderStages::FRAGMENT,
+                            ty: BindingType::Texture {
+                                sample_type: TextureSampleType::Float { filterable: true },
+                                view_dimension: TextureViewDimension::D2Array,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: ShaderStages::FRAGMENT,
+                            ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                    ],
+                });
+            FacePipeline {
+                shader: world.resource::<AssetServer>().load(SHADER),
+                mesh_pipeline: world.resource::<MeshPipeline>().clone(),
+                texture_layout,
+            }
+        }
+    }
+
+    impl SpecializedMeshPipeline for FacePipeline {
+        type Key = MeshPipelineKey;
+
+        fn specialize(
+            &self,
+            key: Self::Key,
+            layout: &MeshVertexBufferLayout,
+        ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+            let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
+            descriptor.vertex.shader = self.shader.clone();
+            descriptor.vertex.buffers.push(VertexBufferLayout {
+                array_stride: VertexFormat::Uint32x2.size(),
+                step_mode: VertexStepMode::Instance,
+                attributes: vec![VertexAttribute {
+                    format: VertexFormat::Uint32x2,
+                    offset: 0,
+                    shader_location: 3,
+                }],
+            });
+            descriptor.layout.push(self.texture_layout.clone());
+            if let Some(fragment) = descriptor.fragment.as_mut() {
+                fragment.shader = self.shader.clone();
+            }
+            Ok(descriptor)
+        }
+    }
+
+    fn queue_faces(
+        draw_functions: Res<DrawFunctions<Opaque3d>>,
+        face_pipeline: Res<FacePipeline>,
+        msaa: Res<Msaa>,
+        mut pipelines: ResMut<SpecializedMeshPipelines<FacePipeline>>,
+        pipeline_cache: Res<PipelineCache>,
+        meshes: Res<RenderAssets<Mesh>>,
+        images: Res<RenderAssets<Image>>,
+        render_device: Res<RenderDevice>,
+        mut face_buffers: ResMut<FaceBuffers>,
+        face_meshes: Query<(&MeshUniform, &Handle<Mesh>)>,
+        mut views: Query<(&ExtractedView, &VisibleEntities, &mut RenderPhase<Opaque3d>)>,
+    ) {
+        let texture_bind_group = face_buffers
+            .texture
+            .as_ref()
+            .and_then(|texture| images.get(texture))
+            .map(|image| {
+                render_device.create_bind_group(&BindGroupDescriptor {
+                    label: Some("face texture"),
+                    layout: &face_pipeline.texture_layout,
+                    entries: &[
+                        BindGroupEntry {
+                            binding: 0,
+                            resource: BindingResource::TextureView(&image.texture_view),
+                        },
+                        BindGroupEntry {
+                            binding: 1,
+                            resource: BindingResource::Sampler(&image.sampler),
+                        },
+                    ],
+                })
+            });
+        face_buffers.texture_bind_group = texture_bind_group;
+        if face_buffers.texture_bind_group.is_none() {
+            return;
+        }
+
+        let draw_faces = draw_functions.read().id::<DrawFaces>();
+        let msaa_key = MeshPipelineKey::from_msaa_samples(msaa.samples());
+        for (view, visible_entities, mut opaque_phase) in views.iter_mut() {
+            let view_key = msaa_key | MeshPipelineKey::from_hdr(view.hdr);
+            let rangefinder = view.rangefinder3d();
+            for &entity in visible_entities.entities.iter() {
+                if !face_buffers.buffers.contains_key(&entity) {
+                    continue;
+                }
+                let Ok((mesh_uniform, mesh_handle)) = face_meshes.get(entity) else {
+                    continue;
+                };
+                let Some(mesh) = meshes.get(mesh_handle) else {
+                    continue;
+                };
+                let key =
+                    view_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology);
+                let pipeline = match pipelines.specialize(
+                    &pipeline_cache,
+                    &face_pipeline,
+                    key,
+                    &mesh.layout,
+                ) {
+                    Ok(pipeline) => pipeline,
+                    Err(err) => {
+                        error!("{}", err);
+                        continue;
+                    }
+                };
+                opaque_phase.add(Opaque3d {
+                    entity,
+                    pipeline,
+                    draw_function: draw_faces,
+                    distance: rangefinder.distance(&mesh_uniform.transform),
+                });
+            }
+        }
+    }
+
+    type DrawFaces = (
+        SetItemPipeline,
+        SetMeshViewBindGroup<0>,
+        SetMeshBindGroup<1>,
+        SetFaceTextureBindGroup<2>,
+        DrawFaceInstances,
+    );
+
+    struct SetFaceTextureBindGroup<const I: usize>;
+
+    impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetFaceTextureBindGroup<I> {
+        type Param = SRes<FaceBuffers>;
+        type ViewWorldQuery = ();
+        type ItemWorldQuery = ();
+
+        fn render<'w>(
+            _item: &P,
+            _view: (),
+            _entity: (),
+            face_buffers: SystemParamItem<'w, '_, Self::Param>,
+            pass: &mut TrackedRenderPass<'w>,
+        ) -> RenderCommandResult {
+            let Some(bind_group) = face_buffers.into_inner().texture_bind_group.as_ref() else {
+                return RenderCommandResult::Failure;
+            };
+            pass.set_bind_group(I, bind_group, &[]);
+            RenderCommandResult::Success
+        }
+    }
+
+    struct DrawFaceInstances;
+
+    impl<P: PhaseItem> RenderCommand<P> for DrawFaceInstances {
+        type Param = (SRes<RenderAssets<Mesh>>, SRes<FaceBuffers>);
+        type ViewWorldQuery = ();
+        type ItemWorldQuery = Read<Handle<Mesh>>;
+
+        fn render<'w>(
+            item: &P,
+            _view: (),
+            mesh_handle: ROQueryItem<'w, Self::ItemWorldQuery>,
+            (meshes, face_buffers): SystemParamItem<'w, '_, Self::Param>,
+            pass: &mut TrackedRenderPass<'w>,
+        ) -> RenderCommandResult {
+            let (Some(gpu_mesh), Some((buffer, count))) = (
+                meshes.into_inner().get(mesh_handle),
+                face_buffers.into_inner().buffers.get(&item.entity()),
+            ) else {
+                return RenderCommandResult::Failure;
+            };
+            pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+            pass.set_vertex_buffer(1, buffer.slice(..));
+            match &gpu_mesh.buffer_info {
+                GpuBufferInfo::Indexed {
+                    buffer,
+                    index_format,
+                    count: index_count,
+                } => {
+                    pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+                    pass.draw_indexed(0..*index_count, 0, 0..*count);
+                }
+                GpuBufferInfo::NonIndexed => {
+                    pass.draw(0..gpu_mesh.vertex_count, 0..*count);
+                }
+            }
+            RenderCommandResult::Success
+        }
+    }
+}
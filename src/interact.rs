@@ -0,0 +1,113 @@
+//! Using blocks: the place button on a block with an `OnUse` in the registry
+//! does what the block does instead of placing against it, flipping levers,
+//! pressing buttons, opening and closing doors and trapdoors, opening the
+//! windows of furnaces and signs and setting the respawn point on beds.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    command::CommandResponse,
+    game_mode::GameMode,
+    input::{InputAction, PlayerInput},
+    power::Circuits,
+    save::WorldSlot,
+    voxel::{
+        self, BlockId, OnUse, VoxelData, VoxelModifyQueue, VoxelPos, AIR, DOOR_TOP, DOOR_TOP_OPEN,
+    },
+    BlockBroken, Cooldowns, CrosshairTarget,
+};
+
+/// The block at `position` was used or placed to open its window
+#[derive(Event, Debug, Clone, Copy)]
+pub struct OpenBlock {
+    pub position: VoxelPos,
+}
+
+/// The other half of the door at `position`
+fn other_half(position: VoxelPos, block: BlockId) -> VoxelPos {
+    if matches!(block, DOOR_TOP | DOOR_TOP_OPEN) {
+        VoxelPos(position.0 - IVec3::Y)
+    } else {
+        VoxelPos(position.0 + IVec3::Y)
+    }
+}
+
+/// What using a block acts on: the blocks it changes, the circuits it powers and the windows
+/// it opens
+#[derive(SystemParam)]
+pub struct UseEffects<'w> {
+    circuits: ResMut<'w, Circuits>,
+    voxel_modify_queue: ResMut<'w, VoxelModifyQueue>,
+    open_events: EventWriter<'w, OpenBlock>,
+}
+
+/// Use the block looked at with the place button, if it does something
+pub fn use_blocks(
+    crosshair: CrosshairTarget,
+    player_input: Res<PlayerInput>,
+    game_mode: Res<GameMode>,
+    mut effects: UseEffects,
+    mut world_slot: ResMut<WorldSlot>,
+    mut responses: EventWriter<CommandResponse>,
+    mut cooldowns: Cooldowns,
+) {
+    if !player_input.actions.just_pressed(InputAction::Place)
+        || *game_mode == GameMode::Spectator
+        || !cooldowns.use_ready()
+    {
+        return;
+    }
+    let Some((target, _)) = crosshair.get() else {
+        return;
+    };
+    let UseEffects {
+        circuits,
+        voxel_modify_queue,
+        open_events,
+    } = &mut effects;
+    let voxel_data = &crosshair.voxel_data;
+    let block = voxel_data.get_block(target);
+    let on_use = voxel::on_use(block);
+    if on_use.is_some() {
+        cooldowns.start_use(crosshair.voxel_settings.use_cooldown);
+    }
+    match on_use {
+        Some(OnUse::Toggle(other)) => voxel_modify_queue.queue.push((target, other)),
+        Some(OnUse::Door(other)) => {
+            voxel_modify_queue.queue.push((target, other));
+            let half = other_half(target, block);
+            if let Some(OnUse::Door(other)) = voxel::on_use(voxel_data.get_block(half)) {
+                voxel_modify_queue.queue.push((half, other));
+            }
+        }
+        Some(OnUse::Press) => circuits.press(target, voxel_modify_queue),
+        Some(OnUse::Open) => open_events.send(OpenBlock { position: target }),
+        Some(OnUse::Respawn) => {
+            world_slot.set_bed(Some(target));
+            responses.send(CommandResponse {
+                message: "Respawn point set".to_string(),
+            });
+        }
+        None => {}
+    }
+}
+
+/// Mining either half of a door takes the other half with it
+pub fn break_doors(
+    voxel_data: Res<VoxelData>,
+    mut broken_events: EventReader<BlockBroken>,
+    mut voxel_modify_queue: ResMut<VoxelModifyQueue>,
+) {
+    for event in broken_events.iter() {
+        if !matches!(voxel::on_use(event.block), Some(OnUse::Door(_))) {
+            continue;
+        }
+        let half = other_half(event.position, event.block);
+        if matches!(
+            voxel::on_use(voxel_data.get_block(half)),
+            Some(OnUse::Door(_))
+        ) {
+            voxel_modify_queue.queue.push((half, AIR));
+        }
+    }
+}
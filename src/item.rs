@@ -0,0 +1,583 @@
+//! Blocks dropped as items when mined: small spinning cubes that fall onto
+//! the terrain, stack with identical items lying next to them and end up in
+//! the inventory once the player walks up to them. Seeds and wheat are items
+//! too, kept as the blocks they plant and grow into. Tools are kept one by
+//! one with the durability they have left, and the inventory is saved with
+//! the world.
+
+use std::collections::{BTreeMap, HashMap};
+
+use bevy::{app::AppExit, ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    farming::position_roll,
+    game_mode::GameMode,
+    input::{InputAction, PlayerInput},
+    player::Player,
+    save,
+    storage::{self, Folder},
+    tool::{Tool, ToolBroke, ToolItem},
+    voxel::{
+        self, BlockId, VoxelData, VoxelPos, WorldGenSettings, AIR, BUTTON_ON, DIRT, DOOR,
+        DOOR_OPEN, DOOR_TOP, DOOR_TOP_OPEN, FARMLAND, FIRE, GRASS, LAMP_ON, LEVER_ON, RIPE_WHEAT,
+        SNOW, TRAPDOOR, TRAPDOOR_OPEN, WATER, WHEAT, WIRE_ON,
+    },
+    BlockBroken, CrosshairTarget, VoxelMaterial,
+};
+
+const INVENTORY_FILE: &str = "inventory.ron";
+const SAVE_DELAY: f32 = 2.0; // seconds the inventory stays unchanged before it's written
+
+const ITEM_SIZE: f32 = 0.25; // edge of the item cube, in blocks
+const GRAVITY: f32 = 20.0;
+const TERMINAL_VELOCITY: f32 = 40.0;
+const SPIN_SPEED: f32 = 2.0; // radians per second
+const POP_SPEED: f32 = 4.0; // upwards speed of a freshly dropped item
+const MERGE_DISTANCE: f32 = 1.0;
+const PICKUP_DISTANCE: f32 = 2.0;
+const PICKUP_DELAY: f32 = 0.5; // seconds before a dropped item can be picked up
+const DESPAWN_AFTER: f32 = 300.0; // seconds an item lies around before it disappears
+const SEEDS_IN_GRASS: u64 = 8; // one in this many grass blocks drops seeds
+
+#[derive(Component, Debug)]
+pub struct DroppedItem {
+    pub block: BlockId,
+    pub count: u32,
+    velocity: Vec3,
+    age: f32, // seconds since it was dropped
+}
+
+impl DroppedItem {
+    /// Throw the item, it flies until it lands again
+    pub fn push(&mut self, velocity: Vec3) {
+        self.velocity += velocity;
+    }
+
+    pub fn age(&self) -> f32 {
+        self.age
+    }
+}
+
+/// Blocks the player picked up, by block id, and the tools they carry
+#[derive(Resource, Default, Debug, Serialize, Deserialize)]
+pub struct Inventory {
+    counts: BTreeMap<BlockId, u32>,
+    #[serde(default)]
+    tools: Vec<ToolItem>,
+}
+
+impl Inventory {
+    pub fn add(&mut self, block: BlockId, count: u32) {
+        *self.counts.entry(block).or_default() += count;
+    }
+
+    /// Remove `count` blocks, false and nothing removed if there aren't that many
+    pub fn take(&mut self, block: BlockId, count: u32) -> bool {
+        let Some(held) = self.counts.get_mut(&block).filter(|held| **held >= count) else {
+            return false;
+        };
+        *held -= count;
+        if *held == 0 {
+            self.counts.remove(&block);
+        }
+        true
+    }
+
+    pub fn count(&self, block: BlockId) -> u32 {
+        self.counts.get(&block).copied().unwrap_or_default()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (BlockId, u32)> + '_ {
+        self.counts.iter().map(|(block, count)| (*block, *count))
+    }
+
+    pub fn add_tool(&mut self, item: ToolItem) {
+        self.tools.push(item);
+    }
+
+    /// The first tool of a kind, the one that gets used
+    pub fn tool(&self, tool: Tool) -> Option<&ToolItem> {
+        self.tools.iter().find(|item| item.tool == tool)
+    }
+
+    pub fn tool_mut(&mut self, tool: Tool) -> Option<&mut ToolItem> {
+        self.tools.iter_mut().find(|item| item.tool == tool)
+    }
+
+    /// Remove the last tool of a kind, unless it's the only one
+    pub fn take_spare_tool(&mut self, tool: Tool) -> Option<ToolItem> {
+        let first = self.tools.iter().position(|item| item.tool == tool)?;
+        let last = self.tools.iter().rposition(|item| item.tool == tool)?;
+        (last != first).then(|| self.tools.remove(last))
+    }
+
+    fn remove_broken_tools(&mut self) {
+        self.tools.retain(|item| !item.durability.is_broken());
+    }
+
+    /// What a new world starts with
+    fn starter() -> Self {
+        let mut inventory = Inventory::default();
+        inventory.add_tool(ToolItem::new(Tool::Hoe));
+        inventory.add_tool(ToolItem::new(Tool::FlintAndSteel));
+        inventory
+    }
+}
+
+/// The inventory the way the game mode uses it: creative has endless items and tools that
+/// never wear, survival uses up the ones it picked up
+#[derive(SystemParam)]
+pub struct PlayerItems<'w> {
+    pub game_mode: Res<'w, GameMode>,
+    pub inventory: ResMut<'w, Inventory>,
+    broke_events: EventWriter<'w, ToolBroke>,
+}
+
+impl PlayerItems<'_> {
+    /// Use up `count` blocks, false and nothing used if survival doesn't have that many
+    pub fn take(&mut self, block: BlockId, count: u32) -> bool {
+        *self.game_mode != GameMode::Survival || self.inventory.take(block, count)
+    }
+
+    /// Wear a use off the first tool of a kind, false if survival has none. A tool worn
+    /// down to nothing breaks and is gone from the inventory
+    pub fn use_tool(&mut self, tool: Tool) -> bool {
+        if *self.game_mode != GameMode::Survival {
+            return true;
+        }
+        let Some(item) = self.inventory.tool_mut(tool) else {
+            return false;
+        };
+        item.durability.wear();
+        if item.durability.is_broken() {
+            self.inventory.remove_broken_tools();
+            self.broke_events.send(ToolBroke(tool.name()));
+        }
+        true
+    }
+}
+
+/// The block the place button puts down, taken from the inventory the way the game mode
+/// uses it
+#[derive(SystemParam)]
+pub struct BlockPlacer<'w> {
+    pub items: PlayerItems<'w>,
+    held_item: Res<'w, HeldItem>,
+    held_block: Res<'w, HeldBlock>,
+}
+
+impl BlockPlacer<'_> {
+    /// The held block, None while another item is held
+    pub fn held(&self) -> Option<BlockId> {
+        (*self.held_item == HeldItem::Block).then_some(self.held_block.0)
+    }
+}
+
+/// What the place button uses
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HeldItem {
+    #[default]
+    Block,
+    Hoe, // tills grass and dirt into farmland
+    Seeds,
+    Food,
+    FlintAndSteel, // sets fire to the block looked at
+    Snowball,      // thrown, made of snow
+}
+
+impl HeldItem {
+    pub fn next(self) -> Self {
+        match self {
+            HeldItem::Block => HeldItem::Hoe,
+            HeldItem::Hoe => HeldItem::Seeds,
+            HeldItem::Seeds => HeldItem::Food,
+            HeldItem::Food => HeldItem::FlintAndSteel,
+            HeldItem::FlintAndSteel => HeldItem::Snowball,
+            HeldItem::Snowball => HeldItem::Block,
+        }
+    }
+
+    /// The inventory item it uses up in survival, none for tools which wear instead
+    pub fn item(self) -> Option<BlockId> {
+        match self {
+            HeldItem::Block => Some(DIRT),
+            HeldItem::Hoe | HeldItem::FlintAndSteel => None,
+            HeldItem::Seeds => Some(WHEAT),
+            HeldItem::Food => Some(RIPE_WHEAT),
+            HeldItem::Snowball => Some(SNOW),
+        }
+    }
+
+    /// The tool it is, if it's one
+    pub fn tool(self) -> Option<Tool> {
+        match self {
+            HeldItem::Hoe => Some(Tool::Hoe),
+            HeldItem::FlintAndSteel => Some(Tool::FlintAndSteel),
+            _ => None,
+        }
+    }
+}
+
+pub fn cycle_held_item(player_input: Res<PlayerInput>, mut held_item: ResMut<HeldItem>) {
+    if player_input.actions.just_pressed(InputAction::NextItem) {
+        *held_item = held_item.next();
+    }
+}
+
+fn inventory_file(settings: &WorldGenSettings) -> String {
+    format!("{}/{}", save::world_dir(settings), INVENTORY_FILE)
+}
+
+/// Read the inventory saved with the world, a world without one starts with the starter kit
+pub fn load_inventory(settings: Res<WorldGenSettings>, mut inventory: ResMut<Inventory>) {
+    let file = inventory_file(&settings);
+    let Some(contents) = storage::read(Folder::Data, &file)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+    else {
+        *inventory = Inventory::starter();
+        return;
+    };
+    match ron::from_str::<Inventory>(&contents) {
+        Ok(loaded) => *inventory = loaded,
+        Err(err) => warn!(
+            "Ignoring inventory file {}: {}",
+            storage::describe(Folder::Data, &file),
+            err
+        ),
+    }
+}
+
+/// Write the inventory once it's been unchanged for `SAVE_DELAY`, and on exit
+pub fn save_inventory(
+    time: Res<Time>,
+    settings: Res<WorldGenSettings>,
+    inventory: Res<Inventory>,
+    mut exit_events: EventReader<AppExit>,
+    mut changed_at: Local<Option<f32>>,
+) {
+    if inventory.is_changed() && !inventory.is_added() {
+        *changed_at = Some(time.elapsed_seconds());
+    }
+    let exiting = exit_events.iter().count() > 0;
+    let settled = changed_at.is_some_and(|at| time.elapsed_seconds() - at >= SAVE_DELAY);
+    if changed_at.is_none() || !(settled || exiting) {
+        return;
+    }
+    *changed_at = None;
+
+    let file = inventory_file(&settings);
+    let result = ron::ser::to_string_pretty(&*inventory, ron::ser::PrettyConfig::default())
+        .map_err(|err| err.to_string())
+        .and_then(|contents| storage::write(Folder::Data, &file, contents.as_bytes()));
+    if let Err(err) = result {
+        warn!(
+            "Failed to save {}: {}",
+            storage::describe(Folder::Data, &file),
+            err
+        );
+    }
+}
+
+/// The block `HeldItem::Block` places
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeldBlock(pub BlockId);
+
+impl Default for HeldBlock {
+    fn default() -> Self {
+        HeldBlock(DIRT)
+    }
+}
+
+/// Hold the block looked at, as the item it drops, to place more of it
+pub fn pick_block(
+    crosshair: CrosshairTarget,
+    player_input: Res<PlayerInput>,
+    game_mode: Res<GameMode>,
+    mut held_item: ResMut<HeldItem>,
+    mut held_block: ResMut<HeldBlock>,
+) {
+    if !player_input.actions.just_pressed(InputAction::PickBlock)
+        || *game_mode == GameMode::Spectator
+    {
+        return;
+    }
+    let Some((target, _)) = crosshair.get() else {
+        return;
+    };
+    let Some(block) = drops(crosshair.voxel_data.get_block(target), target)
+        .first()
+        .copied()
+    else {
+        return;
+    };
+    if *held_item != HeldItem::Block {
+        *held_item = HeldItem::Block;
+    }
+    if held_block.0 != block {
+        held_block.0 = block;
+    }
+}
+
+/// Items a mined block drops: crops give back their seeds, and food once ripe
+fn drops(block: BlockId, position: VoxelPos) -> Vec<BlockId> {
+    if let Some(drops) = voxel::defined_drops(block) {
+        return drops;
+    }
+    match block {
+        AIR | FIRE | WATER => Vec::new(),
+        FARMLAND => vec![DIRT],
+        // back to unpowered
+        WIRE_ON | LEVER_ON | BUTTON_ON | LAMP_ON => vec![BlockId(block.0 - 1)],
+        DOOR_OPEN | DOOR_TOP | DOOR_TOP_OPEN => vec![DOOR],
+        TRAPDOOR_OPEN => vec![TRAPDOOR],
+        RIPE_WHEAT => vec![RIPE_WHEAT, WHEAT, WHEAT],
+        _ if (WHEAT..=RIPE_WHEAT).contains(&block) => vec![WHEAT],
+        GRASS if position_roll(position, 0).is_multiple_of(SEEDS_IN_GRASS) => vec![GRASS, WHEAT],
+        _ => vec![block],
+    }
+}
+
+/// One mesh per block, shared by all the items of that block
+#[derive(Resource, Default)]
+pub struct ItemMeshes {
+    meshes: HashMap<BlockId, Handle<Mesh>>,
+}
+
+impl ItemMeshes {
+    /// The mesh of a block, made the first time it's asked for
+    pub fn mesh(&mut self, meshes: &mut Assets<Mesh>, block: BlockId) -> Handle<Mesh> {
+        self.meshes
+            .entry(block)
+            .or_insert_with(|| meshes.add(voxel::block_mesh(block).into()))
+            .clone()
+    }
+}
+
+pub fn spawn_dropped_items(
+    mut commands: Commands,
+    mut broken_events: EventReader<BlockBroken>,
+    mut item_meshes: ResMut<ItemMeshes>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    voxel_material: Res<VoxelMaterial>,
+) {
+    for event in broken_events.iter() {
+        for block in drops(event.block, event.position) {
+            spawn_item(
+                &mut commands,
+                &mut item_meshes,
+                &mut meshes,
+                &voxel_material,
+                (block, 1),
+                event.position,
+            );
+        }
+    }
+}
+
+/// Drop a stack of `count` of `block` popping up out of the block at `position`
+pub(crate) fn spawn_item(
+    commands: &mut Commands,
+    item_meshes: &mut ItemMeshes,
+    meshes: &mut Assets<Mesh>,
+    voxel_material: &VoxelMaterial,
+    (block, count): (BlockId, u32),
+    position: VoxelPos,
+) {
+    let item = DroppedItem {
+        block,
+        count,
+        velocity: Vec3::Y * POP_SPEED,
+        age: 0.0,
+    };
+    let translation = position.to_world() + Vec3::splat(0.5);
+    spawn_stack(
+        commands,
+        item_meshes,
+        meshes,
+        voxel_material,
+        item,
+        translation,
+    );
+}
+
+/// Put a stack that's `age` seconds old back at `translation`, lying still
+pub(crate) fn respawn_item(
+    commands: &mut Commands,
+    item_meshes: &mut ItemMeshes,
+    meshes: &mut Assets<Mesh>,
+    voxel_material: &VoxelMaterial,
+    (block, count): (BlockId, u32),
+    age: f32,
+    translation: Vec3,
+) {
+    let item = DroppedItem {
+        block,
+        count,
+        velocity: Vec3::ZERO,
+        age,
+    };
+    spawn_stack(
+        commands,
+        item_meshes,
+        meshes,
+        voxel_material,
+        item,
+        translation,
+    );
+}
+
+fn spawn_stack(
+    commands: &mut Commands,
+    item_meshes: &mut ItemMeshes,
+    meshes: &mut Assets<Mesh>,
+    voxel_material: &VoxelMaterial,
+    item: DroppedItem,
+    translation: Vec3,
+) {
+    let block = item.block;
+    let mesh = item_meshes.mesh(meshes, block);
+    let transparent = voxel::is_transparent(block);
+    let material = if transparent {
+        voxel_material.transparent_material.clone()
+    } else {
+        voxel_material.material.clone()
+    };
+    let mut entity = commands.spawn((
+        MaterialMeshBundle {
+            mesh,
+            material,
+            transform: Transform::from_translation(translation).with_scale(Vec3::splat(ITEM_SIZE)),
+            ..default()
+        },
+        item,
+        Name::new("Dropped item"),
+    ));
+    if transparent {
+        entity.insert(voxel::TransparentPass);
+    }
+}
+
+/// Whether a point is inside a block items can't fall through
+fn is_solid(voxel_data: &VoxelData, point: Vec3) -> bool {
+    voxel::is_solid(voxel_data.get_block(VoxelPos::from_world(point)))
+}
+
+/// Fall, spin and land on the block below, items in unloaded chunks wait where they are
+pub fn update_dropped_items(
+    mut commands: Commands,
+    time: Res<Time>,
+    voxel_data: Res<VoxelData>,
+    mut item_query: Query<(Entity, &mut DroppedItem, &mut Transform)>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut item, mut transform) in item_query.iter_mut() {
+        item.age += dt;
+        if item.age > DESPAWN_AFTER {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        transform.rotate_y(SPIN_SPEED * dt);
+        if !voxel_data.is_loaded(voxel::get_chunk_index(&transform.translation)) {
+            continue;
+        }
+
+        item.velocity.y = (item.velocity.y - GRAVITY * dt).max(-TERMINAL_VELOCITY);
+        let mut next = transform.translation + item.velocity * dt;
+        let bottom = next - Vec3::Y * ITEM_SIZE / 2.0;
+        if item.velocity.y <= 0.0 && is_solid(&voxel_data, bottom) {
+            // rest on top of the block
+            next.y = bottom.floor().y + 1.0 + ITEM_SIZE / 2.0;
+            item.velocity = Vec3::ZERO;
+        }
+        transform.translation = next;
+    }
+}
+
+/// Pair the items with a stack of the same block within `MERGE_DISTANCE` to fold into, the
+/// first of a kind in a spot being the stack. Stacks are bucketed by position, and an item
+/// is compared with the stacks in its bucket and the ones around, which may be closer than
+/// the bucket's edge
+fn merge_pairs(items: impl IntoIterator<Item = (Entity, BlockId, Vec3)>) -> Vec<(Entity, Entity)> {
+    let mut stacks: HashMap<(BlockId, IVec3), Vec<(Entity, Vec3)>> = HashMap::new();
+    let mut merged = Vec::new();
+    for (entity, block, position) in items {
+        let bucket = (position / MERGE_DISTANCE).floor().as_ivec3();
+        let near = (-1..=1)
+            .flat_map(|x| (-1..=1).flat_map(move |y| (-1..=1).map(move |z| IVec3::new(x, y, z))))
+            .filter_map(|offset| stacks.get(&(block, bucket + offset)))
+            .flatten()
+            .find(|(_, stack)| stack.distance(position) <= MERGE_DISTANCE);
+        match near {
+            Some(&(stack, _)) => merged.push((entity, stack)),
+            None => stacks
+                .entry((block, bucket))
+                .or_default()
+                .push((entity, position)),
+        }
+    }
+    merged
+}
+
+/// Fold items of the same block lying close together into one stack
+pub fn merge_dropped_items(
+    mut commands: Commands,
+    mut item_query: Query<(Entity, &mut DroppedItem, &Transform)>,
+) {
+    let merged = merge_pairs(
+        item_query
+            .iter()
+            .map(|(entity, item, transform)| (entity, item.block, transform.translation)),
+    );
+    for (entity, stack) in merged {
+        let Ok((_, item, _)) = item_query.get(entity) else {
+            continue;
+        };
+        let (count, age) = (item.count, item.age);
+        if let Ok((_, mut stack_item, _)) = item_query.get_mut(stack) {
+            stack_item.count += count;
+            // the stack lasts as long as its youngest item would have
+            stack_item.age = stack_item.age.min(age);
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Move the items next to the player into the inventory
+pub fn pick_up_dropped_items(
+    mut commands: Commands,
+    mut inventory: ResMut<Inventory>,
+    player_query: Query<&Transform, With<Player>>,
+    item_query: Query<(Entity, &DroppedItem, &Transform)>,
+) {
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+    let player = player.translation;
+    for (entity, item, transform) in item_query.iter() {
+        if item.age >= PICKUP_DELAY && transform.translation.distance(player) <= PICKUP_DISTANCE {
+            inventory.add(item.block, item.count);
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn items_merge_across_bucket_edges_but_not_farther() {
+        let entity = Entity::from_raw;
+        let merged = merge_pairs([
+            (entity(0), DIRT, Vec3::new(0.95, 0.5, 0.5)),
+            // over the edge of the first one's bucket, a few centimeters away
+            (entity(1), DIRT, Vec3::new(1.05, 0.5, 0.5)),
+            // the same bucket as that one, too far from the stack
+            (entity(2), DIRT, Vec3::new(1.99, 0.5, 0.5)),
+            (entity(3), SNOW, Vec3::new(0.95, 0.5, 0.5)),
+        ]);
+        assert_eq!(merged, vec![(entity(1), entity(0))]);
+    }
+}
@@ -0,0 +1,342 @@
+//! Optional event-sourced world: every edit is appended to a journal, and a
+//! chunk is whatever its latest snapshot (or the generator) plus the journal
+//! replays to, so the world or a region can be rolled back to a point in time.
+
+use std::collections::{HashMap, HashSet};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_inspector_egui::prelude::*;
+
+use crate::{
+    codec,
+    decoration::ChunkDecorators,
+    voxel::{self, ChunkData, ChunkIndex, VoxelData, VoxelPos, VoxelWrite},
+    world::VoxelWorld,
+    WorldGenSettings,
+};
+
+#[derive(Reflect, Resource, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub struct JournalSettings {
+    pub enabled: bool,
+    pub snapshot_interval: f32, // seconds between snapshots of the edited chunks
+}
+
+impl Default for JournalSettings {
+    fn default() -> Self {
+        JournalSettings {
+            enabled: false,
+            snapshot_interval: 300.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JournalEntry {
+    pub time: f64, // elapsed seconds when the edit was applied
+    pub position: VoxelPos,
+    pub write: VoxelWrite,
+}
+
+struct ChunkSnapshot {
+    time: f64,
+    bytes: Vec<u8>, // chunk encoded with the codec
+}
+
+/// Which chunks a rollback applies to
+#[derive(Debug, Clone, Copy)]
+pub enum RollbackScope {
+    World,
+    Region { center: ChunkIndex, radius: i32 }, // chunk columns within `radius` of `center`
+}
+
+impl RollbackScope {
+    fn contains(&self, index: &ChunkIndex) -> bool {
+        match self {
+            RollbackScope::World => true,
+            RollbackScope::Region { center, radius } => {
+                (index.x - center.x).abs().max((index.z - center.z).abs()) <= *radius
+            }
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct WorldJournal {
+    pub entries: Vec<JournalEntry>,                     // in time order
+    snapshots: HashMap<ChunkIndex, Vec<ChunkSnapshot>>, // in time order per chunk
+    touched: HashSet<ChunkIndex>,                       // edited since their last snapshot
+    last_snapshot: f64,
+}
+
+impl WorldJournal {
+    pub fn record(&mut self, time: f64, position: VoxelPos, write: VoxelWrite) {
+        self.touched.insert(position.chunk());
+        self.entries.push(JournalEntry {
+            time,
+            position,
+            write,
+        });
+    }
+
+    /// Record the writes `voxel_data` kept since the last call as made at `time`, and have it
+    /// keep them from now on
+    pub fn take_writes(&mut self, time: f64, voxel_data: &mut VoxelData) {
+        for (position, write) in voxel_data.journal.get_or_insert_with(Vec::new).drain(..) {
+            self.record(time, position, write);
+        }
+    }
+
+    /// Rebuild the loaded chunks in `scope` as they were at `time`, returns how many changed.
+    /// The edits to chunks that aren't loaded are kept, to roll them back once they are
+    pub fn rollback(
+        &mut self,
+        time: f64,
+        scope: RollbackScope,
+        world: &mut VoxelWorld,
+        settings: &WorldGenSettings,
+        decorators: &ChunkDecorators,
+    ) -> usize {
+        let affected: HashSet<ChunkIndex> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.time > time)
+            .map(|entry| entry.position.chunk())
+            .filter(|index| scope.contains(index))
+            .collect();
+
+        let mut restored = HashSet::new();
+        for index in affected {
+            if !world.is_loaded(index) {
+                continue;
+            }
+            let Some(chunk) = self.replay(index, time, settings, decorators) else {
+                continue;
+            };
+            // the writes this frame that `record_journal` hasn't taken yet are undone with it
+            world.restore_chunk(chunk);
+            if let Some(snapshots) = self.snapshots.get_mut(&index) {
+                snapshots.retain(|snapshot| snapshot.time <= time);
+            }
+            restored.insert(index);
+        }
+        self.entries
+            .retain(|entry| entry.time <= time || !restored.contains(&entry.position.chunk()));
+        restored.len()
+    }
+
+    /// The chunk at `time`, from its latest snapshot before then and the edits after it
+    fn replay(
+        &self,
+        index: ChunkIndex,
+        time: f64,
+        settings: &WorldGenSettings,
+        decorators: &ChunkDecorators,
+    ) -> Option<ChunkData> {
+        let snapshot = self
+            .snapshots
+            .get(&index)
+            .and_then(|snapshots| snapshots.iter().rev().find(|s| s.time <= time));
+        let (mut chunk, since) = match snapshot {
+            Some(snapshot) => match codec::decode_chunk(&snapshot.bytes) {
+                Ok(chunk) => (chunk, snapshot.time),
+                Err(err) => {
+                    warn!(
+                        "Cannot restore chunk {:?} from its snapshot: {}",
+                        index, err
+                    );
+                    return None;
+                }
+            },
+            None => {
+                let chunk = voxel::generate_chunks(&[index], settings, decorators).pop()?;
+                (chunk, f64::NEG_INFINITY)
+            }
+        };
+
+        for entry in self.entries.iter() {
+            if entry.time <= since || entry.time > time {
+                continue;
+            }
+            let (entry_index, local) = entry.position.split();
+            if entry_index != index {
+                continue;
+            }
+            match entry.write {
+                VoxelWrite::Block(block) => {
+                    chunk.set_block(local, block);
+                }
+                VoxelWrite::State(state) => chunk.set_state(local, state),
+            }
+        }
+        Some(chunk)
+    }
+}
+
+/// Move the writes made to the world this frame into the journal while it's enabled
+pub fn record_journal(
+    time: Res<Time>,
+    journal_settings: Res<JournalSettings>,
+    mut voxel_data: ResMut<VoxelData>,
+    mut journal: ResMut<WorldJournal>,
+) {
+    if !journal_settings.enabled {
+        voxel_data.journal = None;
+        return;
+    }
+    journal.take_writes(time.elapsed_seconds_f64(), &mut voxel_data);
+}
+
+/// The journal with what a rollback needs besides the world, for `/rollback`
+#[derive(SystemParam)]
+pub struct JournalTools<'w> {
+    settings: Res<'w, JournalSettings>,
+    journal: ResMut<'w, WorldJournal>,
+    time: Res<'w, Time>,
+    decorators: Res<'w, ChunkDecorators>,
+}
+
+impl JournalTools<'_> {
+    /// Roll the loaded chunks in `scope` back by `seconds`, returns how many changed
+    pub fn rollback(
+        &mut self,
+        seconds: f64,
+        scope: RollbackScope,
+        world: &mut VoxelWorld,
+        settings: &WorldGenSettings,
+    ) -> Result<usize, String> {
+        if !self.settings.enabled {
+            return Err("the world journal is disabled".to_string());
+        }
+        let time = self.time.elapsed_seconds_f64() - seconds;
+        Ok(self
+            .journal
+            .rollback(time, scope, world, settings, &self.decorators))
+    }
+}
+
+/// Snapshot the chunks edited since the last snapshot, every `snapshot_interval` seconds
+pub fn snapshot_journal(
+    time: Res<Time>,
+    journal_settings: Res<JournalSettings>,
+    voxel_data: Res<VoxelData>,
+    mut journal: ResMut<WorldJournal>,
+) {
+    let now = time.elapsed_seconds_f64();
+    if !journal_settings.enabled
+        || now - journal.last_snapshot < journal_settings.snapshot_interval as f64
+    {
+        return;
+    }
+    journal.last_snapshot = now;
+    let touched: Vec<ChunkIndex> = journal.touched.drain().collect();
+    for index in touched {
+        if let Some(chunk) = voxel_data.chunk(index) {
+            journal
+                .snapshots
+                .entry(index)
+                .or_default()
+                .push(ChunkSnapshot {
+                    time: now,
+                    bytes: codec::encode_chunk(chunk),
+                });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+
+    use super::*;
+    use crate::voxel::{ChunkMeshesUpdateQueue, STONE, STONE_STAIRS};
+
+    const HOME: ChunkIndex = ChunkIndex { x: 0, y: 0, z: 0 };
+    const AWAY: ChunkIndex = ChunkIndex { x: 1, y: 0, z: 0 };
+
+    /// A journal with all-air snapshots of `indices` at time 0, and the world it keeps
+    fn journaled(indices: &[ChunkIndex]) -> (WorldJournal, VoxelData) {
+        let mut voxel_data = VoxelData::air(indices.iter().copied());
+        voxel_data.journal = Some(Vec::new());
+        let mut journal = WorldJournal::default();
+        for index in indices {
+            journal
+                .snapshots
+                .entry(*index)
+                .or_default()
+                .push(ChunkSnapshot {
+                    time: 0.0,
+                    bytes: codec::encode_chunk(voxel_data.chunk(*index).unwrap()),
+                });
+        }
+        (journal, voxel_data)
+    }
+
+    /// Roll back the whole world through a `VoxelWorld` as `/rollback` does
+    fn rollback(journal: &mut WorldJournal, time: f64, voxel_data: &mut VoxelData) -> usize {
+        let mut world = World::new();
+        world.insert_resource(std::mem::take(voxel_data));
+        world.init_resource::<ChunkMeshesUpdateQueue>();
+        let mut state = SystemState::<VoxelWorld>::new(&mut world);
+        let restored = journal.rollback(
+            time,
+            RollbackScope::World,
+            &mut state.get_mut(&mut world),
+            &WorldGenSettings::default(),
+            &ChunkDecorators::default(),
+        );
+        *voxel_data = world.remove_resource().unwrap();
+        restored
+    }
+
+    #[test]
+    fn rollback_restores_blocks_and_states() {
+        let (mut journal, mut voxel_data) = journaled(&[HOME]);
+        let position = VoxelPos::new(3, 4, 5);
+        voxel_data.set_block(position, STONE_STAIRS);
+        voxel_data.set_state(position, 2);
+        journal.take_writes(1.0, &mut voxel_data);
+        voxel_data.set_state(position, 3);
+        journal.take_writes(3.0, &mut voxel_data);
+
+        assert_eq!(rollback(&mut journal, 2.0, &mut voxel_data), 1);
+        assert_eq!(voxel_data.get_block(position), STONE_STAIRS);
+        assert_eq!(voxel_data.get_state(position), 2);
+        assert_eq!(journal.entries.len(), 2);
+    }
+
+    #[test]
+    fn rollback_keeps_the_edits_of_chunks_that_arent_loaded() {
+        let (mut journal, mut voxel_data) = journaled(&[HOME, AWAY]);
+        let home = VoxelPos::new(3, 4, 5);
+        let away = VoxelPos::new(20, 4, 5);
+        voxel_data.set_block(home, STONE);
+        voxel_data.set_block(away, STONE);
+        journal.take_writes(1.0, &mut voxel_data);
+
+        // saved with the edit and unloaded, the rollback can't get at it yet
+        let (saved, _) = voxel_data.remove_chunk(AWAY).unwrap();
+        assert_eq!(rollback(&mut journal, 0.5, &mut voxel_data), 1);
+        assert_eq!(voxel_data.get_block(home), voxel::AIR);
+        assert_eq!(journal.entries.len(), 1);
+
+        voxel_data.insert_chunk(saved);
+        assert_eq!(voxel_data.get_block(away), STONE);
+        assert_eq!(rollback(&mut journal, 0.5, &mut voxel_data), 1);
+        assert_eq!(voxel_data.get_block(away), voxel::AIR);
+        assert!(journal.entries.is_empty());
+    }
+
+    #[test]
+    fn writes_not_taken_yet_are_undone_with_their_chunk() {
+        let (mut journal, mut voxel_data) = journaled(&[HOME]);
+        let position = VoxelPos::new(3, 4, 5);
+        voxel_data.set_block(position, STONE);
+        journal.take_writes(1.0, &mut voxel_data);
+        voxel_data.set_state(position, 1);
+
+        assert_eq!(rollback(&mut journal, 0.5, &mut voxel_data), 1);
+        journal.take_writes(2.0, &mut voxel_data);
+        assert!(journal.entries.is_empty());
+    }
+}
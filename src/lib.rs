@@ -1,40 +1,239 @@
+mod assets;
+mod autotune;
+mod block_entity;
+mod camera;
+mod chat;
+mod chunk_info;
+mod chunk_overlay;
+pub mod codec;
+mod command;
+mod console;
+mod crash;
+mod daytime;
+mod debug_screen;
+mod decoration;
+mod edit;
+mod falling;
+mod farming;
+mod fire;
+mod free_camera;
+mod furnace;
+mod game_mode;
+mod health;
+mod hud;
+mod hunger;
+mod idle;
+mod input;
+mod instanced;
+mod interact;
+mod item;
+mod journal;
+mod main_menu;
+mod memory;
+mod menu;
+mod minimap;
+mod mob;
+mod physics;
+mod player;
+mod power;
+mod profiler;
+mod projectile;
+mod prompts;
+mod protection;
+mod random_tick;
+mod region;
+mod remote_player;
+mod repair;
+mod save;
+mod schematic;
+mod screenshot;
+mod scripting;
+mod server;
+mod settings;
+mod sign;
+mod sky;
+mod storage;
+mod stored_entity;
+mod stress;
+mod swimming;
+mod teleport;
+mod terrain_picking;
+mod texture_pack;
+mod tnt;
+mod tool;
+mod underwater;
 mod voxel;
+mod waypoint;
+mod web;
+mod world;
 
-use std::f32::consts::PI;
+use std::{
+    collections::{HashMap, HashSet},
+    f32::consts::PI,
+};
 
 use bevy::{
     asset::LoadState,
     diagnostic::{Diagnostics, DiagnosticsStore, FrameTimeDiagnosticsPlugin},
-    pbr::wireframe::{Wireframe, WireframeConfig, WireframePlugin},
+    ecs::system::SystemParam,
+    pbr::{wireframe::WireframeConfig, MaterialPipeline, MaterialPipelineKey},
     prelude::*,
     reflect::{TypePath, TypeUuid},
-    render::render_resource::{AsBindGroup, Extent3d, ShaderRef, TextureDimension, TextureFormat},
-    window::PrimaryWindow,
-};
-use smooth_bevy_cameras::{
-    controllers::fps::{FpsCameraBundle, FpsCameraController, FpsCameraPlugin},
-    LookTransformPlugin,
+    render::{
+        mesh::MeshVertexBufferLayout,
+        render_resource::{
+            AsBindGroup, Extent3d, RenderPipelineDescriptor, ShaderRef,
+            SpecializedMeshPipelineError,
+        },
+    },
+    utils::Instant,
+    window::{PresentMode, PrimaryWindow},
 };
+use smooth_bevy_cameras::controllers::fps::{FpsCameraBundle, FpsCameraController};
 
 use bevy_inspector_egui::prelude::*;
-use bevy_inspector_egui::quick::ResourceInspectorPlugin;
 
-use voxel::{Chunk, ChunkColumn, ChunkData, ChunkIndex, ChunkMesh};
+use voxel::{Chunk, ChunkColumn};
 
 use bevy_mod_picking::prelude::*;
+use serde::{Deserialize, Serialize};
 
-pub use voxel::VoxelSettings;
+pub use assets::{
+    setup_fallback_assets, show_asset_errors, use_fallback_fonts, AssetStatus, FallbackFont,
+};
+pub use autotune::{auto_tune, setup_auto_tune_notice, AutoTune, AutoTuneSettings, TunedQuality};
+pub use block_entity::{BlockEntity, BlockEntityRemoved};
+pub use camera::{camera_effects, CameraSettings};
+pub use chat::{chat_overlay, record_chat, ChatHistory, ChatLine, ChatReceived, ChatSent};
+pub use chunk_info::{update_chunk_summaries, ChunkStage, ChunkSummary, ColumnMeshStats};
+pub use chunk_overlay::{chunk_overlay, toggle_chunk_overlay};
+pub use command::{run_commands, CommandRequest, CommandResponse};
+pub use console::{console, console_closed, Console};
+pub use crash::{
+    check_last_crash, crash_prompt, install_crash_reporter, update_crash_context, LastCrash,
+    SafeMode,
+};
+pub use daytime::{advance_time, update_sun, WorldTime};
+pub use debug_screen::{setup_debug_screen, toggle_debug_screen, update_debug_screen};
+pub use decoration::{AddChunkDecorator, ChunkDecorator, ChunkDecorators, DecorationContext};
+pub use edit::{apply_bulk_edits, draw_selection, select_corners, BulkEdits, Selection};
+pub use falling::{spawn_falling_blocks, update_falling_blocks, LooseBlocks};
+pub use farming::use_held_item;
+pub use fire::{find_nearby_fires, ignite, setup_fire, update_fire_effects, NearbyFires};
+pub use free_camera::{toggle_free_camera, FreeCamera};
+pub use furnace::{
+    drop_furnace_contents, furnace_closed, furnace_window, smelt, Furnace, FurnaceWindow,
+    SMELT_TICK,
+};
+pub use game_mode::GameMode;
+pub use health::{
+    apply_damage, death_screen, death_screen_closed, fall_damage, setup_hearts, update_hearts,
+    Damage, DeathScreen, Health, MAX_HEALTH,
+};
+pub use hud::{setup_hud, toggle_hud, update_crosshair, update_hud, Crosshair, HudSettings};
+pub use hunger::{setup_food, update_food, update_hunger, Hunger, MAX_FOOD};
+pub use idle::{update_idle, window_active, IdleSettings, WindowIdle};
+pub use input::{drive_camera, read_player_input, Binding, InputAction, KeyBindings, PlayerInput};
+pub use instanced::InstancedFacesPlugin;
+pub use interact::{break_doors, use_blocks, OpenBlock};
+pub use item::{
+    cycle_held_item, load_inventory, merge_dropped_items, pick_block, pick_up_dropped_items,
+    save_inventory, spawn_dropped_items, update_dropped_items, BlockPlacer, DroppedItem, HeldBlock,
+    HeldItem, Inventory, ItemMeshes, PlayerItems,
+};
+pub use journal::{record_journal, snapshot_journal, JournalSettings, RollbackScope, WorldJournal};
+pub use main_menu::{
+    finish_loading, in_world, leave_menus, main_menu, setup_loading_screen, setup_main_menu,
+    sync_pause_state, AppState, InWorld, MainMenu, MenuCamera,
+};
+pub use memory::{track_memory, MemoryUsage};
+pub use menu::{pause_menu, toggle_pause_menu, PauseMenu};
+pub use minimap::{setup_minimap, update_minimap, Minimap};
+pub use mob::{mob_attacks, move_mobs, setup_mobs, spawn_mobs, update_mob_paths, Mob, MobKind};
+pub use physics::PhysicsPlugin;
+pub use player::{
+    player_gravity, setup_player, toggle_camera_mode, update_player, CameraMode, Player,
+};
+pub use power::{power_at, power_tick, track_circuits, Circuits, MAX_POWER};
+pub use profiler::ProfilerDiagnosticsPlugin;
+pub use projectile::{
+    setup_projectiles, snowball_hits, throw_snowball, update_projectiles, HitTarget, Projectile,
+    ProjectileHit, ProjectileKind,
+};
+pub use prompts::{setup_keybind_hints, update_keybind_hints, Action, KeybindHints};
+pub use protection::{load_claims, save_claims, Claim, Claims, Editor};
+pub use random_tick::{random_ticks, RandomTickSettings, TickContext};
+pub use region::{setup_region_title, update_region_title, WorldMetadata};
+pub use remote_player::{
+    update_name_tags, update_remote_players, PlayerJoined, PlayerLeft, PlayerMoved, RemotePlayer,
+    RemotePlayers,
+};
+pub use repair::{regenerate_world, repair_world, setup_regenerate_button, RepairReport};
+pub use save::{list_worlds, save_modified_chunks, ChunkStore, WorldInfo, WorldSave, WorldSlot};
+pub use schematic::Schematic;
+pub use screenshot::{capture_screenshots, ScreenshotSettings};
+pub use scripting::{
+    load_scripts, script_block_breaks, script_commands, script_ticks, Scripts, SCRIPT_TICK,
+};
+pub use server::{
+    apply_edit_verdicts, apply_granted_sight_range, grant_sight_range, request_sight_range,
+    validate_edits, EditBroadcast, EditRejection, EditRequest, EditVerdict, GrantedSightRange,
+    PendingEdits, PredictedEdits, ServerSettings, SightRangeGranted, SightRangeRequest,
+};
+pub use settings::{load_settings, save_settings};
+pub use sign::{
+    sign_editor, sign_editor_closed, update_sign_texts, Sign, SignEditor, SignTexts,
+    SIGN_TEXT_LIMIT,
+};
+pub use sky::{setup_sky, update_sky, SkySettings};
+pub use stored_entity::{restore_stored_entities, StoredEntity};
+pub use swimming::{
+    detect_underwater, setup_air, underwater_view, update_air, update_breath, Breath, Underwater,
+    MAX_AIR,
+};
+pub use teleport::{update_teleport, Destination, PendingTeleport};
+pub use terrain_picking::{pick_terrain, TerrainPickingPlugin};
+pub use texture_pack::LAYER_NAMES;
+pub use tnt::{explode, light_tnt, update_primed_tnt, Explosion, LightTnt};
+pub use tool::{
+    repair_tool, setup_tool_hud, update_held_tool, update_tool_hud, Durability, HeldTool, Tool,
+    ToolBroke, ToolItem,
+};
+pub use underwater::{
+    detect_underwater_view, underwater_pass, underwater_terrain, UnderwaterPlugin, UnderwaterView,
+};
+pub use voxel::{
+    apply_block_definitions, combine_meshes, greedy_meshing, load_block_definitions,
+    BlockDefinition, BlockDefinitions, BlockDefinitionsLoader, BlockId, BlockTag, ChunkCache,
+    ChunkData, ChunkEntities, ChunkIndex, ChunkLight, ChunkNeighbourhood, ChunkOcclusion,
+    FlatLayer, HeightPoint, MeshData, RegenerateButton, VoxelData, VoxelPos, VoxelSettings,
+    VoxelWrite, WorldGenSettings, WorldPreset, AIR, CHUNK_LIMIT_Y, CHUNK_SIZE, DIRT, GLASS, GRASS,
+    LEAVES, SNOW, STONE, STONE_SLAB, STONE_STAIRS,
+};
+pub use waypoint::{
+    receive_waypoints, setup_compass, update_compass, waypoint_input, WaypointReceived,
+    WaypointShared, Waypoints,
+};
+pub use web::WebPlugin;
+pub use world::VoxelWorld;
+
+pub const SPAWN_POINT: Vec3 = Vec3::new(0.0, 128.0, 5.0);
 
 /// A marker component for our shapes so we can query them separately from the ground plane
 #[derive(Component)]
 pub struct Shape;
 
-pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    world_gen_settings: Res<voxel::WorldGenSettings>,
+    world_slot: Res<WorldSlot>,
+    decorators: Res<ChunkDecorators>,
+    texture_pack: Res<TexturePack>,
+) {
     // Start loading the texture.
-    commands.insert_resource(LoadingTexture {
-        is_loaded: false,
-        handle: asset_server.load("textures/array_texture.png"),
-    });
+    commands.insert_resource(LoadingTexture::load(&asset_server, &texture_pack.path));
+    commands.insert_resource(world_slot.info.game_mode);
 
     commands.spawn(DirectionalLightBundle {
         transform: Transform::from_rotation(Quat::from_rotation_x(-PI / 4.0)),
@@ -52,38 +251,72 @@ pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         ..default()
     });
 
+    let mut chunk_entities = voxel::ChunkEntities::default();
     (0..voxel::INIT_WORLD_SIZE).for_each(|x| {
         (0..voxel::INIT_WORLD_SIZE).for_each(|z| {
             (0..voxel::CHUNK_LIMIT_Y).for_each(|y| {
-                commands.spawn((
-                    Chunk {
-                        index: ChunkIndex {
-                            x: x as i32,
-                            y: y as i32,
-                            z: z as i32,
-                        },
-                    },
-                    Name::new(format!("Chunk {}_{}_{}", x, y, z)),
-                ));
+                let index = ChunkIndex {
+                    x: x as i32,
+                    y: y as i32,
+                    z: z as i32,
+                };
+                let entity = commands
+                    .spawn((
+                        Chunk { index },
+                        Name::new(format!("Chunk {}_{}_{}", x, y, z)),
+                    ))
+                    .id();
+                chunk_entities.chunks.insert(index, entity);
             });
         });
     });
 
+    // generate the initial area up front and in parallel instead of through the per-frame budget
+    let world_save = world_slot.open_save(&world_gen_settings);
+    let mut voxel_data = voxel::VoxelData::default();
+    for index in chunk_entities.chunks.keys() {
+        if let Some(chunk_data) = world_save.load(index) {
+            voxel_data.insert_chunk(chunk_data);
+        }
+    }
+    let init_indices: Vec<ChunkIndex> = chunk_entities
+        .chunks
+        .keys()
+        .filter(|index| !voxel_data.is_loaded(**index))
+        .copied()
+        .collect();
+    let mut chunk_meshes_update_queue = voxel::ChunkMeshesUpdateQueue::default();
+    for chunk_data in voxel_data.iter_chunks() {
+        chunk_meshes_update_queue.queue.insert(ChunkColumn {
+            x: chunk_data.index.x,
+            z: chunk_data.index.z,
+        });
+    }
+    for chunk_data in voxel::generate_chunks(&init_indices, &world_gen_settings, &decorators) {
+        chunk_meshes_update_queue.queue.insert(ChunkColumn {
+            x: chunk_data.index.x,
+            z: chunk_data.index.z,
+        });
+        voxel_data.insert_chunk(chunk_data);
+    }
+
     commands
         .spawn((Camera3dBundle::default(), RaycastPickCamera::default()))
         .insert(FpsCameraBundle::new(
             FpsCameraController::default(),
-            Vec3::new(0.0, 128.0, 5.0),
+            world_slot.spawn_point(),
             Vec3::new(0.0, 2.0, 0.0),
             Vec3::Y,
+        ))
+        .insert((
+            Health::default(),
+            Hunger::default(),
+            Breath::default(),
+            UiCameraConfig::default(),
         ));
 
-    commands.insert_resource(MouseSettings {
-        speed: 10.0,
-        sensitivity: Vec2::new(0.5, 0.5),
-        ui_mode: true,
-    });
-
+    // the closure below takes the asset server
+    let vertex_import = asset_server.load("shaders/voxel_vertex.wgsl");
     let text_section = move |color, value: &str| {
         TextSection::new(
             value,
@@ -95,16 +328,21 @@ pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         )
     };
 
+    let mut stats_sections = vec![
+        text_section(Color::GREEN, "FPS (raw): "),
+        text_section(Color::CYAN, ""),
+        text_section(Color::GREEN, "\nFPS (SMA): "),
+        text_section(Color::CYAN, ""),
+        text_section(Color::GREEN, "\nFPS (EMA): "),
+        text_section(Color::CYAN, ""),
+    ];
+    for (name, _) in profiler::PROFILED {
+        stats_sections.push(text_section(Color::GREEN, &format!("\n{name}: ")));
+        stats_sections.push(text_section(Color::CYAN, ""));
+    }
+    stats_sections.push(text_section(Color::WHITE, ""));
     commands.spawn((
-        TextBundle::from_sections([
-            text_section(Color::GREEN, "FPS (raw): "),
-            text_section(Color::CYAN, ""),
-            text_section(Color::GREEN, "\nFPS (SMA): "),
-            text_section(Color::CYAN, ""),
-            text_section(Color::GREEN, "\nFPS (EMA): "),
-            text_section(Color::CYAN, ""),
-        ])
-        .with_style(Style {
+        TextBundle::from_sections(stats_sections).with_style(Style {
             position_type: PositionType::Absolute,
             top: Val::Px(5.0),
             left: Val::Px(5.0),
@@ -113,137 +351,303 @@ pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         StatsText,
     ));
 
-    commands
-        .spawn(NodeBundle {
-            style: Style {
-                width: Val::Percent(100.),
-                height: Val::Percent(100.),
-                flex_direction: FlexDirection::Row,
-                justify_content: JustifyContent::Center,
-                ..default()
-            },
-            ..default()
-        })
-        .with_children(|parent| {
-            parent.spawn(TextBundle::from_sections([text_section(
-                Color::WHITE,
-                "Press ~ to toggle control mode",
-            )]));
-        });
-
-    commands.spawn(NodeBundle {
-        background_color: BackgroundColor(Color::RED),
-        style: Style {
-            position_type: PositionType::Absolute,
-            top: Val::Percent(50.),
-            left: Val::Percent(50.),
-            width: Val::Px(4.),
-            height: Val::Px(4.),
-            ..default()
-        },
+    commands.insert_resource(voxel_data);
+    commands.insert_resource(chunk_entities);
+    commands.insert_resource(voxel::VoxelMeshes::default());
+    commands.insert_resource(voxel::ChunkCache::default());
+    commands.insert_resource(world_save);
+    commands.insert_resource(VoxelMaterial {
+        _vertex_import: vertex_import,
         ..default()
     });
-    commands.insert_resource(voxel::VoxelData::default());
-    commands.insert_resource(voxel::VoxelMeshes::default());
-    commands.insert_resource(VoxelMaterial::default());
-    commands.insert_resource(voxel::ChunkMeshesUpdateQueue::default());
+    commands.insert_resource(chunk_meshes_update_queue);
     commands.insert_resource(voxel::VoxelModifyQueue::default());
-    commands.insert_resource(voxel::VoxelSettings {
-        sight_range: 8,
-        interact_distance: 10.0,
-    });
 }
 
 pub fn post_setup(ms: Res<MouseSettings>, mut fps_camera_query: Query<&mut FpsCameraController>) {
     fps_camera_query.single_mut().enabled = !ms.ui_mode;
 }
 
-#[derive(Reflect, Resource, Default, Debug, InspectorOptions)]
+#[derive(Reflect, Resource, Debug, InspectorOptions, Serialize, Deserialize)]
 #[reflect(Resource, InspectorOptions)]
+#[serde(default)]
 pub struct MouseSettings {
     speed: f32,
     sensitivity: Vec2,
+    #[serde(skip)]
     ui_mode: bool,
 }
 
+impl Default for MouseSettings {
+    fn default() -> Self {
+        MouseSettings {
+            speed: 10.0,
+            sensitivity: Vec2::new(0.5, 0.5),
+            ui_mode: true,
+        }
+    }
+}
+
 pub fn input_mode(
     mut ms: ResMut<MouseSettings>,
-    keyboard_input: Res<Input<KeyCode>>,
+    player_input: Res<PlayerInput>,
     mut primary_query: Query<&mut Window, With<PrimaryWindow>>,
     mut fps_camera_query: Query<&mut FpsCameraController>,
 ) {
-    if keyboard_input.just_released(KeyCode::Grave) {
+    if player_input
+        .actions
+        .just_released(InputAction::ToggleUiMode)
+    {
         ms.ui_mode = !ms.ui_mode;
+        apply_input_mode(
+            &ms,
+            &mut fps_camera_query.single_mut(),
+            primary_query.get_single_mut().ok().as_deref_mut(),
+        );
+    }
+}
 
-        let mut fps_camera = fps_camera_query.single_mut();
-        fps_camera.enabled = !ms.ui_mode;
-        fps_camera.translate_sensitivity = ms.speed;
-        fps_camera.mouse_rotate_sensitivity = ms.sensitivity;
+/// Push the mouse settings to the camera controller and grab or release the cursor
+pub(crate) fn apply_input_mode(
+    ms: &MouseSettings,
+    fps_camera: &mut FpsCameraController,
+    primary: Option<&mut Window>,
+) {
+    fps_camera.enabled = !ms.ui_mode;
+    fps_camera.translate_sensitivity = ms.speed;
+    fps_camera.mouse_rotate_sensitivity = ms.sensitivity;
 
-        if let Ok(mut primary) = primary_query.get_single_mut() {
-            primary.cursor.visible = ms.ui_mode;
-            primary.cursor.grab_mode = if ms.ui_mode {
-                bevy::window::CursorGrabMode::None
-            } else {
-                bevy::window::CursorGrabMode::Locked
-            };
+    if let Some(primary) = primary {
+        primary.cursor.visible = ms.ui_mode;
+        primary.cursor.grab_mode = if ms.ui_mode {
+            bevy::window::CursorGrabMode::None
+        } else {
+            bevy::window::CursorGrabMode::Locked
         };
+    };
+}
+
+/// The mouse settings with the camera and window they drive, to switch between looking around
+/// and using the UI
+#[derive(SystemParam)]
+pub struct InputMode<'w, 's> {
+    pub ms: ResMut<'w, MouseSettings>,
+    primary_query: Query<'w, 's, &'static mut Window, With<PrimaryWindow>>,
+    fps_camera_query: Query<'w, 's, &'static mut FpsCameraController>,
+}
+
+impl InputMode<'_, '_> {
+    /// Free the cursor for the UI, or grab it back for the camera
+    pub fn set_ui_mode(&mut self, ui_mode: bool) {
+        self.ms.ui_mode = ui_mode;
+        let Ok(mut fps_camera) = self.fps_camera_query.get_single_mut() else {
+            return;
+        };
+        apply_input_mode(
+            &self.ms,
+            &mut fps_camera,
+            self.primary_query.get_single_mut().ok().as_deref_mut(),
+        );
+    }
+}
+
+/// A block mined by the player, sent once the server accepted the edit
+#[derive(Event, Debug, Clone)]
+pub struct BlockBroken {
+    pub position: VoxelPos,
+    pub block: voxel::BlockId,
+}
+
+/// A block set by an edit from the modify queue, sent once it's in the world
+#[derive(Event, Debug, Clone)]
+pub struct BlockChanged {
+    pub position: VoxelPos,
+    pub block: voxel::BlockId,
+}
+
+/// Elapsed time at which mining, placing and using blocks are allowed again
+#[derive(Resource, Default)]
+pub struct InteractCooldowns {
+    break_ready_at: f64,
+    place_ready_at: f64,
+    use_ready_at: f64,
+}
+
+/// The interact cooldowns with the clock they run on
+#[derive(SystemParam)]
+pub struct Cooldowns<'w> {
+    pub time: Res<'w, Time>,
+    ready_at: ResMut<'w, InteractCooldowns>,
+}
+
+impl Cooldowns<'_> {
+    pub fn break_ready(&self) -> bool {
+        self.time.elapsed_seconds_f64() >= self.ready_at.break_ready_at
+    }
+
+    pub fn place_ready(&self) -> bool {
+        self.time.elapsed_seconds_f64() >= self.ready_at.place_ready_at
+    }
+
+    /// Hold mining back for `seconds`
+    pub fn start_break(&mut self, seconds: f32) {
+        self.ready_at.break_ready_at = self.time.elapsed_seconds_f64() + seconds as f64;
+    }
+
+    /// Hold placing back for `seconds`
+    pub fn start_place(&mut self, seconds: f32) {
+        self.ready_at.place_ready_at = self.time.elapsed_seconds_f64() + seconds as f64;
+    }
+
+    pub fn use_ready(&self) -> bool {
+        self.time.elapsed_seconds_f64() >= self.ready_at.use_ready_at
+    }
+
+    /// Hold using blocks back for `seconds`
+    pub fn start_use(&mut self, seconds: f32) {
+        self.ready_at.use_ready_at = self.time.elapsed_seconds_f64() + seconds as f64;
     }
 }
 
 pub fn hit_voxel(
-    voxel_data: Res<voxel::VoxelData>,
-    mouse_input: Res<Input<MouseButton>>,
-    fps_camera_query: Query<&GlobalTransform, With<FpsCameraController>>,
-    mut voxel_modify_queue: ResMut<voxel::VoxelModifyQueue>,
-    voxel_settings: Res<voxel::VoxelSettings>,
+    crosshair: CrosshairTarget,
+    player_input: Res<PlayerInput>,
+    mut edits: PredictedEdits,
+    mut cooldowns: Cooldowns,
+    mut placer: BlockPlacer,
+    mut open_events: EventWriter<OpenBlock>,
+    mut mining: Local<Option<(VoxelPos, f32)>>, // block held at in survival, seconds spent on it
 ) {
-    let transform = fps_camera_query.single();
-    let voxel_positions = voxel::get_intersected_voxels(
-        &transform.translation(),
-        &transform.forward(),
-        voxel_settings.interact_distance,
-    );
+    let Some((eye, forward)) = crosshair.view() else {
+        return;
+    };
+    let game_mode = *placer.items.game_mode;
+    // spectators only look
+    let target = (game_mode != GameMode::Spectator)
+        .then(|| crosshair.get())
+        .flatten();
+    let Some((target, previous)) = target else {
+        *mining = None;
+        return;
+    };
 
-    if voxel_positions.is_empty() {
-        return {};
-    }
-    let mut previous = voxel_positions[0];
+    let voxel_data = &crosshair.voxel_data;
+    let voxel_settings = &crosshair.voxel_settings;
+    let actions = &player_input.actions;
+    let voxel_tid = voxel_data.get_block(target);
+    let breakable = !voxel::has_tag(voxel_tid, voxel::BlockTag::Unbreakable);
+    let broken = if game_mode == GameMode::Survival {
+        // the button is held on the same block for its mining time
+        if actions.pressed(InputAction::Break) && breakable {
+            let spent = match *mining {
+                Some((position, spent)) if position == target => spent,
+                _ => 0.0,
+            };
+            let spent = spent + cooldowns.time.delta_seconds();
+            *mining = Some((target, spent));
+            spent >= voxel::mining_time(voxel_tid)
+        } else {
+            *mining = None;
+            false
+        }
+    } else {
+        actions.just_released(InputAction::Break) && breakable
+    };
 
-    if mouse_input.just_released(MouseButton::Left) {
-        for voxel_position in voxel_positions.iter() {
-            let (chunk_index, voxel_local_index) = voxel::pos_to_voxel(voxel_position);
-            let voxel_tid = voxel_data.chunks.get(&chunk_index).unwrap().voxels
-                [voxel_local_index.x as usize][voxel_local_index.y as usize]
-                [voxel_local_index.z as usize];
-            if voxel_tid != 0 {
-                voxel_modify_queue.queue.push((*voxel_position, 0));
-                break;
-            } else {
-                previous = *voxel_position;
-            }
+    if broken && cooldowns.break_ready() {
+        *mining = None;
+        edits.edit(voxel_data, eye, target, (AIR, 0), None);
+        cooldowns.start_break(voxel_settings.break_cooldown);
+    } else if let Some(block) = placer.held().filter(|_| {
+        actions.just_pressed(InputAction::Place)
+            && voxel::on_use(voxel_tid).is_none()
+            && cooldowns.place_ready()
+    }) {
+        // the ray may have passed over the open part of a slab, don't replace it
+        if voxel_data.get_block(previous) != voxel::AIR {
+            return;
         }
-    } else if mouse_input.just_pressed(MouseButton::Right) {
-        for voxel_position in voxel_positions.iter() {
-            let (chunk_index, voxel_local_index) = voxel::pos_to_voxel(voxel_position);
-            let voxel_tid = voxel_data.chunks.get(&chunk_index).unwrap().voxels
-                [voxel_local_index.x as usize][voxel_local_index.y as usize]
-                [voxel_local_index.z as usize];
-            if voxel_tid != 0 {
-                voxel_modify_queue.queue.push((previous, 1));
-                break;
-            } else {
-                previous = *voxel_position;
-            }
+        // a door goes down with its top half, which needs room above it
+        let above = VoxelPos(previous.0 + IVec3::Y);
+        if block == voxel::DOOR && voxel_data.get_block(above) != voxel::AIR {
+            return;
+        }
+        // creative has endless blocks, survival places the ones it picked up
+        if !placer.items.take(block, 1) {
+            return;
+        }
+        let state = voxel::placed_state(block, previous.0 - target.0, forward);
+        let refund = (game_mode == GameMode::Survival).then_some(block);
+        edits.edit(voxel_data, eye, previous, (block, state), refund);
+        if block == voxel::DOOR {
+            edits.edit(voxel_data, eye, above, (voxel::DOOR_TOP, 0), None);
+        }
+        // a sign is written as soon as it's placed
+        if block == voxel::SIGN {
+            open_events.send(OpenBlock { position: previous });
+        }
+        cooldowns.start_place(voxel_settings.place_cooldown);
+    }
+}
+
+/// First block a ray hits within `range`, and the cell the ray passed through just before it
+pub(crate) fn target_voxel(
+    voxel_data: &voxel::VoxelData,
+    origin: Vec3,
+    direction: Vec3,
+    range: f32,
+) -> Option<(VoxelPos, VoxelPos)> {
+    let voxel_positions = voxel::get_intersected_voxels(&origin, &direction, range);
+    let mut previous = *voxel_positions.first()?;
+    for voxel_position in voxel_positions {
+        let voxel_tid = voxel_data.get_block(voxel_position);
+        let state = voxel_data.get_state(voxel_position);
+        if voxel_tid != voxel::AIR
+            && voxel::ray_hits_block(voxel_tid, state, voxel_position, origin, direction)
+        {
+            return Some((voxel_position, previous));
         }
+        previous = voxel_position;
+    }
+    None
+}
+
+/// The block under the crosshair within reach, from the camera as the camera mode places it
+#[derive(SystemParam)]
+pub struct CrosshairTarget<'w, 's> {
+    pub voxel_data: Res<'w, voxel::VoxelData>,
+    pub voxel_settings: Res<'w, voxel::VoxelSettings>,
+    camera_mode: Res<'w, CameraMode>,
+    camera_query: Query<'w, 's, &'static GlobalTransform, With<FpsCameraController>>,
+}
+
+impl CrosshairTarget<'_, '_> {
+    /// The eye and the direction looked in
+    pub fn view(&self) -> Option<(Vec3, Vec3)> {
+        let transform = self.camera_query.get_single().ok()?;
+        Some((self.camera_mode.eye(transform), transform.forward()))
+    }
+
+    /// The targeted block and the cell in front of it, see `target_voxel`
+    pub fn get(&self) -> Option<(VoxelPos, VoxelPos)> {
+        let (eye, forward) = self.view()?;
+        target_voxel(
+            &self.voxel_data,
+            eye,
+            forward,
+            self.voxel_settings.interact_distance,
+        )
     }
 }
 
 // `InspectorOptions` are completely optional
-#[derive(Reflect, Resource, Default, InspectorOptions)]
+#[derive(Reflect, Resource, Default, Clone, InspectorOptions, Serialize, Deserialize)]
 #[reflect(Resource, InspectorOptions)]
+#[serde(default)]
 pub struct DebugSettings {
     wireframe: bool,
+    chunk_overlay: bool, // chunk boundaries and streaming stats, toggled with F3
+    debug_screen: bool,  // position, target, light, counts and seed, toggled with F6
 }
 
 pub fn debug_system(
@@ -253,6 +657,59 @@ pub fn debug_system(
     wireframe_config.global = debug_settings.wireframe;
 }
 
+#[derive(Reflect, Resource, Clone, InspectorOptions, Serialize, Deserialize, Default)]
+#[reflect(Resource, InspectorOptions)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    vsync: bool,
+    light_bounce: bool, // approximate one bounce of block light off the surfaces around
+    auto_quality: bool, // trade sight range, shadows and mesh budget for frame rate
+}
+
+pub fn apply_graphics_settings(
+    graphics_settings: Res<GraphicsSettings>,
+    mut primary_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if !graphics_settings.is_changed() {
+        return;
+    }
+    if let Ok(mut primary) = primary_query.get_single_mut() {
+        primary.present_mode = if graphics_settings.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        };
+    }
+}
+
+const BOUNCE_COLOR: Vec3 = Vec3::new(0.55, 0.5, 0.4); // average albedo of what light bounces off
+const BOUNCE_STRENGTH: f32 = 0.6;
+
+/// Feed the light bounce strength to the voxel material, the bounced light itself is traced
+/// into the meshes, see `ChunkLight::bounce`
+pub fn update_light_bounce(
+    graphics_settings: Res<GraphicsSettings>,
+    voxel_material: Res<VoxelMaterial>,
+    mut materials: ResMut<Assets<ArrayTextureMaterial>>,
+) {
+    if !voxel_material.loaded {
+        return;
+    }
+    let strength = if graphics_settings.light_bounce {
+        BOUNCE_STRENGTH
+    } else {
+        0.0
+    };
+    let changed = materials
+        .get(&voxel_material.material)
+        .is_some_and(|material| material.light_bounce.w != strength);
+    if changed {
+        if let Some(material) = materials.get_mut(&voxel_material.material) {
+            material.light_bounce = BOUNCE_COLOR.extend(strength);
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct StatsText;
 
@@ -270,233 +727,899 @@ pub fn fps(diagnostics: Res<DiagnosticsStore>, mut query: Query<&mut Text, With<
             text.sections[5].value = format!("{ema:.2}");
         }
     };
+
+    // per-system averages, over the frames where the system had work to do
+    for (i, (_, id)) in profiler::PROFILED.into_iter().enumerate() {
+        if let Some(average) = diagnostics
+            .get(id)
+            .and_then(|diagnostic| diagnostic.average())
+        {
+            text.sections[7 + i * 2].value = format!("{average:.2} ms");
+        }
+    }
+}
+
+/// Where chunks stream in and out around: the camera, and the destination of a pending
+/// teleport, whose chunks load first and stay loaded
+#[derive(SystemParam)]
+pub struct StreamingFocus<'w, 's> {
+    camera_query: Query<'w, 's, &'static GlobalTransform, With<FpsCameraController>>,
+    pending_teleport: Res<'w, PendingTeleport>,
+}
+
+impl StreamingFocus<'_, '_> {
+    /// The chunk the camera is in, None without a camera
+    pub fn camera_chunk(&self) -> Option<ChunkIndex> {
+        let transform = self.camera_query.get_single().ok()?;
+        Some(voxel::get_chunk_index(&transform.translation()))
+    }
+
+    /// Whether a column is around the destination of a pending teleport
+    pub fn preloads(&self, column: ChunkColumn) -> bool {
+        self.pending_teleport.preloads(column)
+    }
+}
+
+/// Generates chunks from the world's seed and generator settings, decorated
+#[derive(SystemParam)]
+pub struct ChunkGenerator<'w> {
+    world_gen_settings: Res<'w, voxel::WorldGenSettings>,
+    decorators: Res<'w, ChunkDecorators>,
+}
+
+impl ChunkGenerator<'_> {
+    pub fn generate(&self, indices: &[ChunkIndex]) -> Vec<voxel::ChunkData> {
+        voxel::generate_chunks(indices, &self.world_gen_settings, &self.decorators)
+    }
 }
 
 pub fn gen_chunks_data(
-    // mut commands: Commands,
-    mut query: Query<&Chunk>,
-    mut voxel_data: ResMut<voxel::VoxelData>,
-    mut chunk_meshes_update_queue: ResMut<voxel::ChunkMeshesUpdateQueue>,
+    query: Query<&Chunk>,
+    focus: StreamingFocus,
+    voxel_settings: Res<voxel::VoxelSettings>,
+    generator: ChunkGenerator,
+    mut world: VoxelWorld,
+    mut chunk_store: ChunkStore,
+    mut diagnostics: Diagnostics,
 ) {
-    for chunk in query.iter_mut() {
-        voxel_data.chunks.entry(chunk.index).or_insert_with(|| {
-            chunk_meshes_update_queue.queue.insert(ChunkColumn {
-                x: chunk.index.x,
-                z: chunk.index.z,
-            });
-            println!(
-                "Chunk {}_{}_{} generated",
-                chunk.index.x, chunk.index.y, chunk.index.z
-            );
-            ChunkData::new(chunk.index)
-        });
+    crash::note_system("gen_chunks_data");
+    let Some(camera_chunk) = focus.camera_chunk() else {
+        return;
+    };
+
+    // generate the chunks closest to the camera first, so the budget is spent where it's visible
+    let mut pending: Vec<ChunkIndex> = query
+        .iter()
+        .map(|chunk| chunk.index)
+        .filter(|index| !world.is_loaded(*index))
+        .collect();
+    // chunks unloaded a moment ago come back from the cache and edited ones from the save,
+    // outside the generation budget
+    pending.retain(|index| {
+        let Some((chunk_data, modified)) = chunk_store.take(index) else {
+            return true;
+        };
+        world.load_chunk(chunk_data, modified);
+        false
+    });
+    // the destination of a teleport goes first, the player is waiting on it
+    pending.sort_by_key(|index| {
+        let column = ChunkColumn {
+            x: index.x,
+            z: index.z,
+        };
+        (
+            !focus.preloads(column),
+            chunk_distance(index, &camera_chunk),
+        )
+    });
+
+    pending.truncate(voxel_settings.chunk_gen_budget as usize);
+
+    if pending.is_empty() {
+        return;
+    }
+    let start = Instant::now();
+    let generated = generator.generate(&pending);
+    diagnostics.add_measurement(ProfilerDiagnosticsPlugin::CHUNK_GEN, || {
+        profiler::elapsed_ms(start)
+    });
+
+    for chunk_data in generated {
+        let index = chunk_data.index;
+        world.load_chunk(chunk_data, false);
+        println!("Chunk {}_{}_{} generated", index.x, index.y, index.z);
     }
 }
 
+/// Horizontal chessboard distance between two chunks, in chunks
+fn chunk_distance(a: &ChunkIndex, b: &ChunkIndex) -> i32 {
+    (a.x - b.x).abs().max((a.z - b.z).abs())
+}
+
+/// Leave the chunks the camera can't see into out of their column's mesh, worked out again
+/// when the camera enters another chunk or chunks were remeshed
+pub fn cull_hidden_chunks(
+    mut chunk_occlusion: ResMut<voxel::ChunkOcclusion>,
+    mut query: Query<&mut voxel::ColumnMesh>,
+    fps_camera_query: Query<&GlobalTransform, With<FpsCameraController>>,
+) {
+    let Ok(transform) = fps_camera_query.get_single() else {
+        return;
+    };
+    let camera_chunk = voxel::get_chunk_index(&transform.translation());
+    if chunk_occlusion.camera_chunk == Some(camera_chunk) && !chunk_occlusion.stale {
+        return;
+    }
+    chunk_occlusion.camera_chunk = Some(camera_chunk);
+    chunk_occlusion.stale = false;
+
+    let connectivity: HashMap<ChunkIndex, voxel::ChunkConnectivity> = query
+        .iter()
+        .flat_map(|column_mesh| {
+            let column = column_mesh.column;
+            column_mesh.sub_meshes.iter().map(move |(&y, sub_mesh)| {
+                let index = ChunkIndex {
+                    x: column.x,
+                    y,
+                    z: column.z,
+                };
+                (index, sub_mesh.connectivity)
+            })
+        })
+        .collect();
+    // above or below the world, or before its chunk is meshed, nothing is hidden
+    let visible = connectivity
+        .contains_key(&camera_chunk)
+        .then(|| voxel::visible_chunks(camera_chunk, &connectivity));
+
+    for mut column_mesh in query.iter_mut() {
+        let column = column_mesh.column;
+        let hidden: HashSet<i32> = match visible.as_ref() {
+            Some(visible) => column_mesh
+                .sub_meshes
+                .keys()
+                .copied()
+                .filter(|&y| {
+                    !visible.contains(&ChunkIndex {
+                        x: column.x,
+                        y,
+                        z: column.z,
+                    })
+                })
+                .collect(),
+            None => HashSet::new(),
+        };
+        if hidden != column_mesh.hidden {
+            column_mesh.hidden = hidden;
+            column_mesh.dirty = true;
+        }
+    }
+}
+
+/// Put `mesh_data` in the mesh asset of `handle`, in place when it was already uploaded,
+/// so a column's update doesn't remove and add assets
+fn upload_mesh(meshes: &mut Assets<Mesh>, handle: &mut Handle<Mesh>, mesh_data: voxel::MeshData) {
+    let written = meshes
+        .get_mut(handle)
+        .is_some_and(|mesh| mesh_data.write_into(mesh));
+    if !written {
+        meshes.remove(handle.clone());
+        *handle = meshes.add(mesh_data.into());
+    }
+}
+
+/// What the column meshes feed: the mesh assets they're uploaded to, the materials they're
+/// drawn with and the occlusion culling that's stale once they change
+#[derive(SystemParam)]
+pub struct ColumnRendering<'w> {
+    meshes: ResMut<'w, Assets<Mesh>>,
+    voxel_material: Res<'w, VoxelMaterial>,
+    chunk_occlusion: ResMut<'w, voxel::ChunkOcclusion>,
+}
+
 pub fn update_column_meshes(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut rendering: ColumnRendering,
     mut query: Query<(Entity, &mut voxel::ColumnMesh)>,
-    voxel_material: Res<VoxelMaterial>,
+    focus: StreamingFocus,
     voxel_data: Res<voxel::VoxelData>,
+    tuned: Res<TunedQuality>,
+    mut diagnostics: Diagnostics,
 ) {
+    crash::note_system("update_column_meshes");
+    let ColumnRendering {
+        meshes,
+        voxel_material,
+        chunk_occlusion,
+    } = &mut rendering;
     if !voxel_material.loaded {
         return;
     }
+    let Some(camera_chunk) = focus.camera_chunk() else {
+        return;
+    };
+
+    let mut dirty_columns: Vec<_> = query
+        .iter_mut()
+        .filter(|(_, column_mesh)| column_mesh.dirty)
+        .collect();
+    dirty_columns.sort_by_key(|(_, column_mesh)| {
+        let distance = chunk_distance(
+            &ChunkIndex {
+                x: column_mesh.column.x,
+                y: 0,
+                z: column_mesh.column.z,
+            },
+            &camera_chunk,
+        );
+        (!focus.preloads(column_mesh.column), distance)
+    });
 
-    for (column_mesh_entity, mut column_mesh) in query.iter_mut() {
-        if column_mesh.dirty {
-            let mut chunk_num = 0;
-            (0..voxel::CHUNK_LIMIT_Y).for_each(|i| {
-                if voxel_data.chunks.contains_key(&ChunkIndex {
-                    x: column_mesh.column.x,
-                    y: i as i32,
-                    z: column_mesh.column.z,
-                }) {
-                    chunk_num += 1;
+    let mut meshed = 0;
+    let mut meshing_ms = 0.0;
+    let mut upload_ms = 0.0;
+    for (column_mesh_entity, mut column_mesh) in dirty_columns {
+        if meshed >= tuned.column_mesh_budget {
+            break;
+        }
+        // remesh only the chunks that changed, the others keep their cached sub-meshes
+        let start = Instant::now();
+        let column_mesh = &mut *column_mesh;
+        let dirty_chunks = std::mem::take(&mut column_mesh.dirty_chunks);
+        if !dirty_chunks.is_empty() {
+            chunk_occlusion.stale = true;
+        }
+        // the chunks to patch in the meshes, remeshed or shown or hidden since they were built
+        let changed: Vec<i32> = dirty_chunks
+            .iter()
+            .chain(
+                column_mesh
+                    .hidden
+                    .symmetric_difference(&column_mesh.meshed_hidden),
+            )
+            .copied()
+            .collect();
+        let replaced = [false, true].map(|transparent| {
+            voxel::sub_mesh_ranges(&column_mesh.sub_meshes, changed.clone(), transparent)
+        });
+        for y in dirty_chunks {
+            let index = ChunkIndex {
+                x: column_mesh.column.x,
+                y,
+                z: column_mesh.column.z,
+            };
+            match voxel_data.chunk(index) {
+                Some(chunk_data) => {
+                    let neighbourhood = voxel::ChunkNeighbourhood::snapshot(&voxel_data, index);
+                    column_mesh
+                        .sub_meshes
+                        .insert(y, voxel::ChunkSubMesh::new(chunk_data, &neighbourhood));
                 }
+                None => {
+                    column_mesh.sub_meshes.remove(&y);
+                }
+            }
+        }
+        // packed vertices have no positions for bevy to compute the bounds from
+        let [opaque_bounds, transparent_bounds] =
+            voxel::sub_mesh_bounds(&column_mesh.sub_meshes, &column_mesh.hidden);
+        let column_meshing_ms = profiler::elapsed_ms(start);
+        meshing_ms += column_meshing_ms;
+
+        // patch the changed chunks into the uploaded meshes, a column without them yet is
+        // combined whole
+        let start = Instant::now();
+        let handles = [&column_mesh.mesh, &column_mesh.transparent_mesh];
+        let patched = replaced
+            .into_iter()
+            .zip(handles.map(Handle::clone))
+            .zip([false, true])
+            .all(|((replaced, handle), transparent)| {
+                meshes.get_mut(&handle).is_some_and(|mesh| {
+                    voxel::patch_sub_meshes(
+                        &mut column_mesh.sub_meshes,
+                        &column_mesh.hidden,
+                        replaced,
+                        transparent,
+                        mesh,
+                    )
+                })
             });
-            if chunk_num == voxel::CHUNK_LIMIT_Y {
-                let mut chunks_mesh_data = Vec::new();
-                (0..voxel::CHUNK_LIMIT_Y).for_each(|i| {
-                    if let Some(chunk_data) = voxel_data.chunks.get(&ChunkIndex {
-                        x: column_mesh.column.x,
-                        y: i as i32,
-                        z: column_mesh.column.z,
-                    }) {
-                        chunks_mesh_data.push(voxel::greedy_meshing(chunk_data));
-                    }
-                });
-                meshes.remove(column_mesh.mesh.clone());
-                column_mesh.mesh = meshes.add(voxel::combine_meshes(&chunks_mesh_data).into());
-                commands
-                    .entity(column_mesh_entity)
-                    .insert(MaterialMeshBundle {
-                        mesh: column_mesh.mesh.clone(),
-                        material: voxel_material.material.clone(),
-                        ..default()
-                    });
-                column_mesh.dirty = false;
-                println!(
-                    "ColumnMesh {}_{} updated",
-                    column_mesh.column.x, column_mesh.column.z
-                );
+        if !patched {
+            let (opaque, transparent) =
+                voxel::combine_sub_meshes(&mut column_mesh.sub_meshes, &column_mesh.hidden);
+            upload_mesh(meshes, &mut column_mesh.mesh, opaque);
+            upload_mesh(meshes, &mut column_mesh.transparent_mesh, transparent);
+        }
+        column_mesh.meshed_hidden = column_mesh.hidden.clone();
+        let column_upload_ms = profiler::elapsed_ms(start);
+        upload_ms += column_upload_ms;
+        column_mesh.remesh_ms = (column_meshing_ms + column_upload_ms) as f32;
+        // meshes are relative to their column, so the packed positions stay small
+        let origin = ChunkIndex {
+            x: column_mesh.column.x,
+            y: 0,
+            z: column_mesh.column.z,
+        }
+        .origin();
+        commands.entity(column_mesh_entity).insert((
+            MaterialMeshBundle {
+                mesh: column_mesh.mesh.clone(),
+                material: voxel_material.material.clone(),
+                transform: Transform::from_translation(origin.as_vec3()),
+                ..default()
+            },
+            opaque_bounds,
+        ));
+        let transparent_bundle = (
+            MaterialMeshBundle {
+                mesh: column_mesh.transparent_mesh.clone(),
+                material: voxel_material.transparent_material.clone(),
+                ..default()
+            },
+            transparent_bounds,
+        );
+        match column_mesh.transparent_entity {
+            Some(entity) => {
+                commands.entity(entity).insert(transparent_bundle);
+            }
+            None => {
+                let entity = commands
+                    .spawn((transparent_bundle, voxel::TransparentPass))
+                    .id();
+                commands.entity(column_mesh_entity).add_child(entity);
+                column_mesh.transparent_entity = Some(entity);
             }
         }
+        column_mesh.dirty = false;
+        meshed += 1;
+        println!(
+            "ColumnMesh {}_{} updated",
+            column_mesh.column.x, column_mesh.column.z
+        );
+    }
+
+    if meshed > 0 {
+        diagnostics.add_measurement(ProfilerDiagnosticsPlugin::MESHING, || meshing_ms);
+        diagnostics.add_measurement(ProfilerDiagnosticsPlugin::MESH_UPLOAD, || upload_ms);
     }
 }
 
 pub fn load_chunks_around(
     mut commands: Commands,
     fps_camera_query: Query<&GlobalTransform, With<FpsCameraController>>,
-    voxel_data: Res<voxel::VoxelData>,
+    mut chunk_entities: ResMut<voxel::ChunkEntities>,
+    granted_sight_range: Res<GrantedSightRange>,
     voxel_settings: Res<voxel::VoxelSettings>,
 ) {
+    crash::note_system("load_chunks_around");
+    let Some(sight_range) = granted_sight_range.sight_range else {
+        return;
+    };
     let transform = fps_camera_query.single();
     let camera_pos = transform.translation();
     let chunk_index = voxel::get_chunk_index(&camera_pos);
-    let sight_range = voxel_settings.sight_range as i32;
+    let sight_range = sight_range as i32;
     for x in -sight_range..=sight_range {
         for z in -sight_range..=sight_range {
-            (0..voxel::CHUNK_LIMIT_Y).for_each(|y| {
-                let chunk_index_to_load = ChunkIndex {
-                    x: chunk_index.x + x,
-                    y: y as i32,
-                    z: chunk_index.z + z,
-                };
-                if !voxel_data.chunks.contains_key(&chunk_index_to_load) {
-                    commands.spawn((
-                        Chunk {
-                            index: chunk_index_to_load,
-                        },
-                        Name::new(format!(
-                            "Chunk {}_{}_{}",
-                            chunk_index_to_load.x, chunk_index_to_load.y, chunk_index_to_load.z
-                        )),
-                    ));
-                }
-            });
+            voxel::vertical_chunks(&chunk_index, voxel_settings.vertical_sight_range).for_each(
+                |y| {
+                    let chunk_index_to_load = ChunkIndex {
+                        x: chunk_index.x + x,
+                        y,
+                        z: chunk_index.z + z,
+                    };
+                    if !chunk_entities.chunks.contains_key(&chunk_index_to_load) {
+                        spawn_chunk(&mut commands, &mut chunk_entities, chunk_index_to_load);
+                    }
+                },
+            );
         }
     }
 }
 
+/// Spawn the entity of a chunk, its data is generated or loaded by `gen_chunks_data`
+pub(crate) fn spawn_chunk(
+    commands: &mut Commands,
+    chunk_entities: &mut voxel::ChunkEntities,
+    index: ChunkIndex,
+) {
+    let entity = commands
+        .spawn((
+            Chunk { index },
+            ChunkSummary::default(),
+            Name::new(format!("Chunk {}_{}_{}", index.x, index.y, index.z)),
+        ))
+        .id();
+    chunk_entities.chunks.insert(index, entity);
+}
+
+/// The entities of what's loaded: the chunks and column meshes with the maps finding them,
+/// and the entities stored with a chunk when it unloads
+#[derive(SystemParam)]
+pub struct LoadedEntities<'w, 's> {
+    chunk_query: Query<'w, 's, (Entity, &'static voxel::Chunk)>,
+    column_mesh_query: Query<'w, 's, (Entity, &'static voxel::ColumnMesh)>,
+    stored_query: Query<'w, 's, (Entity, &'static Transform, stored_entity::Storable)>,
+    chunk_entities: ResMut<'w, voxel::ChunkEntities>,
+    column_meshes: ResMut<'w, voxel::VoxelMeshes>,
+}
+
 pub fn remove_chunk(
     mut commands: Commands,
-    fps_camera_query: Query<&GlobalTransform, With<FpsCameraController>>,
+    focus: StreamingFocus,
+    granted_sight_range: Res<GrantedSightRange>,
     voxel_settings: Res<voxel::VoxelSettings>,
-    chunk_query: Query<(Entity, &voxel::Chunk)>,
-    column_mesh_query: Query<(Entity, &voxel::ColumnMesh)>,
-    mut voxel_data: ResMut<voxel::VoxelData>,
-    mut column_meshes: ResMut<voxel::VoxelMeshes>,
+    mut loaded: LoadedEntities,
+    mut world: VoxelWorld,
+    mut chunk_store: ChunkStore,
 ) {
-    let transform = fps_camera_query.single();
-    let camera_pos = transform.translation();
-    let chunk_index = voxel::get_chunk_index(&camera_pos);
+    crash::note_system("remove_chunk");
+    let Some(sight_range) = granted_sight_range.sight_range else {
+        return;
+    };
+    let Some(chunk_index) = focus.camera_chunk() else {
+        return;
+    };
+    let LoadedEntities {
+        chunk_query,
+        column_mesh_query,
+        stored_query,
+        chunk_entities,
+        column_meshes,
+    } = &mut loaded;
 
-    let sight_range = voxel_settings.sight_range as i32;
+    // unload a little further out than chunks load, so walking along the border doesn't thrash
+    let margin = voxel_settings.unload_margin;
+    let sight_range = sight_range as i32 + margin as i32;
+    let vertical_chunks = voxel::vertical_chunks(
+        &chunk_index,
+        voxel_settings.vertical_sight_range.saturating_add(margin),
+    );
+
+    // items, mobs and falling blocks by chunk, stored with it when it unloads
+    let mut stored: HashMap<ChunkIndex, Vec<(Entity, StoredEntity)>> = HashMap::new();
+    for (entity, transform, components) in stored_query.iter() {
+        if let Some(stored_entity) = stored_entity::store(transform, components) {
+            stored
+                .entry(voxel::get_chunk_index(&transform.translation))
+                .or_default()
+                .push((entity, stored_entity));
+        }
+    }
 
     for (chunk_entity, chunk) in chunk_query.iter() {
-        if (chunk.index.x - chunk_index.x).abs() > sight_range
-            || (chunk.index.z - chunk_index.z).abs() > sight_range
-        {
-            voxel_data.chunks.remove(&chunk.index);
+        let column = ChunkColumn {
+            x: chunk.index.x,
+            z: chunk.index.z,
+        };
+        if focus.preloads(column) {
+            continue;
+        }
+        let out_of_column = (chunk.index.x - chunk_index.x).abs() > sight_range
+            || (chunk.index.z - chunk_index.z).abs() > sight_range;
+        if out_of_column || !vertical_chunks.contains(&chunk.index.y) {
+            if let Some((mut chunk_data, edited)) = world.remove_chunk(chunk.index) {
+                for (entity, stored_entity) in stored.remove(&chunk.index).unwrap_or_default() {
+                    commands.entity(entity).despawn_recursive();
+                    chunk_data.stored.push(stored_entity);
+                }
+                chunk_store.put(chunk_data, edited, voxel_settings.chunk_cache_size as usize);
+            }
+            chunk_entities.chunks.remove(&chunk.index);
             commands.entity(chunk_entity).despawn_recursive();
         }
+        // the column stays, drop the chunk from its mesh
+        if !out_of_column && !vertical_chunks.contains(&chunk.index.y) {
+            world.remesh_chunk(chunk.index);
+        }
     }
 
     for (column_mesh_entity, column_mesh) in column_mesh_query.iter() {
-        if (column_mesh.column.x - chunk_index.x).abs() > sight_range
-            || (column_mesh.column.z - chunk_index.z).abs() > sight_range
-        {
+        let out_of_sight = (column_mesh.column.x - chunk_index.x).abs() > sight_range
+            || (column_mesh.column.z - chunk_index.z).abs() > sight_range;
+        if out_of_sight && !focus.preloads(column_mesh.column) {
             column_meshes.columns.remove(&column_mesh.column);
             commands.entity(column_mesh_entity).despawn_recursive();
         }
     }
 }
 
+/// Relight a few of the chunks whose light an edit or a newly loaded light may have changed,
+/// closest to the camera first, and remesh only the ones whose light did change. Chunks that
+/// are about to be meshed anyway are lit then
+pub fn relight_chunks(
+    voxel_data: Res<voxel::VoxelData>,
+    voxel_settings: Res<voxel::VoxelSettings>,
+    column_meshes: Res<voxel::VoxelMeshes>,
+    column_mesh_query: Query<&voxel::ColumnMesh>,
+    fps_camera_query: Query<&GlobalTransform, With<FpsCameraController>>,
+    mut chunk_meshes_update_queue: ResMut<voxel::ChunkMeshesUpdateQueue>,
+) {
+    crash::note_system("relight_chunks");
+    if chunk_meshes_update_queue.relight.is_empty() {
+        return;
+    }
+    let Ok(transform) = fps_camera_query.get_single() else {
+        return;
+    };
+    let camera_chunk = voxel::get_chunk_index(&transform.translation());
+    let mut pending: Vec<ChunkIndex> = chunk_meshes_update_queue.relight.iter().copied().collect();
+    pending.sort_by_key(|index| chunk_distance(index, &camera_chunk));
+    pending.truncate(voxel_settings.relight_budget as usize);
+
+    for index in pending {
+        chunk_meshes_update_queue.relight.remove(&index);
+        if chunk_meshes_update_queue.chunks.contains(&index) || !voxel_data.is_loaded(index) {
+            continue;
+        }
+        let column = ChunkColumn {
+            x: index.x,
+            z: index.z,
+        };
+        let Some(sub_mesh) = column_meshes
+            .columns
+            .get(&column)
+            .and_then(|entity| column_mesh_query.get(*entity).ok())
+            .filter(|column_mesh| !column_mesh.dirty_chunks.contains(&index.y))
+            .and_then(|column_mesh| column_mesh.sub_meshes.get(&index.y))
+        else {
+            continue;
+        };
+        let neighbourhood = voxel::ChunkNeighbourhood::snapshot(&voxel_data, index);
+        if voxel::ChunkLight::of(&neighbourhood).fingerprint() != sub_mesh.light_fingerprint {
+            chunk_meshes_update_queue.queue_chunk(index);
+        }
+    }
+}
+
 pub fn handle_chunk_meshes_update_queue(
     mut commands: Commands,
     mut chunk_meshes_update_queue: ResMut<voxel::ChunkMeshesUpdateQueue>,
     mut column_meshes: ResMut<voxel::VoxelMeshes>,
+    mut column_mesh_query: Query<&mut voxel::ColumnMesh>,
 ) {
-    for chunk_column in chunk_meshes_update_queue.queue.iter() {
-        if !column_meshes.columns.contains_key(chunk_column) {
-            column_meshes.columns.insert(
-                *chunk_column,
+    crash::note_system("handle_chunk_meshes_update_queue");
+    let queue = &mut *chunk_meshes_update_queue;
+    let mut dirty_chunks: HashMap<ChunkColumn, HashSet<i32>> = HashMap::new();
+    for chunk_column in queue.queue.drain() {
+        dirty_chunks
+            .entry(chunk_column)
+            .or_default()
+            .extend(0..voxel::CHUNK_LIMIT_Y as i32);
+    }
+    for index in queue.chunks.drain() {
+        dirty_chunks
+            .entry(ChunkColumn {
+                x: index.x,
+                z: index.z,
+            })
+            .or_default()
+            .insert(index.y);
+    }
+
+    for (chunk_column, heights) in dirty_chunks {
+        let chunk_column_entity = column_meshes
+            .columns
+            .entry(chunk_column)
+            .or_insert_with(|| {
                 commands
-                    .spawn((Name::new(format!(
-                        "ColumnMesh {}_{}",
-                        chunk_column.x, chunk_column.z
-                    )),))
-                    .id(),
-            );
+                    .spawn((
+                        Name::new(format!("ColumnMesh {}_{}", chunk_column.x, chunk_column.z)),
+                        ColumnMeshStats::default(),
+                    ))
+                    .id()
+            });
+        // keep the meshes of a column that was already built, so its transparent child is reused
+        if let Ok(mut column_mesh) = column_mesh_query.get_mut(*chunk_column_entity) {
+            column_mesh.dirty = true;
+            column_mesh.dirty_chunks.extend(heights);
+            continue;
         }
-        let chunk_column_entity = column_meshes.columns.get(chunk_column).unwrap();
         commands
             .entity(*chunk_column_entity)
             .insert(voxel::ColumnMesh {
-                column: *chunk_column,
+                column: chunk_column,
                 dirty: true,
+                dirty_chunks: heights,
+                sub_meshes: Default::default(),
+                hidden: Default::default(),
+                meshed_hidden: Default::default(),
                 mesh: Default::default(),
+                transparent_mesh: Default::default(),
+                transparent_entity: None,
+                remesh_ms: 0.0,
             });
     }
-    chunk_meshes_update_queue.queue.clear();
 }
 
 pub fn handle_voxel_modify_queue(
-    mut voxel_data: ResMut<voxel::VoxelData>,
+    claims: Res<Claims>,
+    mut world: VoxelWorld,
     mut voxel_modify_queue: ResMut<voxel::VoxelModifyQueue>,
-    mut chunk_meshes_update_queue: ResMut<voxel::ChunkMeshesUpdateQueue>,
+    mut loose_blocks: ResMut<LooseBlocks>,
+    mut changed_events: EventWriter<BlockChanged>,
+    mut removed_events: EventWriter<BlockEntityRemoved>,
 ) {
-    for (voxel_position, tid) in voxel_modify_queue.queue.iter() {
-        let (chunk_index, voxel_local_index) = voxel::pos_to_voxel(voxel_position);
-        let chunk = voxel_data.chunks.get_mut(&chunk_index).unwrap();
-        chunk.voxels[voxel_local_index.x as usize][voxel_local_index.y as usize]
-            [voxel_local_index.z as usize] = *tid;
-        chunk_meshes_update_queue.queue.insert(ChunkColumn {
-            x: chunk_index.x,
-            z: chunk_index.z,
+    crash::note_system("handle_voxel_modify_queue");
+    // the guarded edits go first, then the rest. Loosened blocks add their own edits to the
+    // end of the queue they came from, a stack falls in one go
+    let mut denied = HashSet::new();
+    let (mut guarded, mut index) = (0, 0);
+    loop {
+        let (voxel_position, tid, editor) =
+            if let Some(&(position, block, editor)) = voxel_modify_queue.guarded.get(guarded) {
+                guarded += 1;
+                (position, block, Some(editor))
+            } else if let Some(&(position, block)) = voxel_modify_queue.queue.get(index) {
+                index += 1;
+                (position, block, None)
+            } else {
+                break;
+            };
+        // claims are checked here, where every guarded edit ends up
+        if editor.is_some_and(|editor| !claims.allows(editor, voxel_position)) {
+            denied.insert(voxel_position);
+            continue;
+        }
+        // edits outside the loaded world are dropped
+        let Some(removed) = world.replace_block(voxel_position, tid) else {
+            continue;
+        };
+        changed_events.send(BlockChanged {
+            position: voxel_position,
+            block: tid,
         });
+        if let Some(entity) = removed {
+            removed_events.send(BlockEntityRemoved {
+                position: voxel_position,
+                entity,
+            });
+        }
+        for position in [voxel_position, VoxelPos(voxel_position.0 + IVec3::Y)] {
+            loose_blocks.loosen(world.data(), &mut voxel_modify_queue, position, editor);
+        }
     }
+    voxel_modify_queue.guarded.clear();
     voxel_modify_queue.queue.clear();
+
+    // states go on after the blocks, so a block placed this frame can be given one
+    for (voxel_position, state) in voxel_modify_queue.states.drain(..) {
+        if denied.contains(&voxel_position) {
+            continue;
+        }
+        if world.get_state(voxel_position) != state {
+            world.set_state(voxel_position, state);
+        }
+    }
+    // and block entities, over the new one of a block placed this frame
+    for (voxel_position, entity) in voxel_modify_queue.entities.drain(..) {
+        let takes_entity = block_entity::new_entity(world.get_block(voxel_position)).is_some();
+        if denied.contains(&voxel_position) || !takes_entity {
+            continue;
+        }
+        if world.get_entity(voxel_position) != Some(&entity) {
+            world.set_entity(voxel_position, Some(entity));
+        }
+    }
 }
 
 #[derive(Resource)]
 pub struct LoadingTexture {
     is_loaded: bool,
     handle: Handle<Image>,
+    pack_textures: Vec<Handle<Image>>, // the textures of a folder pack, stacked into `handle`
+}
+
+impl LoadingTexture {
+    /// Start loading a texture pack, a stacked image or a folder of textures
+    fn load(asset_server: &AssetServer, path: &str) -> Self {
+        if !texture_pack::is_folder(path) {
+            return LoadingTexture {
+                is_loaded: false,
+                handle: asset_server.load(path),
+                pack_textures: Vec::new(),
+            };
+        }
+        let pack_textures = match asset_server.load_folder(path) {
+            Ok(handles) => handles.into_iter().map(|handle| handle.typed()).collect(),
+            Err(err) => {
+                warn!("Failed to read texture pack {}: {}", path, err);
+                Vec::new()
+            }
+        };
+        LoadingTexture {
+            is_loaded: false,
+            handle: Handle::default(),
+            pack_textures,
+        }
+    }
 }
 
 #[derive(Resource, Default)]
 pub struct VoxelMaterial {
     loaded: bool,
+    _vertex_import: Handle<Shader>, // `mcrs::voxel_vertex`, held so the voxel shaders can import it
     material: Handle<ArrayTextureMaterial>,
+    transparent_material: Handle<ArrayTextureMaterial>, // alpha tested, for glass and leaves
 }
 
-pub fn create_array_texture(
+/// Texture pack the voxel material is built from, changing it rebuilds the material.
+/// The path is either an image with the textures stacked vertically or a folder with
+/// an image per texture, see `texture_pack`
+#[derive(Reflect, Resource, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub struct TexturePack {
+    pub path: String,
+    pub layers: u32, // textures stacked vertically in the image, folders count their own
+}
+
+impl Default for TexturePack {
+    fn default() -> Self {
+        TexturePack {
+            path: "textures/array_texture.png".to_string(),
+            layers: 28,
+        }
+    }
+}
+
+/// Start over with the texture when the pack is switched or its image is hot reloaded
+pub fn reload_texture_pack(
     asset_server: Res<AssetServer>,
+    texture_pack: Res<TexturePack>,
+    images: Res<Assets<Image>>,
+    mut image_events: EventReader<AssetEvent<Image>>,
     mut loading_texture: ResMut<LoadingTexture>,
+) {
+    if texture_pack.is_changed() && !texture_pack.is_added() {
+        *loading_texture = LoadingTexture::load(&asset_server, &texture_pack.path);
+    }
+    for event in image_events.iter() {
+        if let AssetEvent::Modified { handle } = event {
+            // a reloaded image is a plain stacked 2d image again, unlike our own reinterpretation
+            let reloaded = images
+                .get(handle)
+                .is_some_and(|image| image.texture_descriptor.size.depth_or_array_layers == 1);
+            if *handle == loading_texture.handle && reloaded
+                || loading_texture.pack_textures.contains(handle)
+            {
+                loading_texture.is_loaded = false;
+            }
+        }
+    }
+}
+
+/// The texture pack being loaded, and where what's wrong with it is reported
+#[derive(SystemParam)]
+pub struct TexturePackLoad<'w> {
+    texture_pack: Res<'w, TexturePack>,
+    loading_texture: ResMut<'w, LoadingTexture>,
+    asset_status: ResMut<'w, AssetStatus>,
+}
+
+pub fn create_array_texture(
+    asset_server: Res<AssetServer>,
+    mut pack_load: TexturePackLoad,
     mut images: ResMut<Assets<Image>>,
     mut materials: ResMut<Assets<ArrayTextureMaterial>>,
     mut voxel_material: ResMut<VoxelMaterial>,
+    mut material_query: Query<(
+        &mut Handle<ArrayTextureMaterial>,
+        Option<&voxel::TransparentPass>,
+    )>,
+    mut world: VoxelWorld,
 ) {
-    if loading_texture.is_loaded
-        || asset_server.get_load_state(loading_texture.handle.clone()) != LoadState::Loaded
-    {
+    let TexturePackLoad {
+        texture_pack,
+        loading_texture,
+        asset_status,
+    } = &mut pack_load;
+    if loading_texture.is_loaded {
         return;
     }
+    let mut array_layers = texture_pack.layers.max(1);
+    if texture_pack::is_folder(&texture_pack.path) {
+        let pack_textures = &loading_texture.pack_textures;
+        let load_state = asset_server.get_group_load_state(pack_textures.iter().map(|h| h.id()));
+        let stacked = match load_state {
+            LoadState::Loaded if !pack_textures.is_empty() => {
+                let textures: Vec<(String, &Image)> = pack_textures
+                    .iter()
+                    .filter_map(|handle| {
+                        let path = asset_server.get_handle_path(handle)?;
+                        let name = path.path().file_stem()?.to_str()?.to_lowercase();
+                        Some((name, images.get(handle)?))
+                    })
+                    .collect();
+                texture_pack::stack_textures(&textures)
+            }
+            LoadState::Loading => return,
+            _ => Err("no textures".to_string()),
+        };
+        match stacked {
+            Ok(pack) => {
+                array_layers = pack.layers.len() as u32;
+                loading_texture.handle = images.add(pack.image);
+                if voxel::set_texture_layers(pack.layers) {
+                    world.remesh_all();
+                }
+            }
+            Err(err) => {
+                asset_status.report_missing(format!("{} ({})", texture_pack.path, err));
+                loading_texture.handle = images.add(assets::fallback_array_texture());
+            }
+        }
+    } else {
+        match asset_server.get_load_state(loading_texture.handle.clone()) {
+            LoadState::Loaded => {}
+            LoadState::Failed => {
+                asset_status.report_missing(texture_pack.path.clone());
+                loading_texture.handle = images.add(assets::fallback_array_texture());
+            }
+            _ => return,
+        }
+        let layers = texture_pack::LAYER_NAMES.iter().take(array_layers as usize);
+        if voxel::set_texture_layers(layers.map(|name| name.to_string()).collect()) {
+            world.remesh_all();
+        }
+    }
     loading_texture.is_loaded = true;
     let image = images.get_mut(&loading_texture.handle).unwrap();
 
+    // undo an earlier reinterpretation, e.g. when only the layer count changed
+    let size = image.texture_descriptor.size;
+    if size.depth_or_array_layers != 1 {
+        image.reinterpret_size(Extent3d {
+            width: size.width,
+            height: size.height * size.depth_or_array_layers,
+            depth_or_array_layers: 1,
+        });
+    }
+
     // Create a new array texture asset from the loaded texture.
-    let array_layers = 4;
+    if !image
+        .texture_descriptor
+        .size
+        .height
+        .is_multiple_of(array_layers)
+    {
+        asset_status.report_missing(format!(
+            "{} ({} layers don't fit the image)",
+            texture_pack.path, array_layers
+        ));
+        loading_texture.handle = images.add(assets::fallback_array_texture());
+        array_layers = assets::LAYER_COLORS.len() as u32;
+    }
+    let image = images.get_mut(&loading_texture.handle).unwrap();
     image.reinterpret_stacked_2d_as_array(array_layers);
 
     let material_handle = materials.add(ArrayTextureMaterial {
         array_texture: loading_texture.handle.clone(),
+        underwater: Vec4::ZERO,
+        light_bounce: Vec4::ZERO,
+        alpha_mode: AlphaMode::Opaque,
     });
+    let transparent_material_handle = materials.add(ArrayTextureMaterial {
+        array_texture: loading_texture.handle.clone(),
+        underwater: Vec4::ZERO,
+        light_bounce: Vec4::ZERO,
+        alpha_mode: AlphaMode::Mask(0.5),
+    });
+    // swap the new materials onto the column meshes built with the old ones
+    for (mut material, transparent) in material_query.iter_mut() {
+        *material = if transparent.is_some() {
+            transparent_material_handle.clone()
+        } else {
+            material_handle.clone()
+        };
+    }
+    if voxel_material.loaded {
+        materials.remove(&voxel_material.material);
+        materials.remove(&voxel_material.transparent_material);
+    }
     voxel_material.material = material_handle;
+    voxel_material.transparent_material = transparent_material_handle;
     voxel_material.loaded = true;
 }
 
@@ -506,10 +1629,50 @@ pub struct ArrayTextureMaterial {
     #[texture(0, dimension = "2d_array")]
     #[sampler(1)]
     array_texture: Handle<Image>,
+    #[uniform(2)]
+    underwater: Vec4, // x 1 with the camera in water, y seconds for the waves, z surface height
+    #[uniform(3)]
+    light_bounce: Vec4, // rgb bounce color, w strength
+    alpha_mode: AlphaMode,
 }
 
 impl Material for ArrayTextureMaterial {
+    fn vertex_shader() -> ShaderRef {
+        "shaders/array_texture.wgsl".into()
+    }
+
     fn fragment_shader() -> ShaderRef {
         "shaders/array_texture.wgsl".into()
     }
+
+    fn prepass_vertex_shader() -> ShaderRef {
+        "shaders/array_texture_prepass.wgsl".into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
+    fn specialize(
+        pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayout,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        // the prepass and shadow pipelines decode the packed vertices too, see
+        // `array_texture_prepass.wgsl`
+        descriptor.vertex.buffers = vec![layout.get_layout(&[
+            voxel::ATTRIBUTE_PACKED_VERTEX.at_shader_location(0),
+            voxel::ATTRIBUTE_VOXEL_LIGHT.at_shader_location(1),
+        ])?];
+        if pipeline.vertex_shader.as_ref() != Some(&descriptor.vertex.shader) {
+            // bevy's alpha test there reads StandardMaterial bindings, so glass and leaves cast solid shadows
+            if let Some(fragment) = descriptor.fragment.as_mut() {
+                fragment
+                    .shader_defs
+                    .retain(|def| *def != "MAY_DISCARD".into());
+            }
+        }
+        Ok(())
+    }
 }
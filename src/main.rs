@@ -1,18 +1,22 @@
-use bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
+use bevy::diagnostic::FrameTimeDiagnosticsPlugin;
 use bevy::input::common_conditions::input_toggle_active;
-use bevy::pbr::wireframe::{Wireframe, WireframeConfig, WireframePlugin};
+use bevy::pbr::wireframe::WireframePlugin;
 use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::transform::TransformSystem;
+use bevy::utils::Duration;
 use bevy::window::PresentMode;
-use bevy_inspector_egui::prelude::*;
-use bevy_inspector_egui::quick::ResourceInspectorPlugin;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use bevy_mod_picking::prelude::*;
-use smooth_bevy_cameras::{
-    controllers::fps::{FpsCameraBundle, FpsCameraController, FpsCameraPlugin},
-    LookTransformPlugin,
-};
+use smooth_bevy_cameras::{controllers::fps::FpsCameraPlugin, LookTransformPlugin};
 
 fn main() {
+    mcrs::install_crash_reporter();
+    let Some(world_slot) = mcrs::WorldSlot::from_args(std::env::args().skip(1)) else {
+        return;
+    };
+    // a world picked on the command line skips the main menu
+    let skip_menu = std::env::args().any(|arg| arg == "--world" || arg == "--seed");
     App::new()
         .add_plugins((
             DefaultPlugins
@@ -31,30 +35,575 @@ fn main() {
             WorldInspectorPlugin::default().run_if(input_toggle_active(true, KeyCode::Grave)),
         )
         .add_plugins(LookTransformPlugin)
-        .add_plugins(FpsCameraPlugin::default())
-        .add_plugins(FrameTimeDiagnosticsPlugin::default())
+        .add_plugins(FpsCameraPlugin::new(true)) // driven by `mcrs::drive_camera`
+        .add_plugins(FrameTimeDiagnosticsPlugin)
+        .add_plugins(mcrs::ProfilerDiagnosticsPlugin)
+        .add_plugins(mcrs::WebPlugin)
         // .add_plugins(EguiPlugin)
         .add_plugins(MaterialPlugin::<mcrs::ArrayTextureMaterial>::default())
-        // .add_plugins(DefaultPickingPlugins)
-        .add_systems(Startup, mcrs::setup)
-        .add_systems(PostStartup, mcrs::post_setup)
+        .add_plugins(mcrs::UnderwaterPlugin)
+        .add_plugins(mcrs::InstancedFacesPlugin)
+        .add_plugins(mcrs::PhysicsPlugin)
+        .add_asset::<mcrs::BlockDefinitions>()
+        .init_asset_loader::<mcrs::BlockDefinitionsLoader>()
+        .add_plugins(
+            DefaultPickingPlugins
+                .build()
+                .disable::<bevy_mod_picking::debug::DebugPickingPlugin>(),
+        )
+        .add_plugins(mcrs::TerrainPickingPlugin)
+        .add_state::<mcrs::AppState>()
+        .insert_resource(NextState(skip_menu.then_some(mcrs::AppState::Loading)))
+        .configure_set(PreUpdate, mcrs::InWorld.run_if(mcrs::in_world))
+        .configure_set(Update, mcrs::InWorld.run_if(mcrs::in_world))
+        .configure_set(PostUpdate, mcrs::InWorld.run_if(mcrs::in_world))
+        .init_resource::<mcrs::MainMenu>()
+        // empty until the world is set up, for the plugins' systems that run all along
+        .init_resource::<mcrs::VoxelData>()
+        .init_resource::<mcrs::ChunkEntities>()
+        .add_systems(OnEnter(mcrs::AppState::MainMenu), mcrs::setup_main_menu)
+        .add_systems(
+            Update,
+            mcrs::main_menu.run_if(in_state(mcrs::AppState::MainMenu)),
+        )
+        .add_systems(OnEnter(mcrs::AppState::Loading), mcrs::setup_loading_screen)
+        .add_systems(
+            Update,
+            mcrs::finish_loading.run_if(in_state(mcrs::AppState::Loading)),
+        )
+        .add_systems(OnExit(mcrs::AppState::Loading), mcrs::leave_menus)
+        .add_systems(Update, mcrs::sync_pause_state.in_set(mcrs::InWorld))
+        .add_systems(
+            OnExit(mcrs::AppState::Loading),
+            (mcrs::setup, apply_deferred, mcrs::post_setup).chain(),
+        )
+        .add_systems(OnExit(mcrs::AppState::Loading), mcrs::load_claims)
+        .add_systems(Startup, mcrs::load_scripts)
+        .add_systems(Startup, mcrs::load_block_definitions)
+        .add_systems(Startup, mcrs::setup_fallback_assets)
+        .add_systems(Startup, mcrs::load_settings)
+        .add_systems(OnExit(mcrs::AppState::Loading), mcrs::setup_region_title)
+        .add_systems(OnExit(mcrs::AppState::Loading), mcrs::setup_compass)
+        .add_systems(OnExit(mcrs::AppState::Loading), mcrs::setup_minimap)
+        .add_systems(
+            OnExit(mcrs::AppState::Loading),
+            mcrs::setup_auto_tune_notice,
+        )
+        .add_systems(Startup, mcrs::check_last_crash)
+        .add_systems(OnExit(mcrs::AppState::Loading), mcrs::setup_keybind_hints)
+        .add_systems(OnExit(mcrs::AppState::Loading), mcrs::setup_tool_hud)
+        .add_systems(OnExit(mcrs::AppState::Loading), mcrs::load_inventory)
+        .add_systems(OnExit(mcrs::AppState::Loading), mcrs::setup_mobs)
+        .add_systems(OnExit(mcrs::AppState::Loading), mcrs::setup_hud)
+        .add_systems(OnExit(mcrs::AppState::Loading), mcrs::setup_hearts)
+        .add_systems(OnExit(mcrs::AppState::Loading), mcrs::setup_food)
+        .add_systems(OnExit(mcrs::AppState::Loading), mcrs::setup_air)
+        .add_systems(OnExit(mcrs::AppState::Loading), mcrs::setup_player)
+        .add_systems(OnExit(mcrs::AppState::Loading), mcrs::setup_sky)
+        .add_systems(OnExit(mcrs::AppState::Loading), mcrs::setup_fire)
+        .add_systems(OnExit(mcrs::AppState::Loading), mcrs::setup_projectiles)
+        .add_systems(Startup, mcrs::setup_regenerate_button)
         // .add_systems(Update, bevy::window::close_on_esc)
-        .add_systems(Update, mcrs::input_mode)
+        .add_systems(
+            PreUpdate,
+            mcrs::read_player_input
+                .after(bevy::input::InputSystem)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            PreUpdate,
+            mcrs::toggle_free_camera
+                .after(mcrs::read_player_input)
+                .run_if(mcrs::console_closed)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            PreUpdate,
+            mcrs::detect_underwater
+                .after(mcrs::toggle_free_camera)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            PreUpdate,
+            mcrs::drive_camera
+                .after(mcrs::detect_underwater)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            PreUpdate,
+            mcrs::player_gravity
+                .after(mcrs::detect_underwater)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::input_mode.in_set(mcrs::InWorld))
         .init_resource::<mcrs::MouseSettings>()
         .register_type::<mcrs::MouseSettings>()
         .init_resource::<mcrs::DebugSettings>() // `ResourceInspectorPlugin` won't initialize the resource
         .register_type::<mcrs::DebugSettings>() // you need to register your type to display it
         // .add_plugins(ResourceInspectorPlugin::<mcrs::DebugSettings>::default()) // seperate window for the resource
+        .init_resource::<mcrs::VoxelSettings>()
         .register_type::<mcrs::VoxelSettings>()
-        .add_systems(Update, mcrs::debug_system)
-        .add_systems(Update, mcrs::fps)
-        .add_systems(PreUpdate, mcrs::gen_chunks_data)
-        .add_systems(Update, mcrs::update_column_meshes)
-        .add_systems(Update, mcrs::load_chunks_around)
-        .add_systems(Update, mcrs::handle_chunk_meshes_update_queue)
-        .add_systems(Update, mcrs::create_array_texture)
-        .add_systems(Update, mcrs::handle_voxel_modify_queue)
-        .add_systems(Update, mcrs::hit_voxel)
-        .add_systems(Update, mcrs::remove_chunk)
+        .insert_resource(world_slot.info.settings.clone())
+        .insert_resource(world_slot)
+        .register_type::<mcrs::WorldGenSettings>()
+        .register_type::<mcrs::WorldPreset>()
+        .register_type::<mcrs::FlatLayer>()
+        .register_type::<Vec<mcrs::FlatLayer>>()
+        .register_type::<mcrs::BlockId>()
+        .register_type::<mcrs::HeightPoint>()
+        .register_type::<Vec<mcrs::HeightPoint>>()
+        .register_type::<mcrs::RegenerateButton>()
+        .init_resource::<mcrs::WorldMetadata>()
+        .init_resource::<mcrs::ChunkDecorators>()
+        .init_resource::<mcrs::ChunkOcclusion>()
+        .init_resource::<mcrs::JournalSettings>()
+        .register_type::<mcrs::JournalSettings>()
+        .init_resource::<mcrs::WorldJournal>()
+        .init_resource::<mcrs::Waypoints>()
+        .add_event::<mcrs::WaypointShared>()
+        .add_event::<mcrs::WaypointReceived>()
+        .init_resource::<mcrs::ChatHistory>()
+        .add_event::<mcrs::ChatSent>()
+        .add_event::<mcrs::ChatReceived>()
+        .init_resource::<mcrs::RemotePlayers>()
+        .add_event::<mcrs::PlayerJoined>()
+        .add_event::<mcrs::PlayerMoved>()
+        .add_event::<mcrs::PlayerLeft>()
+        .init_resource::<mcrs::ServerSettings>()
+        .register_type::<mcrs::ServerSettings>()
+        .init_resource::<mcrs::GrantedSightRange>()
+        .add_event::<mcrs::SightRangeRequest>()
+        .add_event::<mcrs::SightRangeGranted>()
+        .init_resource::<mcrs::PendingEdits>()
+        .init_resource::<mcrs::Claims>()
+        .init_resource::<mcrs::Scripts>()
+        .add_event::<mcrs::EditRequest>()
+        .add_event::<mcrs::EditVerdict>()
+        .add_event::<mcrs::EditBroadcast>()
+        .init_resource::<mcrs::IdleSettings>()
+        .register_type::<mcrs::IdleSettings>()
+        .init_resource::<mcrs::WindowIdle>()
+        .init_resource::<mcrs::MemoryUsage>()
+        .init_resource::<mcrs::GraphicsSettings>()
+        .init_resource::<mcrs::AutoTuneSettings>()
+        .register_type::<mcrs::AutoTuneSettings>()
+        .init_resource::<mcrs::AutoTune>()
+        .init_resource::<mcrs::TunedQuality>()
+        .init_resource::<mcrs::LastCrash>()
+        .init_resource::<mcrs::SafeMode>()
+        .register_type::<mcrs::GraphicsSettings>()
+        .init_resource::<mcrs::CameraSettings>()
+        .register_type::<mcrs::CameraSettings>()
+        .init_resource::<mcrs::HudSettings>()
+        .register_type::<mcrs::HudSettings>()
+        .init_resource::<mcrs::PauseMenu>()
+        .init_resource::<mcrs::Console>()
+        .init_resource::<mcrs::AssetStatus>()
+        .init_resource::<mcrs::TexturePack>()
+        .init_resource::<mcrs::ScreenshotSettings>()
+        .register_type::<mcrs::ScreenshotSettings>()
+        .register_type::<mcrs::TexturePack>()
+        .register_type::<mcrs::ChunkStage>()
+        .register_type::<mcrs::ChunkSummary>()
+        .register_type::<mcrs::ColumnMeshStats>()
+        .init_resource::<mcrs::WorldTime>()
+        .init_resource::<mcrs::SkySettings>()
+        .register_type::<mcrs::SkySettings>()
+        .init_resource::<mcrs::GameMode>()
+        .init_resource::<mcrs::HeldTool>()
+        .init_resource::<mcrs::Selection>()
+        .init_resource::<mcrs::BulkEdits>()
+        .init_resource::<mcrs::InteractCooldowns>()
+        .init_resource::<mcrs::CameraMode>()
+        .init_resource::<mcrs::FreeCamera>()
+        .init_resource::<mcrs::PendingTeleport>()
+        .init_resource::<mcrs::DeathScreen>()
+        .init_resource::<mcrs::Underwater>()
+        .init_resource::<mcrs::PlayerInput>()
+        .init_resource::<mcrs::KeyBindings>()
+        .add_event::<mcrs::CommandRequest>()
+        .add_event::<mcrs::CommandResponse>()
+        .add_event::<mcrs::ToolBroke>()
+        .add_event::<mcrs::BlockBroken>()
+        .add_event::<mcrs::BlockChanged>()
+        .add_event::<mcrs::BlockEntityRemoved>()
+        .add_event::<mcrs::OpenBlock>()
+        .add_event::<mcrs::Damage>()
+        .add_event::<mcrs::LightTnt>()
+        .add_event::<mcrs::Explosion>()
+        .add_event::<mcrs::ProjectileHit>()
+        .init_resource::<mcrs::Inventory>()
+        .init_resource::<mcrs::HeldItem>()
+        .init_resource::<mcrs::HeldBlock>()
+        .init_resource::<mcrs::NearbyFires>()
+        .init_resource::<mcrs::LooseBlocks>()
+        .init_resource::<mcrs::Circuits>()
+        .init_resource::<mcrs::FurnaceWindow>()
+        .init_resource::<mcrs::SignEditor>()
+        .init_resource::<mcrs::SignTexts>()
+        .init_resource::<mcrs::RandomTickSettings>()
+        .register_type::<mcrs::RandomTickSettings>()
+        .init_resource::<mcrs::ItemMeshes>()
+        .add_systems(Update, mcrs::debug_system.in_set(mcrs::InWorld))
+        // in Last, after the menus that send AppExit, so the saves on quit see it
+        .add_systems(Last, mcrs::save_settings)
+        .add_systems(Last, mcrs::save_modified_chunks.run_if(mcrs::in_world))
+        // after every system that writes to the world this frame
+        .add_systems(Last, mcrs::record_journal.run_if(mcrs::in_world))
+        .add_systems(Update, mcrs::regenerate_world.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::update_idle.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::apply_graphics_settings.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::update_light_bounce.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::auto_tune.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::update_crash_context.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::crash_prompt)
+        .add_systems(
+            Update,
+            mcrs::toggle_pause_menu
+                .run_if(mcrs::console_closed)
+                .run_if(mcrs::furnace_closed)
+                .run_if(mcrs::sign_editor_closed)
+                .run_if(mcrs::death_screen_closed)
+                .before(mcrs::console)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::console.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::record_chat.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::save_claims.in_set(mcrs::InWorld))
+        .add_systems(
+            Update,
+            mcrs::chat_overlay
+                .after(mcrs::record_chat)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::update_remote_players.in_set(mcrs::InWorld))
+        .add_systems(
+            Update,
+            mcrs::update_name_tags
+                .after(mcrs::update_remote_players)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::apply_block_definitions.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::run_commands.in_set(mcrs::InWorld))
+        .add_systems(
+            Update,
+            mcrs::update_teleport
+                .after(mcrs::run_commands)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::script_commands.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::script_block_breaks.in_set(mcrs::InWorld))
+        .add_systems(
+            Update,
+            mcrs::script_ticks
+                .run_if(on_timer(Duration::from_secs_f32(mcrs::SCRIPT_TICK)))
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::advance_time.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::update_sun.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::update_sky.in_set(mcrs::InWorld))
+        .add_systems(
+            Update,
+            mcrs::pause_menu
+                .after(mcrs::toggle_pause_menu)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::fps.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::capture_screenshots.in_set(mcrs::InWorld))
+        .add_systems(
+            Update,
+            mcrs::toggle_chunk_overlay
+                .run_if(mcrs::console_closed)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::chunk_overlay.in_set(mcrs::InWorld))
+        .add_systems(OnExit(mcrs::AppState::Loading), mcrs::setup_debug_screen)
+        .add_systems(
+            Update,
+            mcrs::toggle_debug_screen
+                .run_if(mcrs::console_closed)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::update_debug_screen.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::track_memory.in_set(mcrs::InWorld))
+        .add_systems(
+            Update,
+            mcrs::update_chunk_summaries
+                .run_if(on_timer(Duration::from_secs_f32(0.5)))
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            PreUpdate,
+            mcrs::gen_chunks_data
+                .run_if(mcrs::window_active)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            Update,
+            mcrs::cull_hidden_chunks
+                .before(mcrs::update_column_meshes)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::update_column_meshes.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::request_sight_range.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::grant_sight_range.in_set(mcrs::InWorld))
+        .add_systems(
+            Update,
+            mcrs::apply_granted_sight_range.in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            Update,
+            mcrs::load_chunks_around
+                .run_if(mcrs::window_active)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            Update,
+            mcrs::handle_chunk_meshes_update_queue.in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            Update,
+            mcrs::relight_chunks
+                .after(mcrs::handle_voxel_modify_queue)
+                .before(mcrs::handle_chunk_meshes_update_queue)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            Update,
+            mcrs::reload_texture_pack
+                .before(mcrs::create_array_texture)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::create_array_texture.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::use_fallback_fonts)
+        .add_systems(Update, mcrs::show_asset_errors)
+        .add_systems(
+            Update,
+            mcrs::apply_bulk_edits
+                .before(mcrs::handle_voxel_modify_queue)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            Update,
+            mcrs::handle_voxel_modify_queue.in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            Update,
+            mcrs::snapshot_journal
+                .after(mcrs::handle_voxel_modify_queue)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            Update,
+            mcrs::hit_voxel
+                .before(mcrs::handle_voxel_modify_queue)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            Update,
+            mcrs::validate_edits
+                .after(mcrs::hit_voxel)
+                .before(mcrs::handle_voxel_modify_queue)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            Update,
+            mcrs::apply_edit_verdicts
+                .after(mcrs::validate_edits)
+                .before(mcrs::handle_voxel_modify_queue)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::cycle_held_item.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::pick_block.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::use_held_item.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::repair_tool.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::update_held_tool.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::save_inventory.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::ignite.in_set(mcrs::InWorld))
+        .add_systems(
+            Update,
+            mcrs::find_nearby_fires
+                .run_if(on_timer(Duration::from_secs_f32(0.5)))
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::update_fire_effects.in_set(mcrs::InWorld))
+        .add_systems(
+            Update,
+            mcrs::light_tnt.after(mcrs::ignite).in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::update_primed_tnt.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::throw_snowball.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::update_projectiles.in_set(mcrs::InWorld))
+        .add_systems(
+            Update,
+            mcrs::snowball_hits
+                .after(mcrs::update_projectiles)
+                .before(mcrs::handle_voxel_modify_queue)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            Update,
+            mcrs::spawn_falling_blocks
+                .after(mcrs::handle_voxel_modify_queue)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            Update,
+            mcrs::update_falling_blocks
+                .before(mcrs::handle_voxel_modify_queue)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            Update,
+            mcrs::explode
+                .after(mcrs::update_primed_tnt)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            Update,
+            mcrs::random_ticks
+                .run_if(on_timer(Duration::from_secs_f32(1.0)))
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::track_circuits.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::use_blocks.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::break_doors.in_set(mcrs::InWorld))
+        .add_systems(
+            Update,
+            mcrs::smelt
+                .run_if(on_timer(Duration::from_secs_f32(mcrs::SMELT_TICK)))
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::drop_furnace_contents.in_set(mcrs::InWorld))
+        .add_systems(
+            Update,
+            mcrs::furnace_window
+                .after(mcrs::toggle_pause_menu)
+                .after(mcrs::use_blocks)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            Update,
+            mcrs::sign_editor
+                .after(mcrs::toggle_pause_menu)
+                .after(mcrs::handle_voxel_modify_queue)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::update_sign_texts.in_set(mcrs::InWorld))
+        .add_systems(
+            Update,
+            mcrs::power_tick
+                .after(mcrs::track_circuits)
+                .run_if(on_timer(Duration::from_secs_f32(0.1)))
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            Update,
+            mcrs::toggle_hud
+                .run_if(mcrs::console_closed)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::update_hud.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::update_crosshair.in_set(mcrs::InWorld))
+        .add_systems(
+            Update,
+            mcrs::spawn_dropped_items
+                .after(mcrs::apply_edit_verdicts)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::update_dropped_items.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::merge_dropped_items.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::pick_up_dropped_items.in_set(mcrs::InWorld))
+        .add_systems(
+            Update,
+            mcrs::spawn_mobs
+                .run_if(on_timer(Duration::from_secs_f32(2.0)))
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::update_mob_paths.in_set(mcrs::InWorld))
+        .add_systems(
+            Update,
+            mcrs::move_mobs
+                .after(mcrs::update_mob_paths)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::mob_attacks.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::fall_damage.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::update_hunger.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::update_breath.in_set(mcrs::InWorld))
+        .add_systems(
+            Update,
+            mcrs::apply_damage
+                .after(mcrs::mob_attacks)
+                .after(mcrs::fall_damage)
+                .after(mcrs::update_hunger)
+                .after(mcrs::update_breath)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            Update,
+            mcrs::death_screen
+                .after(mcrs::apply_damage)
+                .before(mcrs::update_teleport)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::update_hearts.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::update_food.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::update_air.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::underwater_view.in_set(mcrs::InWorld))
+        .add_systems(
+            Update,
+            mcrs::toggle_camera_mode
+                .run_if(mcrs::console_closed)
+                .in_set(mcrs::InWorld),
+        )
+        // after the camera controller, before the camera's offset reaches its global transform
+        .add_systems(
+            PostUpdate,
+            mcrs::update_player
+                .before(TransformSystem::TransformPropagate)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            PostUpdate,
+            mcrs::camera_effects
+                .after(mcrs::update_player)
+                .before(TransformSystem::TransformPropagate)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::update_keybind_hints.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::update_tool_hud.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::remove_chunk.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::restore_stored_entities.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::update_region_title.in_set(mcrs::InWorld))
+        .add_systems(
+            Update,
+            mcrs::waypoint_input
+                .run_if(mcrs::console_closed)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            Update,
+            mcrs::select_corners
+                .run_if(mcrs::console_closed)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(Update, mcrs::draw_selection.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::receive_waypoints.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::update_compass.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::update_minimap.in_set(mcrs::InWorld))
+        .add_systems(Update, mcrs::detect_underwater_view.in_set(mcrs::InWorld))
+        .add_systems(
+            Update,
+            mcrs::underwater_pass
+                .after(mcrs::detect_underwater_view)
+                .in_set(mcrs::InWorld),
+        )
+        .add_systems(
+            Update,
+            mcrs::underwater_terrain
+                .after(mcrs::detect_underwater_view)
+                .in_set(mcrs::InWorld),
+        )
         .run();
 }
@@ -0,0 +1,249 @@
+//! The states the app goes through: the main menu, where a world is created or
+//! picked from the saves, a moment on the loading screen while the world is set
+//! up, then the game, paused while the pause menu is open. The world's systems
+//! are in the `InWorld` set, which only runs in game. Naming a world on the
+//! command line skips the menu.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use bevy::{app::AppExit, prelude::*};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    game_mode::GameMode,
+    menu::PauseMenu,
+    save::{self, WorldInfo, WorldSlot},
+    voxel::{WorldGenSettings, WorldPreset},
+};
+
+const LOADING_FRAMES: u32 = 2; // frames the loading screen is drawn for before the world is set up
+
+#[derive(States, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AppState {
+    #[default]
+    MainMenu,
+    Loading, // the world is set up on the way out, see `OnExit(AppState::Loading)`
+    InGame,
+    Paused, // the world goes on behind the pause menu, it may be shared with other players
+}
+
+/// Systems of the world being played, they only run in game
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InWorld;
+
+pub fn in_world(state: Res<State<AppState>>) -> bool {
+    matches!(state.get(), AppState::InGame | AppState::Paused)
+}
+
+/// The saved worlds and the world being created
+#[derive(Resource, Default)]
+pub struct MainMenu {
+    worlds: Vec<(String, WorldInfo)>,
+    name: String,
+    seed: String, // a number, any other text is hashed, empty for a random seed
+    game_mode: GameMode,
+    preset: WorldPreset,
+    error: Option<String>,
+}
+
+/// Draws the menus until the world's camera takes over
+#[derive(Component)]
+pub struct MenuCamera;
+
+#[derive(Component)]
+pub struct LoadingScreen;
+
+/// The seed typed in: a number is used as is and other text hashed into one, like Minecraft
+fn parse_seed(text: &str) -> Option<u32> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    Some(text.parse().unwrap_or_else(|_| {
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        hasher.finish() as u32
+    }))
+}
+
+pub fn setup_main_menu(mut commands: Commands, mut main_menu: ResMut<MainMenu>) {
+    main_menu.worlds = save::list_worlds();
+    commands.spawn((Camera2dBundle::default(), MenuCamera));
+}
+
+/// Play the world in `slot`, its generator settings replace the ones from the command line
+fn start_world(commands: &mut Commands, next_state: &mut NextState<AppState>, slot: WorldSlot) {
+    commands.insert_resource(slot.info.settings.clone());
+    commands.insert_resource(slot);
+    next_state.set(AppState::Loading);
+}
+
+pub fn main_menu(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut main_menu: ResMut<MainMenu>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut exit_events: EventWriter<AppExit>,
+) {
+    let main_menu = &mut *main_menu;
+    let mut play = None;
+    let mut create = false;
+
+    egui::Window::new("mcrs")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(contexts.ctx_mut(), |ui| {
+            ui.heading("Worlds");
+            if main_menu.worlds.is_empty() {
+                ui.label("No saved worlds yet");
+            }
+            egui::Grid::new("worlds").striped(true).show(ui, |ui| {
+                for (name, info) in &main_menu.worlds {
+                    ui.label(name);
+                    ui.label(format!(
+                        "seed {}, {}, {:?}, played {}",
+                        info.settings.seed,
+                        info.settings.preset.name(),
+                        info.game_mode,
+                        save::format_playtime(info.playtime)
+                    ));
+                    if ui.button("Play").clicked() {
+                        play = Some(name.clone());
+                    }
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+
+            ui.heading("New world");
+            egui::Grid::new("new_world").show(ui, |ui| {
+                ui.label("Name");
+                ui.text_edit_singleline(&mut main_menu.name);
+                ui.end_row();
+                ui.label("Seed");
+                ui.add(egui::TextEdit::singleline(&mut main_menu.seed).hint_text("random"));
+                ui.end_row();
+                ui.label("Game mode");
+                ui.horizontal(|ui| {
+                    for option in GameMode::ALL {
+                        let label = format!("{:?}", option);
+                        ui.selectable_value(&mut main_menu.game_mode, option, label);
+                    }
+                });
+                ui.end_row();
+                ui.label("Generator");
+                ui.horizontal(|ui| {
+                    for option in WorldPreset::ALL {
+                        ui.selectable_value(&mut main_menu.preset, option, option.name());
+                    }
+                });
+                ui.end_row();
+            });
+            if let Some(error) = &main_menu.error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                create = ui.button("Create world").clicked();
+                if ui.button("Quit").clicked() {
+                    exit_events.send(AppExit);
+                }
+            });
+        });
+
+    if let Some(name) = play {
+        start_world(&mut commands, &mut next_state, WorldSlot::open(&name, None));
+    } else if create {
+        let seed = parse_seed(&main_menu.seed);
+        match WorldSlot::create(&main_menu.name, seed, main_menu.preset, main_menu.game_mode) {
+            Ok(slot) => start_world(&mut commands, &mut next_state, slot),
+            Err(err) => main_menu.error = Some(err),
+        }
+    }
+}
+
+pub fn setup_loading_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    world_slot: Res<WorldSlot>,
+    world_gen_settings: Res<WorldGenSettings>,
+) {
+    let name = world_slot.name.as_deref().unwrap_or("world");
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: BackgroundColor(Color::rgb(0.1, 0.1, 0.12)),
+                ..default()
+            },
+            LoadingScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                format!("Loading {} (seed {})...", name, world_gen_settings.seed),
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 32.0,
+                    color: Color::WHITE,
+                },
+            ));
+        });
+}
+
+/// Go in game once the loading screen was drawn, the world's setup blocks the frame it runs in
+pub fn finish_loading(mut frames: Local<u32>, mut next_state: ResMut<NextState<AppState>>) {
+    *frames += 1;
+    if *frames >= LOADING_FRAMES {
+        *frames = 0;
+        next_state.set(AppState::InGame);
+    }
+}
+
+/// The menu camera and the loading screen, gone once in game
+type MenuEntities = Or<(With<MenuCamera>, With<LoadingScreen>)>;
+
+pub fn leave_menus(mut commands: Commands, menu_query: Query<Entity, MenuEntities>) {
+    for entity in menu_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Pause while the pause menu is open
+pub fn sync_pause_state(
+    pause_menu: Res<PauseMenu>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let paused = *state.get() == AppState::Paused;
+    if pause_menu.open != paused {
+        next_state.set(if pause_menu.open {
+            AppState::Paused
+        } else {
+            AppState::InGame
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeds_are_numbers_or_hashed_text() {
+        assert_eq!(parse_seed("  "), None);
+        assert_eq!(parse_seed("1234"), Some(1234));
+        assert_eq!(parse_seed("glacier"), parse_seed(" glacier "));
+        assert_ne!(parse_seed("glacier"), parse_seed("Glacier"));
+    }
+}
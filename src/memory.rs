@@ -0,0 +1,91 @@
+//! Memory accounting: bytes held by the loaded chunks, the chunk cache, the chunk meshes kept
+//! for remeshing and the column meshes uploaded to the GPU. Past `VoxelSettings::memory_budget`
+//! the cached chunks farthest from the camera go first.
+
+use bevy::{prelude::*, render::mesh::Indices};
+use smooth_bevy_cameras::controllers::fps::FpsCameraController;
+
+use crate::voxel::{self, ChunkCache, ColumnMesh, VoxelData, VoxelSettings};
+
+const MB: f32 = 1024.0 * 1024.0;
+
+/// Bytes in use, worked out again every frame
+#[derive(Resource, Default, Debug, Clone, Copy)]
+pub struct MemoryUsage {
+    pub voxel_data: usize,
+    pub chunk_cache: usize,
+    pub mesh_data: usize, // chunk meshes kept to rebuild their columns
+    pub gpu_meshes: usize,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.voxel_data + self.chunk_cache + self.mesh_data + self.gpu_meshes
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{:.1} MB: chunks {:.1}, cache {:.1}, meshes {:.1}, gpu {:.1}",
+            self.total() as f32 / MB,
+            self.voxel_data as f32 / MB,
+            self.chunk_cache as f32 / MB,
+            self.mesh_data as f32 / MB,
+            self.gpu_meshes as f32 / MB,
+        )
+    }
+}
+
+/// Bytes of the vertex and index buffers of a mesh
+fn mesh_bytes(mesh: &Mesh) -> usize {
+    let indices = match mesh.indices() {
+        Some(Indices::U16(indices)) => indices.len() * 2,
+        Some(Indices::U32(indices)) => indices.len() * 4,
+        None => 0,
+    };
+    let vertices: usize = mesh
+        .attributes()
+        .map(|(_, values)| values.get_bytes().len())
+        .sum();
+    vertices + indices
+}
+
+/// Count the bytes in use, then trim the chunk cache until they fit the budget
+pub fn track_memory(
+    voxel_settings: Res<VoxelSettings>,
+    voxel_data: Res<VoxelData>,
+    meshes: Res<Assets<Mesh>>,
+    column_query: Query<&ColumnMesh>,
+    fps_camera_query: Query<&GlobalTransform, With<FpsCameraController>>,
+    mut chunk_cache: ResMut<ChunkCache>,
+    mut memory_usage: ResMut<MemoryUsage>,
+) {
+    let mut usage = MemoryUsage {
+        voxel_data: voxel_data.iter_chunks().map(|c| c.memory_bytes()).sum(),
+        chunk_cache: chunk_cache.memory_bytes(),
+        ..default()
+    };
+    for column in column_query.iter() {
+        usage.mesh_data += column
+            .sub_meshes
+            .values()
+            .map(|sub_mesh| sub_mesh.heap_bytes())
+            .sum::<usize>();
+        usage.gpu_meshes += [&column.mesh, &column.transparent_mesh]
+            .into_iter()
+            .filter_map(|mesh| meshes.get(mesh))
+            .map(mesh_bytes)
+            .sum::<usize>();
+    }
+
+    let budget = voxel_settings.memory_budget as usize * MB as usize;
+    if let Ok(transform) = fps_camera_query.get_single() {
+        let camera_chunk = voxel::get_chunk_index(&transform.translation());
+        while usage.total() > budget {
+            let Some(freed) = chunk_cache.evict_farthest(&camera_chunk) else {
+                break;
+            };
+            usage.chunk_cache -= freed;
+        }
+    }
+    *memory_usage = usage;
+}
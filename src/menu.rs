@@ -0,0 +1,226 @@
+use bevy::{app::AppExit, ecs::system::SystemParam, prelude::*};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    camera::CameraSettings,
+    command::CommandRequest,
+    game_mode::GameMode,
+    input::{Binding, InputAction, KeyBindings, PlayerInput},
+    voxel::{self, VoxelSettings},
+    GraphicsSettings, InputMode,
+};
+
+#[derive(Resource, Default)]
+pub struct PauseMenu {
+    pub open: bool,
+    tab: MenuTab,
+    rebinding: Option<InputAction>, // waiting for the key to bind this action to
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum MenuTab {
+    #[default]
+    General,
+    Controls,
+}
+
+/// Esc opens the pause menu and frees the cursor, Esc again resumes
+pub fn toggle_pause_menu(
+    player_input: Res<PlayerInput>,
+    mut pause_menu: ResMut<PauseMenu>,
+    mut input_mode: InputMode,
+) {
+    // while rebinding, the key goes to the action being rebound
+    if !player_input.actions.just_pressed(InputAction::Menu) || pause_menu.rebinding.is_some() {
+        return;
+    }
+    pause_menu.open = !pause_menu.open;
+    input_mode.set_ui_mode(pause_menu.open);
+}
+
+/// The settings the pause menu edits
+#[derive(SystemParam)]
+pub struct MenuSettings<'w> {
+    voxel: ResMut<'w, VoxelSettings>,
+    graphics: ResMut<'w, GraphicsSettings>,
+    camera: ResMut<'w, CameraSettings>,
+    game_mode: ResMut<'w, GameMode>,
+}
+
+/// The key bindings, with the keys and buttons an action being rebound takes
+#[derive(SystemParam)]
+pub struct Rebinding<'w> {
+    key_bindings: ResMut<'w, KeyBindings>,
+    keyboard_input: Res<'w, Input<KeyCode>>,
+    mouse_input: Res<'w, Input<MouseButton>>,
+}
+
+impl Rebinding<'_> {
+    /// The key or button pressed this frame, a key first
+    fn pressed(&self) -> Option<Binding> {
+        self.keyboard_input
+            .get_just_pressed()
+            .next()
+            .map(|key| Binding::Key(*key))
+            .or_else(|| {
+                self.mouse_input
+                    .get_just_pressed()
+                    .next()
+                    .map(|button| Binding::Mouse(*button))
+            })
+    }
+}
+
+pub fn pause_menu(
+    mut contexts: EguiContexts,
+    mut pause_menu: ResMut<PauseMenu>,
+    mut input_mode: InputMode,
+    mut settings: MenuSettings,
+    mut rebinding_input: Rebinding,
+    mut exit_events: EventWriter<AppExit>,
+    mut command_requests: EventWriter<CommandRequest>,
+) {
+    if !pause_menu.open {
+        return;
+    }
+    if let Some(action) = pause_menu.rebinding {
+        if rebinding_input.keyboard_input.just_pressed(KeyCode::Escape) {
+            pause_menu.rebinding = None;
+        } else if let Some(binding) = rebinding_input.pressed() {
+            rebinding_input.key_bindings.set(action, binding);
+            pause_menu.rebinding = None;
+        }
+    }
+    let MenuSettings {
+        voxel: voxel_settings,
+        graphics: graphics_settings,
+        camera: camera_settings,
+        game_mode,
+    } = &mut settings;
+    let key_bindings = &mut rebinding_input.key_bindings;
+    let ms = &mut input_mode.ms;
+
+    // edit copies, so the resources are only marked changed when a value actually changes
+    let mut mode = **game_mode;
+    let mut sight_range = voxel_settings.sight_range;
+    let mut vertical_sight_range = voxel_settings.vertical_sight_range;
+    let mut sensitivity = ms.sensitivity.x;
+    let mut fov = camera_settings.fov;
+    let mut view_bobbing = camera_settings.view_bobbing;
+    let mut vsync = graphics_settings.vsync;
+    let mut light_bounce = graphics_settings.light_bounce;
+    let mut auto_quality = graphics_settings.auto_quality;
+    let mut resume = false;
+    let mut tab = pause_menu.tab;
+    let mut rebinding = pause_menu.rebinding;
+    let mut reset_bindings = false;
+
+    egui::Window::new("Paused")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut tab, MenuTab::General, "General");
+                ui.selectable_value(&mut tab, MenuTab::Controls, "Controls");
+            });
+            ui.separator();
+            match tab {
+                MenuTab::General => {
+                    ui.horizontal(|ui| {
+                        for option in GameMode::ALL {
+                            ui.selectable_value(&mut mode, option, format!("{:?}", option));
+                        }
+                    });
+                    ui.add(egui::Slider::new(&mut sight_range, 2..=32).text("Sight range"));
+                    ui.add(
+                        egui::Slider::new(
+                            &mut vertical_sight_range,
+                            1..=voxel::CHUNK_LIMIT_Y as u8,
+                        )
+                        .text("Vertical sight range"),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut sensitivity, 0.05..=2.0).text("Mouse sensitivity"),
+                    );
+                    ui.add(egui::Slider::new(&mut fov, 30.0..=120.0).text("FOV"));
+                    ui.checkbox(&mut view_bobbing, "View bobbing");
+                    ui.checkbox(&mut vsync, "VSync");
+                    ui.checkbox(&mut light_bounce, "Light bounce");
+                    ui.checkbox(&mut auto_quality, "Auto quality");
+                }
+                MenuTab::Controls => {
+                    egui::Grid::new("controls").striped(true).show(ui, |ui| {
+                        for action in InputAction::ALL {
+                            // actions sharing a key are shown in red
+                            let mut label = egui::RichText::new(action.label());
+                            if key_bindings.conflicts(action) {
+                                label = label.color(egui::Color32::RED);
+                            }
+                            ui.label(label);
+                            let key = if rebinding == Some(action) {
+                                "press a key, Esc to cancel".to_string()
+                            } else {
+                                key_bindings.get(action).to_string()
+                            };
+                            if ui.button(key).clicked() {
+                                rebinding = Some(action);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                    reset_bindings = ui.button("Reset to defaults").clicked();
+                }
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                resume = ui.button("Resume").clicked();
+                if ui.button("Repair world").clicked() {
+                    command_requests.send(CommandRequest {
+                        line: "/repair".to_string(),
+                    });
+                }
+                if ui.button("Quit").clicked() {
+                    exit_events.send(AppExit);
+                }
+            });
+        });
+
+    if tab != pause_menu.tab || rebinding != pause_menu.rebinding {
+        pause_menu.tab = tab;
+        pause_menu.rebinding = rebinding;
+    }
+    if reset_bindings {
+        key_bindings.reset();
+    }
+    if mode != **game_mode {
+        **game_mode = mode;
+    }
+    if sight_range != voxel_settings.sight_range {
+        voxel_settings.sight_range = sight_range;
+    }
+    if vertical_sight_range != voxel_settings.vertical_sight_range {
+        voxel_settings.vertical_sight_range = vertical_sight_range;
+    }
+    if sensitivity != ms.sensitivity.x {
+        ms.sensitivity = Vec2::splat(sensitivity);
+    }
+    if fov != camera_settings.fov || view_bobbing != camera_settings.view_bobbing {
+        camera_settings.fov = fov;
+        camera_settings.view_bobbing = view_bobbing;
+    }
+    if vsync != graphics_settings.vsync
+        || light_bounce != graphics_settings.light_bounce
+        || auto_quality != graphics_settings.auto_quality
+    {
+        graphics_settings.vsync = vsync;
+        graphics_settings.light_bounce = light_bounce;
+        graphics_settings.auto_quality = auto_quality;
+    }
+
+    if resume {
+        pause_menu.open = false;
+        pause_menu.rebinding = None;
+        input_mode.set_ui_mode(false);
+    }
+}
@@ -0,0 +1,178 @@
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use smooth_bevy_cameras::controllers::fps::FpsCameraController;
+
+use crate::{
+    assets::LAYER_COLORS,
+    voxel::{
+        self, BlockId, ChunkColumn, ChunkIndex, ColumnMesh, VoxelData, CHUNK_LIMIT_Y, CHUNK_SIZE,
+    },
+};
+
+const MINIMAP_RANGE: i32 = 4; // chunks shown around the camera in each direction
+const MINIMAP_CHUNKS: i32 = MINIMAP_RANGE * 2 + 1;
+const MINIMAP_PIXELS: u32 = MINIMAP_CHUNKS as u32 * CHUNK_SIZE as u32;
+const MINIMAP_SCALE: f32 = 1.5; // screen pixels per map pixel
+
+#[derive(Resource)]
+pub struct Minimap {
+    pub image: Handle<Image>,
+    center: Option<ChunkColumn>, // column drawn in the middle of the map
+}
+
+#[derive(Component)]
+pub struct MinimapMarker;
+
+pub fn setup_minimap(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let image = images.add(Image::new_fill(
+        Extent3d {
+            width: MINIMAP_PIXELS,
+            height: MINIMAP_PIXELS,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Rgba8UnormSrgb,
+    ));
+
+    let size = Val::Px(MINIMAP_PIXELS as f32 * MINIMAP_SCALE);
+    commands
+        .spawn(ImageBundle {
+            image: UiImage::new(image.clone()),
+            style: Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(5.0),
+                right: Val::Px(5.0),
+                width: size,
+                height: size,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            // the camera always sits in the middle of the map
+            parent.spawn((
+                NodeBundle {
+                    background_color: BackgroundColor(Color::RED),
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        top: Val::Percent(50.),
+                        left: Val::Percent(50.),
+                        width: Val::Px(4.),
+                        height: Val::Px(4.),
+                        ..default()
+                    },
+                    ..default()
+                },
+                MinimapMarker,
+            ));
+        });
+
+    commands.insert_resource(Minimap {
+        image,
+        center: None,
+    });
+}
+
+/// Redraw the whole map when the camera enters another chunk, otherwise only
+/// the columns whose meshes were rebuilt since the last frame
+pub fn update_minimap(
+    mut minimap: ResMut<Minimap>,
+    mut images: ResMut<Assets<Image>>,
+    voxel_data: Res<VoxelData>,
+    fps_camera_query: Query<&GlobalTransform, With<FpsCameraController>>,
+    changed_columns: Query<&ColumnMesh, Changed<ColumnMesh>>,
+) {
+    let Ok(transform) = fps_camera_query.get_single() else {
+        return;
+    };
+    let camera_chunk = voxel::get_chunk_index(&transform.translation());
+    let center = ChunkColumn {
+        x: camera_chunk.x,
+        z: camera_chunk.z,
+    };
+
+    let columns: Vec<ChunkColumn> = if minimap.center != Some(center) {
+        minimap.center = Some(center);
+        (-MINIMAP_RANGE..=MINIMAP_RANGE)
+            .flat_map(|x| {
+                (-MINIMAP_RANGE..=MINIMAP_RANGE).map(move |z| ChunkColumn {
+                    x: center.x + x,
+                    z: center.z + z,
+                })
+            })
+            .collect()
+    } else {
+        changed_columns
+            .iter()
+            .filter(|column_mesh| !column_mesh.dirty)
+            .map(|column_mesh| column_mesh.column)
+            .collect()
+    };
+    if columns.is_empty() {
+        return;
+    }
+
+    let Some(image) = images.get_mut(&minimap.image) else {
+        return;
+    };
+    for column in columns {
+        draw_column(image, &voxel_data, center, column);
+    }
+}
+
+fn draw_column(
+    image: &mut Image,
+    voxel_data: &VoxelData,
+    center: ChunkColumn,
+    column: ChunkColumn,
+) {
+    let map_x = column.x - center.x + MINIMAP_RANGE;
+    let map_z = column.z - center.z + MINIMAP_RANGE;
+    if !(0..MINIMAP_CHUNKS).contains(&map_x) || !(0..MINIMAP_CHUNKS).contains(&map_z) {
+        return;
+    }
+
+    let chunks: Vec<_> = (0..CHUNK_LIMIT_Y as i32)
+        .map(|y| {
+            voxel_data.chunk(ChunkIndex {
+                x: column.x,
+                y,
+                z: column.z,
+            })
+        })
+        .collect();
+
+    for x in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            let color = surface(&chunks, x, z).map_or([0, 0, 0], |(block, height)| {
+                // brighten higher ground a little so the relief is readable
+                let light = 0.6 + 0.6 * height as f32 / voxel::HEIGHT_LIMIT as f32;
+                // defined blocks may use layers past the built-in texture
+                let layer = (voxel::texture_layer(block) as usize).min(LAYER_COLORS.len() - 1);
+                LAYER_COLORS[layer].map(|c| (c as f32 * light).min(255.0) as u8)
+            });
+            let pixel_x = map_x as usize * CHUNK_SIZE + x;
+            let pixel_y = map_z as usize * CHUNK_SIZE + z;
+            let offset = (pixel_y * MINIMAP_PIXELS as usize + pixel_x) * 4;
+            image.data[offset..offset + 3].copy_from_slice(&color);
+        }
+    }
+}
+
+/// Topmost solid block of a voxel column and its height
+fn surface(chunks: &[Option<&voxel::ChunkData>], x: usize, z: usize) -> Option<(BlockId, usize)> {
+    chunks
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(chunk_y, chunk)| {
+            let chunk = (*chunk)?;
+            (0..CHUNK_SIZE).rev().find_map(|y| {
+                let block = chunk.voxels[x][y][z];
+                (block != voxel::AIR).then_some((block, chunk_y * CHUNK_SIZE + y))
+            })
+        })
+}
@@ -0,0 +1,365 @@
+//! Mobs walking the voxel world: animals wandering around in daylight and
+//! enemies coming out at night or in the dark, chasing the player. They stand
+//! on blocks, step up single blocks, fall off ledges and find their way with
+//! A* over the voxel grid.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    daytime::WorldTime,
+    health::Damage,
+    player::Player,
+    region::splitmix64,
+    voxel::{self, VoxelData, VoxelPos, GRASS},
+};
+
+mod path;
+
+pub use path::{can_stand, find_path};
+
+const MAX_MOBS: usize = 12;
+const SPAWN_DISTANCE: (i32, i32) = (16, 32); // blocks from the player, horizontally
+const SPAWN_SEARCH_HEIGHT: i32 = 64; // blocks above and below the player searched for ground
+const DARK_COVER: i32 = 16; // a block this close overhead keeps a spot dark
+const CHASE_RANGE: f32 = 24.0;
+const WANDER_RANGE: i32 = 8;
+const MAX_PATH_NODES: usize = 256;
+const ATTACK_RANGE: f32 = 1.5; // from an enemy's feet to the player's
+const ATTACK_DAMAGE: u32 = 2;
+const ATTACK_COOLDOWN: f32 = 1.0;
+const GRAVITY: f32 = 20.0;
+const TERMINAL_VELOCITY: f32 = 40.0;
+const KNOCKBACK_DRAG: f32 = 4.0; // how quickly a thrown mob slows down
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MobKind {
+    Animal, // wanders around
+    Enemy,  // chases the player when close
+}
+
+impl MobKind {
+    /// Cells the mob takes up vertically
+    pub fn height(self) -> i32 {
+        match self {
+            MobKind::Animal => 1,
+            MobKind::Enemy => 2,
+        }
+    }
+
+    /// Blocks per second
+    pub fn speed(self) -> f32 {
+        match self {
+            MobKind::Animal => 2.0,
+            MobKind::Enemy => 3.5,
+        }
+    }
+
+    /// Seconds between picking a new path
+    fn repath_interval(self) -> f32 {
+        match self {
+            MobKind::Animal => 4.0,
+            MobKind::Enemy => 1.0,
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct Mob {
+    pub kind: MobKind,
+    vertical_velocity: f32,
+    knockback: Vec3,  // horizontal, blocks per second
+    path: Vec<IVec3>, // cells left to walk through, the next one last
+    repath_in: f32,   // seconds
+    attack_ready_in: f32,
+}
+
+impl Mob {
+    /// Throw the mob, it flies off in a jump and walks on from wherever it lands
+    pub fn knock_back(&mut self, velocity: Vec3) {
+        self.knockback += velocity * Vec3::new(1.0, 0.0, 1.0);
+        self.vertical_velocity = self.vertical_velocity.max(velocity.y);
+        self.path.clear();
+    }
+}
+
+#[derive(Resource)]
+pub struct MobAssets {
+    animal: (Handle<Mesh>, Handle<StandardMaterial>),
+    enemy: (Handle<Mesh>, Handle<StandardMaterial>),
+}
+
+/// Random numbers for spawning and wandering, nothing here needs to be reproducible
+#[derive(Default)]
+pub struct MobRng(u64);
+
+impl MobRng {
+    fn next(&mut self) -> u64 {
+        self.0 = splitmix64(self.0);
+        self.0
+    }
+
+    fn range(&mut self, min: i32, max: i32) -> i32 {
+        min + (self.next() % (max - min + 1) as u64) as i32
+    }
+}
+
+fn is_solid(voxel_data: &VoxelData, cell: IVec3) -> bool {
+    voxel::is_solid(voxel_data.get_block(VoxelPos(cell)))
+}
+
+pub fn setup_mobs(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // meshes start at the feet, where the mob's transform is
+    let mut body = |kind: MobKind, color: Color| {
+        let mesh = meshes.add(Mesh::from(shape::Box {
+            min_x: -0.4,
+            max_x: 0.4,
+            min_y: 0.0,
+            max_y: kind.height() as f32 - 0.1,
+            min_z: -0.4,
+            max_z: 0.4,
+        }));
+        (mesh, materials.add(color.into()))
+    };
+    commands.insert_resource(MobAssets {
+        animal: body(MobKind::Animal, Color::rgb(0.9, 0.75, 0.7)),
+        enemy: body(MobKind::Enemy, Color::rgb(0.2, 0.55, 0.2)),
+    });
+}
+
+/// First cell a mob can stand in, going down a column from above the player
+fn find_ground(voxel_data: &VoxelData, x: i32, z: i32, from_y: i32, height: i32) -> Option<IVec3> {
+    let solid = |cell| is_solid(voxel_data, cell);
+    (from_y - SPAWN_SEARCH_HEIGHT..=from_y + SPAWN_SEARCH_HEIGHT)
+        .rev()
+        .map(|y| IVec3::new(x, y, z))
+        .find(|cell| can_stand(&solid, *cell, height))
+}
+
+/// Try a spawn at a random spot around the player: enemies where it's dark or at night,
+/// animals on grass under the open sky in daylight
+pub fn spawn_mobs(
+    mut spawner: MobSpawner,
+    voxel_data: Res<VoxelData>,
+    world_time: Res<WorldTime>,
+    time: Res<Time>,
+    player_query: Query<&Transform, With<Player>>,
+    mob_query: Query<&Mob>,
+    mut rng: Local<MobRng>,
+) {
+    if mob_query.iter().count() >= MAX_MOBS {
+        return;
+    }
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+    rng.0 ^= time.elapsed().as_nanos() as u64;
+    let player = player.translation.floor().as_ivec3();
+    let distance = rng.range(SPAWN_DISTANCE.0, SPAWN_DISTANCE.1) as f32;
+    let angle = rng.next() as f32 / u64::MAX as f32 * std::f32::consts::TAU;
+    let x = player.x + (angle.cos() * distance) as i32;
+    let z = player.z + (angle.sin() * distance) as i32;
+
+    // the taller kind, so a spot an enemy fits in fits either
+    let Some(cell) = find_ground(&voxel_data, x, z, player.y, MobKind::Enemy.height()) else {
+        return;
+    };
+    let dark = (cell.y..cell.y + DARK_COVER).any(|y| is_solid(&voxel_data, IVec3::new(x, y, z)));
+    let kind = if dark || world_time.is_night() {
+        MobKind::Enemy
+    } else if voxel_data.get_block(VoxelPos(cell - IVec3::Y)) == GRASS {
+        MobKind::Animal
+    } else {
+        return;
+    };
+    spawner.spawn(kind, cell.as_vec3() + Vec3::new(0.5, 0.0, 0.5));
+}
+
+/// Spawns mobs with their meshes
+#[derive(SystemParam)]
+pub struct MobSpawner<'w, 's> {
+    commands: Commands<'w, 's>,
+    mob_assets: Res<'w, MobAssets>,
+}
+
+impl MobSpawner<'_, '_> {
+    /// Spawn a mob standing at `position`
+    pub fn spawn(&mut self, kind: MobKind, position: Vec3) -> Entity {
+        spawn_mob(&mut self.commands, &self.mob_assets, kind, position)
+    }
+}
+
+/// Spawn a mob standing at `position`
+pub(crate) fn spawn_mob(
+    commands: &mut Commands,
+    mob_assets: &MobAssets,
+    kind: MobKind,
+    position: Vec3,
+) -> Entity {
+    let (mesh, material) = match kind {
+        MobKind::Animal => mob_assets.animal.clone(),
+        MobKind::Enemy => mob_assets.enemy.clone(),
+    };
+    commands
+        .spawn((
+            PbrBundle {
+                mesh,
+                material,
+                transform: Transform::from_translation(position),
+                ..default()
+            },
+            Mob {
+                kind,
+                vertical_velocity: 0.0,
+                knockback: Vec3::ZERO,
+                path: Vec::new(),
+                repath_in: 0.0,
+                attack_ready_in: 0.0,
+            },
+            Name::new(format!("{:?}", kind)),
+        ))
+        .id()
+}
+
+/// Pick where each mob walks next, the player for enemies close enough, a random spot otherwise
+pub fn update_mob_paths(
+    time: Res<Time>,
+    voxel_data: Res<VoxelData>,
+    player_query: Query<&Transform, With<Player>>,
+    mut mob_query: Query<(&mut Mob, &Transform)>,
+    mut rng: Local<MobRng>,
+) {
+    let player = player_query
+        .get_single()
+        .map(|transform| transform.translation)
+        .ok();
+    rng.0 ^= time.elapsed().as_nanos() as u64;
+    let solid = |cell| is_solid(&voxel_data, cell);
+    for (mut mob, transform) in mob_query.iter_mut() {
+        mob.repath_in -= time.delta_seconds();
+        if mob.repath_in > 0.0 {
+            continue;
+        }
+        mob.repath_in = mob.kind.repath_interval();
+        let position = transform.translation;
+        let start = position.floor().as_ivec3();
+        let chase = player.filter(|player| {
+            mob.kind == MobKind::Enemy && player.distance(position) <= CHASE_RANGE
+        });
+        let goal = match chase {
+            Some(player) => player.floor().as_ivec3(),
+            None => {
+                start
+                    + IVec3::new(
+                        rng.range(-WANDER_RANGE, WANDER_RANGE),
+                        0,
+                        rng.range(-WANDER_RANGE, WANDER_RANGE),
+                    )
+            }
+        };
+        let mut path =
+            find_path(solid, start, goal, mob.kind.height(), MAX_PATH_NODES).unwrap_or_default();
+        path.reverse();
+        mob.path = path;
+    }
+}
+
+/// Walk along the path, standing on the blocks below and falling where there are none
+pub fn move_mobs(
+    time: Res<Time>,
+    voxel_data: Res<VoxelData>,
+    mut mob_query: Query<(&mut Mob, &mut Transform)>,
+) {
+    let dt = time.delta_seconds();
+    for (mut mob, mut transform) in mob_query.iter_mut() {
+        let mut position = transform.translation;
+        if !voxel_data.is_loaded(voxel::get_chunk_index(&position)) {
+            continue;
+        }
+
+        if mob.knockback != Vec3::ZERO {
+            let thrown = position + mob.knockback * dt;
+            let feet = thrown.floor().as_ivec3();
+            if (0..mob.kind.height()).any(|dy| is_solid(&voxel_data, feet + IVec3::Y * dy)) {
+                mob.knockback = Vec3::ZERO;
+            } else {
+                position = thrown;
+                mob.knockback *= (1.0 - KNOCKBACK_DRAG * dt).max(0.0);
+                if mob.knockback.length() < 0.1 {
+                    mob.knockback = Vec3::ZERO;
+                }
+            }
+        } else if let Some(next) = mob.path.last().copied() {
+            let target = next.as_vec3() + Vec3::new(0.5, 0.0, 0.5);
+            let offset = (target - position) * Vec3::new(1.0, 0.0, 1.0);
+            let step = mob.kind.speed() * dt;
+            let moved = if offset.length() <= step {
+                mob.path.pop();
+                position + offset
+            } else {
+                position + offset.normalize() * step
+            };
+            // blocked by something placed after the path was found, look for another way
+            let feet = moved.floor().as_ivec3();
+            if (1..mob.kind.height()).any(|dy| is_solid(&voxel_data, feet + IVec3::Y * dy))
+                || (feet.y > next.y && is_solid(&voxel_data, feet))
+            {
+                mob.path.clear();
+            } else {
+                position = moved;
+                if offset.length() > f32::EPSILON {
+                    transform.look_to(offset.normalize(), Vec3::Y);
+                }
+            }
+        }
+
+        let feet = position.floor().as_ivec3();
+        if is_solid(&voxel_data, feet) {
+            // stepped into a block, climb on top of it
+            position.y = feet.y as f32 + 1.0;
+            mob.vertical_velocity = 0.0;
+        } else if !is_solid(&voxel_data, feet - IVec3::Y)
+            || position.y > feet.y as f32
+            || mob.vertical_velocity > 0.0
+        {
+            mob.vertical_velocity = (mob.vertical_velocity - GRAVITY * dt).max(-TERMINAL_VELOCITY);
+            position.y += mob.vertical_velocity * dt;
+            let landed = position.floor().as_ivec3();
+            if position.y < feet.y as f32 && is_solid(&voxel_data, landed) {
+                position.y = landed.y as f32 + 1.0;
+                mob.vertical_velocity = 0.0;
+            }
+        }
+        transform.translation = position;
+    }
+}
+
+/// Enemies next to the player hit them, once per `ATTACK_COOLDOWN`
+pub fn mob_attacks(
+    time: Res<Time>,
+    player_query: Query<&Transform, With<Player>>,
+    mut mob_query: Query<(&mut Mob, &Transform)>,
+    mut damage_events: EventWriter<Damage>,
+) {
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+    for (mut mob, transform) in mob_query.iter_mut() {
+        mob.attack_ready_in -= time.delta_seconds();
+        if mob.kind != MobKind::Enemy
+            || mob.attack_ready_in > 0.0
+            || transform.translation.distance(player.translation) > ATTACK_RANGE
+        {
+            continue;
+        }
+        mob.attack_ready_in = ATTACK_COOLDOWN;
+        damage_events.send(Damage {
+            amount: ATTACK_DAMAGE,
+            cause: "were killed by an enemy",
+        });
+    }
+}
@@ -0,0 +1,134 @@
+//! A* over the voxel grid for walking mobs. A node is a cell a mob stands in:
+//! free up to the mob's height, with a solid block below it. Each step moves
+//! one cell sideways, stepping up one block or dropping down a few.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use bevy::prelude::*;
+
+const MAX_STEP_UP: i32 = 1;
+const MAX_DROP: i32 = 3;
+const DIRECTIONS: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+
+/// Whether a mob `height` cells tall can stand in `cell`
+pub fn can_stand(solid: &impl Fn(IVec3) -> bool, cell: IVec3, height: i32) -> bool {
+    solid(cell - IVec3::Y) && (0..height).all(|dy| !solid(cell + IVec3::Y * dy))
+}
+
+/// Where a step from `cell` towards `direction` lands, if the mob can take it
+fn step(
+    solid: &impl Fn(IVec3) -> bool,
+    cell: IVec3,
+    direction: IVec3,
+    height: i32,
+) -> Option<IVec3> {
+    let column = cell + direction;
+    (-MAX_DROP..=MAX_STEP_UP)
+        .rev()
+        .map(|dy| column + IVec3::Y * dy)
+        .find(|target| can_stand(solid, *target, height))
+        .filter(|target| {
+            // room to pass over from one cell to the other at the height of the higher one
+            let top = target.y.max(cell.y) + height;
+            (cell.y..top).all(|y| !solid(IVec3::new(cell.x, y, cell.z)))
+                && (target.y..top).all(|y| !solid(IVec3::new(column.x, y, column.z)))
+        })
+}
+
+fn estimate(from: IVec3, to: IVec3) -> u32 {
+    // every step moves one cell sideways, so this never overestimates
+    ((from.x - to.x).abs() + (from.z - to.z).abs()) as u32
+}
+
+/// Cells to walk through from `start` (excluded) to `goal`. When the goal can't be reached
+/// within `max_nodes` the path leads to the closest cell found instead, None if that's `start`
+pub fn find_path(
+    solid: impl Fn(IVec3) -> bool,
+    start: IVec3,
+    goal: IVec3,
+    height: i32,
+    max_nodes: usize,
+) -> Option<Vec<IVec3>> {
+    let mut open = BinaryHeap::new(); // Reverse((estimated total, index into `cells`))
+    let mut cells = vec![start];
+    let mut cost: HashMap<IVec3, u32> = HashMap::from([(start, 0)]);
+    let mut came_from: HashMap<IVec3, IVec3> = HashMap::new();
+    let mut closest = (estimate(start, goal), start);
+    open.push(Reverse((estimate(start, goal), 0)));
+
+    while let Some(Reverse((_, index))) = open.pop() {
+        let cell = cells[index];
+        if cell == goal {
+            closest = (0, cell);
+            break;
+        }
+        if cells.len() >= max_nodes {
+            break;
+        }
+        let cell_cost = cost[&cell];
+        for direction in DIRECTIONS {
+            let Some(next) = step(&solid, cell, direction, height) else {
+                continue;
+            };
+            let next_cost = cell_cost + 1;
+            if cost.get(&next).is_some_and(|known| *known <= next_cost) {
+                continue;
+            }
+            cost.insert(next, next_cost);
+            came_from.insert(next, cell);
+            let remaining = estimate(next, goal) + (next.y - goal.y).unsigned_abs().min(1);
+            if remaining < closest.0 {
+                closest = (remaining, next);
+            }
+            open.push(Reverse((next_cost + estimate(next, goal), cells.len())));
+            cells.push(next);
+        }
+    }
+
+    let (_, mut cell) = closest;
+    let mut path = Vec::new();
+    while cell != start {
+        path.push(cell);
+        cell = came_from[&cell];
+    }
+    path.reverse();
+    (!path.is_empty()).then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ground(cell: IVec3) -> bool {
+        cell.y < 0
+    }
+
+    #[test]
+    fn walks_straight_over_flat_ground() {
+        let path = find_path(ground, IVec3::ZERO, IVec3::new(3, 0, 0), 2, 256).unwrap();
+        assert_eq!(path.len(), 3);
+        assert_eq!(path.last(), Some(&IVec3::new(3, 0, 0)));
+    }
+
+    #[test]
+    fn goes_around_a_wall_and_climbs_a_step() {
+        // a wall at x = 1 except for z = 2, and a one block step at x = 3
+        let world = |cell: IVec3| {
+            cell.y < 0 || (cell.x == 1 && cell.z != 2 && cell.y < 3) || (cell.x >= 3 && cell.y < 1)
+        };
+        let path = find_path(world, IVec3::ZERO, IVec3::new(4, 1, 0), 2, 256).unwrap();
+        assert_eq!(path.last(), Some(&IVec3::new(4, 1, 0)));
+        assert!(path.contains(&IVec3::new(1, 0, 2)));
+        assert!(path.iter().all(|cell| can_stand(&world, *cell, 2)));
+    }
+
+    #[test]
+    fn heads_for_the_closest_cell_when_walled_off() {
+        let world = |cell: IVec3| cell.y < 0 || (cell.x == 2 && cell.y < 5);
+        let path = find_path(world, IVec3::ZERO, IVec3::new(5, 0, 0), 2, 64).unwrap();
+        assert_eq!(path.last().map(|cell| cell.x), Some(1));
+    }
+}
@@ -0,0 +1,145 @@
+//! Optional rapier physics, behind the `rapier` feature: every column mesh gets a fixed
+//! collider built from its voxels, so rigid bodies can rest on and bounce off the terrain.
+//! The collider is a compound of boxes, one per vertical run of solid blocks that touch a
+//! non-solid one, and it's rebuilt whenever the column is remeshed. Dropped items, falling
+//! blocks and mobs keep their own voxel physics, rigid bodies are up to whoever spawns them.
+
+use bevy::prelude::*;
+
+/// Adds rapier and the terrain colliders when built with the `rapier` feature
+#[derive(Default)]
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, _app: &mut App) {
+        #[cfg(feature = "rapier")]
+        terrain::build(_app);
+    }
+}
+
+#[cfg(feature = "rapier")]
+mod terrain {
+    use bevy::prelude::*;
+    use bevy_rapier3d::prelude::*;
+
+    use crate::{
+        update_column_meshes,
+        voxel::{self, ChunkIndex, ColumnMesh, VoxelData, VoxelPos, CHUNK_SIZE, HEIGHT_LIMIT},
+    };
+
+    pub fn build(app: &mut App) {
+        app.add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
+            .add_systems(Update, update_terrain_colliders.after(update_column_meshes));
+    }
+
+    /// A box of the collider: its center relative to the column and its height in blocks
+    type Run = (Vec3, f32);
+
+    /// Vertical runs of solid blocks with a side open to a non-solid block, the ones a body
+    /// can touch. Chunks that aren't loaded have none
+    pub(super) fn exposed_runs(voxel_data: &VoxelData, origin: IVec3) -> Vec<Run> {
+        let solid = |pos: IVec3| voxel::is_solid(voxel_data.get_block(VoxelPos(pos)));
+        let exposed = |pos: IVec3| {
+            solid(pos)
+                && [
+                    IVec3::X,
+                    IVec3::NEG_X,
+                    IVec3::Y,
+                    IVec3::NEG_Y,
+                    IVec3::Z,
+                    IVec3::NEG_Z,
+                ]
+                .into_iter()
+                .any(|offset| !solid(pos + offset))
+        };
+        let mut runs = Vec::new();
+        for x in 0..CHUNK_SIZE as i32 {
+            for z in 0..CHUNK_SIZE as i32 {
+                let mut start = None;
+                for y in 0..=HEIGHT_LIMIT as i32 {
+                    let pos = origin + IVec3::new(x, y, z);
+                    let loaded = voxel_data.chunks.contains_key(&VoxelPos(pos).chunk());
+                    match (start, y < HEIGHT_LIMIT as i32 && loaded && exposed(pos)) {
+                        (None, true) => start = Some(y),
+                        (Some(bottom), false) => {
+                            let height = (y - bottom) as f32;
+                            let center = Vec3::new(x as f32, bottom as f32, z as f32)
+                                + Vec3::new(0.5, height / 2.0, 0.5);
+                            runs.push((center, height));
+                            start = None;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        runs
+    }
+
+    /// Rebuild the collider of each column that was just remeshed, the mesh handle is
+    /// inserted again every time
+    fn update_terrain_colliders(
+        mut commands: Commands,
+        voxel_data: Res<VoxelData>,
+        column_query: Query<(Entity, &ColumnMesh), Changed<Handle<Mesh>>>,
+    ) {
+        for (entity, column_mesh) in column_query.iter() {
+            let origin = ChunkIndex {
+                x: column_mesh.column.x,
+                y: 0,
+                z: column_mesh.column.z,
+            }
+            .origin();
+            let boxes: Vec<_> = exposed_runs(&voxel_data, origin)
+                .into_iter()
+                .map(|(center, height)| {
+                    (
+                        center,
+                        Quat::IDENTITY,
+                        Collider::cuboid(0.5, height / 2.0, 0.5),
+                    )
+                })
+                .collect();
+            let mut entity = commands.entity(entity);
+            if boxes.is_empty() {
+                entity.remove::<(RigidBody, Collider)>();
+            } else {
+                entity.insert((RigidBody::Fixed, Collider::compound(boxes)));
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::voxel::STONE;
+
+        #[test]
+        fn buried_blocks_get_no_boxes() {
+            let mut voxel_data = VoxelData::air([ChunkIndex { x: 0, y: 0, z: 0 }]);
+            for x in 4..7 {
+                for y in 8..11 {
+                    for z in 4..7 {
+                        voxel_data.set_block(VoxelPos::new(x, y, z), STONE);
+                    }
+                }
+            }
+            let runs = exposed_runs(&voxel_data, IVec3::ZERO);
+            let middle: Vec<_> = runs
+                .iter()
+                .filter(|(center, _)| center.x == 5.5 && center.z == 5.5)
+                .collect();
+            assert_eq!(
+                middle,
+                [
+                    &(Vec3::new(5.5, 8.5, 5.5), 1.0),
+                    &(Vec3::new(5.5, 10.5, 5.5), 1.0)
+                ]
+            );
+            let corner = runs
+                .iter()
+                .find(|(center, _)| center.x == 4.5 && center.z == 4.5);
+            assert_eq!(corner, Some(&(Vec3::new(4.5, 9.5, 4.5), 3.0)));
+        }
+    }
+}
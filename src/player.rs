@@ -0,0 +1,234 @@
+//! The player's body: a blocky model standing where the camera's eye is, the
+//! position gameplay (mobs, items, damage) goes by. F5 switches between
+//! looking through its eyes and an orbit camera behind it. While the free
+//! camera flies around, the body stays where it was left.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use smooth_bevy_cameras::{
+    controllers::fps::{ControlEvent, FpsCameraController},
+    LookTransform,
+};
+
+use crate::{
+    free_camera::FreeCamera,
+    game_mode::GameMode,
+    input::{InputAction, PlayerInput},
+    swimming::{Underwater, SINK_SPEED, SWIM_UP_SPEED, WATER_DRAG},
+    voxel::{self, VoxelData, VoxelPos, AIR},
+};
+
+pub const EYE_HEIGHT: f32 = 1.6; // from the player's feet to the camera
+const THIRD_PERSON_DISTANCE: f32 = 4.0; // blocks behind the eye
+const CAMERA_CLEARANCE: f32 = 0.2; // kept between the camera and a block it's pulled in by
+const CAMERA_PROBE_STEP: f32 = 0.1;
+const GRAVITY: f32 = 25.0; // blocks per second squared
+const TERMINAL_VELOCITY: f32 = 50.0;
+const JUMP_SPEED: f32 = 8.0;
+const STEP_SPEED: f32 = 6.0; // climbing out of a block the feet ended up in
+
+/// Marks the player model, its transform is at the feet and turned the way the player looks
+#[derive(Component)]
+pub struct Player;
+
+#[derive(Resource, Default, Debug)]
+pub struct CameraMode {
+    pub third_person: bool,
+    distance: f32, // from the eye back to the camera, 0 in first person
+}
+
+impl CameraMode {
+    /// Where the player looks from, the camera being behind it in third person
+    pub fn eye(&self, camera: &GlobalTransform) -> Vec3 {
+        camera.translation() + camera.forward() * self.distance
+    }
+}
+
+pub fn setup_player(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let model = spawn_model(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        Color::rgb(0.2, 0.4, 0.8),
+    );
+    commands.entity(model).insert((Player, Name::new("Player")));
+}
+
+/// Spawn a hidden player model with its feet at its transform, in a shirt of `color`
+pub(crate) fn spawn_model(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    color: Color,
+) -> Entity {
+    let mut part = |min: Vec3, max: Vec3, color: Color| PbrBundle {
+        mesh: meshes.add(Mesh::from(shape::Box {
+            min_x: min.x,
+            max_x: max.x,
+            min_y: min.y,
+            max_y: max.y,
+            min_z: min.z,
+            max_z: max.z,
+        })),
+        material: materials.add(color.into()),
+        ..default()
+    };
+    let body = part(
+        Vec3::new(-0.3, 0.0, -0.15),
+        Vec3::new(0.3, 1.4, 0.15),
+        color,
+    );
+    let head = part(
+        Vec3::new(-0.25, 1.4, -0.25),
+        Vec3::new(0.25, 1.9, 0.25),
+        Color::rgb(0.9, 0.7, 0.55),
+    );
+    commands
+        .spawn(SpatialBundle {
+            visibility: Visibility::Hidden,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(body);
+            parent.spawn(head);
+        })
+        .id()
+}
+
+pub fn toggle_camera_mode(player_input: Res<PlayerInput>, mut camera_mode: ResMut<CameraMode>) {
+    if player_input.actions.just_pressed(InputAction::CameraView) {
+        camera_mode.third_person = !camera_mode.third_person;
+    }
+}
+
+/// How far the camera can back away from the eye before a block gets in between
+fn camera_distance(voxel_data: &VoxelData, eye: Vec3, back: Vec3) -> f32 {
+    let mut distance = 0.0;
+    while distance < THIRD_PERSON_DISTANCE {
+        let next = distance + CAMERA_PROBE_STEP;
+        if voxel_data.get_block(VoxelPos::from_world(eye + back * next)) != AIR {
+            return (distance - CAMERA_CLEARANCE).max(0.0);
+        }
+        distance = next;
+    }
+    THIRD_PERSON_DISTANCE
+}
+
+/// Stand the model under the eye the camera controller moved to, then back the camera away
+/// from it in third person. Runs after the controller wrote this frame's camera transform
+pub fn update_player(
+    voxel_data: Res<VoxelData>,
+    free_camera: Res<FreeCamera>,
+    mut camera_mode: ResMut<CameraMode>,
+    mut camera_query: Query<&mut Transform, (With<FpsCameraController>, Without<Player>)>,
+    mut player_query: Query<(&mut Transform, &mut Visibility), With<Player>>,
+) {
+    let (Ok(mut camera), Ok((mut player, mut visibility))) =
+        (camera_query.get_single_mut(), player_query.get_single_mut())
+    else {
+        return;
+    };
+    if free_camera.active() {
+        camera_mode.distance = 0.0;
+        if *visibility != Visibility::Inherited {
+            *visibility = Visibility::Inherited;
+        }
+        return;
+    }
+    let eye = camera.translation;
+    let forward = camera.forward();
+    player.translation = eye - Vec3::Y * EYE_HEIGHT;
+    let facing = Vec3::new(forward.x, 0.0, forward.z);
+    if facing.length_squared() > f32::EPSILON {
+        player.look_to(facing.normalize(), Vec3::Y);
+    }
+
+    let distance = if camera_mode.third_person {
+        camera_distance(&voxel_data, eye, -forward)
+    } else {
+        0.0
+    };
+    camera.translation = eye - forward * distance;
+    camera_mode.distance = distance;
+    let shown = if camera_mode.third_person {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    if *visibility != shown {
+        *visibility = shown;
+    }
+}
+
+/// The eye of the camera controller in use, and the events that move it
+#[derive(SystemParam)]
+pub struct EyeControl<'w, 's> {
+    camera_query: Query<'w, 's, (&'static FpsCameraController, &'static LookTransform)>,
+    control_events: EventWriter<'w, ControlEvent>,
+}
+
+impl EyeControl<'_, '_> {
+    /// Where the enabled controller's eye is, None while none is enabled
+    fn eye(&self) -> Option<Vec3> {
+        self.camera_query
+            .iter()
+            .find(|(controller, _)| controller.enabled)
+            .map(|(_, look)| look.eye)
+    }
+
+    /// Move the eye, by `translation` per second of the frame
+    fn translate(&mut self, translation: Vec3) {
+        self.control_events
+            .send(ControlEvent::TranslateEye(translation));
+    }
+}
+
+/// Survival players walk instead of flying: they fall onto the ground, jump off it and
+/// climb out of the blocks they walk into, and swim in water. Nothing falls into chunks
+/// still loading
+pub fn player_gravity(
+    time: Res<Time>,
+    game_mode: Res<GameMode>,
+    voxel_data: Res<VoxelData>,
+    player_input: Res<PlayerInput>,
+    underwater: Res<Underwater>,
+    mut eye_control: EyeControl,
+    mut vertical_speed: Local<f32>,
+) {
+    let Some(eye) = eye_control.eye() else {
+        return;
+    };
+    let feet = eye - Vec3::Y * EYE_HEIGHT;
+    if *game_mode != GameMode::Survival || !voxel_data.is_loaded(voxel::get_chunk_index(&feet)) {
+        *vertical_speed = 0.0;
+        return;
+    }
+    let solid = |point: Vec3| voxel::is_solid(voxel_data.get_block(VoxelPos::from_world(point)));
+    let dt = time.delta_seconds();
+    *vertical_speed = if solid(feet + Vec3::Y * 0.1) {
+        STEP_SPEED
+    } else if underwater.0 {
+        // buoyant, sinking slowly unless swimming up
+        let target = if player_input.actions.pressed(InputAction::Jump) {
+            SWIM_UP_SPEED
+        } else {
+            -SINK_SPEED
+        };
+        *vertical_speed + (target - *vertical_speed) * (WATER_DRAG * dt).min(1.0)
+    } else if solid(feet - Vec3::Y * 0.05) && *vertical_speed <= 0.0 {
+        if player_input.actions.pressed(InputAction::Jump) {
+            JUMP_SPEED
+        } else {
+            0.0
+        }
+    } else {
+        (*vertical_speed - GRAVITY * dt).max(-TERMINAL_VELOCITY)
+    };
+    if *vertical_speed != 0.0 {
+        // the controller scales it by the frame time
+        eye_control.translate(Vec3::Y * *vertical_speed);
+    }
+}
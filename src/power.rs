@@ -0,0 +1,222 @@
+//! Circuits: levers and buttons power the wire around them, the signal gets
+//! one weaker with every wire it runs through, and lamps next to powered wire
+//! or a switch that's on light up. Every tick the power of each wire is worked
+//! out again from the switches, and wire and lamps switch to their powered
+//! block when it changes. The power level of a wire is kept in its block state,
+//! so it's saved with the chunk.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use bevy::prelude::*;
+
+use crate::{
+    voxel::{
+        BlockId, BlockState, ChunkIndex, VoxelData, VoxelLocalIndex, VoxelModifyQueue, VoxelPos,
+        BUTTON, BUTTON_ON, LAMP, LAMP_ON, LEVER_ON, POWER_MASK, POWER_SHIFT, WIRE, WIRE_ON,
+    },
+    BlockChanged,
+};
+
+pub const MAX_POWER: u8 = 15; // of wire next to a switch that's on
+const BUTTON_TICKS: u32 = 10; // ticks a pressed button stays on
+
+const NEIGHBOURS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+fn is_circuit_part(block: BlockId) -> bool {
+    (WIRE..=LAMP_ON).contains(&block)
+}
+
+/// Power level of the wire at `position`, 0 for anything else
+pub fn power_at(voxel_data: &VoxelData, position: VoxelPos) -> u8 {
+    match voxel_data.get_block(position) {
+        WIRE_ON => ((voxel_data.get_state(position) & POWER_MASK) >> POWER_SHIFT) as u8,
+        _ => 0,
+    }
+}
+
+/// `state` with the power level bits set to `level`
+fn with_power(state: BlockState, level: u8) -> BlockState {
+    (state & !POWER_MASK) | (((level as BlockState) << POWER_SHIFT) & POWER_MASK)
+}
+
+/// Circuit parts in the loaded world
+#[derive(Resource, Default)]
+pub struct Circuits {
+    parts: HashSet<VoxelPos>,
+    scanned: HashSet<ChunkIndex>, // loaded chunks whose parts are in `parts`
+    buttons: HashMap<VoxelPos, u32>, // pressed buttons, ticks until they pop back out
+}
+
+impl Circuits {
+    /// Switch the button at `position` on, for `BUTTON_TICKS`
+    pub(crate) fn press(&mut self, position: VoxelPos, voxel_modify_queue: &mut VoxelModifyQueue) {
+        voxel_modify_queue.queue.push((position, BUTTON_ON));
+        self.buttons.insert(position, BUTTON_TICKS);
+    }
+}
+
+/// Keep track of where circuit parts are, from edits and newly loaded chunks
+pub fn track_circuits(
+    voxel_data: Res<VoxelData>,
+    mut changed_events: EventReader<BlockChanged>,
+    mut circuits: ResMut<Circuits>,
+) {
+    for event in changed_events.iter() {
+        if is_circuit_part(event.block) {
+            circuits.parts.insert(event.position);
+        } else {
+            circuits.parts.remove(&event.position);
+        }
+    }
+
+    let unloaded = circuits
+        .scanned
+        .iter()
+        .any(|index| !voxel_data.is_loaded(*index));
+    if unloaded {
+        let circuits = &mut *circuits;
+        circuits
+            .scanned
+            .retain(|index| voxel_data.is_loaded(*index));
+        circuits
+            .parts
+            .retain(|position| voxel_data.is_loaded(position.split().0));
+    }
+    for chunk in voxel_data.iter_chunks() {
+        if !circuits.scanned.insert(chunk.index) {
+            continue;
+        }
+        for (x, plane) in chunk.voxels.iter().enumerate() {
+            for (y, row) in plane.iter().enumerate() {
+                for (z, block) in row.iter().enumerate() {
+                    if !is_circuit_part(*block) {
+                        continue;
+                    }
+                    let local = VoxelLocalIndex {
+                        x: x as u8,
+                        y: y as u8,
+                        z: z as u8,
+                    };
+                    circuits
+                        .parts
+                        .insert(VoxelPos::from_parts(chunk.index, local));
+                }
+            }
+        }
+    }
+}
+
+/// Let go of buttons pressed long enough, spread power from the switches through the wire
+/// and switch wire and lamps to match, with the level of each wire in its state
+pub fn power_tick(
+    voxel_data: Res<VoxelData>,
+    mut circuits: ResMut<Circuits>,
+    mut voxel_modify_queue: ResMut<VoxelModifyQueue>,
+) {
+    let circuits = &mut *circuits;
+    circuits.buttons.retain(|position, ticks| {
+        *ticks = ticks.saturating_sub(1);
+        if *ticks > 0 {
+            return true;
+        }
+        if voxel_data.get_block(*position) == BUTTON_ON {
+            voxel_modify_queue.queue.push((*position, BUTTON));
+        }
+        false
+    });
+
+    let is_on = |block: BlockId| block == LEVER_ON || block == BUTTON_ON;
+    let is_wire = |block: BlockId| block == WIRE || block == WIRE_ON;
+    // breadth first from the switches, so every wire gets the strongest signal reaching it
+    let mut power = HashMap::new();
+    let mut queue = VecDeque::new();
+    for position in circuits.parts.iter() {
+        if !is_on(voxel_data.get_block(*position)) {
+            continue;
+        }
+        for offset in NEIGHBOURS {
+            let neighbour = VoxelPos(position.0 + offset);
+            if is_wire(voxel_data.get_block(neighbour)) && !power.contains_key(&neighbour) {
+                power.insert(neighbour, MAX_POWER);
+                queue.push_back(neighbour);
+            }
+        }
+    }
+    while let Some(position) = queue.pop_front() {
+        let level = power[&position] - 1;
+        if level == 0 {
+            continue;
+        }
+        for offset in NEIGHBOURS {
+            let neighbour = VoxelPos(position.0 + offset);
+            if is_wire(voxel_data.get_block(neighbour)) && !power.contains_key(&neighbour) {
+                power.insert(neighbour, level);
+                queue.push_back(neighbour);
+            }
+        }
+    }
+
+    for position in circuits.parts.iter() {
+        let block = voxel_data.get_block(*position);
+        let wanted = match block {
+            WIRE | WIRE_ON if power.contains_key(position) => WIRE_ON,
+            WIRE | WIRE_ON => WIRE,
+            LAMP | LAMP_ON => {
+                let lit = NEIGHBOURS.iter().any(|offset| {
+                    let neighbour = VoxelPos(position.0 + *offset);
+                    power.contains_key(&neighbour) || is_on(voxel_data.get_block(neighbour))
+                });
+                if lit {
+                    LAMP_ON
+                } else {
+                    LAMP
+                }
+            }
+            _ => block,
+        };
+        if wanted != block {
+            voxel_modify_queue.queue.push((*position, wanted));
+        }
+        // a new block starts without a state, the level goes on after it
+        let state = if wanted == block {
+            voxel_data.get_state(*position)
+        } else {
+            0
+        };
+        let level = power.get(position).copied().unwrap_or(0);
+        if wanted == WIRE_ON && with_power(state, level) != state {
+            voxel_modify_queue
+                .states
+                .push((*position, with_power(state, level)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wire_keeps_its_power_level_in_its_state() {
+        let mut voxel_data = VoxelData::air([ChunkIndex { x: 0, y: 0, z: 0 }]);
+        let position = VoxelPos::new(1, 2, 3);
+        voxel_data.set_block(position, WIRE_ON);
+        voxel_data.set_state(position, with_power(0, 7));
+        assert_eq!(power_at(&voxel_data, position), 7);
+        // the turns stay as they were
+        assert_eq!(
+            with_power(with_power(0b11, MAX_POWER), 2),
+            with_power(0b11, 2)
+        );
+
+        voxel_data.set_block(position, WIRE);
+        assert_eq!(power_at(&voxel_data, position), 0);
+    }
+}
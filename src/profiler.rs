@@ -0,0 +1,37 @@
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticId, RegisterDiagnostic},
+    prelude::*,
+    utils::Instant,
+};
+
+/// Adds timing diagnostics for chunk generation, meshing and mesh upload
+#[derive(Default)]
+pub struct ProfilerDiagnosticsPlugin;
+
+impl Plugin for ProfilerDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        for (name, id) in PROFILED {
+            app.register_diagnostic(Diagnostic::new(id, name, 60).with_suffix("ms"));
+        }
+    }
+}
+
+impl ProfilerDiagnosticsPlugin {
+    pub const CHUNK_GEN: DiagnosticId =
+        DiagnosticId::from_u128(5593967653324785649127573436050338700);
+    pub const MESHING: DiagnosticId =
+        DiagnosticId::from_u128(15220949864891958339081302494988614392);
+    pub const MESH_UPLOAD: DiagnosticId =
+        DiagnosticId::from_u128(7333737950833648291384774550454220301);
+}
+
+/// Diagnostics shown in the stats text, in display order
+pub(crate) const PROFILED: [(&str, DiagnosticId); 3] = [
+    ("chunk_gen", ProfilerDiagnosticsPlugin::CHUNK_GEN),
+    ("meshing", ProfilerDiagnosticsPlugin::MESHING),
+    ("mesh_upload", ProfilerDiagnosticsPlugin::MESH_UPLOAD),
+];
+
+pub(crate) fn elapsed_ms(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
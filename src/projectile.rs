@@ -0,0 +1,232 @@
+//! Projectiles: thrown snowballs fly on an arc, swept against the voxels and
+//! the mobs every frame so even fast ones can't pass through anything. What
+//! they hit is sent as a `ProjectileHit` for other systems to act on: a
+//! snowball knocks a mob back and puts out a fire.
+
+use bevy::prelude::*;
+use smooth_bevy_cameras::controllers::fps::FpsCameraController;
+
+use crate::{
+    game_mode::GameMode,
+    input::{InputAction, PlayerInput},
+    item::{HeldItem, PlayerItems},
+    mob::Mob,
+    player::CameraMode,
+    target_voxel,
+    voxel::{self, VoxelData, VoxelModifyQueue, VoxelPos, AIR, FIRE, SNOW},
+};
+
+const GRAVITY: f32 = 20.0;
+const MAX_AGE: f32 = 10.0; // seconds before a projectile that hit nothing is gone
+const MOB_HALF_WIDTH: f32 = 0.4;
+const SNOWBALL_SPEED: f32 = 24.0; // blocks per second, thrown
+const SNOWBALL_RADIUS: f32 = 0.12;
+const SNOWBALL_KNOCKBACK: f32 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectileKind {
+    Snowball,
+}
+
+#[derive(Component, Debug)]
+pub struct Projectile {
+    pub kind: ProjectileKind,
+    velocity: Vec3, // blocks per second
+    age: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HitTarget {
+    Block { position: VoxelPos, normal: IVec3 }, // normal of the face hit, zero from inside
+    Mob(Entity),
+}
+
+/// A projectile hit something and is gone
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ProjectileHit {
+    pub kind: ProjectileKind,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub target: HitTarget,
+}
+
+#[derive(Resource)]
+pub struct ProjectileAssets {
+    snowball: (Handle<Mesh>, Handle<StandardMaterial>),
+}
+
+pub fn setup_projectiles(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let mesh = meshes.add(Mesh::from(shape::UVSphere {
+        radius: SNOWBALL_RADIUS,
+        sectors: 8,
+        stacks: 6,
+    }));
+    commands.insert_resource(ProjectileAssets {
+        snowball: (mesh, materials.add(Color::rgb(0.95, 0.97, 1.0).into())),
+    });
+}
+
+/// Distance along the segment from `start` to where it enters the box, None if it misses it
+fn segment_hits_box(start: Vec3, segment: Vec3, min: Vec3, max: Vec3) -> Option<f32> {
+    let length = segment.length();
+    if length == 0.0 {
+        return None;
+    }
+    let direction = segment / length;
+    let near = (min - start) / direction;
+    let far = (max - start) / direction;
+    let enter = near.min(far).max_element().max(0.0);
+    let exit = near.max(far).min_element();
+    (enter <= exit && enter <= length).then_some(enter)
+}
+
+/// Throw a snowball where the player looks with the place button, using one up in survival
+pub fn throw_snowball(
+    mut commands: Commands,
+    player_input: Res<PlayerInput>,
+    held_item: Res<HeldItem>,
+    camera_mode: Res<CameraMode>,
+    projectile_assets: Res<ProjectileAssets>,
+    mut items: PlayerItems,
+    camera_query: Query<&GlobalTransform, With<FpsCameraController>>,
+) {
+    if !player_input.actions.just_pressed(InputAction::Place)
+        || *held_item != HeldItem::Snowball
+        || *items.game_mode == GameMode::Spectator
+    {
+        return;
+    }
+    let Ok(transform) = camera_query.get_single() else {
+        return;
+    };
+    if !items.take(SNOW, 1) {
+        return;
+    }
+    let forward = transform.forward();
+    let (mesh, material) = projectile_assets.snowball.clone();
+    commands.spawn((
+        PbrBundle {
+            mesh,
+            material,
+            transform: Transform::from_translation(camera_mode.eye(transform) + forward * 0.5),
+            ..default()
+        },
+        Projectile {
+            kind: ProjectileKind::Snowball,
+            velocity: forward * SNOWBALL_SPEED,
+            age: 0.0,
+        },
+        Name::new("Snowball"),
+    ));
+}
+
+/// Move the projectiles along their arcs, sending a hit for the first block or mob each one
+/// runs into this frame. Nothing moves in chunks still loading
+pub fn update_projectiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    voxel_data: Res<VoxelData>,
+    mob_query: Query<(Entity, &Transform, &Mob), Without<Projectile>>,
+    mut projectile_query: Query<(Entity, &mut Projectile, &mut Transform)>,
+    mut hit_events: EventWriter<ProjectileHit>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut projectile, mut transform) in projectile_query.iter_mut() {
+        let start = transform.translation;
+        if !voxel_data.is_loaded(voxel::get_chunk_index(&start)) {
+            continue;
+        }
+        projectile.age += dt;
+        projectile.velocity.y -= GRAVITY * dt;
+        let segment = projectile.velocity * dt;
+
+        let direction = segment.normalize_or_zero();
+        let block_hit = target_voxel(&voxel_data, start, direction, segment.length()).map(
+            |(position, previous)| {
+                let min = position.to_world();
+                let distance = segment_hits_box(start, segment, min, min + Vec3::ONE);
+                let target = HitTarget::Block {
+                    position,
+                    normal: previous.0 - position.0,
+                };
+                (distance.unwrap_or(0.0), target)
+            },
+        );
+        let mob_hit = mob_query
+            .iter()
+            .filter_map(|(mob_entity, mob_transform, mob)| {
+                let feet = mob_transform.translation;
+                let half = Vec3::new(MOB_HALF_WIDTH, 0.0, MOB_HALF_WIDTH);
+                let top = Vec3::Y * mob.kind.height() as f32;
+                let distance = segment_hits_box(start, segment, feet - half, feet + half + top)?;
+                Some((distance, HitTarget::Mob(mob_entity)))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        let hit = match (block_hit, mob_hit) {
+            (Some(block), Some(mob)) => Some(if mob.0 < block.0 { mob } else { block }),
+            (block, mob) => block.or(mob),
+        };
+
+        match hit {
+            Some((distance, target)) => {
+                hit_events.send(ProjectileHit {
+                    kind: projectile.kind,
+                    position: start + direction * distance,
+                    velocity: projectile.velocity,
+                    target,
+                });
+                commands.entity(entity).despawn_recursive();
+            }
+            None if projectile.age > MAX_AGE => commands.entity(entity).despawn_recursive(),
+            None => transform.translation += segment,
+        }
+    }
+}
+
+/// Snowballs knock mobs back and put out the fires they land in
+pub fn snowball_hits(
+    voxel_data: Res<VoxelData>,
+    mut hit_events: EventReader<ProjectileHit>,
+    mut voxel_modify_queue: ResMut<VoxelModifyQueue>,
+    mut mob_query: Query<&mut Mob>,
+) {
+    for hit in hit_events.iter() {
+        if hit.kind != ProjectileKind::Snowball {
+            continue;
+        }
+        match hit.target {
+            HitTarget::Mob(entity) => {
+                if let Ok(mut mob) = mob_query.get_mut(entity) {
+                    mob.knock_back(hit.velocity.normalize_or_zero() * SNOWBALL_KNOCKBACK);
+                }
+            }
+            HitTarget::Block { position, .. } => {
+                if voxel_data.get_block(position) == FIRE {
+                    voxel_modify_queue.queue.push((position, AIR));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_hit_boxes_where_they_enter() {
+        let (min, max) = (Vec3::new(2.0, 0.0, -0.5), Vec3::new(3.0, 2.0, 0.5));
+        let start = Vec3::new(0.0, 1.0, 0.0);
+        assert_eq!(segment_hits_box(start, Vec3::X * 4.0, min, max), Some(2.0));
+        // stops short of the box, or passes over it
+        assert_eq!(segment_hits_box(start, Vec3::X * 1.5, min, max), None);
+        assert_eq!(
+            segment_hits_box(start + Vec3::Y * 2.0, Vec3::X * 4.0, min, max),
+            None
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! Keybinding hints at the top of the screen, showing only the keys that do
+//! something right now, e.g. mining and placing while looking at a block.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    console::Console,
+    game_mode::GameMode,
+    input::{InputAction, KeyBindings},
+    item::HeldItem,
+    menu::PauseMenu,
+    voxel::{self, BlockTag},
+    CrosshairTarget, MouseSettings,
+};
+
+const SEPARATOR: &str = "  —  ";
+
+/// Something the player can do, with the key bound to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Mine,
+    Place,
+    Till,
+    Plant,
+    Eat,
+    Throw,
+    Ignite,
+    Use,
+    NextItem,
+    SelectCorners,
+    ControlMode,
+    UiMode,
+    Console,
+    Menu,
+    DropWaypoint,
+    Screenshot,
+    CameraView,
+}
+
+impl Action {
+    fn input_actions(self) -> &'static [InputAction] {
+        match self {
+            Action::Mine => &[InputAction::Break],
+            Action::Place
+            | Action::Till
+            | Action::Plant
+            | Action::Eat
+            | Action::Throw
+            | Action::Ignite
+            | Action::Use => &[InputAction::Place],
+            Action::NextItem => &[InputAction::NextItem],
+            Action::SelectCorners => &[
+                InputAction::SelectFirstCorner,
+                InputAction::SelectSecondCorner,
+            ],
+            Action::ControlMode | Action::UiMode => &[InputAction::ToggleUiMode],
+            Action::Console => &[InputAction::Console],
+            Action::Menu => &[InputAction::Menu],
+            Action::DropWaypoint => &[InputAction::DropWaypoint],
+            Action::Screenshot => &[InputAction::Screenshot],
+            Action::CameraView => &[InputAction::CameraView],
+        }
+    }
+
+    /// Keys or buttons bound to the action, as shown to the player
+    pub fn key(self, key_bindings: &KeyBindings) -> String {
+        self.input_actions()
+            .iter()
+            .map(|action| key_bindings.get(*action).to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Mine => "mine",
+            Action::Place => "place",
+            Action::Till => "till",
+            Action::Plant => "plant",
+            Action::Eat => "eat",
+            Action::Throw => "throw",
+            Action::Ignite => "light a fire",
+            Action::Use => "use",
+            Action::NextItem => "next item",
+            Action::SelectCorners => "select corners",
+            Action::ControlMode => "control mode",
+            Action::UiMode => "UI mode",
+            Action::Console => "console",
+            Action::Menu => "menu",
+            Action::DropWaypoint => "waypoint",
+            Action::Screenshot => "screenshot",
+            Action::CameraView => "camera view",
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct KeybindHints;
+
+pub fn setup_keybind_hints(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Row,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                KeybindHints,
+            ));
+        });
+}
+
+/// The actions that make sense in the current state, most specific first
+fn relevant_actions(
+    ui_mode: bool,
+    game_mode: GameMode,
+    held_item: HeldItem,
+    target: Option<voxel::BlockId>,
+) -> Vec<Action> {
+    if ui_mode {
+        return vec![Action::ControlMode, Action::Console, Action::Menu];
+    }
+    let mut actions = Vec::new();
+    if held_item == HeldItem::Food && game_mode == GameMode::Survival {
+        actions.push(Action::Eat);
+    }
+    if held_item == HeldItem::Snowball {
+        actions.push(Action::Throw);
+    }
+    match target {
+        Some(block) => {
+            if !voxel::has_tag(block, BlockTag::Unbreakable) {
+                actions.push(Action::Mine);
+            }
+            match held_item {
+                _ if voxel::on_use(block).is_some() => actions.push(Action::Use),
+                HeldItem::Block => actions.push(Action::Place),
+                HeldItem::Hoe if voxel::has_tag(block, BlockTag::DirtLike) => {
+                    actions.push(Action::Till)
+                }
+                HeldItem::Seeds if block == voxel::FARMLAND => actions.push(Action::Plant),
+                HeldItem::FlintAndSteel => actions.push(Action::Ignite),
+                _ => {}
+            }
+            if game_mode == GameMode::Creative {
+                actions.push(Action::SelectCorners);
+            }
+        }
+        None => actions.extend([Action::DropWaypoint, Action::Screenshot, Action::CameraView]),
+    }
+    actions.push(Action::NextItem);
+    actions.push(Action::UiMode);
+    actions
+}
+
+/// What the hinted actions depend on besides the targeted block
+#[derive(SystemParam)]
+pub struct HintState<'w> {
+    ms: Res<'w, MouseSettings>,
+    game_mode: Res<'w, GameMode>,
+    held_item: Res<'w, HeldItem>,
+}
+
+pub fn update_keybind_hints(
+    key_bindings: Res<KeyBindings>,
+    console: Res<Console>,
+    pause_menu: Res<PauseMenu>,
+    state: HintState,
+    crosshair: CrosshairTarget,
+    mut hints_query: Query<&mut Text, With<KeybindHints>>,
+) {
+    let Ok(mut text) = hints_query.get_single_mut() else {
+        return;
+    };
+    let hints = if console.open || pause_menu.open {
+        String::new()
+    } else {
+        let target = crosshair
+            .get()
+            .map(|(position, _)| crosshair.voxel_data.get_block(position));
+        relevant_actions(state.ms.ui_mode, *state.game_mode, *state.held_item, target)
+            .into_iter()
+            .map(|action| format!("{}: {}", action.key(&key_bindings), action.label()))
+            .collect::<Vec<_>>()
+            .join(SEPARATOR)
+    };
+    if text.sections[0].value != hints {
+        text.sections[0].value = hints;
+    }
+}
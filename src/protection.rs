@@ -0,0 +1,187 @@
+//! Claims: cuboids of the world owned by a client, where nobody else may edit.
+//! They're made with /claim or through `Claims`, checked by the server for
+//! every edit request and saved with the world, so a map can ship with them.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    save,
+    storage::{self, Folder},
+    voxel::{VoxelPos, WorldGenSettings},
+};
+
+const CLAIMS_FILE: &str = "claims.ron";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Claim {
+    pub name: String,
+    pub owner: u32, // client id
+    pub min: IVec3,
+    pub max: IVec3,
+}
+
+impl Claim {
+    pub fn contains(&self, position: VoxelPos) -> bool {
+        position.0.cmpge(self.min).all() && position.0.cmple(self.max).all()
+    }
+
+    fn overlaps(&self, min: IVec3, max: IVec3) -> bool {
+        self.min.cmple(max).all() && min.cmple(self.max).all()
+    }
+}
+
+/// Who a queued edit is made for, which decides the claimed blocks it may change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Editor {
+    Client(u32),
+    World, // explosions, scripts and what falls from them, which leave all claims alone
+}
+
+/// The claims of the world being played
+#[derive(Resource, Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Claims {
+    pub claims: Vec<Claim>,
+}
+
+impl Claims {
+    /// Claim the cuboid between two corners for `owner`. It may overlap the owner's
+    /// other claims, but not anyone else's
+    pub fn claim(
+        &mut self,
+        owner: u32,
+        name: &str,
+        first: VoxelPos,
+        second: VoxelPos,
+    ) -> Result<&Claim, String> {
+        let (min, max) = (first.0.min(second.0), first.0.max(second.0));
+        if self.claims.iter().any(|claim| claim.name == name) {
+            return Err(format!("there is already a claim named '{}'", name));
+        }
+        if let Some(other) = self
+            .claims
+            .iter()
+            .find(|claim| claim.owner != owner && claim.overlaps(min, max))
+        {
+            return Err(format!("it overlaps the claim '{}'", other.name));
+        }
+        self.claims.push(Claim {
+            name: name.to_string(),
+            owner,
+            min,
+            max,
+        });
+        Ok(&self.claims[self.claims.len() - 1])
+    }
+
+    /// Give up a claim, only its owner can
+    pub fn unclaim(&mut self, owner: u32, name: &str) -> Result<Claim, String> {
+        let index = self
+            .claims
+            .iter()
+            .position(|claim| claim.name == name)
+            .ok_or(format!("no claim named '{}'", name))?;
+        if self.claims[index].owner != owner {
+            return Err(format!("the claim '{}' isn't yours", name));
+        }
+        Ok(self.claims.remove(index))
+    }
+
+    /// Whether `client` may edit the block at `position`: anyone may outside the claims,
+    /// only the owner inside one
+    pub fn may_edit(&self, client: u32, position: VoxelPos) -> bool {
+        self.claims
+            .iter()
+            .all(|claim| claim.owner == client || !claim.contains(position))
+    }
+
+    /// Whether an edit made for `editor` may change the block at `position`
+    pub fn allows(&self, editor: Editor, position: VoxelPos) -> bool {
+        match editor {
+            Editor::Client(client) => self.may_edit(client, position),
+            Editor::World => !self.claims.iter().any(|claim| claim.contains(position)),
+        }
+    }
+}
+
+fn claims_file(settings: &WorldGenSettings) -> String {
+    format!("{}/{}", save::world_dir(settings), CLAIMS_FILE)
+}
+
+pub fn load_claims(world_gen_settings: Res<WorldGenSettings>, mut claims: ResMut<Claims>) {
+    let name = claims_file(&world_gen_settings);
+    let Some(contents) = storage::read(Folder::Data, &name)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+    else {
+        return;
+    };
+    match ron::from_str::<Claims>(&contents) {
+        Ok(loaded) => *claims = loaded,
+        Err(err) => warn!(
+            "Ignoring claims file {}: {}",
+            storage::describe(Folder::Data, &name),
+            err
+        ),
+    }
+}
+
+/// Write the claims whenever they change
+pub fn save_claims(world_gen_settings: Res<WorldGenSettings>, claims: Res<Claims>) {
+    if !claims.is_changed() || claims.is_added() {
+        return;
+    }
+    let name = claims_file(&world_gen_settings);
+    let result = ron::ser::to_string_pretty(&*claims, ron::ser::PrettyConfig::default())
+        .map_err(|err| err.to_string())
+        .and_then(|contents| storage::write(Folder::Data, &name, contents.as_bytes()));
+    if let Err(err) = result {
+        warn!(
+            "Failed to save claims to {}: {}",
+            storage::describe(Folder::Data, &name),
+            err
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_owner_edits_inside_a_claim() {
+        let mut claims = Claims::default();
+        claims
+            .claim(1, "base", VoxelPos::new(10, 0, 10), VoxelPos::new(0, 20, 0))
+            .unwrap();
+        assert!(claims.may_edit(1, VoxelPos::new(5, 5, 5)));
+        assert!(!claims.may_edit(2, VoxelPos::new(5, 5, 5)));
+        assert!(claims.may_edit(2, VoxelPos::new(11, 5, 5)));
+
+        assert!(claims
+            .claim(
+                2,
+                "next door",
+                VoxelPos::new(10, 0, 10),
+                VoxelPos::new(20, 0, 20)
+            )
+            .is_err());
+        assert!(claims.unclaim(2, "base").is_err());
+        assert!(claims.unclaim(1, "base").is_ok());
+        assert!(claims.may_edit(2, VoxelPos::new(5, 5, 5)));
+    }
+
+    #[test]
+    fn the_world_edits_no_claim() {
+        let mut claims = Claims::default();
+        claims
+            .claim(1, "base", VoxelPos::new(0, 0, 0), VoxelPos::new(10, 20, 10))
+            .unwrap();
+        let inside = VoxelPos::new(5, 5, 5);
+        assert!(claims.allows(Editor::Client(1), inside));
+        assert!(!claims.allows(Editor::Client(2), inside));
+        assert!(!claims.allows(Editor::World, inside));
+        assert!(claims.allows(Editor::World, VoxelPos::new(11, 5, 5)));
+    }
+}
@@ -0,0 +1,143 @@
+//! Random ticks: every second a few voxels picked at random in each loaded
+//! chunk get to do what their block does over time, as declared by its
+//! `RandomTick` in the block registry. Grass spreads, crops grow, leaves
+//! with nothing to hold them up decay and fire spreads.
+
+use bevy::prelude::*;
+use bevy_inspector_egui::prelude::*;
+
+use crate::{
+    farming, fire,
+    region::splitmix64,
+    voxel::{
+        self, BlockId, RandomTick, VoxelData, VoxelLocalIndex, VoxelModifyQueue, VoxelPos, AIR,
+        CHUNK_SIZE, DIRT, GRASS, LEAVES,
+    },
+};
+
+const LEAF_REACH: i32 = 4; // blocks from leaves to whatever holds them up
+
+#[derive(Reflect, Resource, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub struct RandomTickSettings {
+    #[inspector(min = 0, max = 4096)]
+    pub per_chunk: u32, // voxels picked in each loaded chunk every second
+}
+
+impl Default for RandomTickSettings {
+    fn default() -> Self {
+        RandomTickSettings { per_chunk: 64 }
+    }
+}
+
+/// What a tick handler sees of the world, its changes land with the player's edits
+pub struct TickContext<'a> {
+    voxel_data: &'a VoxelData,
+    edits: &'a mut Vec<(VoxelPos, BlockId)>,
+    state: &'a mut u64,
+}
+
+impl TickContext<'_> {
+    /// Block at a world position, air where the chunk isn't loaded
+    pub fn get(&self, position: VoxelPos) -> BlockId {
+        self.voxel_data.get_block(position)
+    }
+
+    pub fn is_loaded(&self, position: VoxelPos) -> bool {
+        self.voxel_data.is_loaded(position.chunk())
+    }
+
+    pub fn set(&mut self, position: VoxelPos, block: BlockId) {
+        self.edits.push((position, block));
+    }
+
+    /// A new random number on every call
+    pub fn random(&mut self) -> u64 {
+        *self.state = splitmix64(*self.state);
+        *self.state
+    }
+}
+
+/// Grass turns the dirt around it into grass, and itself back to dirt when covered
+fn spread_grass(ctx: &mut TickContext, position: VoxelPos) {
+    let up = |position: VoxelPos| VoxelPos(position.0 + IVec3::Y);
+    if !voxel::is_transparent(ctx.get(up(position))) {
+        ctx.set(position, DIRT);
+        return;
+    }
+    let roll = ctx.random();
+    let offset = IVec3::new(
+        (roll % 3) as i32 - 1,
+        ((roll >> 8) % 3) as i32 - 1,
+        ((roll >> 16) % 3) as i32 - 1,
+    );
+    let target = VoxelPos(position.0 + offset);
+    if ctx.get(target) == DIRT && voxel::is_transparent(ctx.get(up(target))) {
+        ctx.set(target, GRASS);
+    }
+}
+
+/// Leaves decay once no other block is within `LEAF_REACH`, while all of it is loaded
+fn decay_leaves(ctx: &mut TickContext, position: VoxelPos) {
+    let reach = IVec3::splat(LEAF_REACH);
+    let corners = [position.0 - reach, position.0 + reach];
+    if !corners
+        .iter()
+        .all(|corner| ctx.is_loaded(VoxelPos(*corner)))
+    {
+        return;
+    }
+    for x in -LEAF_REACH..=LEAF_REACH {
+        for y in -LEAF_REACH..=LEAF_REACH {
+            for z in -LEAF_REACH..=LEAF_REACH {
+                let block = ctx.get(VoxelPos(position.0 + IVec3::new(x, y, z)));
+                if block != AIR && block != LEAVES {
+                    return;
+                }
+            }
+        }
+    }
+    ctx.set(position, AIR);
+}
+
+fn dispatch(ctx: &mut TickContext, random_tick: RandomTick, position: VoxelPos, block: BlockId) {
+    match random_tick {
+        RandomTick::GrassSpread => spread_grass(ctx, position),
+        RandomTick::CropGrowth => farming::grow_crop(ctx, position, block),
+        RandomTick::LeafDecay => decay_leaves(ctx, position),
+        RandomTick::FireSpread => fire::spread_fire(ctx, position),
+    }
+}
+
+/// Pick `per_chunk` random voxels in every loaded chunk and tick the ones whose block ticks
+pub fn random_ticks(
+    voxel_data: Res<VoxelData>,
+    settings: Res<RandomTickSettings>,
+    mut voxel_modify_queue: ResMut<VoxelModifyQueue>,
+    mut state: Local<u64>,
+) {
+    let mut ctx = TickContext {
+        voxel_data: &voxel_data,
+        edits: &mut voxel_modify_queue.queue,
+        state: &mut state,
+    };
+    for chunk in voxel_data.iter_chunks() {
+        for _ in 0..settings.per_chunk {
+            let roll = ctx.random();
+            let local = VoxelLocalIndex {
+                x: (roll % CHUNK_SIZE as u64) as u8,
+                y: ((roll >> 16) % CHUNK_SIZE as u64) as u8,
+                z: ((roll >> 32) % CHUNK_SIZE as u64) as u8,
+            };
+            let block = chunk.voxels[local.x as usize][local.y as usize][local.z as usize];
+            if let Some(random_tick) = voxel::random_tick(block) {
+                dispatch(
+                    &mut ctx,
+                    random_tick,
+                    VoxelPos::from_parts(chunk.index, local),
+                    block,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use smooth_bevy_cameras::controllers::fps::FpsCameraController;
+
+use crate::voxel::{self, WorldGenSettings, CHUNK_SIZE};
+
+pub const REGION_SIZE: i32 = 16; // chunks in each direction of a region
+const TITLE_DURATION: f32 = 4.0; // seconds the region title stays on screen
+const TITLE_FADE: f32 = 1.0; // seconds of fade out at the end
+
+const MOUNTAIN_HEIGHT: i32 = 105;
+const LOWLAND_HEIGHT: i32 = 64;
+
+const SYLLABLES: [&str; 24] = [
+    "al", "bar", "cor", "dun", "el", "fen", "gar", "hol", "ir", "kel", "lor", "mar", "nor", "or",
+    "pel", "quin", "ros", "sal", "tor", "ul", "vel", "wyn", "yr", "zan",
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct RegionIndex {
+    pub x: i32,
+    pub z: i32,
+}
+
+impl RegionIndex {
+    pub fn from_position(pos: &Vec3) -> Self {
+        let chunk_index = voxel::get_chunk_index(pos);
+        RegionIndex {
+            x: chunk_index.x.div_euclid(REGION_SIZE),
+            z: chunk_index.z.div_euclid(REGION_SIZE),
+        }
+    }
+
+    /// World column at the middle of the region
+    fn center(&self) -> (i32, i32) {
+        let size = REGION_SIZE * CHUNK_SIZE as i32;
+        (self.x * size + size / 2, self.z * size + size / 2)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RegionKind {
+    Mountains,
+    Hills,
+    Lowlands,
+}
+
+impl RegionKind {
+    fn suffixes(&self) -> &'static [&'static str] {
+        match self {
+            RegionKind::Mountains => &["Peaks", "Mountains", "Range", "Spires"],
+            RegionKind::Hills => &["Hills", "Downs", "Heights", "Wolds"],
+            RegionKind::Lowlands => &["Vale", "Lowlands", "Basin", "Fields"],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RegionName {
+    pub kind: RegionKind,
+    pub title: String,
+}
+
+impl RegionName {
+    pub fn new(region: RegionIndex, settings: &WorldGenSettings) -> Self {
+        let (x, z) = region.center();
+        let height = voxel::surface_height(x, z, settings);
+        let kind = if height >= MOUNTAIN_HEIGHT {
+            RegionKind::Mountains
+        } else if height <= LOWLAND_HEIGHT {
+            RegionKind::Lowlands
+        } else {
+            RegionKind::Hills
+        };
+
+        let mut hash = region_hash(settings.seed, region);
+        let mut next = |n: usize| {
+            hash = splitmix64(hash);
+            (hash % n as u64) as usize
+        };
+
+        let mut name = String::new();
+        (0..2 + next(2)).for_each(|_| name.push_str(SYLLABLES[next(SYLLABLES.len())]));
+        let mut name_chars = name.chars();
+        let name = match name_chars.next() {
+            Some(first) => first.to_uppercase().chain(name_chars).collect::<String>(),
+            None => name,
+        };
+        let suffixes = kind.suffixes();
+        let suffix = suffixes[next(suffixes.len())];
+
+        RegionName {
+            kind,
+            title: format!("{} {}", name, suffix),
+        }
+    }
+}
+
+pub(crate) fn splitmix64(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn region_hash(seed: u32, region: RegionIndex) -> u64 {
+    let coords = ((region.x as u32 as u64) << 32) | region.z as u32 as u64;
+    splitmix64(((seed as u64) << 32) ^ splitmix64(coords))
+}
+
+/// Names given to the regions of the world, filled in as they are visited
+#[derive(Resource, Default)]
+pub struct WorldMetadata {
+    pub regions: HashMap<RegionIndex, RegionName>,
+}
+
+impl WorldMetadata {
+    pub fn region_name(&mut self, region: RegionIndex, settings: &WorldGenSettings) -> &RegionName {
+        self.regions
+            .entry(region)
+            .or_insert_with(|| RegionName::new(region, settings))
+    }
+}
+
+#[derive(Component, Default)]
+pub struct RegionTitle {
+    region: Option<RegionIndex>,
+    shown_at: f32,
+}
+
+pub fn setup_region_title(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(20.),
+                width: Val::Percent(100.),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 40.0,
+                        color: Color::NONE,
+                    },
+                ),
+                RegionTitle::default(),
+            ));
+        });
+}
+
+/// Show the region name for a few seconds whenever the camera enters another region
+pub fn update_region_title(
+    time: Res<Time>,
+    fps_camera_query: Query<&GlobalTransform, With<FpsCameraController>>,
+    world_gen_settings: Res<WorldGenSettings>,
+    mut world_metadata: ResMut<WorldMetadata>,
+    mut title_query: Query<(&mut Text, &mut RegionTitle)>,
+) {
+    let Ok(transform) = fps_camera_query.get_single() else {
+        return;
+    };
+    let Ok((mut text, mut title)) = title_query.get_single_mut() else {
+        return;
+    };
+
+    let region = RegionIndex::from_position(&transform.translation());
+    if title.region != Some(region) {
+        title.region = Some(region);
+        title.shown_at = time.elapsed_seconds();
+        text.sections[0].value = world_metadata
+            .region_name(region, &world_gen_settings)
+            .title
+            .clone();
+    }
+
+    let remaining = TITLE_DURATION - (time.elapsed_seconds() - title.shown_at);
+    let alpha = (remaining / TITLE_FADE).clamp(0.0, 1.0);
+    text.sections[0].style.color = Color::WHITE.with_a(alpha);
+}
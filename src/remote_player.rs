@@ -0,0 +1,176 @@
+//! The other players of a multiplayer game: the network layer tells when they
+//! join, move and leave, and each is drawn with a model like ours and a name
+//! tag floating over its head.
+
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+use smooth_bevy_cameras::controllers::fps::FpsCameraController;
+
+use crate::player::spawn_model;
+
+const COLOR: Color = Color::rgb(0.8, 0.3, 0.2); // shirt of the other players
+const TAG_HEIGHT: f32 = 2.2; // name tag over the feet
+const TAG_DISTANCE: f32 = 48.0; // players further away don't show their name
+const FONT_SIZE: f32 = 18.0;
+
+/// A player joined, sent in by the network layer
+#[derive(Event, Debug, Clone)]
+pub struct PlayerJoined {
+    pub client: u32,
+    pub name: String,
+    pub position: Vec3, // feet
+}
+
+/// A player moved, sent in by the network layer
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PlayerMoved {
+    pub client: u32,
+    pub position: Vec3, // feet
+    pub forward: Vec3,  // where the player looks
+}
+
+/// A player left, sent in by the network layer
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PlayerLeft {
+    pub client: u32,
+}
+
+/// Model of another player, its transform at the feet like our own `Player`
+#[derive(Component)]
+pub struct RemotePlayer {
+    pub client: u32,
+    pub name: String,
+    tag: Entity,
+}
+
+/// Text showing the name of a remote player over its head
+#[derive(Component)]
+pub struct NameTag;
+
+/// Models of the players in the game, by client id
+#[derive(Resource, Default)]
+pub struct RemotePlayers {
+    pub players: HashMap<u32, Entity>,
+}
+
+/// What the network layer tells about the other players
+#[derive(SystemParam)]
+pub struct RemotePlayerEvents<'w, 's> {
+    joined: EventReader<'w, 's, PlayerJoined>,
+    moved: EventReader<'w, 's, PlayerMoved>,
+    left: EventReader<'w, 's, PlayerLeft>,
+}
+
+/// Spawn, move and despawn the models of the other players
+pub fn update_remote_players(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut events: RemotePlayerEvents,
+    mut remote_players: ResMut<RemotePlayers>,
+    mut player_query: Query<(&RemotePlayer, &mut Transform)>,
+) {
+    let despawn = |commands: &mut Commands, entity: Entity| {
+        if let Ok((player, _)) = player_query.get(entity) {
+            commands.entity(player.tag).despawn_recursive();
+        }
+        commands.entity(entity).despawn_recursive();
+    };
+    for event in events.left.iter() {
+        if let Some(entity) = remote_players.players.remove(&event.client) {
+            despawn(&mut commands, entity);
+        }
+    }
+
+    for event in events.joined.iter() {
+        // joining again replaces the old model
+        if let Some(entity) = remote_players.players.remove(&event.client) {
+            despawn(&mut commands, entity);
+        }
+        let tag = commands
+            .spawn((
+                TextBundle::from_section(
+                    event.name.clone(),
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: FONT_SIZE,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_style(Style {
+                    position_type: PositionType::Absolute,
+                    ..default()
+                })
+                .with_background_color(Color::rgba(0.0, 0.0, 0.0, 0.5)),
+                NameTag,
+                Name::new("Name tag"),
+            ))
+            // until it's put over the player
+            .insert(Visibility::Hidden)
+            .id();
+        let model = spawn_model(&mut commands, &mut meshes, &mut materials, COLOR);
+        commands.entity(model).insert((
+            Transform::from_translation(event.position),
+            Visibility::Inherited,
+            RemotePlayer {
+                client: event.client,
+                name: event.name.clone(),
+                tag,
+            },
+            Name::new(format!("Player {}", event.name)),
+        ));
+        remote_players.players.insert(event.client, model);
+    }
+
+    for event in events.moved.iter() {
+        let Some(&entity) = remote_players.players.get(&event.client) else {
+            continue;
+        };
+        let Ok((_, mut transform)) = player_query.get_mut(entity) else {
+            continue;
+        };
+        transform.translation = event.position;
+        let facing = Vec3::new(event.forward.x, 0.0, event.forward.z);
+        if facing.length_squared() > f32::EPSILON {
+            transform.look_to(facing.normalize(), Vec3::Y);
+        }
+    }
+}
+
+/// Put the name tags over the heads of the players near enough to read them
+pub fn update_name_tags(
+    camera_query: Query<(&Camera, &GlobalTransform), With<FpsCameraController>>,
+    player_query: Query<(&RemotePlayer, &GlobalTransform)>,
+    mut tag_query: Query<(&mut Style, &mut Visibility, &Node), With<NameTag>>,
+) {
+    let Ok((camera, camera_transform)) = camera_query.get_single() else {
+        return;
+    };
+    for (player, transform) in player_query.iter() {
+        let Ok((mut style, mut visibility, node)) = tag_query.get_mut(player.tag) else {
+            continue;
+        };
+        let head = transform.translation() + Vec3::Y * TAG_HEIGHT;
+        let screen = camera
+            .world_to_viewport(camera_transform, head)
+            .filter(|_| head.distance(camera_transform.translation()) <= TAG_DISTANCE);
+        let shown = match screen {
+            Some(screen) => {
+                // centered over the head, the node's size is from the last layout
+                let left = Val::Px(screen.x - node.size().x / 2.0);
+                let top = Val::Px(screen.y - node.size().y);
+                if style.left != left {
+                    style.left = left;
+                }
+                if style.top != top {
+                    style.top = top;
+                }
+                Visibility::Inherited
+            }
+            None => Visibility::Hidden,
+        };
+        if *visibility != shown {
+            *visibility = shown;
+        }
+    }
+}
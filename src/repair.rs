@@ -0,0 +1,166 @@
+//! World repair: checks the chunks in memory and the saved ones and drops
+//! broken ones, so the generator rebuilds them, then relights and remeshes
+//! what's loaded. The world keeps no heightmaps, its light is worked out when a
+//! chunk is meshed. Regenerating the world after a change to the generation
+//! settings drops the unedited chunks the same way.
+
+use std::any::{Any, TypeId};
+
+use bevy::prelude::*;
+use bevy_inspector_egui::{
+    egui, inspector_egui_impls::InspectorEguiImpl, reflect_inspector::InspectorUi,
+};
+
+use crate::{
+    command::CommandResponse,
+    save::WorldSave,
+    voxel::{
+        last_block, ChunkCache, ChunkData, ChunkIndex, RegenerateButton, VoxelData,
+        WorldGenSettings,
+    },
+    world::VoxelWorld,
+};
+
+#[derive(Debug, Default)]
+pub struct RepairReport {
+    pub checked: usize,
+    pub misplaced: Vec<ChunkIndex>, // stored under another chunk's index
+    pub unknown_blocks: Vec<ChunkIndex>, // hold block ids no block is registered for
+    pub saves_checked: usize,
+    pub broken_saves: Vec<(ChunkIndex, String)>, // deleted, with why
+}
+
+impl RepairReport {
+    pub fn summary(&self) -> String {
+        format!(
+            "Checked {} chunks, dropped {} misplaced and {} with unknown blocks. Checked {} saved \
+             chunks, deleted {} broken ones. All light and meshes rebuilt",
+            self.checked,
+            self.misplaced.len(),
+            self.unknown_blocks.len(),
+            self.saves_checked,
+            self.broken_saves.len()
+        )
+    }
+}
+
+/// Why a chunk's blocks are broken, None if they aren't
+fn check_blocks(chunk: &ChunkData) -> Option<String> {
+    let last_block = last_block();
+    chunk
+        .voxels
+        .iter()
+        .flatten()
+        .flatten()
+        .find(|block| **block > last_block)
+        .map(|block| format!("unknown block id {}", block.0))
+}
+
+/// Drop the loaded chunks that fail validation and delete the saved ones, then relight and
+/// remesh every column
+pub fn repair_world(world: &mut VoxelWorld, world_save: &mut WorldSave) -> RepairReport {
+    let mut report = RepairReport::default();
+    world.retain_chunks(|index, chunk, _| {
+        report.checked += 1;
+        if chunk.index != index {
+            report.misplaced.push(index);
+        } else if check_blocks(chunk).is_some() {
+            report.unknown_blocks.push(index);
+        } else {
+            return true;
+        }
+        warn!("Dropped broken chunk {:?} for regeneration", index);
+        false
+    });
+    (report.saves_checked, report.broken_saves) =
+        world_save.check_saved(|chunk| check_blocks(chunk).map_or(Ok(()), Err));
+    // the light is worked out again as each chunk is meshed
+    world.remesh_all();
+    report
+}
+
+fn regenerate_button(
+    value: &mut dyn Any,
+    ui: &mut egui::Ui,
+    _: &dyn Any,
+    _: egui::Id,
+    _: InspectorUi<'_, '_>,
+) -> bool {
+    let button = value.downcast_mut::<RegenerateButton>().unwrap();
+    if ui.button("Regenerate world").clicked() {
+        button.pressed = true;
+        return true;
+    }
+    false
+}
+
+fn regenerate_button_readonly(
+    _: &dyn Any,
+    ui: &mut egui::Ui,
+    _: &dyn Any,
+    _: egui::Id,
+    _: InspectorUi<'_, '_>,
+) {
+    ui.add_enabled(false, egui::Button::new("Regenerate world"));
+}
+
+fn regenerate_button_many(
+    ui: &mut egui::Ui,
+    _: &dyn Any,
+    _: egui::Id,
+    _: InspectorUi<'_, '_>,
+    _: &mut [&mut dyn Reflect],
+    _: &dyn Fn(&mut dyn Reflect) -> &mut dyn Reflect,
+) -> bool {
+    ui.add_enabled(false, egui::Button::new("Regenerate world"));
+    false
+}
+
+/// Show `RegenerateButton` as a button in the inspector
+pub fn setup_regenerate_button(type_registry: Res<AppTypeRegistry>) {
+    let mut type_registry = type_registry.write();
+    let Some(registration) = type_registry.get_mut(TypeId::of::<RegenerateButton>()) else {
+        return;
+    };
+    registration.insert(InspectorEguiImpl::new(
+        regenerate_button,
+        regenerate_button_readonly,
+        regenerate_button_many,
+    ));
+}
+
+/// Once the regenerate button is pressed or `/regen` ran, drop the chunks that were neither
+/// edited nor saved, so they are generated again with the current settings. Another seed or
+/// preset is another world: the edits are saved to the old one and every chunk is dropped
+pub fn regenerate_world(
+    mut world_gen_settings: ResMut<WorldGenSettings>,
+    mut voxel_data: ResMut<VoxelData>,
+    mut chunk_cache: ResMut<ChunkCache>,
+    mut world_save: ResMut<WorldSave>,
+    mut responses: EventWriter<CommandResponse>,
+) {
+    if !world_gen_settings.regenerate.pressed {
+        return;
+    }
+    world_gen_settings.regenerate.pressed = false;
+
+    let before = voxel_data.chunk_count();
+    if !world_save.is_for(&world_gen_settings) {
+        voxel_data.write_modified(|chunk| {
+            world_save.write(chunk);
+            true
+        });
+        *world_save = WorldSave::open(&world_gen_settings);
+        voxel_data.retain_chunks(|_, _, _| false);
+        *chunk_cache = ChunkCache::default();
+    } else {
+        voxel_data.retain_chunks(|index, _, edited| edited || world_save.contains(&index));
+        chunk_cache.clear_unmodified();
+    }
+    responses.send(CommandResponse {
+        message: format!(
+            "Dropped {} chunks to generate again, edited ones are kept",
+            before - voxel_data.chunk_count()
+        ),
+    });
+}
@@ -0,0 +1,526 @@
+//! Edited chunks saved to disk, or local storage in the browser, one file per chunk. Chunks the player never
+//! touched aren't written, they generate the same from the seed every time.
+//!
+//! Worlds picked by name, in the main menu or with `--world <name>`, live in their own folder
+//! under `saves/`, with a `world.ron` next to the chunks keeping the seed, generator settings,
+//! spawn point, the bed the player respawns at, game mode and playtime.
+
+use std::collections::HashSet;
+
+use bevy::{app::AppExit, ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    codec,
+    game_mode::GameMode,
+    storage::{self, Folder},
+    stored_entity::{self, Storable},
+    voxel::{
+        self, ChunkCache, ChunkData, ChunkIndex, VoxelData, VoxelPos, WorldGenSettings, WorldPreset,
+    },
+    SPAWN_POINT,
+};
+
+const AUTOSAVE_INTERVAL: f32 = 30.0; // seconds between writes of the edited chunks
+const NAMED_WORLDS: &str = "saves";
+const WORLD_INFO_FILE: &str = "world.ron";
+
+/// The chunk files of the world being played
+#[derive(Resource, Default)]
+pub struct WorldSave {
+    dir: Option<String>,
+    saved: HashSet<ChunkIndex>, // chunks with a file in `dir`
+    named: bool,                // a named world keeps its folder whatever the settings
+}
+
+/// Where unloaded chunks go and come back from: the cache of the ones unloaded a moment ago,
+/// then the save
+#[derive(SystemParam)]
+pub struct ChunkStore<'w> {
+    chunk_cache: ResMut<'w, ChunkCache>,
+    world_save: ResMut<'w, WorldSave>,
+}
+
+impl ChunkStore<'_> {
+    /// A chunk back from the cache or the save, with whether it's edited and not saved yet
+    pub fn take(&mut self, index: &ChunkIndex) -> Option<(ChunkData, bool)> {
+        self.chunk_cache
+            .take(index)
+            .or_else(|| Some((self.world_save.load(index)?, false)))
+    }
+
+    /// Keep an unloaded chunk. Edited chunks and chunks holding entities are written out
+    /// before they're dropped, the cached copy is clean. Past `capacity` the cache drops the
+    /// oldest
+    pub fn put(&mut self, chunk: ChunkData, edited: bool, capacity: usize) {
+        let world_save = &mut self.world_save;
+        let modified = (edited || !chunk.stored.is_empty()) && !world_save.write(&chunk);
+        self.chunk_cache
+            .insert(chunk, modified, capacity, |chunk| world_save.write(chunk));
+    }
+
+    /// Write the loaded chunks edited since they were last saved, and the cached ones that
+    /// failed to write when they unloaded
+    fn write_modified(&mut self, voxel_data: &mut VoxelData) {
+        let world_save = &mut self.world_save;
+        voxel_data.write_modified(|chunk| world_save.write(chunk));
+        self.chunk_cache
+            .write_modified(|chunk| world_save.write(chunk));
+    }
+}
+
+/// What a named world keeps in its `world.ron`, missing entries fall back to the defaults
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldInfo {
+    pub settings: WorldGenSettings, // seed and generator
+    pub spawn: [f32; 3],
+    pub bed: Option<[i32; 3]>, // last used, the player respawns on it while it's there
+    pub playtime: f64,         // seconds played in the world
+    pub game_mode: GameMode,
+}
+
+impl Default for WorldInfo {
+    fn default() -> Self {
+        WorldInfo {
+            settings: WorldGenSettings::default(),
+            spawn: SPAWN_POINT.to_array(),
+            bed: None,
+            playtime: 0.0,
+            game_mode: GameMode::default(),
+        }
+    }
+}
+
+/// The named world being played, picked in the main menu or with `--world <name>`. Without a
+/// name the world is the one generated from the seed, as before
+#[derive(Resource, Default)]
+pub struct WorldSlot {
+    pub name: Option<String>,
+    pub info: WorldInfo,
+}
+
+impl WorldSlot {
+    /// The world named `name`, a new world generated from `seed` if there is none yet
+    pub fn open(name: &str, seed: Option<u32>) -> Self {
+        let info = read_world_info(name).unwrap_or_else(|| {
+            let seed = seed.unwrap_or_else(|| storage::unix_time().as_millis() as u32);
+            WorldInfo {
+                settings: WorldGenSettings { seed, ..default() },
+                ..default()
+            }
+        });
+        WorldSlot {
+            name: Some(name.to_string()),
+            info,
+        }
+    }
+
+    /// A new world named `name`, written right away so it's listed with the others. Fails if
+    /// the name is taken or can't be a folder name
+    pub fn create(
+        name: &str,
+        seed: Option<u32>,
+        preset: WorldPreset,
+        game_mode: GameMode,
+    ) -> Result<Self, String> {
+        let name = name.trim();
+        if !valid_world_name(name) {
+            return Err("use letters, digits, spaces, - and _".to_string());
+        }
+        if read_world_info(name).is_some() {
+            return Err(format!("there is already a world named {name}"));
+        }
+        let seed = seed.unwrap_or_else(|| storage::unix_time().as_millis() as u32);
+        let slot = WorldSlot {
+            name: Some(name.to_string()),
+            info: WorldInfo {
+                settings: WorldGenSettings {
+                    seed,
+                    preset,
+                    ..default()
+                },
+                game_mode,
+                ..default()
+            },
+        };
+        if !slot.write_info() {
+            return Err("couldn't save the world".to_string());
+        }
+        Ok(slot)
+    }
+
+    /// Read `--world <name>`, `--seed <seed>` and `--worlds` from the command line, listing the
+    /// named worlds and returning None for the latter
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Option<Self> {
+        let (mut name, mut seed) = (None, None);
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--world" => name = args.next(),
+                "--seed" => seed = args.next().and_then(|seed| seed.parse().ok()),
+                "--worlds" => {
+                    for (name, info) in list_worlds() {
+                        println!(
+                            "{name}: seed {}, {}, played {}",
+                            info.settings.seed,
+                            info.settings.preset.name(),
+                            format_playtime(info.playtime)
+                        );
+                    }
+                    return None;
+                }
+                _ => {}
+            }
+        }
+        Some(match name {
+            Some(name) => WorldSlot::open(&name, seed),
+            None => {
+                let mut slot = WorldSlot::default();
+                slot.info.settings.seed = seed.unwrap_or(slot.info.settings.seed);
+                slot
+            }
+        })
+    }
+
+    pub fn spawn_point(&self) -> Vec3 {
+        Vec3::from_array(self.info.spawn)
+    }
+
+    pub fn bed(&self) -> Option<VoxelPos> {
+        self.info.bed.map(|bed| VoxelPos(IVec3::from_array(bed)))
+    }
+
+    /// Respawn at `bed` from now on, or at the spawn point again for None. A named world saves
+    /// it right away
+    pub fn set_bed(&mut self, bed: Option<VoxelPos>) {
+        self.info.bed = bed.map(|bed| bed.0.to_array());
+        if self.name.is_some() {
+            self.write_info();
+        }
+    }
+
+    /// The chunk files of this world
+    pub fn open_save(&self, settings: &WorldGenSettings) -> WorldSave {
+        match &self.name {
+            Some(name) => WorldSave::open_dir(named_world_dir(name), true),
+            None => WorldSave::open(settings),
+        }
+    }
+
+    /// Write `world.ron` of a named world, returns false if it couldn't be saved
+    fn write_info(&self) -> bool {
+        let Some(name) = &self.name else {
+            return false;
+        };
+        let file = format!("{}/{}", named_world_dir(name), WORLD_INFO_FILE);
+        let result = ron::ser::to_string_pretty(&self.info, ron::ser::PrettyConfig::default())
+            .map_err(|err| err.to_string())
+            .and_then(|contents| storage::write(Folder::Data, &file, contents.as_bytes()));
+        if let Err(err) = &result {
+            warn!(
+                "Failed to save {}: {}",
+                storage::describe(Folder::Data, &file),
+                err
+            );
+        }
+        result.is_ok()
+    }
+}
+
+fn named_world_dir(name: &str) -> String {
+    format!("{NAMED_WORLDS}/{name}")
+}
+
+fn read_world_info(name: &str) -> Option<WorldInfo> {
+    let file = format!("{}/{}", named_world_dir(name), WORLD_INFO_FILE);
+    let contents = storage::read(Folder::Data, &file).ok()?;
+    match ron::de::from_bytes(&contents) {
+        Ok(info) => Some(info),
+        Err(err) => {
+            warn!(
+                "Ignoring {}: {}",
+                storage::describe(Folder::Data, &file),
+                err
+            );
+            None
+        }
+    }
+}
+
+/// The named worlds with their info, by name
+pub fn list_worlds() -> Vec<(String, WorldInfo)> {
+    let mut worlds: Vec<_> = storage::list(Folder::Data, NAMED_WORLDS)
+        .into_iter()
+        .filter_map(|name| Some((name.clone(), read_world_info(&name)?)))
+        .collect();
+    worlds.sort_by(|a, b| a.0.cmp(&b.0));
+    worlds
+}
+
+/// Whether a world can be named `name`, it names its folder
+fn valid_world_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 32
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+}
+
+pub(crate) fn format_playtime(seconds: f64) -> String {
+    let minutes = (seconds / 60.0) as u64;
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+/// Folder of the world generated from `settings`
+pub(crate) fn world_dir(settings: &WorldGenSettings) -> String {
+    match settings.preset {
+        WorldPreset::Terrain => format!("worlds/{}", settings.seed),
+        // the other generators don't share a seed's saves with its terrain
+        preset => format!("worlds/{}_{}", preset.name(), settings.seed),
+    }
+}
+
+fn file_name(index: ChunkIndex) -> String {
+    format!("{}_{}_{}.chunk", index.x, index.y, index.z)
+}
+
+fn parse_file_name(name: &str) -> Option<ChunkIndex> {
+    let mut coords = name.strip_suffix(".chunk")?.split('_');
+    let index = ChunkIndex {
+        x: coords.next()?.parse().ok()?,
+        y: coords.next()?.parse().ok()?,
+        z: coords.next()?.parse().ok()?,
+    };
+    coords.next().is_none().then_some(index)
+}
+
+impl WorldSave {
+    /// List the chunks saved for the world generated from `settings`
+    pub fn open(settings: &WorldGenSettings) -> Self {
+        WorldSave::open_dir(world_dir(settings), false)
+    }
+
+    fn open_dir(dir: String, named: bool) -> Self {
+        let saved = storage::list(Folder::Data, &dir)
+            .iter()
+            .filter_map(|name| parse_file_name(name))
+            .collect();
+        WorldSave {
+            dir: Some(dir),
+            saved,
+            named,
+        }
+    }
+
+    /// Whether these are the saves of the world generated from `settings`, always for a named
+    /// world as its settings are saved with it
+    pub fn is_for(&self, settings: &WorldGenSettings) -> bool {
+        self.named || self.dir.as_deref() == Some(world_dir(settings).as_str())
+    }
+
+    pub fn contains(&self, index: &ChunkIndex) -> bool {
+        self.saved.contains(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.saved.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.saved.is_empty()
+    }
+
+    /// Read a saved chunk, None if it isn't saved or the file can't be read
+    pub fn load(&self, index: &ChunkIndex) -> Option<ChunkData> {
+        if !self.saved.contains(index) {
+            return None;
+        }
+        let name = format!("{}/{}", self.dir.as_ref()?, file_name(*index));
+        let result = storage::read(Folder::Data, &name)
+            .and_then(|bytes| codec::decode_chunk(&bytes).map_err(|err| err.to_string()));
+        match result {
+            Ok(chunk) if chunk.index == *index => Some(chunk),
+            Ok(_) => {
+                warn!(
+                    "Ignoring {}, it holds another chunk",
+                    storage::describe(Folder::Data, &name)
+                );
+                None
+            }
+            Err(err) => {
+                warn!(
+                    "Cannot load {}: {}",
+                    storage::describe(Folder::Data, &name),
+                    err
+                );
+                None
+            }
+        }
+    }
+
+    /// Read every saved chunk and delete the files that can't be read, hold another chunk
+    /// or fail `check`, so they're generated again. Returns how many were checked and the
+    /// ones deleted, with why
+    pub fn check_saved(
+        &mut self,
+        mut check: impl FnMut(&ChunkData) -> Result<(), String>,
+    ) -> (usize, Vec<(ChunkIndex, String)>) {
+        let Some(dir) = self.dir.clone() else {
+            return (0, Vec::new());
+        };
+        let mut dropped = Vec::new();
+        let checked = self.saved.len();
+        self.saved.retain(|index| {
+            let name = format!("{}/{}", dir, file_name(*index));
+            let problem = match storage::read(Folder::Data, &name)
+                .and_then(|bytes| codec::decode_chunk(&bytes).map_err(|err| err.to_string()))
+            {
+                Ok(chunk) if chunk.index != *index => "it holds another chunk".to_string(),
+                Ok(chunk) => match check(&chunk) {
+                    Ok(()) => return true,
+                    Err(problem) => problem,
+                },
+                Err(err) => err,
+            };
+            warn!(
+                "Deleting {} for regeneration: {}",
+                storage::describe(Folder::Data, &name),
+                problem
+            );
+            let _ = storage::remove(Folder::Data, &name);
+            dropped.push((*index, problem));
+            false
+        });
+        (checked, dropped)
+    }
+
+    /// Write a chunk to its file, returns false if it couldn't be saved
+    pub fn write(&mut self, chunk: &ChunkData) -> bool {
+        let Some(dir) = self.dir.as_ref() else {
+            return false;
+        };
+        let name = format!("{}/{}", dir, file_name(chunk.index));
+        match storage::write(Folder::Data, &name, &codec::encode_chunk(chunk)) {
+            Ok(()) => {
+                self.saved.insert(chunk.index);
+                true
+            }
+            Err(err) => {
+                warn!(
+                    "Failed to save {}: {}",
+                    storage::describe(Folder::Data, &name),
+                    err
+                );
+                false
+            }
+        }
+    }
+}
+
+/// A named world's `world.ron` with the live settings that go in it
+#[derive(SystemParam)]
+pub struct WorldInfoWriter<'w> {
+    world_slot: ResMut<'w, WorldSlot>,
+    world_gen_settings: Res<'w, WorldGenSettings>,
+    game_mode: Res<'w, GameMode>,
+}
+
+impl WorldInfoWriter<'_> {
+    /// Bring the info up to date and write it, for a named world only
+    fn write(&mut self) {
+        if self.world_slot.name.is_some() {
+            self.world_slot.info.settings = self.world_gen_settings.clone();
+            self.world_slot.info.game_mode = *self.game_mode;
+            self.world_slot.write_info();
+        }
+    }
+}
+
+/// Write the chunks edited since they were last saved, every `AUTOSAVE_INTERVAL` and on exit,
+/// along with the info of a named world. Edited chunks that unloaded without being saved are
+/// tried again. On exit the items, mobs and falling blocks go in with their chunk, as when it
+/// unloads. Runs in `Last` so it's after every system that sends `AppExit`
+pub fn save_modified_chunks(
+    time: Res<Time>,
+    mut voxel_data: ResMut<VoxelData>,
+    stored_query: Query<(&Transform, Storable)>,
+    mut chunk_store: ChunkStore,
+    mut world_info: WorldInfoWriter,
+    mut exit_events: EventReader<AppExit>,
+    mut saved_at: Local<f32>,
+) {
+    world_info.world_slot.info.playtime += time.delta_seconds_f64();
+    let exiting = exit_events.iter().count() > 0;
+    if !exiting && time.elapsed_seconds() - *saved_at < AUTOSAVE_INTERVAL {
+        return;
+    }
+    *saved_at = time.elapsed_seconds();
+
+    if exiting {
+        for (transform, components) in stored_query.iter() {
+            let index = voxel::get_chunk_index(&transform.translation);
+            let (Some(stored_entity), Some(chunk)) = (
+                stored_entity::store(transform, components),
+                voxel_data.chunk_mut(index),
+            ) else {
+                continue;
+            };
+            chunk.stored.push(stored_entity);
+            voxel_data.mark_modified(index);
+        }
+    }
+    chunk_store.write_modified(&mut voxel_data);
+    world_info.write();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_file_names_round_trip() {
+        let index = ChunkIndex { x: -3, y: 5, z: 12 };
+        assert_eq!(parse_file_name(&file_name(index)), Some(index));
+        assert_eq!(parse_file_name("1_2.chunk"), None);
+        assert_eq!(parse_file_name("1_2_3_4.chunk"), None);
+        assert_eq!(parse_file_name("1_2_3.ron"), None);
+    }
+
+    #[test]
+    fn world_info_round_trips_and_fills_in_defaults() {
+        let info = WorldInfo {
+            settings: WorldGenSettings {
+                seed: 7,
+                preset: WorldPreset::Flat,
+                ..default()
+            },
+            spawn: [4.0, 90.0, -2.5],
+            bed: Some([3, 71, -8]),
+            playtime: 3725.0,
+            game_mode: GameMode::Survival,
+        };
+        let contents = ron::to_string(&info).unwrap();
+        let loaded: WorldInfo = ron::from_str(&contents).unwrap();
+        assert_eq!(loaded.settings.seed, 7);
+        assert_eq!(loaded.settings.preset, WorldPreset::Flat);
+        assert_eq!(loaded.spawn, info.spawn);
+        assert_eq!(loaded.bed, info.bed);
+        assert_eq!(loaded.game_mode, GameMode::Survival);
+        assert_eq!(format_playtime(loaded.playtime), "1h 02m");
+
+        let loaded: WorldInfo = ron::from_str("(playtime: 60.0)").unwrap();
+        assert_eq!(loaded.settings.seed, WorldGenSettings::default().seed);
+        assert_eq!(loaded.spawn, SPAWN_POINT.to_array());
+        assert_eq!(loaded.bed, None);
+        assert_eq!(loaded.game_mode, GameMode::Creative);
+    }
+
+    #[test]
+    fn world_names_must_make_folder_names() {
+        assert!(valid_world_name("My world_2-b"));
+        assert!(!valid_world_name(""));
+        assert!(!valid_world_name("../escape"));
+        assert!(!valid_world_name(&"a".repeat(33)));
+    }
+}
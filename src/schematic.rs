@@ -0,0 +1,222 @@
+//! Blueprints of built structures: a cuboid of blocks exported to a file with
+//! its own palette, pasted back anywhere through the voxel modify queue. The
+//! states of the blocks and their block entities, like the contents of a
+//! furnace or the text of a sign, go with them.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    block_entity::BlockEntity,
+    codec,
+    storage::{self, Folder},
+    voxel::{BlockId, BlockState, VoxelData, VoxelModifyQueue, VoxelPos},
+};
+
+/// Blocks of a cuboid, stored as indices into a palette of the block ids it uses
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Schematic {
+    pub(crate) size: [u32; 3],
+    pub(crate) palette: Vec<BlockId>,
+    pub(crate) blocks: Vec<u8>,                // x-major, then y, then z
+    pub(crate) states: Vec<(u32, BlockState)>, // by index into `blocks`, the ones with a state
+    pub(crate) entities: Vec<(u32, BlockEntity)>, // by index into `blocks`
+}
+
+fn schematic_file(name: &str) -> Result<String, String> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "invalid schematic name '{}', use letters, digits, - and _",
+            name
+        ));
+    }
+    Ok(format!("schematics/{name}.schem"))
+}
+
+impl Schematic {
+    /// Copy the blocks between two corners, both included
+    pub fn capture(voxel_data: &VoxelData, from: VoxelPos, to: VoxelPos) -> Self {
+        let min = from.0.min(to.0);
+        let max = from.0.max(to.0);
+        let size = (max - min + IVec3::ONE).as_uvec3();
+        let mut palette: Vec<BlockId> = Vec::new();
+        let mut blocks = Vec::with_capacity(size.x as usize * size.y as usize * size.z as usize);
+        let mut states = Vec::new();
+        let mut entities = Vec::new();
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    let position = VoxelPos::new(x, y, z);
+                    let block = voxel_data.get_block(position);
+                    let index = palette.iter().position(|b| *b == block).unwrap_or_else(|| {
+                        palette.push(block);
+                        palette.len() - 1
+                    });
+                    let at = blocks.len() as u32;
+                    blocks.push(index as u8);
+                    let state = voxel_data.get_state(position);
+                    if state != 0 {
+                        states.push((at, state));
+                    }
+                    if let Some(entity) = voxel_data.get_entity(position) {
+                        entities.push((at, entity.clone()));
+                    }
+                }
+            }
+        }
+        Schematic {
+            size: size.to_array(),
+            palette,
+            blocks,
+            states,
+            entities,
+        }
+    }
+
+    pub fn volume(&self) -> usize {
+        self.size.iter().map(|side| *side as usize).product()
+    }
+
+    /// Where the block at `at` in `blocks` goes with the minimum corner at `origin`
+    fn position(&self, origin: VoxelPos, at: u32) -> VoxelPos {
+        let [_, size_y, size_z] = self.size;
+        let offset = UVec3::new(at / (size_y * size_z), at / size_z % size_y, at % size_z);
+        VoxelPos(origin.0 + offset.as_ivec3())
+    }
+
+    /// Queue the blocks with their minimum corner at `origin`, with their states and block
+    /// entities, returns the blocks queued
+    pub fn paste(&self, origin: VoxelPos, voxel_modify_queue: &mut VoxelModifyQueue) -> usize {
+        for (at, index) in self.blocks.iter().enumerate() {
+            voxel_modify_queue.queue.push((
+                self.position(origin, at as u32),
+                self.palette[*index as usize],
+            ));
+        }
+        for (at, state) in &self.states {
+            voxel_modify_queue
+                .states
+                .push((self.position(origin, *at), *state));
+        }
+        for (at, entity) in &self.entities {
+            voxel_modify_queue
+                .entities
+                .push((self.position(origin, *at), entity.clone()));
+        }
+        self.blocks.len()
+    }
+
+    /// Reasons the blocks don't fit the size and palette, from a damaged or edited file
+    fn validate(&self) -> Result<(), String> {
+        if self.blocks.len() != self.volume() {
+            return Err(format!(
+                "{} blocks for a {:?} cuboid",
+                self.blocks.len(),
+                self.size
+            ));
+        }
+        if self
+            .blocks
+            .iter()
+            .any(|index| *index as usize >= self.palette.len())
+        {
+            return Err(format!(
+                "block outside the palette of {}",
+                self.palette.len()
+            ));
+        }
+        let outside = |at: &u32| *at as usize >= self.volume();
+        if self.states.iter().any(|(at, _)| outside(at))
+            || self.entities.iter().any(|(at, _)| outside(at))
+        {
+            return Err(format!(
+                "state or block entity outside the {:?} cuboid",
+                self.size
+            ));
+        }
+        Ok(())
+    }
+
+    /// Write the schematic, returns where it was saved
+    pub fn save(&self, name: &str) -> Result<String, String> {
+        let file = schematic_file(name)?;
+        storage::write(Folder::Data, &file, &codec::encode_schematic(self))?;
+        Ok(storage::describe(Folder::Data, &file))
+    }
+
+    pub fn load(name: &str) -> Result<Self, String> {
+        let file = schematic_file(name)?;
+        let bytes = storage::read(Folder::Data, &file).map_err(|err| {
+            format!(
+                "cannot read {}: {}",
+                storage::describe(Folder::Data, &file),
+                err
+            )
+        })?;
+        let schematic = codec::decode_schematic(&bytes).map_err(|err| err.to_string())?;
+        schematic.validate()?;
+        Ok(schematic)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        sign::Sign,
+        voxel::{ChunkIndex, SIGN, STONE, STONE_STAIRS},
+    };
+
+    #[test]
+    fn pastes_what_was_captured() {
+        let mut voxel_data = VoxelData::air([ChunkIndex { x: 0, y: 0, z: 0 }]);
+        voxel_data.set_block(VoxelPos::new(1, 2, 3), STONE);
+
+        let schematic =
+            Schematic::capture(&voxel_data, VoxelPos::new(2, 3, 4), VoxelPos::new(0, 0, 0));
+        assert_eq!(schematic.palette.len(), 2);
+        assert!(schematic.validate().is_ok());
+
+        let mut queue = VoxelModifyQueue::default();
+        assert_eq!(
+            schematic.paste(VoxelPos::new(10, 0, 0), &mut queue),
+            3 * 4 * 5
+        );
+        let solid: Vec<VoxelPos> = queue
+            .queue
+            .iter()
+            .filter(|(_, block)| *block == STONE)
+            .map(|(position, _)| *position)
+            .collect();
+        assert_eq!(solid, vec![VoxelPos::new(11, 2, 3)]);
+    }
+
+    #[test]
+    fn states_and_block_entities_go_with_the_blocks() {
+        let mut voxel_data = VoxelData::air([ChunkIndex { x: 0, y: 0, z: 0 }]);
+        let (stairs, sign) = (VoxelPos::new(1, 0, 2), VoxelPos::new(2, 1, 0));
+        voxel_data.set_block(stairs, STONE_STAIRS);
+        voxel_data.set_state(stairs, 3);
+        voxel_data.set_block(sign, SIGN);
+        let text = BlockEntity::Sign(Sign {
+            text: "Home".to_string(),
+        });
+        voxel_data.set_entity(sign, Some(text.clone()));
+
+        let schematic =
+            Schematic::capture(&voxel_data, VoxelPos::new(0, 0, 0), VoxelPos::new(3, 2, 3));
+        assert!(schematic.validate().is_ok());
+        let bytes = codec::encode_schematic(&schematic);
+        assert_eq!(codec::decode_schematic(&bytes).unwrap(), schematic);
+
+        let mut queue = VoxelModifyQueue::default();
+        let origin = VoxelPos::new(20, 5, 0);
+        schematic.paste(origin, &mut queue);
+        assert_eq!(queue.states, vec![(VoxelPos::new(21, 5, 2), 3)]);
+        assert_eq!(queue.entities, vec![(VoxelPos::new(22, 6, 0), text)]);
+    }
+}
@@ -0,0 +1,100 @@
+use std::{fs, path::PathBuf};
+
+use bevy::{prelude::*, render::view::screenshot::ScreenshotManager, window::PrimaryWindow};
+use bevy_inspector_egui::prelude::*;
+
+use crate::{
+    input::{InputAction, PlayerInput},
+    storage,
+};
+
+#[derive(Reflect, Resource, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub struct ScreenshotSettings {
+    pub timelapse: bool, // save a frame every `interval` seconds
+    pub interval: f32,
+}
+
+impl Default for ScreenshotSettings {
+    fn default() -> Self {
+        ScreenshotSettings {
+            timelapse: false,
+            interval: 10.0,
+        }
+    }
+}
+
+/// A running timelapse, its frames are numbered in their own folder
+pub struct Timelapse {
+    dir: PathBuf,
+    frame: u32,
+    timer: Timer,
+}
+
+fn screenshots_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("mcrs").join("screenshots"))
+}
+
+fn timestamp() -> u128 {
+    storage::unix_time().as_millis()
+}
+
+fn save(manager: &mut ScreenshotManager, window: Entity, dir: PathBuf, file_name: String) {
+    if let Err(err) = fs::create_dir_all(&dir) {
+        warn!("Cannot create screenshot folder {}: {err}", dir.display());
+        return;
+    }
+    if let Err(err) = manager.save_screenshot_to_disk(window, dir.join(file_name)) {
+        warn!("Screenshot skipped: {err}");
+    }
+}
+
+/// F2 saves the current frame, the timelapse saves one every few seconds while enabled
+pub fn capture_screenshots(
+    time: Res<Time>,
+    player_input: Res<PlayerInput>,
+    screenshot_settings: Res<ScreenshotSettings>,
+    primary_query: Query<Entity, With<PrimaryWindow>>,
+    mut manager: ResMut<ScreenshotManager>,
+    mut timelapse: Local<Option<Timelapse>>,
+) {
+    let Ok(window) = primary_query.get_single() else {
+        return;
+    };
+    let Some(dir) = screenshots_dir() else {
+        return;
+    };
+
+    if player_input.actions.just_pressed(InputAction::Screenshot) {
+        save(
+            &mut manager,
+            window,
+            dir.clone(),
+            format!("screenshot-{}.png", timestamp()),
+        );
+    }
+
+    if !screenshot_settings.timelapse {
+        *timelapse = None;
+        return;
+    }
+    let interval = screenshot_settings.interval.max(0.1);
+    let timelapse = timelapse.get_or_insert_with(|| Timelapse {
+        dir: dir.join(format!("timelapse-{}", timestamp())),
+        frame: 0,
+        timer: Timer::from_seconds(interval, TimerMode::Repeating),
+    });
+    if timelapse.timer.duration().as_secs_f32() != interval {
+        timelapse.timer = Timer::from_seconds(interval, TimerMode::Repeating);
+    }
+    // real time, so the timelapse keeps going while the game is paused
+    if timelapse.timer.tick(time.raw_delta()).just_finished() {
+        save(
+            &mut manager,
+            window,
+            timelapse.dir.clone(),
+            format!("frame-{:05}.png", timelapse.frame),
+        );
+        timelapse.frame += 1;
+    }
+}
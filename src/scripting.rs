@@ -0,0 +1,369 @@
+//! Gameplay scripts: Rhai files in `assets/scripts`, loaded at startup, so gameplay
+//! can be tried out without recompiling. Scripts get a small API:
+//!
+//! - `get_block(x, y, z)` and `set_block(x, y, z, block)`, blocks by id
+//! - `raycast([x, y, z], [dx, dy, dz], distance)`, the first block hit as `[x, y, z]` or `()`
+//! - `spawn_mob("animal" | "enemy", x, y, z)`
+//! - `register_command(name, function)`, `/name words...` then calls `function(words)`
+//!   and shows what it returns in the console
+//! - `say(text)`, a line in the console
+//!
+//! and the hooks `on_block_break(x, y, z, block)`, when the player mines a block, and
+//! `on_tick()`, every `SCRIPT_TICK`, are called in each script that defines them.
+
+use std::sync::{Arc, Mutex};
+
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, FnPtr, Scope, AST};
+
+use crate::{
+    command::{self, CommandRequest, CommandResponse},
+    mob::{self, MobAssets, MobKind},
+    protection::Editor,
+    target_voxel,
+    voxel::{last_block, BlockId, VoxelData, VoxelModifyQueue, VoxelPos},
+    BlockBroken,
+};
+
+const SCRIPTS_DIR: &str = "assets/scripts";
+pub const SCRIPT_TICK: f32 = 0.05; // seconds between `on_tick` calls
+const MAX_RAYCAST: f32 = 256.0;
+
+/// What the scripts see of the game while they run, and what they ask of it
+#[derive(Default)]
+struct ScriptWorld {
+    voxel_data: VoxelData, // lent for the duration of a call
+    loading: usize,        // script whose top level is running
+    edits: Vec<(VoxelPos, BlockId)>,
+    mobs: Vec<(MobKind, Vec3)>,
+    messages: Vec<String>,
+    commands: HashMap<String, (usize, FnPtr)>, // by name, with the script defining them
+}
+
+struct Script {
+    name: String,
+    ast: AST,
+    scope: Scope<'static>,
+}
+
+impl Script {
+    fn defines(&self, function: &str) -> bool {
+        self.ast.iter_functions().any(|f| f.name == function)
+    }
+}
+
+#[derive(Resource)]
+pub struct Scripts {
+    engine: Engine,
+    scripts: Vec<Script>,
+    world: Arc<Mutex<ScriptWorld>>,
+}
+
+impl Default for Scripts {
+    fn default() -> Self {
+        let world = Arc::new(Mutex::new(ScriptWorld::default()));
+        let mut engine = Engine::new();
+
+        let shared = world.clone();
+        engine.register_fn("get_block", move |x: i64, y: i64, z: i64| {
+            let world = shared.lock().unwrap();
+            world.voxel_data.get_block(voxel_pos(x, y, z)).0 as i64
+        });
+        let shared = world.clone();
+        engine.register_fn(
+            "set_block",
+            move |x: i64, y: i64, z: i64, block: i64| -> Result<(), Box<EvalAltResult>> {
+                let block = u8::try_from(block)
+                    .ok()
+                    .map(BlockId)
+                    .filter(|&block| block <= last_block())
+                    .ok_or(format!("unknown block {}", block))?;
+                let mut world = shared.lock().unwrap();
+                world.edits.push((voxel_pos(x, y, z), block));
+                Ok(())
+            },
+        );
+        let shared = world.clone();
+        engine.register_fn(
+            "raycast",
+            move |origin: Array, direction: Array, distance: f64| -> Dynamic {
+                let (Some(origin), Some(direction)) = (vec3(&origin), vec3(&direction)) else {
+                    return Dynamic::UNIT;
+                };
+                let world = shared.lock().unwrap();
+                let range = (distance as f32).min(MAX_RAYCAST);
+                match target_voxel(&world.voxel_data, origin, direction.normalize(), range) {
+                    Some((hit, _)) => {
+                        let hit: Array = hit.0.to_array().map(|v| Dynamic::from(v as i64)).into();
+                        Dynamic::from_array(hit)
+                    }
+                    None => Dynamic::UNIT,
+                }
+            },
+        );
+        let shared = world.clone();
+        engine.register_fn(
+            "spawn_mob",
+            move |kind: &str, x: f64, y: f64, z: f64| -> Result<(), Box<EvalAltResult>> {
+                let kind = match kind {
+                    "animal" => MobKind::Animal,
+                    "enemy" => MobKind::Enemy,
+                    _ => return Err(format!("unknown mob '{}'", kind).into()),
+                };
+                let position = Vec3::new(x as f32, y as f32, z as f32);
+                shared.lock().unwrap().mobs.push((kind, position));
+                Ok(())
+            },
+        );
+        let shared = world.clone();
+        engine.register_fn(
+            "register_command",
+            move |name: &str, function: FnPtr| -> Result<(), Box<EvalAltResult>> {
+                if command::COMMANDS.contains(&name) {
+                    return Err(format!("/{} is a built-in command", name).into());
+                }
+                let mut world = shared.lock().unwrap();
+                let script = world.loading;
+                world.commands.insert(name.to_string(), (script, function));
+                Ok(())
+            },
+        );
+        let shared = world.clone();
+        engine.register_fn("say", move |text: &str| {
+            shared.lock().unwrap().messages.push(text.to_string());
+        });
+
+        Scripts {
+            engine,
+            scripts: Vec::new(),
+            world,
+        }
+    }
+}
+
+fn voxel_pos(x: i64, y: i64, z: i64) -> VoxelPos {
+    VoxelPos::new(x as i32, y as i32, z as i32)
+}
+
+fn vec3(array: &Array) -> Option<Vec3> {
+    let coordinate = |value: &Dynamic| {
+        value
+            .as_float()
+            .ok()
+            .or_else(|| value.as_int().ok().map(|v| v as f64))
+            .map(|v| v as f32)
+    };
+    match array.as_slice() {
+        [x, y, z] => Some(Vec3::new(coordinate(x)?, coordinate(y)?, coordinate(z)?)),
+        _ => None,
+    }
+}
+
+impl Scripts {
+    /// Compile a script and run its top level, where it registers its commands
+    fn load(&mut self, name: &str, source: &str) -> Result<(), String> {
+        let ast = self.engine.compile(source).map_err(|err| err.to_string())?;
+        let mut scope = Scope::new();
+        let index = self.scripts.len();
+        self.world.lock().unwrap().loading = index;
+        if let Err(err) = self.engine.run_ast_with_scope(&mut scope, &ast) {
+            // the commands it registered before failing go with it
+            let mut world = self.world.lock().unwrap();
+            world.commands.retain(|_, (script, _)| *script != index);
+            return Err(err.to_string());
+        }
+        self.scripts.push(Script {
+            name: name.to_string(),
+            ast,
+            scope,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_scripts(mut scripts: ResMut<Scripts>, mut responses: EventWriter<CommandResponse>) {
+    let Ok(entries) = std::fs::read_dir(SCRIPTS_DIR) else {
+        return;
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "rhai")
+        })
+        .collect();
+    paths.sort();
+    for path in paths {
+        let name = path.display().to_string();
+        let result = std::fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|source| scripts.load(&name, &source));
+        match result {
+            Ok(()) => info!("Loaded script {}", name),
+            Err(err) => {
+                warn!("Failed to load script {}: {}", name, err);
+                responses.send(CommandResponse {
+                    message: format!("Error in {}: {}", name, err),
+                });
+            }
+        }
+    }
+}
+
+/// The web build has no folder to read scripts from
+#[cfg(target_arch = "wasm32")]
+pub fn load_scripts() {}
+
+/// Runs scripts against the game, then applies what they asked for
+#[derive(SystemParam)]
+pub struct ScriptHost<'w, 's> {
+    commands: Commands<'w, 's>,
+    scripts: ResMut<'w, Scripts>,
+    voxel_data: ResMut<'w, VoxelData>,
+    voxel_modify_queue: ResMut<'w, VoxelModifyQueue>,
+    mob_assets: Res<'w, MobAssets>,
+    responses: EventWriter<'w, CommandResponse>,
+}
+
+impl ScriptHost<'_, '_> {
+    /// Lend the world to the scripts while `f` runs them. The lines `f` returns are shown
+    /// in the console with what the scripts said
+    fn run(&mut self, f: impl FnOnce(&Engine, &mut [Script]) -> Vec<String>) {
+        let Scripts {
+            engine,
+            scripts,
+            world,
+        } = &mut *self.scripts;
+        if scripts.is_empty() {
+            return;
+        }
+        world.lock().unwrap().voxel_data = std::mem::take(&mut *self.voxel_data);
+        let lines = f(engine, scripts);
+
+        let mut world = world.lock().unwrap();
+        *self.voxel_data = std::mem::take(&mut world.voxel_data);
+        self.voxel_modify_queue.guarded.extend(
+            world
+                .edits
+                .drain(..)
+                .map(|(position, block)| (position, block, Editor::World)),
+        );
+        for (kind, position) in world.mobs.drain(..) {
+            mob::spawn_mob(&mut self.commands, &self.mob_assets, kind, position);
+        }
+        for message in world.messages.drain(..).chain(lines) {
+            self.responses.send(CommandResponse { message });
+        }
+    }
+
+    /// Call `hook` in every script defining it
+    fn hook(&mut self, hook: &str, args: impl rhai::FuncArgs + Clone) {
+        self.run(|engine, scripts| {
+            let mut errors = Vec::new();
+            for script in scripts.iter_mut().filter(|script| script.defines(hook)) {
+                let result =
+                    engine.call_fn::<Dynamic>(&mut script.scope, &script.ast, hook, args.clone());
+                if let Err(err) = result {
+                    warn!("{} in {} failed: {}", hook, script.name, err);
+                    errors.push(format!("Error in {}: {}", script.name, err));
+                }
+            }
+            errors
+        });
+    }
+}
+
+pub fn script_ticks(mut host: ScriptHost) {
+    host.hook("on_tick", ());
+}
+
+pub fn script_block_breaks(mut host: ScriptHost, mut broken_events: EventReader<BlockBroken>) {
+    for event in broken_events.iter() {
+        let VoxelPos(position) = event.position;
+        host.hook(
+            "on_block_break",
+            (
+                position.x as i64,
+                position.y as i64,
+                position.z as i64,
+                event.block.0 as i64,
+            ),
+        );
+    }
+}
+
+/// Run the commands scripts registered, the built-in ones are left to `run_commands`
+pub fn script_commands(mut host: ScriptHost, mut requests: EventReader<CommandRequest>) {
+    for request in requests.iter() {
+        let mut words = request
+            .line
+            .trim()
+            .trim_start_matches('/')
+            .split_whitespace();
+        let Some(name) = words.next() else {
+            continue;
+        };
+        if command::COMMANDS.contains(&name) {
+            continue;
+        }
+        let args: Array = words.map(|word| Dynamic::from(word.to_string())).collect();
+        let registered = host
+            .scripts
+            .world
+            .lock()
+            .unwrap()
+            .commands
+            .get(name)
+            .cloned();
+        let Some((script, function)) = registered else {
+            host.responses.send(CommandResponse {
+                message: format!("Error: unknown command /{}", name),
+            });
+            continue;
+        };
+        host.run(|engine, scripts| {
+            match function.call::<Dynamic>(engine, &scripts[script].ast, (args,)) {
+                Ok(result) if result.is_unit() => Vec::new(),
+                Ok(result) => vec![result.to_string()],
+                Err(err) => vec![format!("Error: {}", err)],
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripts_register_commands_and_queue_edits() {
+        let mut scripts = Scripts::default();
+        scripts
+            .load(
+                "test",
+                r#"
+                    fn pillar(words) { set_block(0, 1, 0, 4); "built" }
+                    register_command("pillar", pillar);
+                "#,
+            )
+            .unwrap();
+        assert!(scripts
+            .load("bad", r#"register_command("tp", |w| 0);"#)
+            .is_err());
+
+        let (script, function) = scripts.world.lock().unwrap().commands["pillar"].clone();
+        let result = function
+            .call::<Dynamic>(
+                &scripts.engine,
+                &scripts.scripts[script].ast,
+                (Array::new(),),
+            )
+            .unwrap();
+        assert_eq!(result.to_string(), "built");
+        assert_eq!(
+            scripts.world.lock().unwrap().edits,
+            vec![(VoxelPos::new(0, 1, 0), BlockId(4))]
+        );
+    }
+}
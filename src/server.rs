@@ -0,0 +1,391 @@
+use std::{collections::HashMap, fmt};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_inspector_egui::{prelude::ReflectInspectorOptions, InspectorOptions};
+
+use crate::{
+    autotune::TunedQuality,
+    game_mode::GameMode,
+    item::Inventory,
+    protection::{Claims, Editor},
+    voxel::{
+        self, BlockId, BlockState, BlockTag, VoxelData, VoxelModifyQueue, VoxelPos, VoxelSettings,
+        AIR,
+    },
+    BlockBroken,
+};
+
+/// Client id of the local player, in single player the server runs in-process
+pub const LOCAL_CLIENT: u32 = 0;
+const REACH_SLACK: f32 = 2.0; // past the interact distance, for the block's size and a door's top
+
+/// Limits the server applies to every client
+#[derive(Reflect, Resource, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub struct ServerSettings {
+    pub max_sight_range: u8, // in chunk
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        ServerSettings {
+            max_sight_range: 16,
+        }
+    }
+}
+
+/// Render distance a client would like chunks streamed for
+#[derive(Event, Debug, Clone, Copy)]
+pub struct SightRangeRequest {
+    pub client: u32,
+    pub sight_range: u8,
+}
+
+/// Render distance the server agreed to stream to a client
+#[derive(Event, Debug, Clone, Copy)]
+pub struct SightRangeGranted {
+    pub client: u32,
+    pub sight_range: u8,
+}
+
+/// Negotiated render distance of the local client, none until the server answered
+#[derive(Resource, Default)]
+pub struct GrantedSightRange {
+    pub sight_range: Option<u8>,
+}
+
+/// Ask the server for the sight range in use whenever it changes
+pub fn request_sight_range(tuned: Res<TunedQuality>, mut requests: EventWriter<SightRangeRequest>) {
+    if tuned.is_changed() {
+        requests.send(SightRangeRequest {
+            client: LOCAL_CLIENT,
+            sight_range: tuned.sight_range,
+        });
+    }
+}
+
+/// Server side: clamp every request to the configured maximum, and re-grant
+/// all clients when the maximum changes
+pub fn grant_sight_range(
+    server_settings: Res<ServerSettings>,
+    mut requests: EventReader<SightRangeRequest>,
+    mut granted: EventWriter<SightRangeGranted>,
+    mut requested: Local<HashMap<u32, u8>>,
+) {
+    let mut changed: Vec<u32> = Vec::new();
+    for request in requests.iter() {
+        requested.insert(request.client, request.sight_range);
+        changed.push(request.client);
+    }
+    if server_settings.is_changed() {
+        changed = requested.keys().copied().collect();
+    }
+
+    for client in changed {
+        granted.send(SightRangeGranted {
+            client,
+            sight_range: requested[&client].min(server_settings.max_sight_range),
+        });
+    }
+}
+
+pub fn apply_granted_sight_range(
+    mut granted: EventReader<SightRangeGranted>,
+    mut granted_sight_range: ResMut<GrantedSightRange>,
+) {
+    for grant in granted.iter() {
+        if grant.client == LOCAL_CLIENT {
+            granted_sight_range.sight_range = Some(grant.sight_range);
+        }
+    }
+}
+
+/// A block edit a client made and applied ahead of the server, for the server to check
+#[derive(Event, Debug, Clone, Copy)]
+pub struct EditRequest {
+    pub client: u32,
+    pub id: u32, // the client's, to match the verdict
+    pub position: VoxelPos,
+    pub block: BlockId,
+    pub state: BlockState,
+    pub eye: Vec3, // where the client looked from
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditRejection {
+    OutOfReach,
+    GameMode,
+    Unbreakable,
+    Occupied,  // placing into a block that isn't air
+    Protected, // inside somebody else's claim
+}
+
+impl fmt::Display for EditRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditRejection::OutOfReach => write!(f, "out of reach"),
+            EditRejection::GameMode => write!(f, "not allowed in this game mode"),
+            EditRejection::Unbreakable => write!(f, "the block can't be mined"),
+            EditRejection::Occupied => write!(f, "there's a block in the way"),
+            EditRejection::Protected => write!(f, "the block is in somebody else's claim"),
+        }
+    }
+}
+
+/// The server's answer to an `EditRequest`, sent back to the client that made it
+#[derive(Event, Debug, Clone, Copy)]
+pub struct EditVerdict {
+    pub client: u32,
+    pub id: u32,
+    pub result: Result<(), EditRejection>,
+}
+
+/// An edit the server accepted, to be broadcast by the network layer to the other clients
+#[derive(Event, Debug, Clone, Copy)]
+pub struct EditBroadcast {
+    pub client: u32, // the one that made it
+    pub position: VoxelPos,
+    pub block: BlockId,
+    pub state: BlockState,
+}
+
+/// Whether the server lets a client make an edit to the world as it is now
+fn validate_edit(
+    request: &EditRequest,
+    voxel_data: &VoxelData,
+    claims: &Claims,
+    interact_distance: f32,
+    game_mode: GameMode,
+) -> Result<(), EditRejection> {
+    if game_mode == GameMode::Spectator {
+        return Err(EditRejection::GameMode);
+    }
+    if !claims.may_edit(request.client, request.position) {
+        return Err(EditRejection::Protected);
+    }
+    let center = request.position.to_world() + Vec3::splat(0.5);
+    if request.eye.distance(center) > interact_distance + REACH_SLACK {
+        return Err(EditRejection::OutOfReach);
+    }
+    let current = voxel_data.get_block(request.position);
+    if request.block == AIR && voxel::has_tag(current, BlockTag::Unbreakable) {
+        return Err(EditRejection::Unbreakable);
+    }
+    if request.block != AIR && current != AIR {
+        return Err(EditRejection::Occupied);
+    }
+    Ok(())
+}
+
+/// What an edit is checked against: the world, the claims, the reach and the game mode
+#[derive(SystemParam)]
+pub struct EditRules<'w> {
+    voxel_settings: Res<'w, VoxelSettings>,
+    game_mode: Res<'w, GameMode>,
+    voxel_data: Res<'w, VoxelData>,
+    claims: Res<'w, Claims>,
+}
+
+impl EditRules<'_> {
+    fn check(&self, request: &EditRequest) -> Result<(), EditRejection> {
+        validate_edit(
+            request,
+            &self.voxel_data,
+            &self.claims,
+            self.voxel_settings.interact_distance,
+            *self.game_mode,
+        )
+    }
+}
+
+/// Server side: check the edits clients made, answer each and apply and broadcast the
+/// accepted ones. The local client applied its own already, the server shares its world
+pub fn validate_edits(
+    rules: EditRules,
+    mut requests: EventReader<EditRequest>,
+    mut verdicts: EventWriter<EditVerdict>,
+    mut broadcasts: EventWriter<EditBroadcast>,
+    mut voxel_modify_queue: ResMut<VoxelModifyQueue>,
+) {
+    for request in requests.iter() {
+        let result = rules.check(request);
+        verdicts.send(EditVerdict {
+            client: request.client,
+            id: request.id,
+            result,
+        });
+        if result.is_err() {
+            continue;
+        }
+        if request.client != LOCAL_CLIENT {
+            voxel_modify_queue.guarded.push((
+                request.position,
+                request.block,
+                Editor::Client(request.client),
+            ));
+            if request.state != 0 {
+                voxel_modify_queue
+                    .states
+                    .push((request.position, request.state));
+            }
+        }
+        broadcasts.send(EditBroadcast {
+            client: request.client,
+            position: request.position,
+            block: request.block,
+            state: request.state,
+        });
+    }
+}
+
+/// An edit applied ahead of the server's verdict, with what to put back if it's rejected
+#[derive(Debug, Clone, Copy)]
+struct PendingEdit {
+    position: VoxelPos,
+    block: BlockId,
+    previous: BlockId,
+    previous_state: BlockState,
+    refund: Option<BlockId>, // taken from the inventory to place the block
+}
+
+/// Edits of the local client waiting for the server's verdict, by request id
+#[derive(Resource, Default)]
+pub struct PendingEdits {
+    next_id: u32,
+    edits: HashMap<u32, PendingEdit>,
+}
+
+/// Edits of the local player: applied right away and sent to the server to check
+#[derive(SystemParam)]
+pub struct PredictedEdits<'w> {
+    queue: ResMut<'w, VoxelModifyQueue>,
+    pending: ResMut<'w, PendingEdits>,
+    requests: EventWriter<'w, EditRequest>,
+}
+
+impl PredictedEdits<'_> {
+    /// Set `block` with `state` at `position` as seen from `eye`. `refund` is the item
+    /// to give back if the server rejects the edit
+    pub fn edit(
+        &mut self,
+        voxel_data: &VoxelData,
+        eye: Vec3,
+        position: VoxelPos,
+        (block, state): (BlockId, BlockState),
+        refund: Option<BlockId>,
+    ) {
+        self.queue
+            .guarded
+            .push((position, block, Editor::Client(LOCAL_CLIENT)));
+        if state != 0 {
+            self.queue.states.push((position, state));
+        }
+        let id = self.pending.next_id;
+        self.pending.next_id = id.wrapping_add(1);
+        self.pending.edits.insert(
+            id,
+            PendingEdit {
+                position,
+                block,
+                previous: voxel_data.get_block(position),
+                previous_state: voxel_data.get_state(position),
+                refund,
+            },
+        );
+        self.requests.send(EditRequest {
+            client: LOCAL_CLIENT,
+            id,
+            position,
+            block,
+            state,
+            eye,
+        });
+    }
+}
+
+/// Settle the local client's edits: accepted blocks mined drop their items, rejected
+/// edits are rolled back and what they took from the inventory given back
+pub fn apply_edit_verdicts(
+    mut verdicts: EventReader<EditVerdict>,
+    mut pending: ResMut<PendingEdits>,
+    mut voxel_modify_queue: ResMut<VoxelModifyQueue>,
+    mut inventory: ResMut<Inventory>,
+    mut broken_events: EventWriter<BlockBroken>,
+) {
+    for verdict in verdicts.iter() {
+        if verdict.client != LOCAL_CLIENT {
+            continue;
+        }
+        let Some(edit) = pending.edits.remove(&verdict.id) else {
+            continue;
+        };
+        match verdict.result {
+            Ok(()) => {
+                // mined blocks drop once the server agreed they're gone
+                if edit.block == AIR && edit.previous != AIR {
+                    broken_events.send(BlockBroken {
+                        position: edit.position,
+                        block: edit.previous,
+                    });
+                }
+            }
+            Err(rejection) => {
+                info!("Edit at {:?} rejected: {}", edit.position.0, rejection);
+                voxel_modify_queue
+                    .queue
+                    .push((edit.position, edit.previous));
+                if edit.previous_state != 0 {
+                    voxel_modify_queue
+                        .states
+                        .push((edit.position, edit.previous_state));
+                }
+                if let Some(item) = edit.refund {
+                    inventory.add(item, 1);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::{ChunkIndex, STONE};
+
+    #[test]
+    fn edits_are_rejected_out_of_reach_by_spectators_or_in_claims() {
+        let voxel_data = VoxelData::air([ChunkIndex { x: 0, y: 0, z: 0 }]);
+        let position = VoxelPos::new(3, 4, 5);
+        let request = EditRequest {
+            client: 1,
+            id: 0,
+            position,
+            block: STONE,
+            state: 0,
+            eye: Vec3::new(3.5, 6.0, 8.0),
+        };
+        let mut claims = Claims::default();
+        let validate = |request: &EditRequest, claims: &Claims, game_mode| {
+            validate_edit(request, &voxel_data, claims, 10.0, game_mode)
+        };
+
+        assert_eq!(validate(&request, &claims, GameMode::Survival), Ok(()));
+        assert_eq!(
+            validate(&request, &claims, GameMode::Spectator),
+            Err(EditRejection::GameMode)
+        );
+        let far = EditRequest {
+            eye: Vec3::new(3.5, 6.0, 30.0),
+            ..request
+        };
+        assert_eq!(
+            validate(&far, &claims, GameMode::Creative),
+            Err(EditRejection::OutOfReach)
+        );
+        claims.claim(2, "base", position, position).unwrap();
+        assert_eq!(
+            validate(&request, &claims, GameMode::Creative),
+            Err(EditRejection::Protected)
+        );
+    }
+}
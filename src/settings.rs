@@ -0,0 +1,159 @@
+use std::borrow::Cow;
+
+use bevy::{app::AppExit, ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::CameraSettings,
+    crash::SafeMode,
+    hud::HudSettings,
+    input::KeyBindings,
+    storage::{self, Folder},
+    voxel::VoxelSettings,
+    DebugSettings, GraphicsSettings, MouseSettings,
+};
+
+const SAVE_DELAY: f32 = 1.0; // seconds to wait after the last change before writing the file
+
+/// Everything persisted in the settings file, missing entries fall back to the defaults
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct SettingsFile {
+    mouse: MouseSettings,
+    voxel: VoxelSettings,
+    debug: DebugSettings,
+    graphics: GraphicsSettings,
+    camera: CameraSettings,
+    hud: HudSettings,
+    controls: KeyBindings,
+}
+
+/// Same layout as `SettingsFile`, borrowing the live resources for saving, or copies of them
+/// without what's only for the session
+#[derive(Serialize)]
+struct SettingsFileRef<'a> {
+    mouse: &'a MouseSettings,
+    voxel: Cow<'a, VoxelSettings>,
+    debug: Cow<'a, DebugSettings>,
+    graphics: Cow<'a, GraphicsSettings>,
+    camera: &'a CameraSettings,
+    hud: &'a HudSettings,
+    controls: &'a KeyBindings,
+}
+
+const SETTINGS_FILE: &str = "settings.ron";
+
+pub fn load_settings(
+    mut mouse_settings: ResMut<MouseSettings>,
+    mut voxel_settings: ResMut<VoxelSettings>,
+    mut debug_settings: ResMut<DebugSettings>,
+    mut graphics_settings: ResMut<GraphicsSettings>,
+    mut camera_settings: ResMut<CameraSettings>,
+    mut hud_settings: ResMut<HudSettings>,
+    mut key_bindings: ResMut<KeyBindings>,
+) {
+    let Some(contents) = storage::read(Folder::Config, SETTINGS_FILE)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+    else {
+        return;
+    };
+    match ron::from_str::<SettingsFile>(&contents) {
+        Ok(file) => {
+            *mouse_settings = MouseSettings {
+                ui_mode: mouse_settings.ui_mode,
+                ..file.mouse
+            };
+            *voxel_settings = file.voxel;
+            *debug_settings = file.debug;
+            *graphics_settings = file.graphics;
+            *camera_settings = file.camera;
+            *hud_settings = file.hud;
+            *key_bindings = file.controls;
+        }
+        Err(err) => warn!(
+            "Ignoring settings file {}: {}",
+            storage::describe(Folder::Config, SETTINGS_FILE),
+            err
+        ),
+    }
+}
+
+/// The live resources the settings file is written from, leaving out safe mode
+#[derive(SystemParam)]
+pub struct SavedSettings<'w> {
+    safe_mode: Res<'w, SafeMode>,
+    mouse: Res<'w, MouseSettings>,
+    voxel: Res<'w, VoxelSettings>,
+    debug: Res<'w, DebugSettings>,
+    graphics: Res<'w, GraphicsSettings>,
+    camera: Res<'w, CameraSettings>,
+    hud: Res<'w, HudSettings>,
+    controls: Res<'w, KeyBindings>,
+}
+
+impl SavedSettings<'_> {
+    fn is_changed(&self) -> bool {
+        self.mouse.is_changed()
+            || self.voxel.is_changed()
+            || self.debug.is_changed()
+            || self.graphics.is_changed()
+            || self.camera.is_changed()
+            || self.hud.is_changed()
+            || self.controls.is_changed()
+    }
+
+    fn file(&self) -> SettingsFileRef<'_> {
+        let (voxel, debug, graphics) = if self.safe_mode.is_active() {
+            let (voxel, debug, graphics) =
+                self.safe_mode
+                    .saved(&self.voxel, &self.debug, &self.graphics);
+            (Cow::Owned(voxel), Cow::Owned(debug), Cow::Owned(graphics))
+        } else {
+            (
+                Cow::Borrowed(&*self.voxel),
+                Cow::Borrowed(&*self.debug),
+                Cow::Borrowed(&*self.graphics),
+            )
+        };
+        SettingsFileRef {
+            mouse: &self.mouse,
+            voxel,
+            debug,
+            graphics,
+            camera: &self.camera,
+            hud: &self.hud,
+            controls: &self.controls,
+        }
+    }
+}
+
+/// Write the settings once they stopped changing for a moment, and on exit. Runs in `Last`
+/// so it's after every system that sends `AppExit`
+pub fn save_settings(
+    time: Res<Time>,
+    settings: SavedSettings,
+    mut exit_events: EventReader<AppExit>,
+    mut changed_at: Local<Option<f32>>,
+) {
+    if settings.is_changed() {
+        *changed_at = Some(time.elapsed_seconds());
+    }
+    let exiting = exit_events.iter().count() > 0;
+    let settled = changed_at.is_some_and(|at| time.elapsed_seconds() - at >= SAVE_DELAY);
+    if changed_at.is_none() || !(settled || exiting) {
+        return;
+    }
+    *changed_at = None;
+
+    let result = ron::ser::to_string_pretty(&settings.file(), ron::ser::PrettyConfig::default())
+        .map_err(|err| err.to_string())
+        .and_then(|contents| storage::write(Folder::Config, SETTINGS_FILE, contents.as_bytes()));
+    if let Err(err) = result {
+        warn!(
+            "Failed to save settings to {}: {}",
+            storage::describe(Folder::Config, SETTINGS_FILE),
+            err
+        );
+    }
+}
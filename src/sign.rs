@@ -0,0 +1,255 @@
+//! Signs: a board with a line of text, written when the sign is placed and
+//! rewritten when it's used. The text of the signs near the camera floats on
+//! their front, following them across the screen.
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+use smooth_bevy_cameras::controllers::fps::FpsCameraController;
+
+use crate::{
+    block_entity::BlockEntity,
+    interact::OpenBlock,
+    voxel::{self, VoxelData, VoxelLocalIndex, VoxelPos, CHUNK_SIZE},
+    InputMode,
+};
+
+pub const SIGN_TEXT_LIMIT: usize = 48; // characters on a sign
+const TEXT_DISTANCE: f32 = 16.0; // signs further from the camera don't show their text
+const FONT_SIZE: f32 = 18.0;
+const TEXT_WIDTH: f32 = 160.0; // longer text wraps
+const BOARD_DEPTH: f32 = 3.0 / 16.0; // the front of the board from the back of its cell
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Sign {
+    pub text: String,
+}
+
+/// The sign being written
+#[derive(Resource, Default)]
+pub struct SignEditor {
+    pub open: Option<VoxelPos>,
+    text: String,
+}
+
+pub fn sign_editor_closed(sign_editor: Res<SignEditor>) -> bool {
+    sign_editor.open.is_none()
+}
+
+/// The window writing a sign, opened when a sign is placed or used. The text goes
+/// on the sign as it's typed, Enter or Esc closes the window
+pub fn sign_editor(
+    mut contexts: EguiContexts,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut open_events: EventReader<OpenBlock>,
+    mut voxel_data: ResMut<VoxelData>,
+    mut sign_editor: ResMut<SignEditor>,
+    mut input_mode: InputMode,
+) {
+    let was_open = sign_editor.open.is_some();
+    for event in open_events.iter() {
+        if let Some(BlockEntity::Sign(sign)) = voxel_data.get_entity(event.position) {
+            sign_editor.text = sign.text.clone();
+            sign_editor.open = Some(event.position);
+        }
+    }
+    let Some(position) = sign_editor.open else {
+        return;
+    };
+    let sign_editor = sign_editor.as_mut();
+    let mut open = !(was_open && keyboard_input.just_pressed(KeyCode::Escape));
+    if open {
+        egui::Window::new("Sign")
+            .collapsible(false)
+            .resizable(false)
+            .show(contexts.ctx_mut(), |ui| {
+                let input = ui.add(egui::TextEdit::singleline(&mut sign_editor.text));
+                if !was_open {
+                    input.request_focus();
+                }
+                let entered = input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                if ui.button("Done").clicked() || entered {
+                    open = false;
+                }
+            });
+        if sign_editor.text.chars().count() > SIGN_TEXT_LIMIT {
+            sign_editor.text = sign_editor.text.chars().take(SIGN_TEXT_LIMIT).collect();
+        }
+    }
+
+    let changed = match voxel_data.get_entity(position) {
+        Some(BlockEntity::Sign(sign)) => sign.text != sign_editor.text,
+        // the sign is gone
+        _ => {
+            open = false;
+            false
+        }
+    };
+    if changed {
+        if let Some(BlockEntity::Sign(sign)) = voxel_data.entity_mut(position) {
+            sign.text = sign_editor.text.clone();
+        }
+    }
+
+    if !open {
+        sign_editor.open = None;
+    }
+    // the cursor is free while the window is open
+    if open != input_mode.ms.ui_mode && (open || was_open) {
+        input_mode.set_ui_mode(open);
+    }
+}
+
+/// Text of the sign at `position`, shown on its front
+#[derive(Component)]
+pub struct SignText {
+    position: VoxelPos,
+}
+
+/// Signs near the camera whose text is on the screen
+#[derive(Resource, Default)]
+pub struct SignTexts {
+    texts: HashMap<VoxelPos, Entity>,
+}
+
+/// Put the text of the signs near the camera over their front, hidden while the
+/// camera is behind a sign or the sign is off the screen
+pub fn update_sign_texts(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    voxel_data: Res<VoxelData>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<FpsCameraController>>,
+    mut sign_texts: ResMut<SignTexts>,
+    mut text_query: Query<(&SignText, &mut Text, &mut Style, &mut Visibility, &Node)>,
+) {
+    let Ok((camera, camera_transform)) = camera_query.get_single() else {
+        return;
+    };
+    let eye = camera_transform.translation();
+
+    // chunks are skipped by the distance to their center first
+    let chunk_radius = CHUNK_SIZE as f32 * 3f32.sqrt() / 2.0;
+    let mut nearby = HashMap::new();
+    for chunk in voxel_data.iter_chunks() {
+        if chunk.entities.is_empty() {
+            continue;
+        }
+        let center =
+            (chunk.index.origin().as_vec3() + Vec3::splat(CHUNK_SIZE as f32 / 2.0)).distance(eye);
+        if center > TEXT_DISTANCE + chunk_radius {
+            continue;
+        }
+        for (packed, entity) in chunk.entities.iter() {
+            let (BlockEntity::Sign(sign), Some(local)) =
+                (entity, VoxelLocalIndex::from_packed(*packed))
+            else {
+                continue;
+            };
+            let position = VoxelPos::from_parts(chunk.index, local);
+            if !sign.text.is_empty()
+                && (position.to_world() + Vec3::splat(0.5)).distance(eye) <= TEXT_DISTANCE
+            {
+                nearby.insert(position, sign.text.as_str());
+            }
+        }
+    }
+
+    sign_texts.texts.retain(|position, entity| {
+        let keep = nearby.contains_key(position);
+        if !keep {
+            commands.entity(*entity).despawn_recursive();
+        }
+        keep
+    });
+    for (&position, &text) in nearby.iter() {
+        if !sign_texts.texts.contains_key(&position) {
+            let entity = commands
+                .spawn((
+                    TextBundle::from_section(
+                        text,
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: FONT_SIZE,
+                            color: Color::WHITE,
+                        },
+                    )
+                    .with_text_alignment(TextAlignment::Center)
+                    .with_style(Style {
+                        position_type: PositionType::Absolute,
+                        max_width: Val::Px(TEXT_WIDTH),
+                        ..default()
+                    }),
+                    SignText { position },
+                    Name::new("Sign text"),
+                ))
+                // until it's put over the sign
+                .insert(Visibility::Hidden)
+                .id();
+            sign_texts.texts.insert(position, entity);
+        }
+    }
+
+    for (sign_text, mut text, mut style, mut visibility, node) in text_query.iter_mut() {
+        let Some(&value) = nearby.get(&sign_text.position) else {
+            continue;
+        };
+        if text.sections[0].value != value {
+            text.sections[0].value = value.to_string();
+        }
+        // the front of the board faces +z before the sign is turned
+        let rotation = voxel::state_rotation(voxel_data.get_state(sign_text.position));
+        let center = sign_text.position.to_world() + Vec3::splat(0.5);
+        let front = center + rotation * Vec3::new(0.0, 0.0, BOARD_DEPTH - 0.5);
+        let facing = (eye - front).dot(rotation * Vec3::Z) > 0.0;
+        let screen = camera
+            .world_to_viewport(camera_transform, front)
+            .filter(|_| facing);
+        let shown = match screen {
+            Some(screen) => {
+                // centered on the front, the node's size is from the last layout
+                let left = Val::Px(screen.x - node.size().x / 2.0);
+                let top = Val::Px(screen.y - node.size().y / 2.0);
+                if style.left != left {
+                    style.left = left;
+                }
+                if style.top != top {
+                    style.top = top;
+                }
+                Visibility::Inherited
+            }
+            None => Visibility::Hidden,
+        };
+        if *visibility != shown {
+            *visibility = shown;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        codec,
+        voxel::{ChunkIndex, SIGN},
+    };
+
+    #[test]
+    fn sign_text_survives_encoding() {
+        let index = ChunkIndex { x: 0, y: 0, z: 0 };
+        let mut voxel_data = VoxelData::air([index]);
+        let position = VoxelPos::new(3, 4, 5);
+        voxel_data.set_block(position, SIGN);
+        if let Some(BlockEntity::Sign(sign)) = voxel_data.entity_mut(position) {
+            sign.text = "Farm".to_string();
+        }
+
+        let chunk =
+            codec::decode_chunk(&codec::encode_chunk(voxel_data.chunk(index).unwrap())).unwrap();
+        assert_eq!(
+            chunk.entity(position.local()),
+            Some(&BlockEntity::Sign(Sign {
+                text: "Farm".to_string()
+            }))
+        );
+    }
+}
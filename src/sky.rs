@@ -0,0 +1,314 @@
+//! The sky around the camera: a dome shaded from the horizon up to the zenith,
+//! the sun and the moon as billboards across from each other, and stars that
+//! fade in at night, all following the time of day.
+
+use std::f32::consts::TAU;
+
+use bevy::{
+    ecs::system::SystemParam,
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    render::mesh::Indices,
+    render::render_resource::PrimitiveTopology,
+};
+use bevy_inspector_egui::prelude::*;
+use smooth_bevy_cameras::controllers::fps::FpsCameraController;
+
+use crate::{
+    daytime::{WorldTime, HOURS_PER_DAY},
+    region::splitmix64,
+};
+
+// within the camera's far plane, beyond the furthest chunks in sight
+const DOME_RADIUS: f32 = 900.0;
+const STAR_DISTANCE: f32 = 850.0;
+const SUN_DISTANCE: f32 = 800.0;
+const SUN_SIZE: f32 = 60.0;
+const MOON_SIZE: f32 = 40.0;
+const STARS: usize = 800;
+const STAR_SIZE: f32 = 1.5;
+const STAR_SEED: u64 = 0x5747_4152;
+const TWILIGHT: f32 = 0.25; // sun elevation, as a sine, over which the sky turns from night to day
+
+#[derive(Reflect, Resource, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub struct SkySettings {
+    pub day_zenith: Color,
+    pub day_horizon: Color,
+    pub night_zenith: Color,
+    pub night_horizon: Color,
+    pub sunset_horizon: Color, // the horizon blends into it while the sun is low
+    pub sun: Color,
+    pub moon: Color,
+    #[inspector(min = 0.0, max = 1.0)]
+    pub star_brightness: f32,
+}
+
+impl Default for SkySettings {
+    fn default() -> Self {
+        SkySettings {
+            day_zenith: Color::rgb(0.25, 0.5, 0.95),
+            day_horizon: Color::rgb(0.7, 0.85, 1.0),
+            night_zenith: Color::rgb(0.0, 0.0, 0.03),
+            night_horizon: Color::rgb(0.03, 0.04, 0.1),
+            sunset_horizon: Color::rgb(1.0, 0.5, 0.2),
+            sun: Color::rgb(1.0, 0.95, 0.7),
+            moon: Color::rgb(0.85, 0.88, 0.95),
+            star_brightness: 1.0,
+        }
+    }
+}
+
+/// Centered on the camera, the parent of the rest of the sky
+#[derive(Component)]
+pub struct Sky;
+
+#[derive(Component)]
+pub struct SkyDome;
+
+#[derive(Component)]
+pub struct Sun;
+
+#[derive(Component)]
+pub struct Moon;
+
+#[derive(Component)]
+pub struct Stars;
+
+/// Unit vector towards the sun, matching the sun light of `update_sun`
+fn sun_direction(world_time: &WorldTime) -> Vec3 {
+    let angle = world_time.time_of_day / HOURS_PER_DAY * TAU;
+    Vec3::new(0.0, -angle.cos(), angle.sin())
+}
+
+/// 0 at night, 1 in daylight, in between while the sun is close to the horizon
+fn daylight(sun_elevation: f32) -> f32 {
+    ((sun_elevation + TWILIGHT) / (2.0 * TWILIGHT)).clamp(0.0, 1.0)
+}
+
+fn mix(from: Color, to: Color, amount: f32) -> Color {
+    let (from, to) = (Vec4::from(from.as_rgba_f32()), Vec4::from(to.as_rgba_f32()));
+    Color::from(from.lerp(to, amount))
+}
+
+/// Zenith and horizon colors for the sun at this elevation
+fn sky_colors(settings: &SkySettings, sun_elevation: f32) -> (Color, Color) {
+    let day = daylight(sun_elevation);
+    let sunset = (1.0 - sun_elevation.abs() / TWILIGHT).clamp(0.0, 1.0);
+    let zenith = mix(settings.night_zenith, settings.day_zenith, day);
+    let horizon = mix(settings.night_horizon, settings.day_horizon, day);
+    (zenith, mix(horizon, settings.sunset_horizon, sunset * 0.8))
+}
+
+fn billboard(color: Color, materials: &mut Assets<StandardMaterial>) -> Handle<StandardMaterial> {
+    materials.add(StandardMaterial {
+        base_color: color,
+        unlit: true,
+        cull_mode: None,
+        fog_enabled: false,
+        alpha_mode: AlphaMode::Blend,
+        ..default()
+    })
+}
+
+/// Small quads facing the center, scattered over the sphere
+fn stars_mesh() -> Mesh {
+    let mut positions = Vec::with_capacity(STARS * 4);
+    let mut indices = Vec::with_capacity(STARS * 6);
+    let mut state = STAR_SEED;
+    let mut random = || {
+        state = splitmix64(state);
+        (state >> 40) as f32 / (1u64 << 24) as f32
+    };
+    for star in 0..STARS {
+        // uniform over the sphere
+        let y = random() * 2.0 - 1.0;
+        let angle = random() * TAU;
+        let radius = (1.0 - y * y).sqrt();
+        let direction = Vec3::new(radius * angle.cos(), y, radius * angle.sin());
+        let size = STAR_SIZE * (0.5 + random());
+        let side = direction.any_orthonormal_vector() * size;
+        let up = direction.cross(side);
+        let center = direction * STAR_DISTANCE;
+        for corner in [-side - up, side - up, side + up, -side + up] {
+            positions.push((center + corner).to_array());
+        }
+        let first = star as u32 * 4;
+        indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+    let normals = vec![[0.0, 1.0, 0.0]; positions.len()];
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+pub fn setup_sky(
+    mut commands: Commands,
+    sky_settings: Res<SkySettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let mut dome = Mesh::from(shape::UVSphere {
+        radius: DOME_RADIUS,
+        sectors: 32,
+        stacks: 16,
+    });
+    let vertices = dome.count_vertices();
+    dome.insert_attribute(Mesh::ATTRIBUTE_COLOR, vec![[0.0; 4]; vertices]);
+    let dome_material = materials.add(StandardMaterial {
+        base_color: Color::WHITE,
+        unlit: true,
+        cull_mode: None,
+        fog_enabled: false,
+        ..default()
+    });
+    let sun_material = billboard(sky_settings.sun, &mut materials);
+    let moon_material = billboard(sky_settings.moon, &mut materials);
+    let stars_material = billboard(Color::NONE, &mut materials);
+
+    let sky_part = || (NotShadowCaster, NotShadowReceiver);
+    commands
+        .spawn((SpatialBundle::default(), Sky, Name::new("Sky")))
+        .with_children(|parent| {
+            parent.spawn((
+                PbrBundle {
+                    mesh: meshes.add(dome),
+                    material: dome_material,
+                    ..default()
+                },
+                SkyDome,
+                sky_part(),
+            ));
+            parent.spawn((
+                PbrBundle {
+                    mesh: meshes.add(stars_mesh()),
+                    material: stars_material,
+                    ..default()
+                },
+                Stars,
+                sky_part(),
+            ));
+            parent.spawn((
+                PbrBundle {
+                    mesh: meshes.add(shape::Quad::new(Vec2::splat(SUN_SIZE)).into()),
+                    material: sun_material,
+                    ..default()
+                },
+                Sun,
+                sky_part(),
+            ));
+            parent.spawn((
+                PbrBundle {
+                    mesh: meshes.add(shape::Quad::new(Vec2::splat(MOON_SIZE)).into()),
+                    material: moon_material,
+                    ..default()
+                },
+                Moon,
+                sky_part(),
+            ));
+        });
+}
+
+/// Where the sky's colors go: the clear color, the dome's vertex colors and the materials of
+/// the stars, sun and moon
+#[derive(SystemParam)]
+pub struct SkyShading<'w> {
+    clear_color: ResMut<'w, ClearColor>,
+    meshes: ResMut<'w, Assets<Mesh>>,
+    materials: ResMut<'w, Assets<StandardMaterial>>,
+}
+
+/// The dome, stars, sun and moon, with which of them each is
+type SkyPart = (
+    &'static mut Transform,
+    &'static Handle<StandardMaterial>,
+    Option<&'static Handle<Mesh>>,
+    AnyOf<(
+        &'static SkyDome,
+        &'static Stars,
+        &'static Sun,
+        &'static Moon,
+    )>,
+);
+
+/// Keep the sky around the camera, move the sun, moon and stars with the time of day
+/// and shade the dome and stars for it
+pub fn update_sky(
+    world_time: Res<WorldTime>,
+    sky_settings: Res<SkySettings>,
+    mut shading: SkyShading,
+    camera_query: Query<&GlobalTransform, With<FpsCameraController>>,
+    mut sky_query: Query<&mut Transform, With<Sky>>,
+    mut part_query: Query<SkyPart, Without<Sky>>,
+    mut last_colors: Local<Option<(Color, Color)>>,
+) {
+    let (Ok(camera), Ok(mut sky)) = (camera_query.get_single(), sky_query.get_single_mut()) else {
+        return;
+    };
+    sky.translation = camera.translation();
+
+    let sun = sun_direction(&world_time);
+    let (zenith, horizon) = sky_colors(&sky_settings, sun.y);
+    let colors_changed = *last_colors != Some((zenith, horizon)) || sky_settings.is_changed();
+    *last_colors = Some((zenith, horizon));
+    if shading.clear_color.0 != horizon {
+        shading.clear_color.0 = horizon;
+    }
+
+    for (mut transform, material, mesh, (dome, stars, sun_part, moon_part)) in part_query.iter_mut()
+    {
+        if dome.is_some() {
+            if !colors_changed {
+                continue;
+            }
+            let Some(mesh) = mesh.and_then(|mesh| shading.meshes.get_mut(mesh)) else {
+                continue;
+            };
+            let Some(positions) = mesh
+                .attribute(Mesh::ATTRIBUTE_POSITION)
+                .and_then(|positions| positions.as_float3())
+            else {
+                continue;
+            };
+            // horizon color below the horizon too, the ground hides it anyway
+            let colors: Vec<[f32; 4]> = positions
+                .iter()
+                .map(|position| {
+                    let height = (position[1] / DOME_RADIUS).max(0.0).sqrt();
+                    mix(horizon, zenith, height).as_linear_rgba_f32()
+                })
+                .collect();
+            mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+        } else if stars.is_some() {
+            // the stars turn with the sky, the moon and sun along with them
+            let angle = world_time.time_of_day / HOURS_PER_DAY * TAU;
+            transform.rotation = Quat::from_rotation_x(angle);
+            let alpha = (1.0 - daylight(sun.y)) * sky_settings.star_brightness;
+            let faded = (alpha * 100.0).round() / 100.0;
+            let changed = shading
+                .materials
+                .get(material)
+                .is_some_and(|star| star.base_color.a() != faded);
+            if changed {
+                if let Some(star) = shading.materials.get_mut(material) {
+                    star.base_color = Color::WHITE.with_a(faded);
+                }
+            }
+        } else {
+            let (direction, color) = match (sun_part, moon_part) {
+                (Some(_), _) => (sun, sky_settings.sun),
+                _ => (-sun, sky_settings.moon),
+            };
+            // the sun and moon only move in the yz plane, so x is never parallel to them
+            *transform = Transform::from_translation(direction * SUN_DISTANCE)
+                .looking_at(Vec3::ZERO, Vec3::X);
+            if sky_settings.is_changed() {
+                if let Some(billboard) = shading.materials.get_mut(material) {
+                    billboard.base_color = color;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Where the game keeps its files: the config and data folders on desktop,
+//! the browser's local storage on the web. Files are named with `/` between
+//! folders either way, e.g. `worlds/42/0_1_0.chunk`.
+
+pub use backend::{describe, list, read, remove, unix_time, write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Folder {
+    Config, // settings
+    Data,   // worlds, schematics, crash reports
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod backend {
+    use std::{
+        fs,
+        path::PathBuf,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    use super::Folder;
+
+    fn path(folder: Folder, name: &str) -> Result<PathBuf, String> {
+        let root = match folder {
+            Folder::Config => dirs::config_dir(),
+            Folder::Data => dirs::data_dir(),
+        };
+        root.map(|root| root.join("mcrs").join(name))
+            .ok_or_else(|| format!("no {:?} folder to keep {} in", folder, name))
+    }
+
+    /// Where a file is kept, to tell the player
+    pub fn describe(folder: Folder, name: &str) -> String {
+        path(folder, name).map_or_else(|_| name.to_string(), |path| path.display().to_string())
+    }
+
+    pub fn read(folder: Folder, name: &str) -> Result<Vec<u8>, String> {
+        fs::read(path(folder, name)?).map_err(|err| err.to_string())
+    }
+
+    pub fn write(folder: Folder, name: &str, bytes: &[u8]) -> Result<(), String> {
+        let path = path(folder, name)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        }
+        fs::write(path, bytes).map_err(|err| err.to_string())
+    }
+
+    pub fn remove(folder: Folder, name: &str) -> Result<(), String> {
+        fs::remove_file(path(folder, name)?).map_err(|err| err.to_string())
+    }
+
+    /// Names of the files directly in `dir`
+    pub fn list(folder: Folder, dir: &str) -> Vec<String> {
+        path(folder, dir)
+            .ok()
+            .and_then(|dir| fs::read_dir(dir).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .collect()
+    }
+
+    /// Time since the unix epoch, `SystemTime` panics in the browser so this is the one to use
+    pub fn unix_time() -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+}
+
+/// Local storage only holds strings, each byte is kept as the char with the same code
+#[cfg(target_arch = "wasm32")]
+mod backend {
+    use std::time::Duration;
+
+    use super::Folder;
+
+    fn key(folder: Folder, name: &str) -> String {
+        let folder = match folder {
+            Folder::Config => "config",
+            Folder::Data => "data",
+        };
+        format!("mcrs/{folder}/{name}")
+    }
+
+    fn storage() -> Result<web_sys::Storage, String> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or_else(|| "local storage is unavailable".to_string())
+    }
+
+    pub fn describe(folder: Folder, name: &str) -> String {
+        format!("local storage '{}'", key(folder, name))
+    }
+
+    pub fn read(folder: Folder, name: &str) -> Result<Vec<u8>, String> {
+        let value = storage()?
+            .get_item(&key(folder, name))
+            .map_err(|_| "cannot read local storage".to_string())?
+            .ok_or_else(|| format!("{} not found", name))?;
+        Ok(value.chars().map(|c| c as u32 as u8).collect())
+    }
+
+    pub fn write(folder: Folder, name: &str, bytes: &[u8]) -> Result<(), String> {
+        let value: String = bytes.iter().map(|byte| *byte as char).collect();
+        storage()?
+            .set_item(&key(folder, name), &value)
+            .map_err(|_| "local storage is full".to_string())
+    }
+
+    pub fn remove(folder: Folder, name: &str) -> Result<(), String> {
+        storage()?
+            .remove_item(&key(folder, name))
+            .map_err(|_| "cannot write local storage".to_string())
+    }
+
+    pub fn list(folder: Folder, dir: &str) -> Vec<String> {
+        let Ok(storage) = storage() else {
+            return Vec::new();
+        };
+        let prefix = key(folder, &format!("{dir}/"));
+        let count = storage.length().unwrap_or_default();
+        (0..count)
+            .filter_map(|index| storage.key(index).ok().flatten())
+            .filter_map(|key| {
+                let name = key.strip_prefix(&prefix)?;
+                (!name.contains('/')).then(|| name.to_string())
+            })
+            .collect()
+    }
+
+    pub fn unix_time() -> Duration {
+        Duration::from_millis(js_sys::Date::now() as u64)
+    }
+}
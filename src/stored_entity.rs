@@ -0,0 +1,157 @@
+//! Entities stored with their chunk: dropped items, mobs and falling blocks in a
+//! chunk that unloads are taken out of the world and kept in its `ChunkData`,
+//! saved along with it, and spawned again where they were once it loads. The
+//! ones still around on exit are saved with their chunk the same way.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    falling::{self, FallingBlock},
+    item::{self, DroppedItem, ItemMeshes},
+    mob::{self, Mob, MobAssets, MobKind},
+    protection::Editor,
+    voxel::{BlockId, VoxelData},
+    VoxelMaterial,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StoredEntity {
+    Item {
+        block: BlockId,
+        count: u32,
+        age: f32, // seconds since it was dropped
+        position: [f32; 3],
+    },
+    Mob {
+        kind: MobKind,
+        position: [f32; 3], // of its feet
+    },
+    FallingBlock {
+        block: BlockId,
+        velocity: f32,
+        position: [f32; 3],
+    },
+}
+
+/// The components of the entities that are stored, one of which each has
+pub(crate) type Storable = AnyOf<(&'static DroppedItem, &'static Mob, &'static FallingBlock)>;
+
+/// The entity with these components as it's stored, None if it isn't one that's stored
+pub(crate) fn store(
+    transform: &Transform,
+    (item, mob, falling): (Option<&DroppedItem>, Option<&Mob>, Option<&FallingBlock>),
+) -> Option<StoredEntity> {
+    let position = transform.translation.to_array();
+    match (item, mob, falling) {
+        (Some(item), _, _) => Some(StoredEntity::Item {
+            block: item.block,
+            count: item.count,
+            age: item.age(),
+            position,
+        }),
+        (_, Some(mob), _) => Some(StoredEntity::Mob {
+            kind: mob.kind,
+            position,
+        }),
+        (_, _, Some(falling)) => Some(StoredEntity::FallingBlock {
+            block: falling.block(),
+            velocity: falling.velocity(),
+            position,
+        }),
+        _ => None,
+    }
+}
+
+/// Spawn the entities stored in the chunks that were just loaded, the chunks count as edited
+/// until they're saved again without them
+pub fn restore_stored_entities(
+    mut commands: Commands,
+    mut voxel_data: ResMut<VoxelData>,
+    mut item_meshes: ResMut<ItemMeshes>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    voxel_material: Res<VoxelMaterial>,
+    mob_assets: Res<MobAssets>,
+) {
+    if !voxel_material.loaded {
+        return;
+    }
+    let holding: Vec<_> = voxel_data
+        .iter_chunks()
+        .filter(|chunk| !chunk.stored.is_empty())
+        .map(|chunk| chunk.index)
+        .collect();
+    for index in holding {
+        let Some(chunk) = voxel_data.chunk_mut(index) else {
+            continue;
+        };
+        for stored in std::mem::take(&mut chunk.stored) {
+            match stored {
+                StoredEntity::Item {
+                    block,
+                    count,
+                    age,
+                    position,
+                } => item::respawn_item(
+                    &mut commands,
+                    &mut item_meshes,
+                    &mut meshes,
+                    &voxel_material,
+                    (block, count),
+                    age,
+                    Vec3::from_array(position),
+                ),
+                StoredEntity::Mob { kind, position } => {
+                    mob::spawn_mob(&mut commands, &mob_assets, kind, Vec3::from_array(position));
+                }
+                StoredEntity::FallingBlock {
+                    block,
+                    velocity,
+                    position,
+                } => {
+                    let mesh = item_meshes.mesh(&mut meshes, block);
+                    falling::spawn_falling_block(
+                        &mut commands,
+                        mesh,
+                        &voxel_material,
+                        // who it fell for isn't stored, it lands wherever the world may edit
+                        (block, Some(Editor::World)),
+                        velocity,
+                        Vec3::from_array(position),
+                    );
+                }
+            }
+        }
+        voxel_data.mark_modified(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        codec,
+        voxel::{ChunkData, SAND},
+    };
+
+    #[test]
+    fn stored_entities_are_saved_with_their_chunk() {
+        let chunk = ChunkData {
+            stored: vec![
+                StoredEntity::Item {
+                    block: SAND,
+                    count: 3,
+                    age: 12.5,
+                    position: [1.5, 70.25, -3.5],
+                },
+                StoredEntity::Mob {
+                    kind: MobKind::Animal,
+                    position: [4.5, 68.0, 2.5],
+                },
+            ],
+            ..Default::default()
+        };
+        let loaded = codec::decode_chunk(&codec::encode_chunk(&chunk)).unwrap();
+        assert_eq!(loaded.stored, chunk.stored);
+    }
+}
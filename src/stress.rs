@@ -0,0 +1,103 @@
+//! Pathological worlds for the `/stress` developer command, to measure one
+//! subsystem at a time under load with the chunk overlay (F3) open. There's
+//! no water drain test, water doesn't flow in this tree.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    item::{self, ItemMeshes},
+    server::GrantedSightRange,
+    voxel::{ChunkIndex, VoxelPos, AIR, CHUNK_SIZE, STONE},
+    world::VoxelWorld,
+    DebugSettings, VoxelMaterial,
+};
+
+pub const PREGEN_SIGHT_RANGE: u8 = 32;
+const CHECKERBOARD_RADIUS: i32 = 1; // chunk columns around the player in each direction
+const ITEMS_SIDE: i32 = 100; // dropped items along each side of the square, 10k in all
+const ITEMS_HEIGHT: i32 = 8; // blocks above the player they drop from
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StressTest {
+    Checkerboard, // every other block solid, the worst case for greedy meshing
+    Pregen,       // load and mesh everything within `PREGEN_SIGHT_RANGE`
+    Items,        // ten thousand dropped items falling onto the terrain, none of them merging
+}
+
+impl StressTest {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "checkerboard" => Some(StressTest::Checkerboard),
+            "pregen" => Some(StressTest::Pregen),
+            "items" => Some(StressTest::Items),
+            _ => None,
+        }
+    }
+}
+
+/// What the stress tests change besides the world
+#[derive(SystemParam)]
+pub struct StressTools<'w, 's> {
+    pub debug_settings: ResMut<'w, DebugSettings>,
+    pub granted_sight_range: ResMut<'w, GrantedSightRange>,
+    commands: Commands<'w, 's>,
+    item_meshes: ResMut<'w, ItemMeshes>,
+    meshes: ResMut<'w, Assets<Mesh>>,
+    voxel_material: Res<'w, VoxelMaterial>,
+}
+
+impl StressTools<'_, '_> {
+    /// Drop stone on a square above `center`, returns how many items
+    pub fn drop_items(&mut self, center: VoxelPos) -> usize {
+        let positions = item_positions(center);
+        for position in positions.iter() {
+            item::spawn_item(
+                &mut self.commands,
+                &mut self.item_meshes,
+                &mut self.meshes,
+                &self.voxel_material,
+                (STONE, 1),
+                *position,
+            );
+        }
+        positions.len()
+    }
+}
+
+/// A square of positions above `center`, a block apart so the items there never stack
+fn item_positions(center: VoxelPos) -> Vec<VoxelPos> {
+    let corner = center.0 + IVec3::new(-ITEMS_SIDE / 2, ITEMS_HEIGHT, -ITEMS_SIDE / 2);
+    (0..ITEMS_SIDE * ITEMS_SIDE)
+        .map(|i| VoxelPos(corner + IVec3::new(i % ITEMS_SIDE, 0, i / ITEMS_SIDE)))
+        .collect()
+}
+
+/// Fill the loaded chunks around `center` with a 3d checkerboard, returns the chunks changed
+pub fn checkerboard(world: &mut VoxelWorld, center: ChunkIndex) -> usize {
+    let indices: Vec<ChunkIndex> = world
+        .iter_chunks()
+        .map(|chunk| chunk.index)
+        .filter(|index| {
+            (index.x - center.x).abs() <= CHECKERBOARD_RADIUS
+                && (index.z - center.z).abs() <= CHECKERBOARD_RADIUS
+        })
+        .collect();
+    for index in indices.iter() {
+        let origin = index.origin();
+        let modified = world.is_modified(*index);
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    let block = if (x + y + z) % 2 == 0 { STONE } else { AIR };
+                    let offset = IVec3::new(x as i32, y as i32, z as i32);
+                    world.set_block(VoxelPos(origin + offset), block);
+                }
+            }
+        }
+        // the journal keeps the writes, but they aren't flagged for the save to pick up
+        if !modified {
+            world.forget_edits(*index);
+        }
+    }
+    indices.len()
+}
@@ -0,0 +1,219 @@
+//! Swimming: with the player's eye in water they move slower, sink slowly and
+//! swim up while holding jump, and the view turns blue and murky. In survival
+//! an air meter shows above the food while it runs low, and drowning hurts
+//! once it's empty.
+
+use bevy::prelude::*;
+use smooth_bevy_cameras::{controllers::fps::FpsCameraController, LookTransform};
+
+use crate::{
+    game_mode::GameMode,
+    health::Damage,
+    voxel::{VoxelData, VoxelPos, WATER},
+};
+
+pub const MAX_AIR: u32 = 10; // in bubbles
+pub(crate) const SWIM_SPEED: f32 = 0.5; // of the speed on land
+pub(crate) const SWIM_UP_SPEED: f32 = 4.0; // blocks per second, holding jump
+pub(crate) const SINK_SPEED: f32 = 1.5;
+pub(crate) const WATER_DRAG: f32 = 4.0; // how quickly the vertical speed eases to those
+const AIR_INTERVAL: f32 = 1.5; // seconds under water before a bubble is gone
+const REFILL_INTERVAL: f32 = 0.2; // seconds out of water for each bubble back
+const DROWN_INTERVAL: f32 = 1.0; // seconds between hits once out of air
+const DROWN_DAMAGE: u32 = 2;
+const BUBBLE_SIZE: f32 = 12.0;
+const TINT: Color = Color::rgba(0.1, 0.3, 0.7, 0.35);
+const FOG_COLOR: Color = Color::rgb(0.1, 0.25, 0.5);
+
+/// Whether the player's eye is in water, worked out before anything moves the player
+#[derive(Resource, Default, Debug)]
+pub struct Underwater(pub bool);
+
+#[derive(Component, Debug)]
+pub struct Breath {
+    pub air: u32,
+    pub max: u32,
+    tick: f32, // seconds towards the next bubble lost, regained or drowning hit
+}
+
+impl Default for Breath {
+    fn default() -> Self {
+        Breath {
+            air: MAX_AIR,
+            max: MAX_AIR,
+            tick: 0.0,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct AirHud;
+
+#[derive(Component)]
+pub struct Bubble(u32); // position in the row, from the left
+
+#[derive(Component)]
+pub struct UnderwaterTint;
+
+pub fn setup_air(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                ..default()
+            },
+            background_color: TINT.into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        UnderwaterTint,
+    ));
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(50.0), // just above the food
+                    width: Val::Percent(100.),
+                    justify_content: JustifyContent::Center,
+                    column_gap: Val::Px(6.0),
+                    ..default()
+                },
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            AirHud,
+        ))
+        .with_children(|parent| {
+            for index in 0..MAX_AIR {
+                parent.spawn((
+                    NodeBundle {
+                        style: Style {
+                            width: Val::Px(BUBBLE_SIZE),
+                            height: Val::Px(BUBBLE_SIZE),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    Bubble(index),
+                ));
+            }
+        });
+}
+
+pub fn detect_underwater(
+    voxel_data: Res<VoxelData>,
+    mut underwater: ResMut<Underwater>,
+    camera_query: Query<(&FpsCameraController, &LookTransform)>,
+) {
+    let Some((_, look)) = camera_query
+        .iter()
+        .find(|(controller, _)| controller.enabled)
+    else {
+        return;
+    };
+    let in_water = voxel_data.get_block(VoxelPos::from_world(look.eye)) == WATER;
+    if underwater.0 != in_water {
+        underwater.0 = in_water;
+    }
+}
+
+/// Tint the view and close in the fog under water
+pub fn underwater_view(
+    mut commands: Commands,
+    underwater: Res<Underwater>,
+    camera_query: Query<Entity, With<FpsCameraController>>,
+    mut tint_query: Query<&mut Visibility, With<UnderwaterTint>>,
+) {
+    if !underwater.is_changed() {
+        return;
+    }
+    let (Ok(camera), Ok(mut visibility)) = (camera_query.get_single(), tint_query.get_single_mut())
+    else {
+        return;
+    };
+    if underwater.0 {
+        *visibility = Visibility::Inherited;
+        commands.entity(camera).insert(FogSettings {
+            color: FOG_COLOR,
+            falloff: FogFalloff::Linear {
+                start: 2.0,
+                end: 24.0,
+            },
+            ..default()
+        });
+    } else {
+        *visibility = Visibility::Hidden;
+        commands.entity(camera).remove::<FogSettings>();
+    }
+}
+
+/// Use up air under water in survival and drown once it's gone, breathing it back out of water
+pub fn update_breath(
+    time: Res<Time>,
+    game_mode: Res<GameMode>,
+    underwater: Res<Underwater>,
+    mut damage_events: EventWriter<Damage>,
+    mut breath_query: Query<&mut Breath>,
+) {
+    let Ok(mut breath) = breath_query.get_single_mut() else {
+        return;
+    };
+    if *game_mode != GameMode::Survival || !underwater.0 {
+        if breath.air == breath.max {
+            breath.tick = 0.0;
+            return;
+        }
+        breath.tick += time.delta_seconds();
+        if breath.tick >= REFILL_INTERVAL {
+            breath.tick = 0.0;
+            breath.air += 1;
+        }
+        return;
+    }
+    breath.tick += time.delta_seconds();
+    if breath.air > 0 && breath.tick >= AIR_INTERVAL {
+        breath.tick = 0.0;
+        breath.air -= 1;
+    } else if breath.air == 0 && breath.tick >= DROWN_INTERVAL {
+        breath.tick = 0.0;
+        damage_events.send(Damage {
+            amount: DROWN_DAMAGE,
+            cause: "drowned",
+        });
+    }
+}
+
+/// Show the bubbles left while under water or short of air, in survival
+pub fn update_air(
+    game_mode: Res<GameMode>,
+    underwater: Res<Underwater>,
+    breath_query: Query<&Breath>,
+    mut hud_query: Query<&mut Visibility, With<AirHud>>,
+    mut bubble_query: Query<(&Bubble, &mut BackgroundColor)>,
+) {
+    let (Ok(mut visibility), Ok(breath)) = (hud_query.get_single_mut(), breath_query.get_single())
+    else {
+        return;
+    };
+    let shown = if *game_mode == GameMode::Survival && (underwater.0 || breath.air < breath.max) {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    if *visibility != shown {
+        *visibility = shown;
+    }
+    for (bubble, mut background) in bubble_query.iter_mut() {
+        let color = if bubble.0 < breath.air {
+            Color::rgb(0.6, 0.85, 1.0)
+        } else {
+            Color::rgba(0.1, 0.2, 0.3, 0.4)
+        };
+        if background.0 != color {
+            background.0 = color;
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Teleporting with `/tp` and `/spawn`. The player doesn't go right away: the
+//! columns around the destination are loaded first, ahead of the ones around
+//! the camera, and the player is moved once the column they land in is
+//! generated and meshed, so they don't fall through terrain that isn't there
+//! yet. Respawning at a bed goes the same way.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use smooth_bevy_cameras::{controllers::fps::FpsCameraController, LookTransform};
+
+use crate::{
+    command::CommandResponse,
+    player::EYE_HEIGHT,
+    save::WorldSlot,
+    spawn_chunk,
+    voxel::{
+        self, ChunkColumn, ChunkEntities, ChunkIndex, ColumnMesh, VoxelData, VoxelMeshes, VoxelPos,
+        BED,
+    },
+};
+
+const PRELOAD_RADIUS: i32 = 2; // columns around the destination loaded before the player goes
+
+/// Where a teleport goes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Destination {
+    Position(Vec3),
+    Spawn,         // the world's spawn point
+    Bed(VoxelPos), // standing on the bed, the spawn point if it's gone once it's loaded
+}
+
+/// The eye of the player standing on the bed at `bed`
+fn bed_eye(bed: VoxelPos) -> Vec3 {
+    bed.to_world() + Vec3::new(0.5, 1.0 + EYE_HEIGHT, 0.5)
+}
+
+/// The teleport waiting for its destination to load, a new one replaces it
+#[derive(Resource, Default)]
+pub struct PendingTeleport {
+    destination: Option<Destination>,
+}
+
+impl PendingTeleport {
+    pub fn request(&mut self, destination: Destination) {
+        self.destination = Some(destination);
+    }
+
+    fn position(&self) -> Option<Vec3> {
+        match self.destination? {
+            Destination::Position(position) => Some(position),
+            Destination::Bed(bed) => Some(bed_eye(bed)),
+            Destination::Spawn => None, // until `update_teleport` looks it up
+        }
+    }
+
+    /// Whether a column is loaded for the teleport, it's generated and meshed first and kept
+    /// however far it is from the camera
+    pub fn preloads(&self, column: ChunkColumn) -> bool {
+        self.position().is_some_and(|position| {
+            let center = voxel::get_chunk_index(&position);
+            (column.x - center.x).abs() <= PRELOAD_RADIUS
+                && (column.z - center.z).abs() <= PRELOAD_RADIUS
+        })
+    }
+}
+
+/// Where the player is and where they're going, for the commands working around the player
+#[derive(SystemParam)]
+pub struct PlayerTravel<'w, 's> {
+    pending_teleport: ResMut<'w, PendingTeleport>,
+    look_query: Query<'w, 's, &'static LookTransform, With<FpsCameraController>>,
+}
+
+impl PlayerTravel<'_, '_> {
+    pub fn eye(&self) -> Result<Vec3, String> {
+        self.look_query
+            .get_single()
+            .map(|look| look.eye)
+            .map_err(|err| err.to_string())
+    }
+
+    pub fn teleport(&mut self, destination: Destination) {
+        self.pending_teleport.request(destination);
+    }
+}
+
+/// The ground the player lands on, loaded as far down as chunks load
+#[derive(SystemParam)]
+pub struct LandingGround<'w, 's> {
+    voxel_settings: Res<'w, voxel::VoxelSettings>,
+    voxel_data: Res<'w, VoxelData>,
+    voxel_meshes: Res<'w, VoxelMeshes>,
+    column_query: Query<'w, 's, &'static ColumnMesh>,
+}
+
+impl LandingGround<'_, '_> {
+    /// The chunks of the column around `center` that load
+    fn vertical_chunks(&self, center: ChunkIndex) -> impl Iterator<Item = i32> {
+        voxel::vertical_chunks(&center, self.voxel_settings.vertical_sight_range)
+    }
+
+    /// Whether the column the player lands in is generated and meshed down to the bottom of
+    /// the world, or as far down as chunks load
+    fn ready(&self, center: ChunkIndex) -> bool {
+        let column = ChunkColumn {
+            x: center.x,
+            z: center.z,
+        };
+        let generated = self
+            .vertical_chunks(center)
+            .filter(|&y| y <= center.y)
+            .all(|y| self.voxel_data.is_loaded(ChunkIndex { y, ..center }));
+        let meshed = self
+            .voxel_meshes
+            .columns
+            .get(&column)
+            .and_then(|entity| self.column_query.get(*entity).ok())
+            .is_some_and(|column_mesh| !column_mesh.dirty);
+        generated && meshed
+    }
+}
+
+/// Load the columns around the destination of the pending teleport, and move the player
+/// there once the ground under it is meshed
+pub fn update_teleport(
+    mut commands: Commands,
+    mut world_slot: ResMut<WorldSlot>,
+    ground: LandingGround,
+    mut chunk_entities: ResMut<ChunkEntities>,
+    mut pending_teleport: ResMut<PendingTeleport>,
+    mut look_query: Query<&mut LookTransform, With<FpsCameraController>>,
+    mut responses: EventWriter<CommandResponse>,
+) {
+    if pending_teleport.destination == Some(Destination::Spawn) {
+        pending_teleport.request(Destination::Position(world_slot.spawn_point()));
+    }
+    let Some(position) = pending_teleport.position() else {
+        return;
+    };
+    let center = voxel::get_chunk_index(&position);
+    for x in -PRELOAD_RADIUS..=PRELOAD_RADIUS {
+        for z in -PRELOAD_RADIUS..=PRELOAD_RADIUS {
+            for y in ground.vertical_chunks(center) {
+                let index = ChunkIndex {
+                    x: center.x + x,
+                    y,
+                    z: center.z + z,
+                };
+                if !chunk_entities.chunks.contains_key(&index) {
+                    spawn_chunk(&mut commands, &mut chunk_entities, index);
+                }
+            }
+        }
+    }
+
+    if !ground.ready(center) {
+        return;
+    }
+    if let Some(Destination::Bed(bed)) = pending_teleport.destination {
+        if ground.voxel_data.get_block(bed) != BED {
+            world_slot.set_bed(None);
+            pending_teleport.request(Destination::Spawn);
+            responses.send(CommandResponse {
+                message: "Your bed is missing, respawning at the spawn point".to_string(),
+            });
+            return;
+        }
+    }
+    let Ok(mut look) = look_query.get_single_mut() else {
+        return;
+    };
+    let direction = look.target - look.eye;
+    look.eye = position;
+    look.target = position + direction;
+    pending_teleport.destination = None;
+    responses.send(CommandResponse {
+        message: format!("Teleported to {}", position),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_around_the_destination_are_preloaded() {
+        let mut pending_teleport = PendingTeleport::default();
+        let column = |x, z| ChunkColumn { x, z };
+        assert!(!pending_teleport.preloads(column(0, 0)));
+
+        // block 100 is in column 6, the spawn point isn't known until it's looked up
+        pending_teleport.request(Destination::Spawn);
+        assert!(!pending_teleport.preloads(column(0, 0)));
+        pending_teleport.request(Destination::Position(Vec3::new(100.0, 80.0, -1.0)));
+        assert!(pending_teleport.preloads(column(6, -1)));
+        assert!(pending_teleport.preloads(column(4, 1)));
+        assert!(!pending_teleport.preloads(column(9, -1)));
+        // the eye of the player standing on the bed is up in the chunk above it
+        pending_teleport.request(Destination::Bed(VoxelPos::new(-20, 15, 3)));
+        assert!(pending_teleport.preloads(column(-3, 0)));
+        assert_eq!(
+            voxel::get_chunk_index(&pending_teleport.position().unwrap()).y,
+            1
+        );
+    }
+}
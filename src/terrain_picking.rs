@@ -0,0 +1,106 @@
+//! Terrain as a picking backend. The column meshes are far too big to raycast
+//! triangle by triangle, so pointers are cast through the voxels instead, and
+//! pointer events (`Pointer<Click>`, `Pointer<Over>`, ...) fire on the chunk
+//! entity of the block under the pointer, with where the ray hit it and the
+//! normal of the face. While the cursor is grabbed the pointer is the
+//! crosshair in the middle of the screen.
+
+use bevy::{
+    prelude::*,
+    window::{CursorGrabMode, PrimaryWindow},
+};
+use bevy_mod_picking::backend::prelude::*;
+
+use crate::{
+    target_voxel,
+    voxel::{ChunkEntities, VoxelData, VoxelPos},
+};
+
+const PICK_RANGE: f32 = 128.0; // in blocks, anything farther is out of reach of the pointer
+
+/// Adds the terrain backend, the picking plugins themselves are added by the app
+#[derive(Default)]
+pub struct TerrainPickingPlugin;
+
+impl Plugin for TerrainPickingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, pick_terrain.in_set(PickSet::Backend));
+    }
+}
+
+/// Where a ray enters the cube of `voxel`, the origin itself if it starts inside
+fn entry_point(voxel: VoxelPos, origin: Vec3, direction: Vec3) -> Vec3 {
+    let min = voxel.to_world();
+    let near = (min - origin) / direction;
+    let far = (min + Vec3::ONE - origin) / direction;
+    origin + direction * near.min(far).max_element().max(0.0)
+}
+
+/// Cast every pointer over a camera's view into the voxels, reporting the first block it hits
+pub fn pick_terrain(
+    voxel_data: Res<VoxelData>,
+    chunk_entities: Res<ChunkEntities>,
+    pointer_query: Query<(&PointerId, &PointerLocation)>,
+    camera_query: Query<(Entity, &Camera, &GlobalTransform)>,
+    primary_query: Query<(Entity, &Window), With<PrimaryWindow>>,
+    mut output: EventWriter<PointerHits>,
+) {
+    let primary = primary_query.get_single().ok();
+    for (pointer, location) in pointer_query.iter() {
+        let Some(location) = location.location() else {
+            continue;
+        };
+        for (camera_entity, camera, camera_transform) in camera_query.iter() {
+            if !camera.is_active
+                || camera.target.normalize(primary.map(|(window, _)| window))
+                    != Some(location.target.clone())
+            {
+                continue;
+            }
+            let position = match primary {
+                Some((_, window)) if window.cursor.grab_mode != CursorGrabMode::None => {
+                    Vec2::new(window.width(), window.height()) / 2.0
+                }
+                _ => location.position,
+            };
+            let Some(ray) = camera.viewport_to_world(camera_transform, position) else {
+                continue;
+            };
+            let Some((hit, previous)) =
+                target_voxel(&voxel_data, ray.origin, ray.direction, PICK_RANGE)
+            else {
+                continue;
+            };
+            let Some(&chunk_entity) = chunk_entities.chunks.get(&hit.chunk()) else {
+                continue;
+            };
+            let point = entry_point(hit, ray.origin, ray.direction);
+            let normal = (previous != hit).then(|| (previous.0 - hit.0).as_vec3());
+            let data = HitData::new(
+                camera_entity,
+                point.distance(ray.origin),
+                Some(point),
+                normal,
+            );
+            output.send(PointerHits::new(
+                *pointer,
+                vec![(chunk_entity, data)],
+                camera.order as f32,
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rays_enter_voxels_on_their_faces() {
+        let voxel = VoxelPos::new(2, 0, 0);
+        let point = entry_point(voxel, Vec3::new(0.5, 0.5, 0.5), Vec3::X);
+        assert_eq!(point, Vec3::new(2.0, 0.5, 0.5));
+        let inside = Vec3::new(2.25, 0.5, 0.75);
+        assert_eq!(entry_point(voxel, inside, Vec3::NEG_Y), inside);
+    }
+}
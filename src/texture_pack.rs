@@ -0,0 +1,143 @@
+//! Texture packs made of a folder with one image per block texture, named after
+//! the texture, e.g. `stone.png`. They're stacked into the array texture when the
+//! pack loads: the built-in layers first, then the pack's other textures, which
+//! block definitions pick by name.
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::assets::{self, LAYER_COLORS};
+
+/// Texture names of the built-in layers, in the order of `textures/array_texture.png`
+pub const LAYER_NAMES: [&str; 28] = [
+    "grass", "dirt", "snow", "stone", "glass", "leaves", "farmland", "wheat_0", "wheat_1",
+    "wheat_2", "wheat_3", "fire", "log", "tnt", "sand", "gravel", "wire", "wire_on", "lever",
+    "lever_on", "button", "lamp", "lamp_on", "door", "planks", "furnace", "water", "bed",
+];
+
+/// Whether a texture pack path is a folder of textures rather than a stacked image
+pub(crate) fn is_folder(path: &str) -> bool {
+    !path.to_lowercase().ends_with(".png")
+}
+
+/// The array texture of a folder pack, stacked 2d like `textures/array_texture.png`,
+/// and the name of each of its layers
+pub(crate) struct StackedPack {
+    pub image: Image,
+    pub layers: Vec<String>,
+}
+
+/// Stack the textures of a folder pack, by name. Built-in layers the pack lacks are
+/// filled with their flat color, and every texture is scaled to the size of the largest
+pub(crate) fn stack_textures(textures: &[(String, &Image)]) -> Result<StackedPack, String> {
+    let mut pixels: Vec<(String, u32, Vec<u8>)> = Vec::new();
+    for (name, image) in textures.iter() {
+        let size = image.texture_descriptor.size;
+        if size.width != size.height || size.depth_or_array_layers != 1 {
+            warn!(
+                "Skipping texture {}: it's {}x{}, not square",
+                name, size.width, size.height
+            );
+            continue;
+        }
+        let converted;
+        let image = if image.texture_descriptor.format == TextureFormat::Rgba8UnormSrgb {
+            *image
+        } else {
+            match image.convert(TextureFormat::Rgba8UnormSrgb) {
+                Some(image) => {
+                    converted = image;
+                    &converted
+                }
+                None => {
+                    warn!("Skipping texture {}: unsupported pixel format", name);
+                    continue;
+                }
+            }
+        };
+        pixels.push((name.clone(), size.width, image.data.clone()));
+    }
+    let Some(size) = pixels.iter().map(|(_, size, _)| *size).max() else {
+        return Err("no usable textures".to_string());
+    };
+
+    let extra = pixels
+        .iter()
+        .map(|(name, _, _)| name)
+        .filter(|name| !LAYER_NAMES.contains(&name.as_str()));
+    let mut layers: Vec<String> = LAYER_NAMES.iter().map(|name| name.to_string()).collect();
+    layers.extend(extra.cloned());
+
+    let mut data = Vec::with_capacity((size * size * 4) as usize * layers.len());
+    for (index, layer) in layers.iter().enumerate() {
+        match pixels.iter().find(|(name, _, _)| name == layer) {
+            Some((_, from, texture)) => data.extend(scale(texture, *from, size)),
+            None => data.extend(assets::fallback_layer(LAYER_COLORS[index], size)),
+        }
+    }
+    let image = Image::new(
+        Extent3d {
+            width: size,
+            height: size * layers.len() as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    Ok(StackedPack { image, layers })
+}
+
+/// Nearest neighbour scaling of a square rgba texture
+fn scale(pixels: &[u8], from: u32, to: u32) -> Vec<u8> {
+    if from == to {
+        return pixels.to_vec();
+    }
+    let mut scaled = Vec::with_capacity((to * to * 4) as usize);
+    for y in 0..to {
+        for x in 0..to {
+            let index = ((y * from / to * from + x * from / to) * 4) as usize;
+            scaled.extend_from_slice(&pixels[index..index + 4]);
+        }
+    }
+    scaled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texture(size: u32, color: [u8; 4]) -> Image {
+        Image::new_fill(
+            Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &color,
+            TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+
+    #[test]
+    fn textures_stack_after_the_built_in_layers() {
+        let stone = texture(32, [1, 2, 3, 255]);
+        let bricks = texture(16, [4, 5, 6, 255]);
+        let pack = stack_textures(&[
+            ("bricks".to_string(), &bricks),
+            ("stone".to_string(), &stone),
+        ])
+        .unwrap();
+        assert_eq!(pack.layers.len(), LAYER_NAMES.len() + 1);
+        assert_eq!(pack.layers.last().map(String::as_str), Some("bricks"));
+
+        let layer_bytes = 32 * 32 * 4;
+        let stone_layer = &pack.image.data[3 * layer_bytes..4 * layer_bytes];
+        assert!(stone_layer.chunks(4).all(|pixel| pixel == [1, 2, 3, 255]));
+        let bricks_layer = &pack.image.data[LAYER_NAMES.len() * layer_bytes..];
+        assert!(bricks_layer.chunks(4).all(|pixel| pixel == [4, 5, 6, 255]));
+    }
+}
@@ -0,0 +1,250 @@
+//! TNT: lit with flint and steel it burns a fuse for a few seconds, then
+//! blows a ball of blocks with ragged edges away, throws the mobs, items and
+//! player around it back and lights the TNT caught in the blast, which goes
+//! off shortly after.
+
+use std::{collections::HashSet, f32::consts::TAU};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use smooth_bevy_cameras::{controllers::fps::FpsCameraController, LookTransform};
+
+use crate::{
+    farming::position_roll,
+    game_mode::GameMode,
+    health::Damage,
+    item::DroppedItem,
+    mob::Mob,
+    player::EYE_HEIGHT,
+    protection::Editor,
+    voxel::{self, BlockTag, VoxelData, VoxelModifyQueue, VoxelPos, AIR, TNT},
+    VoxelMaterial,
+};
+
+const FUSE: f32 = 4.0; // seconds from lighting TNT to the blast
+const CHAIN_FUSE: (f32, f32) = (0.5, 1.5); // shortest and longest fuse of TNT lit by a blast
+const BLAST_RADIUS: f32 = 4.0; // blocks
+const ROUGHNESS: f32 = 0.35; // up to this fraction of the radius is left standing, block by block
+const KNOCKBACK_RADIUS: f32 = 8.0; // blocks from the blast that still feel it
+const KNOCKBACK_SPEED: f32 = 14.0; // blocks per second, for mobs and items right at the blast
+const KNOCKBACK_DISTANCE: f32 = 3.0; // blocks the player is pushed right at the blast
+const BLAST_DAMAGE: f32 = 12.0; // half hearts right at the blast
+const PULSE_RATE: f32 = 2.0; // times per second lit TNT swells
+const PULSE_SIZE: f32 = 0.1;
+
+/// Light the TNT block at `position`, it goes off after `fuse` seconds
+#[derive(Event, Debug, Clone, Copy)]
+pub struct LightTnt {
+    pub position: VoxelPos,
+    pub fuse: f32,
+}
+
+impl LightTnt {
+    pub fn new(position: VoxelPos) -> Self {
+        LightTnt {
+            position,
+            fuse: FUSE,
+        }
+    }
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Explosion {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+/// Lit TNT, taken out of the world while its fuse burns
+#[derive(Component, Debug)]
+pub struct PrimedTnt {
+    position: VoxelPos,
+    fuse: f32, // seconds left
+}
+
+/// The mesh of lit TNT, made the first time some is lit
+#[derive(SystemParam)]
+pub struct PrimedTntMesh<'w, 's> {
+    meshes: ResMut<'w, Assets<Mesh>>,
+    mesh: Local<'s, Option<Handle<Mesh>>>,
+}
+
+impl PrimedTntMesh<'_, '_> {
+    fn get(&mut self) -> Handle<Mesh> {
+        let meshes = &mut self.meshes;
+        self.mesh
+            .get_or_insert_with(|| meshes.add(voxel::block_mesh(TNT).into()))
+            .clone()
+    }
+}
+
+/// Swap lit TNT blocks for entities burning their fuse
+pub fn light_tnt(
+    mut commands: Commands,
+    mut light_events: EventReader<LightTnt>,
+    voxel_data: Res<VoxelData>,
+    voxel_material: Res<VoxelMaterial>,
+    mut voxel_modify_queue: ResMut<VoxelModifyQueue>,
+    mut mesh: PrimedTntMesh,
+    primed_query: Query<&PrimedTnt>,
+) {
+    // the block only turns to air once the queue is handled, don't light it twice meanwhile
+    let mut lit: HashSet<VoxelPos> = primed_query.iter().map(|tnt| tnt.position).collect();
+    for event in light_events.iter() {
+        if voxel_data.get_block(event.position) != TNT || !lit.insert(event.position) {
+            continue;
+        }
+        voxel_modify_queue.queue.push((event.position, AIR));
+        commands.spawn((
+            MaterialMeshBundle {
+                mesh: mesh.get(),
+                material: voxel_material.material.clone(),
+                transform: Transform::from_translation(
+                    event.position.to_world() + Vec3::splat(0.5),
+                ),
+                ..default()
+            },
+            PrimedTnt {
+                position: event.position,
+                fuse: event.fuse,
+            },
+            Name::new("Primed TNT"),
+        ));
+    }
+}
+
+/// Burn the fuses down, swelling the TNT as they go, and blow up the ones that run out
+pub fn update_primed_tnt(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut explosions: EventWriter<Explosion>,
+    mut primed_query: Query<(Entity, &mut PrimedTnt, &mut Transform)>,
+) {
+    for (entity, mut tnt, mut transform) in primed_query.iter_mut() {
+        tnt.fuse -= time.delta_seconds();
+        if tnt.fuse <= 0.0 {
+            explosions.send(Explosion {
+                center: transform.translation,
+                radius: BLAST_RADIUS,
+            });
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        let pulse = (tnt.fuse * PULSE_RATE * TAU).sin().abs();
+        transform.scale = Vec3::splat(1.0 + PULSE_SIZE * pulse);
+    }
+}
+
+/// 1 at the blast, down to 0 at `KNOCKBACK_RADIUS`
+fn falloff(center: Vec3, point: Vec3) -> f32 {
+    (1.0 - center.distance(point) / KNOCKBACK_RADIUS).max(0.0)
+}
+
+/// Away from the blast and a little up, so things on the ground take off
+fn knockback_direction(center: Vec3, point: Vec3) -> Vec3 {
+    ((point - center).normalize_or_zero() + Vec3::Y * 0.5).normalize()
+}
+
+/// The mobs, items and player a blast throws back
+#[derive(SystemParam)]
+pub struct BlastTargets<'w, 's> {
+    mob_query: Query<'w, 's, (&'static mut Mob, &'static Transform)>,
+    item_query: Query<'w, 's, (&'static mut DroppedItem, &'static Transform)>,
+    player_query: Query<'w, 's, &'static mut LookTransform, With<FpsCameraController>>,
+}
+
+/// Clear the blocks the blasts reach in one batch of edits, light the TNT among
+/// them and throw back everything close by
+pub fn explode(
+    mut explosions: EventReader<Explosion>,
+    voxel_data: Res<VoxelData>,
+    game_mode: Res<GameMode>,
+    mut voxel_modify_queue: ResMut<VoxelModifyQueue>,
+    mut light_events: EventWriter<LightTnt>,
+    mut damage_events: EventWriter<Damage>,
+    mut targets: BlastTargets,
+) {
+    for explosion in explosions.iter() {
+        let center = explosion.center;
+        // a different edge for every blast
+        let salt = ((center.x.to_bits() as u64) << 32) ^ center.z.to_bits() as u64;
+        let reach = explosion.radius.ceil() as i32;
+        let middle = VoxelPos::from_world(center).0;
+        for x in -reach..=reach {
+            for y in -reach..=reach {
+                for z in -reach..=reach {
+                    let position = VoxelPos(middle + IVec3::new(x, y, z));
+                    let roll = (position_roll(position, salt) >> 40) as f32 / (1u64 << 24) as f32;
+                    let radius = explosion.radius * (1.0 - ROUGHNESS * roll);
+                    let distance = (position.to_world() + Vec3::splat(0.5)).distance(center);
+                    let block = voxel_data.get_block(position);
+                    if distance > radius
+                        || block == AIR
+                        || voxel::has_tag(block, BlockTag::Unbreakable)
+                    {
+                        continue;
+                    }
+                    if block == TNT {
+                        let (shortest, longest) = CHAIN_FUSE;
+                        light_events.send(LightTnt {
+                            position,
+                            fuse: shortest + (longest - shortest) * roll,
+                        });
+                    } else {
+                        voxel_modify_queue
+                            .guarded
+                            .push((position, AIR, Editor::World));
+                    }
+                }
+            }
+        }
+
+        for (mut mob, transform) in targets.mob_query.iter_mut() {
+            let strength = falloff(center, transform.translation);
+            if strength > 0.0 {
+                let direction = knockback_direction(center, transform.translation);
+                mob.knock_back(direction * KNOCKBACK_SPEED * strength);
+            }
+        }
+        for (mut item, transform) in targets.item_query.iter_mut() {
+            let strength = falloff(center, transform.translation);
+            if strength > 0.0 {
+                let direction = knockback_direction(center, transform.translation);
+                item.push(direction * KNOCKBACK_SPEED * strength);
+            }
+        }
+
+        if *game_mode == GameMode::Spectator {
+            continue;
+        }
+        let Ok(mut look) = targets.player_query.get_single_mut() else {
+            continue;
+        };
+        let body = look.eye - Vec3::Y * EYE_HEIGHT / 2.0;
+        let strength = falloff(center, body);
+        if strength <= 0.0 {
+            continue;
+        }
+        let amount = (BLAST_DAMAGE * strength).round() as u32;
+        if amount > 0 {
+            damage_events.send(Damage {
+                amount,
+                cause: "blew up",
+            });
+        }
+        // pushed in small steps, stopping short of walls
+        let solid =
+            |point: Vec3| voxel::is_solid(voxel_data.get_block(VoxelPos::from_world(point)));
+        let step = knockback_direction(center, body) * 0.25;
+        let mut pushed = Vec3::ZERO;
+        for _ in 0..(KNOCKBACK_DISTANCE * strength / 0.25) as u32 {
+            let eye = look.eye + pushed + step;
+            if solid(eye) || solid(eye - Vec3::Y * (EYE_HEIGHT - 0.1)) {
+                break;
+            }
+            pushed += step;
+        }
+        if pushed != Vec3::ZERO {
+            look.eye += pushed;
+            look.target += pushed;
+        }
+    }
+}
@@ -0,0 +1,396 @@
+//! Item durability: a tool is made with a number of uses in it and every use
+//! wears one off. Worn down to nothing it breaks, and a bar at the bottom of
+//! the screen shows what's left of the held one, flashing when it breaks.
+//! A worn tool is repaired by combining it with pieces of its material, each
+//! giving back a quarter, or anvil style with another tool of its kind.
+//!
+//! The durability is part of the item data, so it's kept and saved with the
+//! tool in the inventory. Creative tools never wear.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game_mode::GameMode,
+    input::{InputAction, PlayerInput},
+    item::{HeldItem, Inventory},
+    voxel::{BlockId, STONE},
+};
+
+pub const REPAIR_PARTS: u32 = 4; // pieces of material that repair a tool from broken to new
+const COMBINE_BONUS: u32 = 20; // percent of a new tool's uses added when two are combined
+const BAR_WIDTH: f32 = 80.0;
+const BAR_HEIGHT: f32 = 6.0;
+const BREAK_FLASH: f32 = 1.5; // seconds the bar shows a tool that just broke
+const WORN_COLOR: Vec3 = Vec3::new(0.9, 0.2, 0.1);
+const NEW_COLOR: Vec3 = Vec3::new(0.2, 0.9, 0.2);
+const BROKEN_COLOR: Color = Color::rgb(0.6, 0.0, 0.0);
+
+/// How many uses an item has in it and how many are gone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Durability {
+    max: u32,
+    used: u32,
+}
+
+impl Durability {
+    /// A new item that lasts `max` uses
+    pub fn new(max: u32) -> Self {
+        Durability { max, used: 0 }
+    }
+
+    pub fn max(&self) -> u32 {
+        self.max
+    }
+
+    pub fn uses_left(&self) -> u32 {
+        self.max.saturating_sub(self.used)
+    }
+
+    pub fn is_broken(&self) -> bool {
+        self.uses_left() == 0
+    }
+
+    pub fn is_worn(&self) -> bool {
+        self.used > 0
+    }
+
+    /// What's left of it, from 0 broken to 1 new
+    pub fn fraction(&self) -> f32 {
+        if self.max == 0 {
+            return 0.0;
+        }
+        self.uses_left() as f32 / self.max as f32
+    }
+
+    /// Take a use off, false without doing so if it's broken
+    pub fn wear(&mut self) -> bool {
+        if self.is_broken() {
+            return false;
+        }
+        self.used += 1;
+        true
+    }
+
+    /// Mend it with up to `pieces` of its material, returns how many it took
+    pub fn repair(&mut self, pieces: u32) -> u32 {
+        let part = self.max.div_ceil(REPAIR_PARTS);
+        let mut taken = 0;
+        while self.is_worn() && taken < pieces {
+            self.used = self.used.saturating_sub(part);
+            taken += 1;
+        }
+        taken
+    }
+
+    /// Combine it with another of its kind into one with the uses left of both and a bonus,
+    /// up to new
+    pub fn combine(self, other: Durability) -> Durability {
+        let bonus = self.max * COMBINE_BONUS / 100;
+        let uses_left = (self.uses_left() + other.uses_left() + bonus).min(self.max);
+        Durability {
+            max: self.max,
+            used: self.max - uses_left,
+        }
+    }
+}
+
+/// The kinds of tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Tool {
+    Hoe,
+    FlintAndSteel,
+}
+
+impl Tool {
+    pub fn name(self) -> &'static str {
+        match self {
+            Tool::Hoe => "Hoe",
+            Tool::FlintAndSteel => "Flint and steel",
+        }
+    }
+
+    /// Uses a new one has in it
+    pub fn max_uses(self) -> u32 {
+        match self {
+            Tool::Hoe => 60,
+            Tool::FlintAndSteel => 64,
+        }
+    }
+
+    /// The block it's made of and repaired with
+    pub fn material(self) -> BlockId {
+        match self {
+            Tool::Hoe | Tool::FlintAndSteel => STONE,
+        }
+    }
+}
+
+/// A tool in the inventory with what's left of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolItem {
+    pub tool: Tool,
+    pub durability: Durability,
+}
+
+impl ToolItem {
+    pub fn new(tool: Tool) -> Self {
+        ToolItem {
+            tool,
+            durability: Durability::new(tool.max_uses()),
+        }
+    }
+}
+
+/// The tool in the player's hand, by name, with its durability
+#[derive(Resource, Default, Debug)]
+pub struct HeldTool(pub Option<(&'static str, Durability)>);
+
+/// Sent with the name of a tool when it breaks
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ToolBroke(pub &'static str);
+
+/// Follow the held item to the first tool of its kind in the inventory, in survival only
+pub fn update_held_tool(
+    game_mode: Res<GameMode>,
+    held_item: Res<HeldItem>,
+    inventory: Res<Inventory>,
+    mut held_tool: ResMut<HeldTool>,
+) {
+    let held = held_item
+        .tool()
+        .filter(|_| *game_mode == GameMode::Survival)
+        .and_then(|tool| inventory.tool(tool))
+        .map(|item| (item.tool.name(), item.durability));
+    if held_tool.0 != held {
+        held_tool.0 = held;
+    }
+}
+
+/// Repair the held tool with pieces of its material from the inventory, or failing that
+/// combine it with another of its kind
+pub fn repair_tool(
+    player_input: Res<PlayerInput>,
+    game_mode: Res<GameMode>,
+    held_item: Res<HeldItem>,
+    mut inventory: ResMut<Inventory>,
+) {
+    if !player_input.actions.just_pressed(InputAction::RepairTool)
+        || *game_mode != GameMode::Survival
+    {
+        return;
+    }
+    let Some(tool) = held_item.tool() else {
+        return;
+    };
+    let pieces = inventory.count(tool.material());
+    let Some(durability) = inventory.tool(tool).map(|item| item.durability) else {
+        return;
+    };
+    if !durability.is_worn() {
+        return;
+    }
+    let mut repaired = durability;
+    let taken = repaired.repair(pieces);
+    if taken > 0 {
+        inventory.take(tool.material(), taken);
+    } else if let Some(spare) = inventory.take_spare_tool(tool) {
+        repaired = durability.combine(spare.durability);
+    }
+    if let Some(item) = inventory.tool_mut(tool) {
+        item.durability = repaired;
+    }
+}
+
+#[derive(Component)]
+pub struct ToolHud;
+
+#[derive(Component)]
+pub struct ToolBar;
+
+#[derive(Component)]
+pub struct ToolLabel;
+
+pub fn setup_tool_hud(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(70.0),
+                    width: Val::Percent(100.),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(2.0),
+                    ..default()
+                },
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            ToolHud,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 14.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+                ToolLabel,
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(BAR_WIDTH),
+                        height: Val::Px(BAR_HEIGHT),
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                height: Val::Percent(100.),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        ToolBar,
+                    ));
+                });
+        });
+}
+
+/// Show the durability of the held tool, and flash the bar of one that just broke
+pub fn update_tool_hud(
+    time: Res<Time>,
+    held_tool: Res<HeldTool>,
+    mut broke_events: EventReader<ToolBroke>,
+    mut flash: Local<Option<(&'static str, f32)>>, // the tool that broke, seconds of flashing left
+    mut hud_query: Query<&mut Visibility, With<ToolHud>>,
+    mut bar_query: Query<(&mut Style, &mut BackgroundColor), With<ToolBar>>,
+    mut label_query: Query<&mut Text, With<ToolLabel>>,
+) {
+    if let Some(ToolBroke(name)) = broke_events.iter().last() {
+        *flash = Some((name, BREAK_FLASH));
+    }
+    if let Some((_, left)) = flash.as_mut() {
+        *left -= time.delta_seconds();
+    }
+    if flash.is_some_and(|(_, left)| left <= 0.0) {
+        *flash = None;
+    }
+
+    let (Ok(mut visibility), Ok((mut style, mut color)), Ok(mut text)) = (
+        hud_query.get_single_mut(),
+        bar_query.get_single_mut(),
+        label_query.get_single_mut(),
+    ) else {
+        return;
+    };
+    let shown = if held_tool.0.is_some() || flash.is_some() {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    if *visibility != shown {
+        *visibility = shown;
+    }
+
+    let (width, fill, label) = match (*flash, held_tool.0) {
+        (Some((broke, seconds)), _) if seconds % 0.5 < 0.25 => {
+            (100.0, BROKEN_COLOR, format!("{} broke!", broke))
+        }
+        (Some((broke, _)), _) => (0.0, BROKEN_COLOR, format!("{} broke!", broke)),
+        (None, Some((name, durability))) => {
+            let left = durability.fraction();
+            let fill = WORN_COLOR.lerp(NEW_COLOR, left);
+            (
+                left * 100.0,
+                Color::rgb(fill.x, fill.y, fill.z),
+                name.to_string(),
+            )
+        }
+        (None, None) => return,
+    };
+    if style.width != Val::Percent(width) {
+        style.width = Val::Percent(width);
+    }
+    if color.0 != fill {
+        color.0 = fill;
+    }
+    if text.sections[0].value != label {
+        text.sections[0].value = label;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tools_wear_out_and_break() {
+        let mut durability = Durability::new(3);
+        assert!(!durability.is_worn());
+        assert!(durability.wear() && durability.wear() && durability.wear());
+        assert!(durability.is_broken());
+        assert!(!durability.wear());
+        assert_eq!(durability.uses_left(), 0);
+        assert_eq!(durability.fraction(), 0.0);
+    }
+
+    #[test]
+    fn repairing_takes_only_the_pieces_needed() {
+        let mut durability = Durability::new(60);
+        for _ in 0..20 {
+            durability.wear();
+        }
+        // a piece gives back 15 uses, so two mend 20
+        assert_eq!(durability.repair(10), 2);
+        assert_eq!(durability.uses_left(), 60);
+        assert_eq!(durability.repair(10), 0);
+    }
+
+    #[test]
+    fn combining_adds_up_the_uses_left_with_a_bonus() {
+        let mut worn = Durability::new(100);
+        let mut other = Durability::new(100);
+        for _ in 0..80 {
+            worn.wear();
+            other.wear();
+        }
+        assert_eq!(worn.combine(other).uses_left(), 20 + 20 + 20);
+        assert_eq!(worn.combine(Durability::new(100)).uses_left(), 100);
+    }
+
+    #[test]
+    fn durability_round_trips_through_ron() {
+        let mut durability = Durability::new(64);
+        durability.wear();
+        let contents = ron::to_string(&durability).unwrap();
+        assert_eq!(ron::from_str::<Durability>(&contents).unwrap(), durability);
+    }
+
+    #[test]
+    fn the_inventory_keeps_tool_wear() {
+        let mut inventory = Inventory::default();
+        let mut worn = ToolItem::new(Tool::Hoe);
+        worn.durability.wear();
+        inventory.add_tool(worn);
+        assert_eq!(inventory.take_spare_tool(Tool::Hoe), None);
+        inventory.add_tool(ToolItem::new(Tool::Hoe));
+
+        let contents = ron::to_string(&inventory).unwrap();
+        let mut loaded = ron::from_str::<Inventory>(&contents).unwrap();
+        assert_eq!(loaded.tool(Tool::Hoe), Some(&worn));
+        assert_eq!(
+            loaded.take_spare_tool(Tool::Hoe),
+            Some(ToolItem::new(Tool::Hoe))
+        );
+    }
+}
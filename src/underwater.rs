@@ -0,0 +1,317 @@
+//! Underwater rendering: while the camera is in water a post-process pass
+//! waves the picture and grades it toward blue-green, and the terrain shader
+//! plays animated caustics over the submerged surfaces near the camera.
+//!
+//! [`UnderwaterView`] says whether the camera is in water and where the
+//! surface is, looked up in the voxel data every frame.
+
+use bevy::{
+    core_pipeline::{core_3d, fullscreen_vertex_shader::fullscreen_shader_vertex_state},
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{
+        extract_component::{ComponentUniforms, ExtractComponentPlugin, UniformComponentPlugin},
+        render_graph::{
+            NodeRunError, RenderGraphApp, RenderGraphContext, ViewNode, ViewNodeRunner,
+        },
+        render_resource::{
+            BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+            BindGroupLayoutEntry, BindingResource, BindingType, BufferBindingType,
+            CachedRenderPipelineId, ColorTargetState, ColorWrites, FragmentState, MultisampleState,
+            Operations, PipelineCache, PrimitiveState, RenderPassColorAttachment,
+            RenderPassDescriptor, RenderPipelineDescriptor, Sampler, SamplerBindingType,
+            SamplerDescriptor, ShaderStages, ShaderType, TextureFormat, TextureSampleType,
+            TextureViewDimension,
+        },
+        renderer::{RenderContext, RenderDevice},
+        texture::BevyDefault,
+        view::ViewTarget,
+        RenderApp,
+    },
+};
+use smooth_bevy_cameras::controllers::fps::FpsCameraController;
+
+use crate::{
+    voxel::{VoxelData, VoxelPos, WATER},
+    ArrayTextureMaterial, VoxelMaterial,
+};
+
+pub use pass::UnderwaterPass;
+
+const UNDERWATER_NODE: &str = "underwater";
+
+/// Whether the camera is in water, and the height of the water's surface above it
+#[derive(Resource, Default)]
+pub struct UnderwaterView {
+    pub submerged: bool,
+    pub surface: f32,
+}
+
+// the `ShaderType` derive adds a check function per field that's never called
+#[allow(dead_code)]
+mod pass {
+    use bevy::{
+        prelude::*,
+        render::{extract_component::ExtractComponent, render_resource::ShaderType},
+    };
+
+    /// Drives the post-process pass on the camera, only there while the camera is in water
+    #[derive(Component, Clone, Copy, ExtractComponent, ShaderType)]
+    pub struct UnderwaterPass {
+        pub(super) time: f32,             // seconds, for the waves
+        pub(super) _webgl2_padding: Vec3, // uniforms are 16 byte aligned on WebGL2
+    }
+}
+
+/// Adds the underwater post-process pass to the 3d render graph, after tone mapping
+pub struct UnderwaterPlugin;
+
+impl Plugin for UnderwaterPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<UnderwaterView>().add_plugins((
+            ExtractComponentPlugin::<UnderwaterPass>::default(),
+            UniformComponentPlugin::<UnderwaterPass>::default(),
+        ));
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .add_render_graph_node::<ViewNodeRunner<UnderwaterNode>>(
+                core_3d::graph::NAME,
+                UNDERWATER_NODE,
+            )
+            .add_render_graph_edges(
+                core_3d::graph::NAME,
+                &[
+                    core_3d::graph::node::TONEMAPPING,
+                    UNDERWATER_NODE,
+                    core_3d::graph::node::END_MAIN_PASS_POST_PROCESSING,
+                ],
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app.init_resource::<UnderwaterPipeline>();
+    }
+}
+
+#[derive(Default)]
+struct UnderwaterNode;
+
+impl ViewNode for UnderwaterNode {
+    type ViewQuery = (&'static ViewTarget, With<UnderwaterPass>);
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        (view_target, _): QueryItem<Self::ViewQuery>,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let underwater_pipeline = world.resource::<UnderwaterPipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let Some(pipeline) = pipeline_cache.get_render_pipeline(underwater_pipeline.pipeline_id)
+        else {
+            return Ok(());
+        };
+        let uniforms = world.resource::<ComponentUniforms<UnderwaterPass>>();
+        let Some(uniform_binding) = uniforms.uniforms().binding() else {
+            return Ok(());
+        };
+
+        // reads the frame so far and writes the waved, graded one
+        let post_process = view_target.post_process_write();
+        let bind_group = render_context
+            .render_device()
+            .create_bind_group(&BindGroupDescriptor {
+                label: Some("underwater_bind_group"),
+                layout: &underwater_pipeline.layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(post_process.source),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::Sampler(&underwater_pipeline.sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: uniform_binding,
+                    },
+                ],
+            });
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("underwater_pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: post_process.destination,
+                resolve_target: None,
+                ops: Operations::default(),
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_render_pipeline(pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+        Ok(())
+    }
+}
+
+#[derive(Resource)]
+struct UnderwaterPipeline {
+    layout: BindGroupLayout,
+    sampler: Sampler,
+    pipeline_id: CachedRenderPipelineId,
+}
+
+impl FromWorld for UnderwaterPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("underwater_bind_group_layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: Some(UnderwaterPass::min_size()),
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let sampler = render_device.create_sampler(&SamplerDescriptor::default());
+        let shader = world
+            .resource::<AssetServer>()
+            .load("shaders/underwater.wgsl");
+        let pipeline_id =
+            world
+                .resource_mut::<PipelineCache>()
+                .queue_render_pipeline(RenderPipelineDescriptor {
+                    label: Some("underwater_pipeline".into()),
+                    layout: vec![layout.clone()],
+                    vertex: fullscreen_shader_vertex_state(),
+                    fragment: Some(FragmentState {
+                        shader,
+                        shader_defs: vec![],
+                        entry_point: "fragment".into(),
+                        targets: vec![Some(ColorTargetState {
+                            format: TextureFormat::bevy_default(),
+                            blend: None,
+                            write_mask: ColorWrites::ALL,
+                        })],
+                    }),
+                    primitive: PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: MultisampleState::default(),
+                    push_constant_ranges: vec![],
+                });
+        UnderwaterPipeline {
+            layout,
+            sampler,
+            pipeline_id,
+        }
+    }
+}
+
+/// Look up whether the camera is in water, and the top of the water above it
+pub fn detect_underwater_view(
+    voxel_data: Res<VoxelData>,
+    mut underwater: ResMut<UnderwaterView>,
+    camera_query: Query<&GlobalTransform, With<FpsCameraController>>,
+) {
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    let mut position = VoxelPos::from_world(camera.translation());
+    let submerged = voxel_data.get_block(position) == WATER;
+    let mut surface = 0.0;
+    if submerged {
+        while voxel_data.get_block(position) == WATER {
+            position.0.y += 1;
+        }
+        surface = position.0.y as f32;
+    }
+    if underwater.submerged != submerged || underwater.surface != surface {
+        underwater.submerged = submerged;
+        underwater.surface = surface;
+    }
+}
+
+/// Put the post-process pass on the camera while it's in water and take it off out of it
+pub fn underwater_pass(
+    mut commands: Commands,
+    time: Res<Time>,
+    underwater: Res<UnderwaterView>,
+    mut camera_query: Query<(Entity, Option<&mut UnderwaterPass>), With<FpsCameraController>>,
+) {
+    let Ok((camera, pass)) = camera_query.get_single_mut() else {
+        return;
+    };
+    let time = time.elapsed_seconds_wrapped();
+    match (underwater.submerged, pass) {
+        (true, Some(mut pass)) => pass.time = time,
+        (true, None) => {
+            commands.entity(camera).insert(UnderwaterPass {
+                time,
+                _webgl2_padding: Vec3::ZERO,
+            });
+        }
+        (false, Some(_)) => {
+            commands.entity(camera).remove::<UnderwaterPass>();
+        }
+        (false, None) => {}
+    }
+}
+
+/// Feed the camera being in water and the height of the surface to the terrain shader, which
+/// plays caustics over the surfaces below it near the camera, see `array_texture.wgsl`
+pub fn underwater_terrain(
+    time: Res<Time>,
+    underwater: Res<UnderwaterView>,
+    voxel_material: Res<VoxelMaterial>,
+    mut materials: ResMut<Assets<ArrayTextureMaterial>>,
+) {
+    if !voxel_material.loaded {
+        return;
+    }
+    let wanted = if underwater.submerged {
+        Vec4::new(1.0, time.elapsed_seconds_wrapped(), underwater.surface, 0.0)
+    } else {
+        Vec4::ZERO
+    };
+    for handle in [
+        &voxel_material.material,
+        &voxel_material.transparent_material,
+    ] {
+        let changed = materials
+            .get(handle)
+            .is_some_and(|material| material.underwater != wanted);
+        if changed {
+            if let Some(material) = materials.get_mut(handle) {
+                material.underwater = wanted;
+            }
+        }
+    }
+}
@@ -1,21 +1,704 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    f32::consts::FRAC_PI_2,
+    fmt,
+    mem::size_of,
+    ops::{Range, RangeInclusive},
+};
 
 use bevy::{
     prelude::*,
-    render::{mesh::Indices, render_resource::PrimitiveTopology},
+    render::{
+        mesh::{Indices, MeshVertexAttribute, VertexAttributeValues},
+        primitives::Aabb,
+        render_resource::{PrimitiveTopology, VertexFormat},
+    },
+    tasks::ComputeTaskPool,
+    utils::Instant,
 };
 
 use bevy_inspector_egui::{prelude::ReflectInspectorOptions, InspectorOptions};
 use lerp::Lerp;
-use noise::{NoiseFn, Perlin, Seedable};
+use noise::{NoiseFn, Perlin};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    block_entity::{self, BlockEntity},
+    codec,
+    decoration::ChunkDecorators,
+    profiler,
+    protection::Editor,
+    stored_entity::StoredEntity,
+};
+
+mod cache;
+mod coords;
+mod definitions;
+mod light;
+mod neighbourhood;
+mod occlusion;
+
+pub use cache::ChunkCache;
+pub use coords::{get_chunk_index, VoxelLocalIndex, VoxelPos};
+pub use definitions::{
+    apply_block_definitions, load_block_definitions, set_texture_layers, BlockDefinition,
+    BlockDefinitions, BlockDefinitionsLoader,
+};
+pub use light::{ChunkLight, LightColor, MAX_LIGHT};
+pub use neighbourhood::ChunkNeighbourhood;
+pub use occlusion::{visible_chunks, ChunkConnectivity, ChunkOcclusion};
 
-pub const WORLD_SIZE: usize = 100; // 4 chunks in each direction
 pub const INIT_WORLD_SIZE: usize = 4; // 4 chunks in each direction at the beginning
 pub const CHUNK_SIZE: usize = 16; // 16 voxels in each direction
 const WAVE_LENGTH: usize = 64; // voxel wave length in each direction
 pub const CHUNK_LIMIT_Y: usize = 16; // chunk limit in y direction
 pub const HEIGHT_LIMIT: usize = CHUNK_SIZE * CHUNK_LIMIT_Y; // height limit of the world
 
+/// Id of a block in the registry. Chunks store these bytes as they are, everywhere else
+/// they're kept apart from texture layers, states and counts
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Reflect,
+    Serialize,
+    Deserialize,
+)]
+#[serde(transparent)]
+#[repr(transparent)]
+pub struct BlockId(pub u8);
+
+impl BlockId {
+    /// The id `n` after this one, like a later growth stage of a crop
+    pub const fn offset(self, n: u8) -> BlockId {
+        BlockId(self.0 + n)
+    }
+
+    /// Index of the block's entry in the registry, air has none
+    fn registry_index(self) -> usize {
+        (self.0 as usize).wrapping_sub(1)
+    }
+}
+
+impl std::str::FromStr for BlockId {
+    type Err = std::num::ParseIntError;
+
+    /// An id as typed in commands, its number
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(BlockId)
+    }
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&block_name(*self))
+    }
+}
+
+/// Extra state of a single voxel next to its block id, what it means is up to the block.
+/// The lowest bits turn the block, see `state_rotation`
+pub type BlockState = u16;
+pub const TURNS_MASK: BlockState = 0b11; // quarter turns about y
+pub const AXIS_SHIFT: BlockState = 2; // then the axis the block's y lies along, 0 y, 1 x, 2 z
+pub const AXIS_MASK: BlockState = 0b11 << AXIS_SHIFT;
+pub const POWER_SHIFT: BlockState = 4; // then the power level of wire, see `power::power_at`
+pub const POWER_MASK: BlockState = 0b1111 << POWER_SHIFT;
+
+// block ids, 0 is air
+pub const AIR: BlockId = BlockId(0);
+pub const DIRT: BlockId = BlockId(1);
+pub const GRASS: BlockId = BlockId(2);
+pub const SNOW: BlockId = BlockId(3);
+pub const STONE: BlockId = BlockId(4);
+pub const GLASS: BlockId = BlockId(5);
+pub const LEAVES: BlockId = BlockId(6);
+pub const STONE_SLAB: BlockId = BlockId(7);
+pub const STONE_STAIRS: BlockId = BlockId(8);
+pub const FARMLAND: BlockId = BlockId(9);
+pub const WHEAT: BlockId = BlockId(10); // just planted, the first of `WHEAT_STAGES` growth stages
+pub const WHEAT_STAGES: u8 = 4;
+pub const RIPE_WHEAT: BlockId = WHEAT.offset(WHEAT_STAGES - 1);
+pub const FIRE: BlockId = BlockId(14);
+pub const LOG: BlockId = BlockId(15);
+pub const TNT: BlockId = BlockId(16);
+pub const SAND: BlockId = BlockId(17);
+pub const GRAVEL: BlockId = BlockId(18);
+// circuit parts come in pairs, the second one powered
+pub const WIRE: BlockId = BlockId(19);
+pub const WIRE_ON: BlockId = BlockId(20);
+pub const LEVER: BlockId = BlockId(21);
+pub const LEVER_ON: BlockId = BlockId(22);
+pub const BUTTON: BlockId = BlockId(23);
+pub const BUTTON_ON: BlockId = BlockId(24);
+pub const LAMP: BlockId = BlockId(25);
+pub const LAMP_ON: BlockId = BlockId(26);
+// doors are two blocks tall, their halves opening together
+pub const DOOR: BlockId = BlockId(27);
+pub const DOOR_OPEN: BlockId = BlockId(28);
+pub const DOOR_TOP: BlockId = BlockId(29);
+pub const DOOR_TOP_OPEN: BlockId = BlockId(30);
+pub const TRAPDOOR: BlockId = BlockId(31);
+pub const TRAPDOOR_OPEN: BlockId = BlockId(32);
+pub const FURNACE: BlockId = BlockId(33);
+pub const SIGN: BlockId = BlockId(34);
+pub const WATER: BlockId = BlockId(35);
+pub const BED: BlockId = BlockId(36);
+
+const SOIL_DEPTH: i32 = 3; // dirt layers below the surface block
+const BEACH_HEIGHT: i32 = 1; // land up to this high above the sea is sand
+const SAND_DEPTH: i32 = 4; // the sea floor is sand up to this deep, gravel below
+const STONE_SLOPE: i32 = 3; // height difference to a neighbour column that exposes stone
+const SNOW_LINE: i32 = 100; // snow line at neutral temperature
+const SNOW_LINE_TEMPERATURE_SHIFT: f64 = 24.0; // how far temperature moves the snow line
+const SNOW_LINE_JITTER: f64 = 4.0; // small scale noise on the snow line
+const TEMPERATURE_WAVE_LENGTH: f64 = 512.0;
+const SNOW_LINE_WAVE_LENGTH: f64 = 16.0;
+const VOID_PLATFORM_Y: i32 = 64; // top of the void world's platform
+const VOID_PLATFORM_RADIUS: i32 = 8; // the platform is twice this across, around the origin
+
+/// Vertex attribute of the voxel meshes, two words per vertex instead of full floats:
+/// the position on the 1/16 grid, then the texture layer, the normal and the uv corner.
+/// Decoded in `shaders/voxel_vertex.wgsl`
+pub const ATTRIBUTE_PACKED_VERTEX: MeshVertexAttribute =
+    MeshVertexAttribute::new("PackedVoxelVertex", 988540918, VertexFormat::Uint32x2);
+/// Vertex attribute of the voxel meshes with the smooth block light at the vertex, 4 bits
+/// per channel, then the light bounced onto its face, see `light::pack_lights`
+pub const ATTRIBUTE_VOXEL_LIGHT: MeshVertexAttribute =
+    MeshVertexAttribute::new("VoxelLight", 988540919, VertexFormat::Uint32);
+const POSITION_STEPS: f32 = 16.0; // per block, shapes are on a 1/16 grid
+const POSITION_BIAS: f32 = 1.0; // in blocks, so meshes centered on their origin stay positive
+
+/// Geometry of a block inside its cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum BlockShape {
+    Cube,
+    BottomSlab,
+    Stairs,    // steps up towards -z
+    Flat,      // lies on the block below, like wire
+    Panel,     // stands on the -z side, like a closed door
+    SidePanel, // stands on the -x side, like an open door
+    Hatch,     // a thin slab, like a closed trapdoor
+}
+
+const CUBE_BOXES: [(Vec3, Vec3); 1] = [(Vec3::ZERO, Vec3::ONE)];
+const SLAB_BOXES: [(Vec3, Vec3); 1] = [(Vec3::ZERO, Vec3::new(1.0, 0.5, 1.0))];
+const STAIRS_BOXES: [(Vec3, Vec3); 2] = [
+    (Vec3::ZERO, Vec3::new(1.0, 0.5, 1.0)),
+    (Vec3::new(0.0, 0.5, 0.0), Vec3::new(1.0, 1.0, 0.5)),
+];
+const FLAT_BOXES: [(Vec3, Vec3); 1] = [(Vec3::ZERO, Vec3::new(1.0, 1.0 / 16.0, 1.0))];
+const PANEL_BOXES: [(Vec3, Vec3); 1] = [(Vec3::ZERO, Vec3::new(1.0, 1.0, 3.0 / 16.0))];
+const SIDE_PANEL_BOXES: [(Vec3, Vec3); 1] = [(Vec3::ZERO, Vec3::new(3.0 / 16.0, 1.0, 1.0))];
+const HATCH_BOXES: [(Vec3, Vec3); 1] = [(Vec3::ZERO, Vec3::new(1.0, 3.0 / 16.0, 1.0))];
+
+impl BlockShape {
+    /// Mesh template of the shape: boxes as min and max corners within the unit cell,
+    /// also used as the shape's bounds for raycasts
+    pub fn boxes(self) -> &'static [(Vec3, Vec3)] {
+        match self {
+            BlockShape::Cube => &CUBE_BOXES,
+            BlockShape::BottomSlab => &SLAB_BOXES,
+            BlockShape::Stairs => &STAIRS_BOXES,
+            BlockShape::Flat => &FLAT_BOXES,
+            BlockShape::Panel => &PANEL_BOXES,
+            BlockShape::SidePanel => &SIDE_PANEL_BOXES,
+            BlockShape::Hatch => &HATCH_BOXES,
+        }
+    }
+}
+
+/// Group of blocks that gameplay targets instead of a list of ids
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockTag {
+    Logs,
+    DirtLike,    // ground plants can grow on
+    Unbreakable, // can't be mined by the player
+    Climbable,
+    Flammable, // fire spreads onto it
+}
+
+impl BlockTag {
+    pub const ALL: [BlockTag; 5] = [
+        BlockTag::Logs,
+        BlockTag::DirtLike,
+        BlockTag::Unbreakable,
+        BlockTag::Climbable,
+        BlockTag::Flammable,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BlockTag::Logs => "#logs",
+            BlockTag::DirtLike => "#dirt_like",
+            BlockTag::Unbreakable => "#unbreakable",
+            BlockTag::Climbable => "#climbable",
+            BlockTag::Flammable => "#flammable",
+        }
+    }
+
+    /// Tag from its name, e.g. `#logs`
+    pub fn from_name(name: &str) -> Option<Self> {
+        BlockTag::ALL.into_iter().find(|tag| tag.name() == name)
+    }
+
+    const fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// What a block does when a random tick picks it, see `random_tick`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RandomTick {
+    GrassSpread, // onto the dirt around it, or back to dirt once covered
+    CropGrowth,  // a stage closer to ripe
+    LeafDecay,   // away once nothing holds it up
+    FireSpread,  // onto flammable blocks around it, burning out in the end
+}
+
+/// What using a block with the place button does instead of placing against it,
+/// see `on_use`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnUse {
+    Toggle(BlockId), // turns into the other block, like a lever flipping
+    Door(BlockId),   // toggles along with the other half of the door
+    Press,           // a button, on until the circuit lets it go
+    Open,            // opens the window of its block entity, like a furnace's or a sign's
+    Respawn,         // makes it where the player respawns, like a bed
+}
+
+/// How a block is turned when it's placed, see `placed_state`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Fixed,
+    Facing, // its -z side away from the player, like stairs stepping up away from them
+    Axis,   // lies along the normal of the face it's placed against, like logs
+}
+
+/// How a block is drawn, which tags it carries and what it does over time
+#[derive(Debug, Clone, Copy)]
+pub struct BlockProperties {
+    pub layer: u32,        // layer in `textures/array_texture.png`
+    pub transparent: bool, // see-through, drawn in the alpha tested pass
+    pub shape: BlockShape,
+    tags: u8, // one bit per `BlockTag`
+    pub random_tick: Option<RandomTick>,
+    pub falls: bool,       // drops down when there's nothing under it
+    pub glows: bool,       // lit by itself, however dark it is around it
+    pub light: LightColor, // given off to the voxels around it, see `light`
+    pub passable: bool,    // mobs, items and the player go through it
+    pub on_use: Option<OnUse>,
+    pub orientation: Orientation,
+}
+
+impl BlockProperties {
+    const fn cube(layer: u32) -> Self {
+        BlockProperties {
+            layer,
+            transparent: false,
+            shape: BlockShape::Cube,
+            tags: 0,
+            random_tick: None,
+            falls: false,
+            glows: false,
+            light: [0; 3],
+            passable: false,
+            on_use: None,
+            orientation: Orientation::Fixed,
+        }
+    }
+
+    const fn see_through(layer: u32) -> Self {
+        BlockProperties {
+            layer,
+            transparent: true,
+            shape: BlockShape::Cube,
+            tags: 0,
+            random_tick: None,
+            falls: false,
+            glows: false,
+            light: [0; 3],
+            passable: false,
+            on_use: None,
+            orientation: Orientation::Fixed,
+        }
+    }
+
+    const fn shaped(layer: u32, shape: BlockShape) -> Self {
+        BlockProperties {
+            layer,
+            transparent: false,
+            shape,
+            tags: 0,
+            random_tick: None,
+            falls: false,
+            glows: false,
+            light: [0; 3],
+            passable: false,
+            on_use: None,
+            orientation: Orientation::Fixed,
+        }
+    }
+
+    const fn tagged(mut self, tag: BlockTag) -> Self {
+        self.tags |= tag.bit();
+        self
+    }
+
+    const fn ticking(mut self, random_tick: RandomTick) -> Self {
+        self.random_tick = Some(random_tick);
+        self
+    }
+
+    const fn falling(mut self) -> Self {
+        self.falls = true;
+        self
+    }
+
+    const fn glowing(mut self) -> Self {
+        self.glows = true;
+        self
+    }
+
+    const fn lit(mut self, light: LightColor) -> Self {
+        self.light = light;
+        self
+    }
+
+    const fn passable(mut self) -> Self {
+        self.passable = true;
+        self
+    }
+
+    const fn used(mut self, on_use: OnUse) -> Self {
+        self.on_use = Some(on_use);
+        self
+    }
+
+    const fn oriented(mut self, orientation: Orientation) -> Self {
+        self.orientation = orientation;
+        self
+    }
+
+    pub fn has_tag(&self, tag: BlockTag) -> bool {
+        self.tags & tag.bit() != 0
+    }
+}
+
+/// Block registry, indexed by block id from `DIRT` on
+const BLOCKS: [BlockProperties; 36] = [
+    BlockProperties::cube(1).tagged(BlockTag::DirtLike), // dirt
+    BlockProperties::cube(0)
+        .tagged(BlockTag::DirtLike)
+        .ticking(RandomTick::GrassSpread), // grass
+    BlockProperties::cube(2),                            // snow
+    BlockProperties::cube(3),                            // stone
+    BlockProperties::see_through(4),                     // glass
+    BlockProperties::see_through(5)
+        .tagged(BlockTag::Flammable)
+        .ticking(RandomTick::LeafDecay), // leaves
+    BlockProperties::shaped(3, BlockShape::BottomSlab),  // stone slab
+    BlockProperties::shaped(3, BlockShape::Stairs).oriented(Orientation::Facing), // stone stairs
+    BlockProperties::cube(6),                            // farmland
+    BlockProperties::see_through(7).ticking(RandomTick::CropGrowth), // wheat, growing
+    BlockProperties::see_through(8).ticking(RandomTick::CropGrowth),
+    BlockProperties::see_through(9).ticking(RandomTick::CropGrowth),
+    BlockProperties::see_through(10), // ripe wheat
+    BlockProperties::see_through(11)
+        .ticking(RandomTick::FireSpread)
+        .lit([15, 9, 3]), // fire
+    BlockProperties::cube(12)
+        .tagged(BlockTag::Logs)
+        .tagged(BlockTag::Flammable)
+        .oriented(Orientation::Axis), // log
+    BlockProperties::cube(13),        // tnt
+    BlockProperties::cube(14).falling(), // sand
+    BlockProperties::cube(15).falling(), // gravel
+    BlockProperties::shaped(16, BlockShape::Flat), // wire
+    BlockProperties::shaped(17, BlockShape::Flat)
+        .glowing()
+        .lit([8, 0, 0]),
+    BlockProperties::shaped(18, BlockShape::Flat).used(OnUse::Toggle(LEVER_ON)), // lever
+    BlockProperties::shaped(19, BlockShape::Flat).used(OnUse::Toggle(LEVER)),
+    BlockProperties::shaped(20, BlockShape::Flat).used(OnUse::Press), // button
+    BlockProperties::shaped(20, BlockShape::Flat),
+    BlockProperties::cube(21), // lamp
+    BlockProperties::cube(22).glowing().lit([14, 13, 10]),
+    BlockProperties::shaped(23, BlockShape::Panel)
+        .tagged(BlockTag::Flammable)
+        .used(OnUse::Door(DOOR_OPEN)), // door
+    BlockProperties::shaped(23, BlockShape::SidePanel)
+        .tagged(BlockTag::Flammable)
+        .passable()
+        .used(OnUse::Door(DOOR)),
+    BlockProperties::shaped(23, BlockShape::Panel)
+        .tagged(BlockTag::Flammable)
+        .used(OnUse::Door(DOOR_TOP_OPEN)), // door, upper half
+    BlockProperties::shaped(23, BlockShape::SidePanel)
+        .tagged(BlockTag::Flammable)
+        .passable()
+        .used(OnUse::Door(DOOR_TOP)),
+    BlockProperties::shaped(24, BlockShape::Hatch)
+        .tagged(BlockTag::Flammable)
+        .used(OnUse::Toggle(TRAPDOOR_OPEN)), // trapdoor
+    BlockProperties::shaped(24, BlockShape::Panel)
+        .tagged(BlockTag::Flammable)
+        .passable()
+        .used(OnUse::Toggle(TRAPDOOR)),
+    BlockProperties::cube(25)
+        .oriented(Orientation::Facing)
+        .used(OnUse::Open), // furnace
+    BlockProperties::shaped(24, BlockShape::Panel)
+        .tagged(BlockTag::Flammable)
+        .passable()
+        .oriented(Orientation::Facing)
+        .used(OnUse::Open), // sign
+    BlockProperties::see_through(26)
+        .tagged(BlockTag::Unbreakable)
+        .passable(), // water
+    BlockProperties::shaped(27, BlockShape::BottomSlab)
+        .tagged(BlockTag::Flammable)
+        .oriented(Orientation::Facing)
+        .used(OnUse::Respawn), // bed
+];
+
+/// Highest block id with an entry in the built-in registry
+pub const LAST_BUILT_IN_BLOCK: BlockId = BlockId(BLOCKS.len() as u8);
+
+/// Highest block id with an entry in the registry, including the defined blocks
+pub fn last_block() -> BlockId {
+    let defined = BlockId(definitions::DEFINED_BLOCKS.read().unwrap().len() as u8);
+    defined.max(LAST_BUILT_IN_BLOCK)
+}
+
+/// Name of a block, as shown to the player
+pub fn block_name(block: BlockId) -> String {
+    let index = block.registry_index();
+    let defined = definitions::DEFINED_BLOCKS.read().unwrap();
+    if let Some(name) = defined.get(index).and_then(|block| block.name.as_ref()) {
+        return name.clone();
+    }
+    built_in_name(block).to_string()
+}
+
+fn built_in_name(block: BlockId) -> &'static str {
+    match block {
+        AIR => "Air",
+        DIRT => "Dirt",
+        GRASS => "Grass",
+        SNOW => "Snow",
+        STONE => "Stone",
+        GLASS => "Glass",
+        LEAVES => "Leaves",
+        STONE_SLAB => "Stone slab",
+        STONE_STAIRS => "Stone stairs",
+        FARMLAND => "Farmland",
+        RIPE_WHEAT => "Wheat",
+        _ if (WHEAT..=RIPE_WHEAT).contains(&block) => "Seeds",
+        FIRE => "Fire",
+        LOG => "Log",
+        TNT => "TNT",
+        SAND => "Sand",
+        GRAVEL => "Gravel",
+        WIRE | WIRE_ON => "Wire",
+        LEVER | LEVER_ON => "Lever",
+        BUTTON | BUTTON_ON => "Button",
+        LAMP | LAMP_ON => "Lamp",
+        _ if (DOOR..=DOOR_TOP_OPEN).contains(&block) => "Door",
+        TRAPDOOR | TRAPDOOR_OPEN => "Trapdoor",
+        FURNACE => "Furnace",
+        SIGN => "Sign",
+        WATER => "Water",
+        BED => "Bed",
+        _ => "Unknown block",
+    }
+}
+
+/// Properties of a block, unknown ids draw as stone
+pub fn block_properties(block: BlockId) -> BlockProperties {
+    let index = block.registry_index();
+    let defined = definitions::DEFINED_BLOCKS.read().unwrap();
+    if !defined.is_empty() {
+        return defined
+            .get(index)
+            .unwrap_or(&defined[STONE.registry_index()])
+            .properties;
+    }
+    BLOCKS
+        .get(index)
+        .copied()
+        .unwrap_or(BLOCKS[STONE.registry_index()])
+}
+
+/// What a mined block drops when its definition says so, see `item::drops` otherwise
+pub fn defined_drops(block: BlockId) -> Option<Vec<BlockId>> {
+    let index = block.registry_index();
+    let defined = definitions::DEFINED_BLOCKS.read().unwrap();
+    defined.get(index).and_then(|block| block.drops.clone())
+}
+
+/// Layer in `textures/array_texture.png` used to draw a block
+pub fn texture_layer(block: BlockId) -> u32 {
+    block_properties(block).layer
+}
+
+/// Set on the texture layer of glowing blocks' vertices, the shader lights them fully
+const GLOW_BIT: u32 = 1 << 16;
+
+/// Texture layer as stored in the mesh, with `GLOW_BIT` for glowing blocks
+fn vertex_layer(block: BlockId) -> u32 {
+    let properties = block_properties(block);
+    if properties.glows {
+        properties.layer | GLOW_BIT
+    } else {
+        properties.layer
+    }
+}
+
+/// What the block does when a random tick picks it, if anything
+pub fn random_tick(block: BlockId) -> Option<RandomTick> {
+    if block == AIR {
+        return None;
+    }
+    block_properties(block).random_tick
+}
+
+/// What using the block does, if it does anything
+pub fn on_use(block: BlockId) -> Option<OnUse> {
+    if block == AIR {
+        return None;
+    }
+    block_properties(block).on_use
+}
+
+/// Whether mobs, items and the player stand on the block and can't go through it
+pub fn is_solid(block: BlockId) -> bool {
+    block != AIR && !block_properties(block).passable
+}
+
+/// State of `block` placed against the face with `normal`, by a player looking along `forward`
+pub fn placed_state(block: BlockId, normal: IVec3, forward: Vec3) -> BlockState {
+    if block == AIR {
+        return 0;
+    }
+    match block_properties(block).orientation {
+        Orientation::Fixed => 0,
+        // the turns that bring -z closest to `forward`
+        Orientation::Facing if forward.z.abs() >= forward.x.abs() => {
+            if forward.z < 0.0 {
+                0
+            } else {
+                2
+            }
+        }
+        Orientation::Facing => {
+            if forward.x < 0.0 {
+                1
+            } else {
+                3
+            }
+        }
+        Orientation::Axis if normal.x != 0 => 1 << AXIS_SHIFT,
+        Orientation::Axis if normal.z != 0 => 2 << AXIS_SHIFT,
+        Orientation::Axis => 0,
+    }
+}
+
+/// Rotation about the middle of its cell of a block in `state`
+pub fn state_rotation(state: BlockState) -> Quat {
+    let turns = Quat::from_rotation_y((state & TURNS_MASK) as f32 * FRAC_PI_2);
+    let axis = match (state & AXIS_MASK) >> AXIS_SHIFT {
+        1 => Quat::from_rotation_z(FRAC_PI_2),
+        2 => Quat::from_rotation_x(FRAC_PI_2),
+        _ => Quat::IDENTITY,
+    };
+    axis * turns
+}
+
+/// Whether the block drops down when the one under it is gone
+pub fn falls(block: BlockId) -> bool {
+    block != AIR && block_properties(block).falls
+}
+
+pub fn has_tag(block: BlockId, tag: BlockTag) -> bool {
+    block != AIR && block_properties(block).has_tag(tag)
+}
+
+/// Every registered block carrying `tag`
+pub fn blocks_with_tag(tag: BlockTag) -> impl Iterator<Item = BlockId> {
+    (1..=last_block().0)
+        .map(BlockId)
+        .filter(move |block| has_tag(*block, tag))
+}
+
+/// Seconds of holding the break button to mine a block by hand in survival
+pub fn mining_time(block: BlockId) -> f32 {
+    let index = block.registry_index();
+    let defined = definitions::DEFINED_BLOCKS.read().unwrap();
+    if let Some(time) = defined.get(index).and_then(|block| block.mining_time) {
+        return time;
+    }
+    match block {
+        AIR => 0.0,
+        FIRE | TNT => 0.0,
+        _ if (WHEAT..=RIPE_WHEAT).contains(&block) || (WIRE..=BUTTON_ON).contains(&block) => 0.0,
+        LEAVES | LAMP | LAMP_ON | BED => 0.3,
+        SNOW | GLASS | SAND => 0.5,
+        FURNACE => 3.5,
+        DIRT | FARMLAND | GRAVEL => 0.75,
+        GRASS => 0.9,
+        _ => 2.0,
+    }
+}
+
+pub fn is_transparent(block: BlockId) -> bool {
+    block == AIR || block_properties(block).transparent
+}
+
+/// Whether a block fills its whole cell with opaque faces, hiding its neighbours' faces
+fn occludes(block: BlockId) -> bool {
+    !is_transparent(block) && block_properties(block).shape == BlockShape::Cube
+}
+
+/// Whether `block` shows its face towards `neighbour`: faces next to air, a see-through
+/// or a shaped block are drawn, faces between two of the same see-through block aren't
+fn shows_face(block: BlockId, neighbour: BlockId) -> bool {
+    !occludes(neighbour)
+        && (neighbour != block || block_properties(block).shape != BlockShape::Cube)
+}
+
+/// Whether a ray enters the shape of `block` in `state` at `position`, so it passes over
+/// the open part of slabs and stairs
+pub fn ray_hits_block(
+    block: BlockId,
+    state: BlockState,
+    position: VoxelPos,
+    origin: Vec3,
+    direction: Vec3,
+) -> bool {
+    // turn the ray instead of the shape, into the cell of the unturned block
+    let rotation = state_rotation(state).inverse();
+    let center = position.0.as_vec3() + Vec3::splat(0.5);
+    let origin = center + rotation * (origin - center);
+    let direction = rotation * direction;
+    let cell = position.0.as_vec3();
+    let inverse = direction.recip();
+    block_properties(block)
+        .shape
+        .boxes()
+        .iter()
+        .any(|(min, max)| {
+            let t1 = (cell + *min - origin) * inverse;
+            let t2 = (cell + *max - origin) * inverse;
+            t1.max(t2).min_element() >= t1.min(t2).max_element().max(0.0)
+        })
+}
+
 // cube cornors
 const CORNORS: [Vec3; 8] = [
     Vec3::new(1.0, 1.0, 1.0),
@@ -45,74 +728,179 @@ const UVS: [Vec2; 4] = [
     Vec2::new(1.0, 1.0),
 ];
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ChunkIndex {
     pub x: i32,
     pub y: i32,
     pub z: i32,
 }
 
-#[derive(Debug, Copy, Clone)]
-pub struct Block {
-    pub size: f32,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "codec::RleChunk", try_from = "codec::RleChunk")]
+pub struct ChunkData {
+    pub level: u32, // level or lod, normally 0
+    pub index: ChunkIndex,
+    pub voxels: [[[BlockId; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE], // row(z), col(x), depth(y)
+    pub states: BTreeMap<u16, BlockState>, // by packed local index, only the voxels with a state
+    pub entities: BTreeMap<u16, BlockEntity>, // by packed local index
+    pub stored: Vec<StoredEntity>,         // items, mobs and falling blocks from when it unloaded
 }
 
-impl Block {
-    pub fn new(size: f32) -> Self {
-        Block { size }
+impl ChunkData {
+    /// Bytes the chunk takes up, its voxels and roughly its states and block entities
+    pub fn memory_bytes(&self) -> usize {
+        size_of::<Self>()
+            + self.states.len() * size_of::<(u16, BlockState)>()
+            + self.entities.len() * size_of::<(u16, BlockEntity)>()
+            + self.stored.len() * size_of::<StoredEntity>()
     }
-}
 
-impl Default for Block {
-    fn default() -> Self {
-        Block { size: 1.0 }
+    /// State of the voxel at `local`, 0 if it has none
+    pub fn state(&self, local: VoxelLocalIndex) -> BlockState {
+        self.states.get(&local.packed()).copied().unwrap_or(0)
     }
-}
 
-impl From<Block> for Mesh {
-    fn from(block: Block) -> Self {
-        shape::Box::new(block.size, block.size, block.size).into()
+    /// Give the voxel at `local` a state, 0 takes it away
+    pub fn set_state(&mut self, local: VoxelLocalIndex, state: BlockState) {
+        if state == 0 {
+            self.states.remove(&local.packed());
+        } else {
+            self.states.insert(local.packed(), state);
+        }
     }
-}
 
-#[derive(Debug, Copy, Clone)]
-pub struct ChunkData {
-    pub level: u32, // level or lod, normally 0
-    pub index: ChunkIndex,
-    pub voxels: [[[u8; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE], // row(z), col(x), depth(y)
-}
+    /// Set the block at `local` without a state, a different block than the one there gets
+    /// a new block entity. Returns the block entity of the block it replaced
+    pub fn set_block(&mut self, local: VoxelLocalIndex, block: BlockId) -> Option<BlockEntity> {
+        self.set_state(local, 0);
+        let voxel = &mut self.voxels[local.x as usize][local.y as usize][local.z as usize];
+        if *voxel == block {
+            return None;
+        }
+        *voxel = block;
+        self.set_entity(local, block_entity::new_entity(block))
+    }
 
-impl ChunkData {
-    pub fn new(chunk_index: ChunkIndex) -> Self {
-        let perlin = Perlin::new(123);
+    pub fn entity(&self, local: VoxelLocalIndex) -> Option<&BlockEntity> {
+        self.entities.get(&local.packed())
+    }
 
-        let chunk_offset = Vec3::new(
-            chunk_index.x as f32 * CHUNK_SIZE as f32,
-            chunk_index.y as f32 * CHUNK_SIZE as f32,
-            chunk_index.z as f32 * CHUNK_SIZE as f32,
-        );
+    pub fn entity_mut(&mut self, local: VoxelLocalIndex) -> Option<&mut BlockEntity> {
+        self.entities.get_mut(&local.packed())
+    }
+
+    /// Put `entity` at `local`, or take the one there away with `None`, returns the one it replaced
+    pub fn set_entity(
+        &mut self,
+        local: VoxelLocalIndex,
+        entity: Option<BlockEntity>,
+    ) -> Option<BlockEntity> {
+        match entity {
+            Some(entity) => self.entities.insert(local.packed(), entity),
+            None => self.entities.remove(&local.packed()),
+        }
+    }
+
+    pub fn new(chunk_index: ChunkIndex, settings: &WorldGenSettings) -> Self {
+        match settings.preset {
+            WorldPreset::Terrain => Self::terrain(chunk_index, settings),
+            WorldPreset::Flat | WorldPreset::Void => {
+                let origin = chunk_index.origin();
+                let mut voxels = [[[AIR; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+                for (x, plane) in voxels.iter_mut().enumerate() {
+                    for (y, row) in plane.iter_mut().enumerate() {
+                        for (z, voxel) in row.iter_mut().enumerate() {
+                            let world = origin + IVec3::new(x as i32, y as i32, z as i32);
+                            *voxel = preset_block(world, settings);
+                        }
+                    }
+                }
+                ChunkData {
+                    level: 0,
+                    index: chunk_index,
+                    voxels,
+                    states: BTreeMap::new(),
+                    entities: BTreeMap::new(),
+                    stored: Vec::new(),
+                }
+            }
+        }
+    }
+
+    /// Noise terrain, the default world
+    fn terrain(chunk_index: ChunkIndex, settings: &WorldGenSettings) -> Self {
+        let noise = TerrainNoise::new(settings.seed);
+        let temperature_noise = Perlin::new(settings.seed.wrapping_add(1));
+        let snow_line_noise = Perlin::new(settings.seed.wrapping_add(2));
 
-        let mut voxels = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+        let chunk_offset = chunk_index.origin().as_vec3();
+
+        // heightmap with a one voxel border, so slopes can be computed at the chunk edges
+        let heights = heightmap(&noise, chunk_offset, settings);
+
+        let mut voxels = [[[AIR; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
         (0..CHUNK_SIZE).for_each(|x| {
             (0..CHUNK_SIZE).for_each(|z| {
-                let val = perlin.get([
-                    (x as f64 + chunk_offset.x as f64) / WAVE_LENGTH as f64,
-                    (z as f64 + chunk_offset.z as f64) / WAVE_LENGTH as f64,
-                    0.0,
+                let world_x = x as f64 + chunk_offset.x as f64;
+                let world_z = z as f64 + chunk_offset.z as f64;
+                let land = heights[x + 1][z + 1];
+
+                // steepest drop to a neighbour column
+                let slope = [
+                    heights[x][z + 1],
+                    heights[x + 2][z + 1],
+                    heights[x + 1][z],
+                    heights[x + 1][z + 2],
+                ]
+                .iter()
+                .map(|h| (land - h).abs())
+                .max()
+                .unwrap_or(0);
+
+                // colder regions have a lower snow line
+                let temperature = temperature_noise.get([
+                    world_x / TEMPERATURE_WAVE_LENGTH,
+                    world_z / TEMPERATURE_WAVE_LENGTH,
                 ]);
-                let land = 48.0.lerp(128.0, (val + 1.0) / 2.0) as i32;
-                // println!(
-                //     "Land at ({}, {}): {}",
-                //     x + chunk_offset.x as usize,
-                //     z + chunk_offset.z as usize,
-                //     land
-                // );
+                let snow_line = SNOW_LINE as f64
+                    + temperature * SNOW_LINE_TEMPERATURE_SHIFT
+                    + snow_line_noise.get([
+                        world_x / SNOW_LINE_WAVE_LENGTH,
+                        world_z / SNOW_LINE_WAVE_LENGTH,
+                    ]) * SNOW_LINE_JITTER;
+
+                let surface = if slope >= STONE_SLOPE {
+                    STONE
+                } else if land < settings.sea_level - SAND_DEPTH {
+                    GRAVEL
+                } else if land <= settings.sea_level + BEACH_HEIGHT {
+                    SAND
+                } else if land as f64 > snow_line {
+                    SNOW
+                } else {
+                    GRASS
+                };
+
+                // beaches and the sea floor are sand or gravel all the way down to the stone
+                let soil = match surface {
+                    SAND | GRAVEL => surface,
+                    _ => DIRT,
+                };
+
                 (0..CHUNK_SIZE).for_each(|y: usize| {
-                    if (y + chunk_offset.y as usize) as i32 > land {
-                        voxels[x][y][z] = 0;
+                    let world_y = (y + chunk_offset.y as usize) as i32;
+                    let depth = land - world_y;
+                    voxels[x][y][z] = if depth < 0 && world_y < settings.sea_level {
+                        WATER
+                    } else if depth < 0 {
+                        AIR
+                    } else if depth == 0 {
+                        surface
+                    } else if depth <= SOIL_DEPTH && surface != STONE {
+                        soil
                     } else {
-                        voxels[x][y][z] = 1;
-                    }
+                        STONE
+                    };
                 })
             })
         });
@@ -121,13 +909,270 @@ impl ChunkData {
             level: 0,
             index: chunk_index,
             voxels,
+            states: BTreeMap::new(),
+            entities: BTreeMap::new(),
+            stored: Vec::new(),
+        }
+    }
+}
+
+/// Generate many chunks at once, spread over the compute task pool
+pub fn generate_chunks(
+    indices: &[ChunkIndex],
+    settings: &WorldGenSettings,
+    decorators: &ChunkDecorators,
+) -> Vec<ChunkData> {
+    if indices.is_empty() {
+        return Vec::new();
+    }
+    let pool = ComputeTaskPool::get();
+    let batch_size = indices.len().div_ceil(pool.thread_num().max(1));
+    pool.scope(|scope| {
+        for batch in indices.chunks(batch_size) {
+            scope.spawn(async move {
+                batch
+                    .iter()
+                    .map(|index| {
+                        let mut chunk = ChunkData::new(*index, settings);
+                        decorators.decorate(&mut chunk, settings);
+                        chunk
+                    })
+                    .collect::<Vec<_>>()
+            });
+        }
+    })
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Noises the land height is made of: the height itself and the warp along each axis
+struct TerrainNoise {
+    height: Perlin,
+    warp_x: Perlin,
+    warp_z: Perlin,
+}
+
+impl TerrainNoise {
+    fn new(seed: u32) -> Self {
+        TerrainNoise {
+            height: Perlin::new(seed),
+            warp_x: Perlin::new(seed.wrapping_add(3)),
+            warp_z: Perlin::new(seed.wrapping_add(4)),
+        }
+    }
+}
+
+/// Fractal noise, about -1 to 1: `octaves` samples, each `lacunarity` times finer and
+/// `persistence` times weaker than the one before, blended towards ridges by `ridges`
+fn fractal_noise(perlin: &Perlin, x: f64, z: f64, settings: &WorldGenSettings) -> f64 {
+    let (mut sum, mut total) = (0.0, 0.0);
+    let (mut frequency, mut amplitude) = (1.0, 1.0);
+    for octave in 0..settings.octaves.max(1) {
+        // every octave in its own slice of the noise, so they don't line up
+        let mut sample = perlin.get([x * frequency, z * frequency, octave as f64 * 0.5]);
+        if settings.ridges > 0.0 {
+            sample = sample.lerp(1.0 - 2.0 * sample.abs(), settings.ridges as f64);
+        }
+        sum += sample * amplitude;
+        total += amplitude;
+        frequency *= settings.lacunarity as f64;
+        amplitude *= settings.persistence as f64;
+    }
+    sum / total
+}
+
+/// Land height for a noise value, along the straight segments between the spline's points,
+/// flat past its ends
+fn spline_height(spline: &[HeightPoint], value: f64) -> f64 {
+    let (Some(first), Some(last)) = (spline.first(), spline.last()) else {
+        return 48.0.lerp(128.0, (value + 1.0) / 2.0);
+    };
+    if value <= first.noise as f64 {
+        return first.height as f64;
+    }
+    for pair in spline.windows(2) {
+        let (from, to) = (pair[0], pair[1]);
+        if value <= to.noise as f64 {
+            let t = (value - from.noise as f64) / (to.noise as f64 - from.noise as f64);
+            return (from.height as f64).lerp(to.height as f64, t);
+        }
+    }
+    last.height as f64
+}
+
+fn land_height(
+    noise: &TerrainNoise,
+    world_x: f64,
+    world_z: f64,
+    settings: &WorldGenSettings,
+) -> f32 {
+    let (mut x, mut z) = (world_x, world_z);
+    // domain warping, the height is sampled where two more noises push the column to
+    if settings.warp_strength != 0.0 {
+        let warp_wave_length = settings.warp_wave_length as f64;
+        let point = [world_x / warp_wave_length, world_z / warp_wave_length, 0.0];
+        x += noise.warp_x.get(point) * settings.warp_strength as f64;
+        z += noise.warp_z.get(point) * settings.warp_strength as f64;
+    }
+    let wave_length = settings.wave_length as f64;
+    let value = fractal_noise(&noise.height, x / wave_length, z / wave_length, settings);
+    spline_height(&settings.height_spline, value) as f32
+}
+
+/// Land height at a world column, before erosion
+pub fn surface_height(world_x: i32, world_z: i32, settings: &WorldGenSettings) -> i32 {
+    match settings.preset {
+        WorldPreset::Terrain => {
+            let noise = TerrainNoise::new(settings.seed);
+            land_height(&noise, world_x as f64, world_z as f64, settings) as i32
+        }
+        WorldPreset::Flat => flat_height(&settings.flat_layers) - 1,
+        WorldPreset::Void => VOID_PLATFORM_Y,
+    }
+}
+
+/// Blocks the flat layers stack up to
+fn flat_height(layers: &[FlatLayer]) -> i32 {
+    layers.iter().map(|layer| layer.thickness as i32).sum()
+}
+
+/// Block at a world position of the flat or the void world
+fn preset_block(world: IVec3, settings: &WorldGenSettings) -> BlockId {
+    match settings.preset {
+        WorldPreset::Flat => {
+            let mut bottom = 0;
+            for layer in settings.flat_layers.iter() {
+                bottom += layer.thickness as i32;
+                if world.y < bottom {
+                    return layer.block;
+                }
+            }
+            AIR
+        }
+        WorldPreset::Void => {
+            let on_platform = world.y == VOID_PLATFORM_Y
+                && (-VOID_PLATFORM_RADIUS..VOID_PLATFORM_RADIUS).contains(&world.x)
+                && (-VOID_PLATFORM_RADIUS..VOID_PLATFORM_RADIUS).contains(&world.z);
+            if on_platform {
+                STONE
+            } else {
+                AIR
+            }
         }
+        WorldPreset::Terrain => unreachable!("terrain is generated a chunk at a time"),
     }
 }
 
+/// Land heights of a chunk plus a one voxel border around it
+fn heightmap(
+    noise: &TerrainNoise,
+    chunk_offset: Vec3,
+    settings: &WorldGenSettings,
+) -> [[i32; CHUNK_SIZE + 2]; CHUNK_SIZE + 2] {
+    // every erosion iteration reads one cell further out, so sample a wider area
+    // and keep only the center, which then matches the neighbour chunks exactly
+    let padding = if settings.erosion {
+        settings.erosion_iterations as usize + 1
+    } else {
+        1
+    };
+    let size = CHUNK_SIZE + padding * 2;
+
+    let mut heights = vec![vec![0.0; size]; size];
+    (0..size).for_each(|x| {
+        (0..size).for_each(|z| {
+            heights[x][z] = land_height(
+                noise,
+                chunk_offset.x as f64 + x as f64 - padding as f64,
+                chunk_offset.z as f64 + z as f64 - padding as f64,
+                settings,
+            );
+        })
+    });
+
+    if settings.erosion {
+        (0..settings.erosion_iterations).for_each(|_| {
+            thermal_erosion(&mut heights, settings.talus);
+        });
+    }
+
+    let mut result = [[0; CHUNK_SIZE + 2]; CHUNK_SIZE + 2];
+    (0..CHUNK_SIZE + 2).for_each(|x| {
+        (0..CHUNK_SIZE + 2).for_each(|z| {
+            result[x][z] = heights[x + padding - 1][z + padding - 1] as i32;
+        })
+    });
+    result
+}
+
+/// One step of thermal erosion: material slides down wherever the drop to a
+/// neighbour is steeper than `talus`, leaving talus slopes below cliffs
+fn thermal_erosion(heights: &mut [Vec<f32>], talus: f32) {
+    let size = heights.len();
+    let mut delta = vec![vec![0.0; size]; size];
+    (0..size).for_each(|x| {
+        (0..size).for_each(|z| {
+            let h = heights[x][z];
+            let neighbours = [
+                (x.wrapping_sub(1), z),
+                (x + 1, z),
+                (x, z.wrapping_sub(1)),
+                (x, z + 1),
+            ];
+            for (nx, nz) in neighbours {
+                if nx >= size || nz >= size {
+                    continue;
+                }
+                let drop = h - heights[nx][nz];
+                if drop > talus {
+                    // an eighth per neighbour, so a cell never gives away more than half its excess
+                    let amount = (drop - talus) / 8.0;
+                    delta[x][z] -= amount;
+                    delta[nx][nz] += amount;
+                }
+            }
+        })
+    });
+    (0..size).for_each(|x| {
+        (0..size).for_each(|z| {
+            heights[x][z] += delta[x][z];
+        })
+    });
+}
+
 impl Default for ChunkData {
     fn default() -> Self {
-        ChunkData::new(ChunkIndex { x: 0, y: 0, z: 0 })
+        ChunkData::new(
+            ChunkIndex { x: 0, y: 0, z: 0 },
+            &WorldGenSettings::default(),
+        )
+    }
+}
+
+#[cfg(test)]
+impl ChunkData {
+    /// A chunk of nothing but air for tests to build in, whatever the generator puts there
+    pub(crate) fn air(index: ChunkIndex) -> Self {
+        ChunkData {
+            index,
+            voxels: [[[AIR; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+impl VoxelData {
+    /// World data with all-air chunks loaded at `indices`
+    pub(crate) fn air(indices: impl IntoIterator<Item = ChunkIndex>) -> Self {
+        VoxelData {
+            chunks: indices
+                .into_iter()
+                .map(|index| (index, ChunkData::air(index)))
+                .collect(),
+            ..Default::default()
+        }
     }
 }
 
@@ -194,6 +1239,8 @@ pub struct MeshData {
     indices: Vec<u32>,
     normals: Vec<Vec3>,
     uvs: Vec<Vec2>,
+    layers: Vec<u32>,
+    lights: Vec<u32>, // packed, see `light::pack_light`
 }
 
 impl MeshData {
@@ -203,11 +1250,61 @@ impl MeshData {
             indices: Vec::new(),
             normals: Vec::new(),
             uvs: Vec::new(),
+            layers: Vec::new(),
+            lights: Vec::new(),
         }
     }
+
+    /// Bytes held by the vertex and index buffers
+    pub fn heap_bytes(&self) -> usize {
+        self.positions.capacity() * size_of::<Vec3>()
+            + self.indices.capacity() * size_of::<u32>()
+            + self.normals.capacity() * size_of::<Vec3>()
+            + self.uvs.capacity() * size_of::<Vec2>()
+            + self.layers.capacity() * size_of::<u32>()
+            + self.lights.capacity() * size_of::<u32>()
+    }
+
+    /// Bounds of the vertices, bevy can't compute them from the packed vertices
+    pub fn bounds(&self) -> Option<Aabb> {
+        let first = *self.positions.first()?;
+        let (min, max) = self
+            .positions
+            .iter()
+            .fold((first, first), |(min, max), p| (min.min(*p), max.max(*p)));
+        Some(Aabb::from_min_max(min, max))
+    }
+}
+
+/// Where a chunk's faces sit in its column's mesh, which is drawn at the column's origin
+fn column_offset(index: ChunkIndex) -> Vec3 {
+    Vec3::new(0.0, (index.y * CHUNK_SIZE as i32) as f32, 0.0)
+}
+
+/// Pack a vertex into `ATTRIBUTE_PACKED_VERTEX`: x, z and y on the 1/16 grid in 9, 9 and 14
+/// bits, then the layer with its glow bit in 17 bits, the normal and the uv corner
+fn pack_vertex(position: Vec3, normal: Vec3, uv: Vec2, layer: u32) -> [u32; 2] {
+    let grid = ((position + POSITION_BIAS) * POSITION_STEPS)
+        .round()
+        .as_uvec3();
+    let normal = NORMALS.iter().position(|n| *n == normal).unwrap_or(1) as u32;
+    let corner = UVS.iter().position(|c| *c == uv).unwrap_or(0) as u32;
+    [
+        grid.x.min(0x1ff) | grid.z.min(0x1ff) << 9 | grid.y.min(0x3fff) << 18,
+        layer & 0x1ffff | normal << 17 | corner << 20,
+    ]
 }
 
-fn add_face(mesh: &mut MeshData, face: &CubeFace, offset: Vec3, size: Vec3) {
+/// Emit a face, `lights` being the packed light of its corners in the order of
+/// `CubeFace::cornor_indices`
+fn add_face(
+    mesh: &mut MeshData,
+    layer: u32,
+    lights: [u32; 4],
+    face: &CubeFace,
+    offset: Vec3,
+    size: Vec3,
+) {
     let index_start: u32 = mesh.positions.len() as u32;
 
     for (i, &value) in face.cornor_indices.iter().enumerate() {
@@ -216,6 +1313,8 @@ fn add_face(mesh: &mut MeshData, face: &CubeFace, offset: Vec3, size: Vec3) {
         // mesh.normals
         // .push((CORNORS[value as usize] - Vec3::new(0.5, 0.5, 0.5)).normalize()); // merge the normals of the same vertex
         mesh.uvs.push(UVS[i]);
+        mesh.layers.push(layer);
+        mesh.lights.push(lights[i]);
     }
 
     mesh.indices.push(index_start);
@@ -226,48 +1325,167 @@ fn add_face(mesh: &mut MeshData, face: &CubeFace, offset: Vec3, size: Vec3) {
     mesh.indices.push(index_start);
 }
 
-fn can_merge_mesh(voxel1: u8, voxel2: u8) -> bool {
-    voxel1 == voxel2
+/// Emit the boxes of a shaped or turned block, skipping faces flush against a neighbour
+/// that hides them. They're all lit by the light in the block's own cell
+fn add_shape(
+    mesh: &mut MeshData,
+    chunk: &ChunkData,
+    layer: u32,
+    light: u32,
+    shape: BlockShape,
+    local: IVec3,
+    rotation: Quat,
+) {
+    let offset = column_offset(chunk.index) + local.as_vec3();
+    let center = Vec3::splat(0.5);
+    for (min, max) in shape.boxes() {
+        let faces = [
+            (&CubeFace::RIGHT_FACE, max.x == 1.0, IVec3::X),
+            (&CubeFace::TOP_FACE, max.y == 1.0, IVec3::Y),
+            (&CubeFace::FRONT_FACE, max.z == 1.0, IVec3::Z),
+            (&CubeFace::LEFT_FACE, min.x == 0.0, IVec3::NEG_X),
+            (&CubeFace::BOTTOM_FACE, min.y == 0.0, IVec3::NEG_Y),
+            (&CubeFace::BACK_FACE, min.z == 0.0, IVec3::NEG_Z),
+        ];
+        for (face, on_cell_side, direction) in faces {
+            let direction = (rotation * direction.as_vec3()).round().as_ivec3();
+            let neighbour = local + direction;
+            let in_chunk = neighbour.cmpge(IVec3::ZERO).all()
+                && neighbour.cmplt(IVec3::splat(CHUNK_SIZE as i32)).all();
+            if on_cell_side
+                && in_chunk
+                && occludes(
+                    chunk.voxels[neighbour.x as usize][neighbour.y as usize][neighbour.z as usize],
+                )
+            {
+                continue;
+            }
+            let start = mesh.positions.len();
+            add_face(mesh, layer, [light; 4], face, *min, *max - *min);
+            // turned about the middle of the cell, snapped back to the 1/16 grid the shapes are on
+            for position in mesh.positions[start..].iter_mut() {
+                let turned = center + rotation * (*position - center);
+                *position = offset + (turned * 16.0).round() / 16.0;
+            }
+            for normal in mesh.normals[start..].iter_mut() {
+                *normal = (rotation * *normal).round();
+            }
+        }
+    }
+}
+
+/// Every face of a lone block, centered on the origin, for blocks drawn outside a chunk
+pub fn block_mesh(block: BlockId) -> MeshData {
+    let mut mesh = MeshData::new();
+    let properties = block_properties(block);
+    for (min, max) in properties.shape.boxes() {
+        for face in [
+            &CubeFace::RIGHT_FACE,
+            &CubeFace::TOP_FACE,
+            &CubeFace::FRONT_FACE,
+            &CubeFace::LEFT_FACE,
+            &CubeFace::BOTTOM_FACE,
+            &CubeFace::BACK_FACE,
+        ] {
+            add_face(
+                &mut mesh,
+                vertex_layer(block),
+                [0; 4],
+                face,
+                *min - Vec3::splat(0.5),
+                *max - *min,
+            );
+        }
+    }
+    mesh
 }
 
+/// A quad for every face next to air, what greedy meshing is checked against
+#[cfg(test)]
 fn default_mesh(chunk: ChunkData) -> MeshData {
     let mut mesh_data = MeshData::new();
     (0..CHUNK_SIZE).for_each(|y| {
         (0..CHUNK_SIZE).for_each(|z| {
             (0..CHUNK_SIZE).for_each(|x| {
                 // println!("Element at ({}, {}, {}): {}", x, y, z, elem);
-                if chunk.voxels[x][y][z] == 0 {
+                let block = chunk.voxels[x][y][z];
+                if block == AIR {
                     return;
                 }
+                let layer = vertex_layer(block);
 
-                let offset = Vec3::new(
-                    chunk.index.x as f32 * CHUNK_SIZE as f32,
-                    chunk.index.y as f32 * CHUNK_SIZE as f32,
-                    chunk.index.z as f32 * CHUNK_SIZE as f32,
-                ) + Vec3::new(x as f32, y as f32, z as f32);
+                let offset = column_offset(chunk.index) + Vec3::new(x as f32, y as f32, z as f32);
 
-                if y == CHUNK_SIZE - 1 || (y < CHUNK_SIZE - 1 && chunk.voxels[x][y + 1][z] == 0) {
-                    add_face(&mut mesh_data, &CubeFace::TOP_FACE, offset, Vec3::ONE);
+                if y == CHUNK_SIZE - 1
+                    || (y < CHUNK_SIZE - 1 && shows_face(block, chunk.voxels[x][y + 1][z]))
+                {
+                    add_face(
+                        &mut mesh_data,
+                        layer,
+                        [0; 4],
+                        &CubeFace::TOP_FACE,
+                        offset,
+                        Vec3::ONE,
+                    );
                 }
 
-                if y == 0 || (y > 0 && chunk.voxels[x][y - 1][z] == 0) {
-                    add_face(&mut mesh_data, &CubeFace::BOTTOM_FACE, offset, Vec3::ONE);
+                if y == 0 || (y > 0 && shows_face(block, chunk.voxels[x][y - 1][z])) {
+                    add_face(
+                        &mut mesh_data,
+                        layer,
+                        [0; 4],
+                        &CubeFace::BOTTOM_FACE,
+                        offset,
+                        Vec3::ONE,
+                    );
                 }
 
-                if x == 0 || (x > 0 && chunk.voxels[x - 1][y][z] == 0) {
-                    add_face(&mut mesh_data, &CubeFace::LEFT_FACE, offset, Vec3::ONE);
+                if x == 0 || (x > 0 && shows_face(block, chunk.voxels[x - 1][y][z])) {
+                    add_face(
+                        &mut mesh_data,
+                        layer,
+                        [0; 4],
+                        &CubeFace::LEFT_FACE,
+                        offset,
+                        Vec3::ONE,
+                    );
                 }
 
-                if x == CHUNK_SIZE - 1 || (x < CHUNK_SIZE - 1 && chunk.voxels[x + 1][y][z] == 0) {
-                    add_face(&mut mesh_data, &CubeFace::RIGHT_FACE, offset, Vec3::ONE);
+                if x == CHUNK_SIZE - 1
+                    || (x < CHUNK_SIZE - 1 && shows_face(block, chunk.voxels[x + 1][y][z]))
+                {
+                    add_face(
+                        &mut mesh_data,
+                        layer,
+                        [0; 4],
+                        &CubeFace::RIGHT_FACE,
+                        offset,
+                        Vec3::ONE,
+                    );
                 }
 
-                if z == CHUNK_SIZE - 1 || (z < CHUNK_SIZE - 1 && chunk.voxels[x][y][z + 1] == 0) {
-                    add_face(&mut mesh_data, &CubeFace::FRONT_FACE, offset, Vec3::ONE);
+                if z == CHUNK_SIZE - 1
+                    || (z < CHUNK_SIZE - 1 && shows_face(block, chunk.voxels[x][y][z + 1]))
+                {
+                    add_face(
+                        &mut mesh_data,
+                        layer,
+                        [0; 4],
+                        &CubeFace::FRONT_FACE,
+                        offset,
+                        Vec3::ONE,
+                    );
                 }
 
-                if z == 0 || (z > 0 && chunk.voxels[x][y][z - 1] == 0) {
-                    add_face(&mut mesh_data, &CubeFace::BACK_FACE, offset, Vec3::ONE);
+                if z == 0 || (z > 0 && shows_face(block, chunk.voxels[x][y][z - 1])) {
+                    add_face(
+                        &mut mesh_data,
+                        layer,
+                        [0; 4],
+                        &CubeFace::BACK_FACE,
+                        offset,
+                        Vec3::ONE,
+                    );
                 }
             })
         })
@@ -276,360 +1494,297 @@ fn default_mesh(chunk: ChunkData) -> MeshData {
     mesh_data
 }
 
-pub fn greedy_meshing(chunk: &ChunkData) -> MeshData {
-    let mut sizes: [[[Vec3; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE] =
-        [[[Vec3::ONE; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
-    (0..CHUNK_SIZE).for_each(|y| {
-        (0..CHUNK_SIZE).for_each(|z| {
-            (1..CHUNK_SIZE).for_each(|x| {
-                if can_merge_mesh(chunk.voxels[x][y][z], chunk.voxels[x - 1][y][z]) {
-                    sizes[x][y][z].x += sizes[x - 1][y][z].x;
-                    sizes[x - 1][y][z] = Vec3::ZERO;
-                }
-            })
-        })
-    });
-
-    (0..CHUNK_SIZE).for_each(|y| {
-        (0..CHUNK_SIZE).for_each(|x| {
-            (1..CHUNK_SIZE).for_each(|z| {
-                if sizes[x][y][z] == Vec3::ZERO || sizes[x][y][z - 1] == Vec3::ZERO {
-                    return;
-                }
-                if can_merge_mesh(chunk.voxels[x][y][z], chunk.voxels[x][y][z - 1])
-                    && sizes[x][y][z - 1].x == sizes[x][y][z].x
-                {
-                    sizes[x][y][z].z += sizes[x][y][z - 1].z;
-                    sizes[x][y][z - 1] = Vec3::ZERO;
-                }
-            })
-        })
-    });
-
+/// One instance per visible face of the opaque cubes of a chunk, for the experimental
+/// instanced renderer: x, z and the height in the column in 4, 4 and 8 bits, then the
+/// face as an index into `NORMALS`, and the texture layer in the second word
+#[cfg(feature = "instanced-faces")]
+pub fn face_instances(chunk: &ChunkData) -> Vec<[u32; 2]> {
+    let mut instances = Vec::new();
+    let column_y = chunk.index.y * CHUNK_SIZE as i32;
     (0..CHUNK_SIZE).for_each(|x| {
-        (0..CHUNK_SIZE).for_each(|z| {
-            (1..CHUNK_SIZE).for_each(|y| {
-                if sizes[x][y][z] == Vec3::ZERO || sizes[x][y - 1][z] == Vec3::ZERO {
+        (0..CHUNK_SIZE).for_each(|y| {
+            (0..CHUNK_SIZE).for_each(|z| {
+                let block = chunk.voxels[x][y][z];
+                let properties = block_properties(block);
+                if block == AIR || properties.transparent || properties.shape != BlockShape::Cube {
                     return;
                 }
-                if can_merge_mesh(chunk.voxels[x][y][z], chunk.voxels[x][y - 1][z])
-                    && sizes[x][y - 1][z].x == sizes[x][y][z].x
-                    && sizes[x][y - 1][z].z == sizes[x][y][z].z
-                {
-                    sizes[x][y][z].y += sizes[x][y - 1][z].y;
-                    sizes[x][y - 1][z] = Vec3::ZERO;
+                let layer = vertex_layer(block);
+                let position = IVec3::new(x as i32, y as i32, z as i32);
+                for (face, normal) in NORMALS.iter().enumerate() {
+                    let neighbour = position + normal.as_ivec3();
+                    let inside = neighbour.cmpge(IVec3::ZERO).all()
+                        && neighbour.cmplt(IVec3::splat(CHUNK_SIZE as i32)).all();
+                    if inside {
+                        let (nx, ny, nz) = (neighbour.x, neighbour.y, neighbour.z);
+                        let neighbour = chunk.voxels[nx as usize][ny as usize][nz as usize];
+                        if !shows_face(block, neighbour) {
+                            continue;
+                        }
+                    }
+                    let height = (column_y + y as i32) as u32;
+                    instances.push([
+                        x as u32 | (z as u32) << 4 | height << 8 | (face as u32) << 16,
+                        layer,
+                    ]);
                 }
             })
         })
     });
+    instances
+}
 
-    let mut mesh_data = MeshData::new();
-    (0..CHUNK_SIZE).for_each(|y| {
-        (0..CHUNK_SIZE).for_each(|z| {
-            (0..CHUNK_SIZE).for_each(|x| {
-                // println!("Element at ({}, {}, {}): {}", x, y, z, elem);
-                if chunk.voxels[x][y][z] == 0 {
-                    return;
-                }
+/// Faces of a cube in the order of `NORMALS`
+const CUBE_FACES: [CubeFace; 6] = [
+    CubeFace::RIGHT_FACE,
+    CubeFace::TOP_FACE,
+    CubeFace::FRONT_FACE,
+    CubeFace::LEFT_FACE,
+    CubeFace::BOTTOM_FACE,
+    CubeFace::BACK_FACE,
+];
 
-                if sizes[x][y][z] == Vec3::ZERO {
-                    return;
-                }
+/// Smooth light of the corners of `face`, in the order of its vertices, with the light
+/// bounced onto the face, `front` being the voxel the face looks into
+fn corner_lights(chunk_light: &ChunkLight, face: &CubeFace, front: IVec3) -> [u32; 4] {
+    let normal = NORMALS[face.normal_index as usize].as_ivec3();
+    let bounced = chunk_light.bounce(front, normal);
+    face.cornor_indices.map(|corner| {
+        let corner = CORNORS[corner as usize].as_ivec3();
+        light::pack_lights(chunk_light.corner(front, normal, corner), bounced)
+    })
+}
 
-                let offset = Vec3::new(
-                    chunk.index.x as f32 * CHUNK_SIZE as f32,
-                    chunk.index.y as f32 * CHUNK_SIZE as f32,
-                    chunk.index.z as f32 * CHUNK_SIZE as f32,
-                ) + Vec3::new(x as f32, y as f32, z as f32);
+/// The visible faces of a slice of a chunk, each with its block and the light of its corners
+type FaceMask = [[Option<(BlockId, [u32; 4])>; CHUNK_SIZE]; CHUNK_SIZE];
 
-                // top face of the chunk
-                if y == CHUNK_SIZE - 1 {
-                    add_face(
-                        &mut mesh_data,
-                        &CubeFace::TOP_FACE,
-                        offset
-                            + Vec3::new(-(sizes[x][y][z].x - 1.0), 0.0, -(sizes[x][y][z].z - 1.0)),
-                        Vec3::new(sizes[x][y][z].x, 1.0, sizes[x][y][z].z),
-                    );
-                }
+/// Mesh either the opaque or the transparent blocks of a chunk, they are drawn with different
+/// materials. Full cubes are merged per face direction: each slice of the chunk gets a mask
+/// of its visible faces, then runs of the same block with the same corner lights grow into
+/// rectangles, first along one axis of the slice then the other. Shaped and turned blocks
+/// keep their own geometry
+pub fn greedy_meshing(chunk: &ChunkData, chunk_light: &ChunkLight, transparent: bool) -> MeshData {
+    let mut mesh_data = MeshData::new();
+    let size = CHUNK_SIZE as i32;
+    let voxel = |p: IVec3| chunk.voxels[p.x as usize][p.y as usize][p.z as usize];
+    let state = |p: IVec3| {
+        chunk.state(VoxelLocalIndex {
+            x: p.x as u8,
+            y: p.y as u8,
+            z: p.z as u8,
+        })
+    };
 
-                if y < CHUNK_SIZE - 1
-                // && sizes[x][y + 1][z].x >= sizes[x][y][z].x  // can't simple check the cell above, because the cell above may be merged with other cells
-                // && sizes[x][y + 1][z].z >= sizes[x][y][z].z)
-                {
-                    // check if the top surface is exposed
-                    // if not, skip the top face
-                    let mut is_exposed = false;
-                    'check_surface: for z1 in (1 + z - sizes[x][y][z].z as usize)..=z {
-                        for x1 in (1 + x - sizes[x][y][z].x as usize)..=x {
-                            if chunk.voxels[x1][y + 1][z1] == 0 {
-                                is_exposed = true;
-                                break 'check_surface;
-                            }
-                        }
-                    }
-                    if is_exposed {
-                        add_face(
-                            &mut mesh_data,
-                            &CubeFace::TOP_FACE,
-                            offset
-                                + Vec3::new(
-                                    -(sizes[x][y][z].x - 1.0),
-                                    0.0,
-                                    -(sizes[x][y][z].z - 1.0),
-                                ),
-                            Vec3::new(sizes[x][y][z].x, 1.0, sizes[x][y][z].z),
-                        );
-                    }
-                }
+    // by block id, whether it's a full cube of the kind of block meshed
+    let mut cubes = vec![false; last_block().0 as usize + 1];
+    for (block, cube) in cubes.iter_mut().enumerate() {
+        let block = BlockId(block as u8);
+        *cube = block != AIR
+            && is_transparent(block) == transparent
+            && block_properties(block).shape == BlockShape::Cube;
+    }
 
-                // bottom face of the chunk
-                if 1 + y - sizes[x][y][z].y as usize == 0 {
-                    add_face(
+    // shaped and turned blocks
+    for x in 0..size {
+        for y in 0..size {
+            for z in 0..size {
+                let p = IVec3::new(x, y, z);
+                let block = voxel(p);
+                if block == AIR || is_transparent(block) != transparent {
+                    continue;
+                }
+                let shape = block_properties(block).shape;
+                let block_state = state(p);
+                if shape != BlockShape::Cube || block_state != 0 {
+                    add_shape(
                         &mut mesh_data,
-                        &CubeFace::BOTTOM_FACE,
-                        offset
-                            + Vec3::new(-(sizes[x][y][z].x - 1.0), 0.0, -(sizes[x][y][z].z - 1.0))
-                            + Vec3::new(0.0, -(sizes[x][y][z].y - 1.0), 0.0), // because after merge, the cell has a size of non-zero is the top-right front cell
-                        Vec3::new(sizes[x][y][z].x, 1.0, sizes[x][y][z].z),
+                        chunk,
+                        vertex_layer(block),
+                        light::pack_light(chunk_light.at(p)),
+                        shape,
+                        p,
+                        state_rotation(block_state),
                     );
-                } else {
-                    // check if the bottom surface is exposed
-                    // if not, skip the bottom face
-                    let mut is_exposed = false;
-                    'check_surface: for z1 in (1 + z - sizes[x][y][z].z as usize)..=z {
-                        for x1 in (1 + x - sizes[x][y][z].x as usize)..=x {
-                            if chunk.voxels[x1][y - sizes[x][y][z].y as usize][z1] == 0 {
-                                is_exposed = true;
-                                break 'check_surface;
-                            }
-                        }
-                    }
-
-                    if is_exposed {
-                        add_face(
-                            &mut mesh_data,
-                            &CubeFace::BOTTOM_FACE,
-                            offset
-                                + Vec3::new(
-                                    -(sizes[x][y][z].x - 1.0),
-                                    0.0,
-                                    -(sizes[x][y][z].z - 1.0),
-                                )
-                                + Vec3::new(0.0, -(sizes[x][y][z].y - 1.0), 0.0), // because after merge, the cell has a size of non-zero is the top-right front cell
-                            Vec3::new(sizes[x][y][z].x, 1.0, sizes[x][y][z].z),
-                        );
-                    }
                 }
+            }
+        }
+    }
 
-                // left face of the chunk
-                if 1 + x - sizes[x][y][z].x as usize == 0 {
-                    add_face(
-                        &mut mesh_data,
-                        &CubeFace::LEFT_FACE,
-                        offset
-                            + Vec3::new(0.0, -(sizes[x][y][z].y - 1.0), -(sizes[x][y][z].z - 1.0))
-                            + Vec3::new(-(sizes[x][y][z].x - 1.0), 0.0, 0.0),
-                        Vec3::new(1.0, sizes[x][y][z].y, sizes[x][y][z].z),
-                    );
-                } else {
-                    // check if the left surface is exposed
-                    // if not, skip the left face
-                    let mut is_exposed = false;
-                    'check_surface: for z1 in (1 + z - sizes[x][y][z].z as usize)..=z {
-                        for y1 in (1 + y - sizes[x][y][z].y as usize)..=y {
-                            if chunk.voxels[x - sizes[x][y][z].x as usize][y1][z1] == 0 {
-                                is_exposed = true;
-                                break 'check_surface;
-                            }
-                        }
+    // only the same full cube without a state merges, so glass and leaves never merge into
+    // opaque faces
+    for (direction, face) in CUBE_FACES.iter().enumerate() {
+        let normal = NORMALS[direction].as_ivec3();
+        let axis = direction % 3;
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        for slice in 0..size {
+            let cell = |i: usize, j: usize| {
+                let mut p = IVec3::ZERO;
+                p[axis] = slice;
+                p[u] = i as i32;
+                p[v] = j as i32;
+                p
+            };
+            // the block of each visible face and the light of its corners
+            let mut mask: FaceMask = [[None; CHUNK_SIZE]; CHUNK_SIZE];
+            for (i, row) in mask.iter_mut().enumerate() {
+                for (j, visible) in row.iter_mut().enumerate() {
+                    let p = cell(i, j);
+                    let block = voxel(p);
+                    if !cubes.get(block.0 as usize).copied().unwrap_or(false) || state(p) != 0 {
+                        continue;
                     }
-
-                    if is_exposed {
-                        add_face(
-                            &mut mesh_data,
-                            &CubeFace::LEFT_FACE,
-                            offset
-                                + Vec3::new(
-                                    0.0,
-                                    -(sizes[x][y][z].y - 1.0),
-                                    -(sizes[x][y][z].z - 1.0),
-                                )
-                                + Vec3::new(-(sizes[x][y][z].x - 1.0), 0.0, 0.0),
-                            Vec3::new(1.0, sizes[x][y][z].y, sizes[x][y][z].z),
-                        );
+                    let neighbour = p + normal;
+                    // faces on the chunk's border are always drawn
+                    let inside = neighbour.cmpge(IVec3::ZERO).all()
+                        && neighbour.cmplt(IVec3::splat(size)).all();
+                    if !inside || shows_face(block, voxel(neighbour)) {
+                        *visible = Some((block, corner_lights(chunk_light, face, neighbour)));
                     }
                 }
+            }
 
-                // right face of the chunk
-                if x == CHUNK_SIZE - 1 {
-                    add_face(
-                        &mut mesh_data,
-                        &CubeFace::RIGHT_FACE,
-                        offset
-                            + Vec3::new(0.0, -(sizes[x][y][z].y - 1.0), -(sizes[x][y][z].z - 1.0)),
-                        Vec3::new(1.0, sizes[x][y][z].y, sizes[x][y][z].z),
-                    );
-                } else {
-                    // check if the right surface is exposed
-                    // if not, skip the right face
-                    let mut is_exposed = false;
-                    'check_surface: for z1 in (1 + z - sizes[x][y][z].z as usize)..=z {
-                        for y1 in (1 + y - sizes[x][y][z].y as usize)..=y {
-                            if chunk.voxels[x + 1][y1][z1] == 0 {
-                                is_exposed = true;
-                                break 'check_surface;
-                            }
-                        }
+            for j in 0..CHUNK_SIZE {
+                let mut i = 0;
+                while i < CHUNK_SIZE {
+                    let Some(face_key) = mask[i][j] else {
+                        i += 1;
+                        continue;
+                    };
+                    let mut width = 1;
+                    while i + width < CHUNK_SIZE && mask[i + width][j] == Some(face_key) {
+                        width += 1;
                     }
-
-                    if is_exposed {
-                        add_face(
-                            &mut mesh_data,
-                            &CubeFace::RIGHT_FACE,
-                            offset
-                                + Vec3::new(
-                                    0.0,
-                                    -(sizes[x][y][z].y - 1.0),
-                                    -(sizes[x][y][z].z - 1.0),
-                                ),
-                            Vec3::new(1.0, sizes[x][y][z].y, sizes[x][y][z].z),
-                        );
+                    let mut height = 1;
+                    while j + height < CHUNK_SIZE
+                        && (i..i + width).all(|k| mask[k][j + height] == Some(face_key))
+                    {
+                        height += 1;
                     }
-                }
-
-                // front face of the chunk
-                if z == CHUNK_SIZE - 1 {
-                    add_face(
-                        &mut mesh_data,
-                        &CubeFace::FRONT_FACE,
-                        offset
-                            + Vec3::new(-(sizes[x][y][z].x - 1.0), -(sizes[x][y][z].y - 1.0), 0.0),
-                        Vec3::new(sizes[x][y][z].x, sizes[x][y][z].y, 1.0),
-                    );
-                } else {
-                    // check if the front surface is exposed
-                    // if not, skip the front face
-                    let mut is_exposed = false;
-                    'check_surface: for x1 in (1 + x - sizes[x][y][z].x as usize)..=x {
-                        for y1 in (1 + y - sizes[x][y][z].y as usize)..=y {
-                            if chunk.voxels[x1][y1][z + 1] == 0 {
-                                is_exposed = true;
-                                break 'check_surface;
-                            }
+                    for row in mask[i..i + width].iter_mut() {
+                        for visible in row[j..j + height].iter_mut() {
+                            *visible = None;
                         }
                     }
 
-                    if is_exposed {
-                        add_face(
-                            &mut mesh_data,
-                            &CubeFace::FRONT_FACE,
-                            offset
-                                + Vec3::new(
-                                    -(sizes[x][y][z].x - 1.0),
-                                    -(sizes[x][y][z].y - 1.0),
-                                    0.0,
-                                ),
-                            Vec3::new(sizes[x][y][z].x, sizes[x][y][z].y, 1.0),
-                        );
-                    }
-                }
-
-                // back face of the chunk
-                if 1 + z - sizes[x][y][z].z as usize == 0 {
+                    let mut extent = Vec3::ONE;
+                    extent[u] = width as f32;
+                    extent[v] = height as f32;
+                    let offset = column_offset(chunk.index) + cell(i, j).as_vec3();
+                    let (block, lights) = face_key;
                     add_face(
                         &mut mesh_data,
-                        &CubeFace::BACK_FACE,
-                        offset
-                            + Vec3::new(-(sizes[x][y][z].x - 1.0), -(sizes[x][y][z].y - 1.0), 0.0)
-                            + Vec3::new(0.0, 0.0, -(sizes[x][y][z].z - 1.0)),
-                        Vec3::new(sizes[x][y][z].x, sizes[x][y][z].y, 1.0),
+                        vertex_layer(block),
+                        lights,
+                        face,
+                        offset,
+                        extent,
                     );
-                } else {
-                    // check if the back surface is exposed
-                    // if not, skip the back face
-                    let mut is_exposed = false;
-                    'check_surface: for x1 in (1 + x - sizes[x][y][z].x as usize)..=x {
-                        for y1 in (1 + y - sizes[x][y][z].y as usize)..=y {
-                            if chunk.voxels[x1][y1][z - sizes[x][y][z].z as usize] == 0 {
-                                is_exposed = true;
-                                break 'check_surface;
-                            }
-                        }
-                    }
-
-                    if is_exposed {
-                        add_face(
-                            &mut mesh_data,
-                            &CubeFace::BACK_FACE,
-                            offset
-                                + Vec3::new(
-                                    -(sizes[x][y][z].x - 1.0),
-                                    -(sizes[x][y][z].y - 1.0),
-                                    0.0,
-                                )
-                                + Vec3::new(0.0, 0.0, -(sizes[x][y][z].z - 1.0)),
-                            Vec3::new(sizes[x][y][z].x, sizes[x][y][z].y, 1.0),
-                        );
-                    }
+                    i += width;
                 }
-            })
-        })
-    });
+            }
+        }
+    }
 
     mesh_data
 }
 
+impl MeshData {
+    fn packed_vertices(&self) -> impl Iterator<Item = [u32; 2]> + '_ {
+        (0..self.positions.len()).map(|i| {
+            pack_vertex(
+                self.positions[i],
+                self.normals[i],
+                self.uvs[i],
+                self.layers[i],
+            )
+        })
+    }
+
+    /// Replace the vertices and indices at `range` in a mesh combined from `MeshData`s with
+    /// this mesh's, moving the indices after them by the vertices added or removed. Where
+    /// this mesh ends up, None if `mesh` doesn't have the buffers or `range` isn't in them
+    pub fn splice_into(&self, mesh: &mut Mesh, range: &MeshRange) -> Option<MeshRange> {
+        let new = range.placed(self);
+        let moved = new.vertices.end as i64 - range.vertices.end as i64;
+        let vertices = range.vertices.start as usize..range.vertices.end as usize;
+        let Some(VertexAttributeValues::Uint32x2(packed)) =
+            mesh.attribute_mut(ATTRIBUTE_PACKED_VERTEX)
+        else {
+            return None;
+        };
+        if vertices.end > packed.len() {
+            return None;
+        }
+        packed.splice(vertices.clone(), self.packed_vertices());
+        let Some(VertexAttributeValues::Uint32(lights)) = mesh.attribute_mut(ATTRIBUTE_VOXEL_LIGHT)
+        else {
+            return None;
+        };
+        lights.splice(vertices, self.lights.iter().copied());
+        let Some(Indices::U32(indices)) = mesh.indices_mut() else {
+            return None;
+        };
+        let replaced = range.indices.start as usize..range.indices.end as usize;
+        if replaced.end > indices.len() {
+            return None;
+        }
+        let start = new.vertices.start;
+        indices.splice(replaced, self.indices.iter().map(|i| i + start));
+        for index in &mut indices[new.indices.end as usize..] {
+            *index = (*index as i64 + moved) as u32;
+        }
+        Some(new)
+    }
+
+    /// Write the mesh data into a mesh built from an earlier `MeshData`, reusing its
+    /// buffers, they only grow when there are more vertices than before. Whether `mesh`
+    /// had the buffers to reuse
+    pub fn write_into(&self, mesh: &mut Mesh) -> bool {
+        let Some(Indices::U32(indices)) = mesh.indices_mut() else {
+            return false;
+        };
+        indices.clear();
+        indices.extend_from_slice(&self.indices);
+        let Some(VertexAttributeValues::Uint32x2(vertices)) =
+            mesh.attribute_mut(ATTRIBUTE_PACKED_VERTEX)
+        else {
+            return false;
+        };
+        vertices.clear();
+        vertices.extend(self.packed_vertices());
+        let Some(VertexAttributeValues::Uint32(lights)) = mesh.attribute_mut(ATTRIBUTE_VOXEL_LIGHT)
+        else {
+            return false;
+        };
+        lights.clear();
+        lights.extend_from_slice(&self.lights);
+        true
+    }
+}
+
 impl From<MeshData> for Mesh {
     fn from(value: MeshData) -> Self {
-        // let mesh_data = merge_vertex(&mesh_data, 0.01);
+        let vertices: Vec<[u32; 2]> = value.packed_vertices().collect();
         let indices = Indices::U32(value.indices);
 
         let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
         mesh.set_indices(Some(indices));
-        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, value.positions);
-        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, value.normals);
-        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, value.uvs);
+        mesh.insert_attribute(ATTRIBUTE_PACKED_VERTEX, vertices);
+        mesh.insert_attribute(ATTRIBUTE_VOXEL_LIGHT, value.lights);
         mesh
     }
 }
 
-fn merge_vertex(source: &MeshData, threshold: f32) -> MeshData {
-    let mut dest = MeshData::new();
-    let mut vertex_map = HashMap::new();
-    (0..source.positions.len()).for_each(|i| {
-        let mut found = false;
-        for j in 0..dest.positions.len() {
-            if Vec3::length_squared(source.positions[i] - dest.positions[j]) < threshold {
-                vertex_map.insert(i, j);
-                found = true;
-                break;
-            }
-        }
-
-        if !found {
-            vertex_map.insert(i, dest.positions.len());
-            dest.positions.push(source.positions[i]);
-            dest.normals.push(source.normals[i]);
-        }
-    });
-
-    (0..source.indices.len()).for_each(|i| {
-        dest.indices
-            .push(vertex_map[&(source.indices[i] as usize)] as u32);
-    });
-    // dest.normals = source.normals.clone();
-    dest
-}
-
 /// Combine multiple meshes into one mesh
-pub fn combine_meshes(meshes: &[MeshData]) -> MeshData {
+pub fn combine_meshes<'a>(meshes: impl IntoIterator<Item = &'a MeshData>) -> MeshData {
     let mut mesh_data = MeshData::new();
     let mut index_start: u32 = 0;
     for mesh in meshes {
         mesh_data.positions.extend(mesh.positions.iter());
         mesh_data.normals.extend(mesh.normals.iter());
         mesh_data.uvs.extend(mesh.uvs.iter());
+        mesh_data.layers.extend(mesh.layers.iter());
+        mesh_data.lights.extend(mesh.lights.iter());
         mesh_data
             .indices
             .extend(mesh.indices.iter().map(|i| i + index_start));
@@ -638,16 +1793,199 @@ pub fn combine_meshes(meshes: &[MeshData]) -> MeshData {
     mesh_data
 }
 
-#[derive(Component)]
-pub struct ChunkMesh {
-    pub dirty: bool,
-    pub mesh: Handle<Mesh>,
-    pub merged: bool,
+/// A write to one voxel, as the journal keeps it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VoxelWrite {
+    Block(BlockId), // clears the state, like `ChunkData::set_block`
+    State(BlockState),
 }
 
 #[derive(Resource, Default)]
 pub struct VoxelData {
-    pub chunks: HashMap<ChunkIndex, ChunkData>,
+    chunks: HashMap<ChunkIndex, ChunkData>,
+    modified: HashSet<ChunkIndex>, // loaded chunks edited since they were last saved
+    // writes since the journal last took them, None while it isn't kept
+    pub journal: Option<Vec<(VoxelPos, VoxelWrite)>>,
+}
+
+impl VoxelData {
+    /// Block at a world position, air where the chunk isn't loaded
+    pub fn get_block(&self, position: VoxelPos) -> BlockId {
+        let (index, local) = position.split();
+        self.chunks.get(&index).map_or(AIR, |chunk| {
+            chunk.voxels[local.x as usize][local.y as usize][local.z as usize]
+        })
+    }
+
+    /// Set a block at a world position like `ChunkData::set_block`, returns false where the
+    /// chunk isn't loaded
+    pub fn set_block(&mut self, position: VoxelPos, block: BlockId) -> bool {
+        self.replace_block(position, block).is_some()
+    }
+
+    /// Set a block like `set_block` and return the block entity of the block it replaced,
+    /// None where the chunk isn't loaded. Every block written to the loaded world goes
+    /// through here, so the journal sees them all
+    pub fn replace_block(
+        &mut self,
+        position: VoxelPos,
+        block: BlockId,
+    ) -> Option<Option<BlockEntity>> {
+        let (index, local) = position.split();
+        let chunk = self.chunks.get_mut(&index)?;
+        let removed = chunk.set_block(local, block);
+        self.modified.insert(index);
+        if let Some(writes) = self.journal.as_mut() {
+            writes.push((position, VoxelWrite::Block(block)));
+        }
+        Some(removed)
+    }
+
+    /// State of the voxel at a world position, 0 where the chunk isn't loaded
+    pub fn get_state(&self, position: VoxelPos) -> BlockState {
+        let (index, local) = position.split();
+        self.chunks
+            .get(&index)
+            .map_or(0, |chunk| chunk.state(local))
+    }
+
+    /// Set the state of the voxel at a world position, returns false where the chunk isn't loaded
+    pub fn set_state(&mut self, position: VoxelPos, state: BlockState) -> bool {
+        let (index, local) = position.split();
+        let Some(chunk) = self.chunks.get_mut(&index) else {
+            return false;
+        };
+        chunk.set_state(local, state);
+        self.modified.insert(index);
+        if let Some(writes) = self.journal.as_mut() {
+            writes.push((position, VoxelWrite::State(state)));
+        }
+        true
+    }
+
+    /// Block entity at a world position, if its block has one and the chunk is loaded
+    pub fn get_entity(&self, position: VoxelPos) -> Option<&BlockEntity> {
+        let (index, local) = position.split();
+        self.chunks.get(&index)?.entity(local)
+    }
+
+    /// Put a block entity at a world position, or take the one there away with `None`,
+    /// returns false where the chunk isn't loaded
+    pub fn set_entity(&mut self, position: VoxelPos, entity: Option<BlockEntity>) -> bool {
+        let (index, local) = position.split();
+        let Some(chunk) = self.chunks.get_mut(&index) else {
+            return false;
+        };
+        chunk.set_entity(local, entity);
+        self.modified.insert(index);
+        true
+    }
+
+    /// Block entity at a world position to change, its chunk is saved again
+    pub fn entity_mut(&mut self, position: VoxelPos) -> Option<&mut BlockEntity> {
+        let (index, local) = position.split();
+        let entity = self.chunks.get_mut(&index)?.entity_mut(local)?;
+        self.modified.insert(index);
+        Some(entity)
+    }
+
+    pub fn is_loaded(&self, index: ChunkIndex) -> bool {
+        self.chunks.contains_key(&index)
+    }
+
+    /// Block light at a world position, worked out afresh from the chunks around it, so not
+    /// something to call every frame
+    pub fn block_light(&self, position: VoxelPos) -> LightColor {
+        let (index, local) = position.split();
+        let neighbourhood = ChunkNeighbourhood::snapshot(self, index);
+        ChunkLight::of(&neighbourhood).at(IVec3::new(
+            local.x as i32,
+            local.y as i32,
+            local.z as i32,
+        ))
+    }
+
+    pub fn iter_chunks(&self) -> impl Iterator<Item = &ChunkData> {
+        self.chunks.values()
+    }
+
+    /// The loaded chunks to change directly, mark the ones changed with `mark_modified`
+    pub fn iter_chunks_mut(&mut self) -> impl Iterator<Item = &mut ChunkData> {
+        self.chunks.values_mut()
+    }
+
+    pub fn chunk(&self, index: ChunkIndex) -> Option<&ChunkData> {
+        self.chunks.get(&index)
+    }
+
+    /// A loaded chunk to change directly, mark it with `mark_modified` if it changed
+    pub fn chunk_mut(&mut self, index: ChunkIndex) -> Option<&mut ChunkData> {
+        self.chunks.get_mut(&index)
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Load a chunk, in place of whatever was loaded at its index
+    pub fn insert_chunk(&mut self, chunk: ChunkData) {
+        self.chunks.insert(chunk.index, chunk);
+    }
+
+    /// Unload a chunk, returns it with whether it was edited since it was last saved
+    pub fn remove_chunk(&mut self, index: ChunkIndex) -> Option<(ChunkData, bool)> {
+        let chunk = self.chunks.remove(&index)?;
+        Some((chunk, self.modified.remove(&index)))
+    }
+
+    /// Put back a loaded chunk as it was at an earlier time, to be saved again. The writes to
+    /// it the journal hasn't taken yet are undone with it
+    pub fn restore_chunk(&mut self, chunk: ChunkData) {
+        let index = chunk.index;
+        if let Some(writes) = self.journal.as_mut() {
+            writes.retain(|(position, _)| position.chunk() != index);
+        }
+        self.chunks.insert(index, chunk);
+        self.modified.insert(index);
+    }
+
+    /// Unload the chunks `keep` returns false for, given each chunk with the index it's loaded
+    /// at and whether it was edited since it was last saved. Their edits are lost
+    pub fn retain_chunks(&mut self, mut keep: impl FnMut(ChunkIndex, &ChunkData, bool) -> bool) {
+        let modified = &mut self.modified;
+        self.chunks.retain(|index, chunk| {
+            let kept = keep(*index, chunk, modified.contains(index));
+            if !kept {
+                modified.remove(index);
+            }
+            kept
+        });
+    }
+
+    /// Whether a loaded chunk was edited since it was last saved
+    pub fn is_modified(&self, index: ChunkIndex) -> bool {
+        self.modified.contains(&index)
+    }
+
+    /// Save a chunk again, once it's written or unloaded
+    pub fn mark_modified(&mut self, index: ChunkIndex) {
+        if self.chunks.contains_key(&index) {
+            self.modified.insert(index);
+        }
+    }
+
+    /// Leave a chunk's edits out of the save, they're lost when it unloads
+    pub fn forget_edits(&mut self, index: ChunkIndex) {
+        self.modified.remove(&index);
+    }
+
+    /// Write the chunks edited since they were last saved with `write`, the ones it fails for
+    /// are tried again next time
+    pub fn write_modified(&mut self, mut write: impl FnMut(&ChunkData) -> bool) {
+        let chunks = &self.chunks;
+        self.modified
+            .retain(|index| chunks.get(index).is_some_and(|chunk| !write(chunk)));
+    }
 }
 
 #[derive(Resource, Default)]
@@ -655,6 +1993,12 @@ pub struct VoxelMeshes {
     pub columns: HashMap<ChunkColumn, Entity>,
 }
 
+/// Chunk entities that have been spawned, whether or not their data is generated yet
+#[derive(Resource, Default)]
+pub struct ChunkEntities {
+    pub chunks: HashMap<ChunkIndex, Entity>,
+}
+
 #[derive(Component)]
 pub struct Chunk {
     pub index: ChunkIndex,
@@ -666,156 +2010,872 @@ pub struct ChunkColumn {
     pub z: i32,
 }
 
-#[derive(Component)]
-pub struct ColumnMesh {
-    pub column: ChunkColumn,
-    pub dirty: bool,
-    pub mesh: Handle<Mesh>,
+/// Where a chunk's faces sit inside one of its column's meshes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshRange {
+    pub vertices: Range<u32>,
+    pub indices: Range<u32>,
 }
 
-pub fn get_chunk_index(pos: &Vec3) -> ChunkIndex {
-    ChunkIndex {
-        x: (pos.x / CHUNK_SIZE as f32).floor() as i32,
-        y: (pos.y / CHUNK_SIZE as f32).floor() as i32,
-        z: (pos.z / CHUNK_SIZE as f32).floor() as i32,
+impl MeshRange {
+    /// An empty range where `mesh` starts once it's placed after `end`
+    fn after(end: &MeshRange) -> MeshRange {
+        MeshRange {
+            vertices: end.vertices.end..end.vertices.end,
+            indices: end.indices.end..end.indices.end,
+        }
     }
-}
 
-#[derive(Resource, Default)]
-pub struct ChunkMeshesUpdateQueue {
-    pub queue: HashSet<ChunkColumn>,
+    /// Where `mesh` sits once it's placed at the start of this range
+    fn placed(&self, mesh: &MeshData) -> MeshRange {
+        let (vertices, indices) = (self.vertices.start, self.indices.start);
+        MeshRange {
+            vertices: vertices..vertices + mesh.positions.len() as u32,
+            indices: indices..indices + mesh.indices.len() as u32,
+        }
+    }
+
+    /// Move the range by the vertices and indices added or removed before it
+    fn shift(&mut self, vertices: i64, indices: i64) {
+        let by = |range: &Range<u32>, by: i64| {
+            (range.start as i64 + by) as u32..(range.end as i64 + by) as u32
+        };
+        self.vertices = by(&self.vertices, vertices);
+        self.indices = by(&self.indices, indices);
+    }
 }
 
-#[derive(Resource, Default)]
-pub struct VoxelModifyQueue {
-    pub queue: Vec<(Vec3, u8)>,
+/// Mesh of one chunk, kept so the column can be rebuilt without remeshing its other chunks
+#[derive(Debug, Clone)]
+pub struct ChunkSubMesh {
+    opaque: MeshData,
+    transparent: MeshData,
+    bounds: [Option<Aabb>; 2], // of the opaque and the transparent mesh
+    pub connectivity: ChunkConnectivity,
+    pub light_fingerprint: u64, // of the light it was meshed with, see `ChunkLight::fingerprint`
+    pub meshing_ms: f32,        // how long lighting and meshing it took
+    pub opaque_range: MeshRange, // inside `ColumnMesh::mesh`
+    pub transparent_range: MeshRange, // inside `ColumnMesh::transparent_mesh`
 }
 
-fn to_voxel_position(pos: &Vec3) -> Vec3 {
-    Vec3 {
-        x: pos.x.floor(),
-        y: pos.y.floor(),
-        z: pos.z.floor(),
+impl ChunkSubMesh {
+    /// Mesh a chunk, lit by the lights in it and in the chunks around it in `neighbourhood`
+    pub fn new(chunk: &ChunkData, neighbourhood: &ChunkNeighbourhood) -> Self {
+        let start = Instant::now();
+        let light = ChunkLight::of(neighbourhood);
+        let opaque = greedy_meshing(chunk, &light, false);
+        let transparent = greedy_meshing(chunk, &light, true);
+        ChunkSubMesh {
+            bounds: [opaque.bounds(), transparent.bounds()],
+            opaque,
+            transparent,
+            connectivity: ChunkConnectivity::of(chunk),
+            light_fingerprint: light.fingerprint(),
+            meshing_ms: profiler::elapsed_ms(start) as f32,
+            opaque_range: MeshRange::default(),
+            transparent_range: MeshRange::default(),
+        }
     }
-}
 
-pub fn get_intersected_voxels(start_point: &Vec3, direction: &Vec3, range: f32) -> Vec<Vec3> {
-    // Ensures passed direction is normalized
-    let n_direction = direction.normalize();
-    let end_point = *start_point + n_direction * range;
-    let start_voxel = to_voxel_position(start_point);
+    /// Bytes held by the opaque and transparent meshes
+    pub fn heap_bytes(&self) -> usize {
+        self.opaque.heap_bytes() + self.transparent.heap_bytes()
+    }
 
-    // +1, -1, or 0
-    let step_x = if n_direction.x > 0.0 {
-        1.0
-    } else if n_direction.x < 0.0 {
-        -1.0
-    } else {
-        0.0
-    };
-    let step_y = if n_direction.y > 0.0 {
-        1.0
-    } else if n_direction.y < 0.0 {
-        -1.0
-    } else {
-        0.0
-    };
-    let step_z = if n_direction.z > 0.0 {
-        1.0
-    } else if n_direction.z < 0.0 {
-        -1.0
-    } else {
-        0.0
-    };
+    /// Vertices of the opaque and transparent meshes
+    pub fn vertices(&self) -> u32 {
+        (self.opaque.positions.len() + self.transparent.positions.len()) as u32
+    }
 
-    let t_delta_x = if step_x != 0.0 {
-        f32::min(step_x / (end_point.x - start_point.x), f32::MAX)
-    } else {
-        f32::MAX
-    };
+    /// The opaque or the transparent mesh
+    fn mesh(&self, transparent: bool) -> &MeshData {
+        if transparent {
+            &self.transparent
+        } else {
+            &self.opaque
+        }
+    }
 
-    let t_delta_y = if step_y != 0.0 {
-        f32::min(step_y / (end_point.y - start_point.y), f32::MAX)
-    } else {
-        f32::MAX
-    };
+    /// Where the opaque or the transparent mesh sits in its column's mesh
+    pub fn range(&self, transparent: bool) -> &MeshRange {
+        if transparent {
+            &self.transparent_range
+        } else {
+            &self.opaque_range
+        }
+    }
 
-    let t_delta_z = if step_z != 0.0 {
-        f32::min(step_z / (end_point.z - start_point.z), f32::MAX)
-    } else {
-        f32::MAX
-    };
+    fn range_mut(&mut self, transparent: bool) -> &mut MeshRange {
+        if transparent {
+            &mut self.transparent_range
+        } else {
+            &mut self.opaque_range
+        }
+    }
+}
 
-    let mut t_max_x = if step_x > 0.0 {
-        t_delta_x * (1.0 - start_point.x + start_voxel.x)
-    } else {
-        t_delta_x * (start_point.x - start_voxel.x)
+/// Combine the chunk meshes of a column bottom to top into its opaque and transparent
+/// meshes, recording where each chunk ended up. The `hidden` chunks are left out
+pub fn combine_sub_meshes(
+    sub_meshes: &mut BTreeMap<i32, ChunkSubMesh>,
+    hidden: &HashSet<i32>,
+) -> (MeshData, MeshData) {
+    let empty = MeshData::new();
+    let mut ends = [MeshRange::default(), MeshRange::default()];
+    for (y, sub_mesh) in sub_meshes.iter_mut() {
+        for (transparent, end) in [false, true].into_iter().zip(ends.iter_mut()) {
+            let mesh = if hidden.contains(y) {
+                &empty
+            } else {
+                sub_mesh.mesh(transparent)
+            };
+            *end = MeshRange::after(end).placed(mesh);
+            *sub_mesh.range_mut(transparent) = end.clone();
+        }
+    }
+    let shown = || {
+        sub_meshes
+            .iter()
+            .filter(|(y, _)| !hidden.contains(y))
+            .map(|(_, sub_mesh)| sub_mesh)
     };
+    (
+        combine_meshes(shown().map(|sub_mesh| &sub_mesh.opaque)),
+        combine_meshes(shown().map(|sub_mesh| &sub_mesh.transparent)),
+    )
+}
 
-    let mut t_max_y = if step_y > 0.0 {
-        t_delta_y * (1.0 - start_point.y + start_voxel.y)
-    } else {
-        t_delta_y * (start_point.y - start_voxel.y)
-    };
+/// Where the chunks at `heights` sit in a column's opaque or transparent mesh, before their
+/// sub-meshes change. A chunk not in it yet sits empty after the chunk below it
+pub fn sub_mesh_ranges(
+    sub_meshes: &BTreeMap<i32, ChunkSubMesh>,
+    heights: impl IntoIterator<Item = i32>,
+    transparent: bool,
+) -> BTreeMap<i32, MeshRange> {
+    heights
+        .into_iter()
+        .map(|y| {
+            let range = match sub_meshes.get(&y) {
+                Some(sub_mesh) => sub_mesh.range(transparent).clone(),
+                None => sub_meshes
+                    .range(..y)
+                    .next_back()
+                    .map_or_else(MeshRange::default, |(_, below)| {
+                        MeshRange::after(below.range(transparent))
+                    }),
+            };
+            (y, range)
+        })
+        .collect()
+}
 
-    let mut t_max_z = if step_z > 0.0 {
-        t_delta_z * (1.0 - start_point.z + start_voxel.z)
-    } else {
-        t_delta_z * (start_point.z - start_voxel.z)
-    };
+/// Patch the chunks that changed into a column's opaque or transparent mesh, combined from its
+/// sub-meshes before, instead of combining the whole column again. `replaced` has where each
+/// of them sat, see `sub_mesh_ranges`, and the chunks above move by the difference. The
+/// `hidden` chunks are left out. False if `mesh` doesn't match the ranges, then it has to be
+/// combined again
+pub fn patch_sub_meshes(
+    sub_meshes: &mut BTreeMap<i32, ChunkSubMesh>,
+    hidden: &HashSet<i32>,
+    replaced: BTreeMap<i32, MeshRange>,
+    transparent: bool,
+    mesh: &mut Mesh,
+) -> bool {
+    let empty = MeshData::new();
+    // top down, so the ranges below the one patched are still where they were
+    for (y, old) in replaced.into_iter().rev() {
+        let shown = sub_meshes
+            .get(&y)
+            .filter(|_| !hidden.contains(&y))
+            .map_or(&empty, |sub_mesh| sub_mesh.mesh(transparent));
+        let Some(new) = shown.splice_into(mesh, &old) else {
+            return false;
+        };
+        let vertices = new.vertices.end as i64 - old.vertices.end as i64;
+        let indices = new.indices.end as i64 - old.indices.end as i64;
+        for (_, above) in sub_meshes.range_mut(y + 1..) {
+            above.range_mut(transparent).shift(vertices, indices);
+        }
+        if let Some(sub_mesh) = sub_meshes.get_mut(&y) {
+            *sub_mesh.range_mut(transparent) = new;
+        }
+    }
+    true
+}
 
-    let mut current_voxel = start_voxel;
-    let mut intersected = Vec::new();
-    intersected.push(start_voxel);
+/// Bounds of a column's opaque and transparent meshes, from its shown chunks
+pub fn sub_mesh_bounds(
+    sub_meshes: &BTreeMap<i32, ChunkSubMesh>,
+    hidden: &HashSet<i32>,
+) -> [Aabb; 2] {
+    [0, 1].map(|mesh| {
+        sub_meshes
+            .iter()
+            .filter(|(y, _)| !hidden.contains(y))
+            .filter_map(|(_, sub_mesh)| sub_mesh.bounds[mesh])
+            .map(|bounds| (Vec3::from(bounds.min()), Vec3::from(bounds.max())))
+            .reduce(|(min, max), (other_min, other_max)| (min.min(other_min), max.max(other_max)))
+            .map_or_else(Aabb::default, |(min, max)| Aabb::from_min_max(min, max))
+    })
+}
 
-    // sanity check to prevent leak
-    while intersected.len() < range as usize * 3 {
-        if (t_max_x < t_max_y) {
-            if (t_max_x < t_max_z) {
-                current_voxel.x += step_x;
-                t_max_x += t_delta_x;
-            } else {
-                current_voxel.z += step_z;
-                t_max_z += t_delta_z;
+#[derive(Component)]
+pub struct ColumnMesh {
+    pub column: ChunkColumn,
+    pub dirty: bool,
+    pub dirty_chunks: HashSet<i32>, // heights of the chunks to remesh
+    pub sub_meshes: BTreeMap<i32, ChunkSubMesh>, // by chunk height, only the loaded chunks
+    pub hidden: HashSet<i32>,       // heights of the chunks the camera can't see into
+    pub meshed_hidden: HashSet<i32>, // `hidden` as the meshes were last built with
+    pub mesh: Handle<Mesh>,
+    pub transparent_mesh: Handle<Mesh>,
+    pub transparent_entity: Option<Entity>, // child drawing `transparent_mesh`
+    pub remesh_ms: f32,                     // how long its last update took, meshing and upload
+}
+
+/// Marks the child of a column mesh that draws its glass and leaves
+#[derive(Component)]
+pub struct TransparentPass;
+
+#[derive(Resource, Default)]
+pub struct ChunkMeshesUpdateQueue {
+    pub queue: HashSet<ChunkColumn>,  // columns to remesh whole
+    pub chunks: HashSet<ChunkIndex>,  // single chunks to remesh within their column
+    pub relight: HashSet<ChunkIndex>, // chunks whose light may have changed, see `relight_chunks`
+}
+
+impl ChunkMeshesUpdateQueue {
+    /// Remesh just this chunk and patch it into its column's mesh
+    pub fn queue_chunk(&mut self, index: ChunkIndex) {
+        self.chunks.insert(index);
+    }
+
+    /// Remesh the chunk of a block changed at `position`, and relight the other loaded chunks
+    /// its light could reach
+    pub fn queue_block(&mut self, voxel_data: &VoxelData, position: VoxelPos) {
+        self.queue_chunk(position.chunk());
+        let reach = IVec3::splat(MAX_LIGHT as i32 - 1);
+        let min = VoxelPos(position.0 - reach).chunk();
+        let max = VoxelPos(position.0 + reach).chunk();
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    let index = ChunkIndex { x, y, z };
+                    if index != position.chunk() && voxel_data.is_loaded(index) {
+                        self.relight.insert(index);
+                    }
+                }
             }
-        } else {
-            if (t_max_y < t_max_z) {
-                current_voxel.y += step_y;
-                t_max_y += t_delta_y;
-            } else {
-                current_voxel.z += step_z;
-                t_max_z += t_delta_z;
+        }
+    }
+
+    /// Mesh a chunk that was just loaded, and relight the loaded chunks around it when its
+    /// lights reach into them
+    pub fn queue_loaded(&mut self, voxel_data: &VoxelData, index: ChunkIndex) {
+        self.queue_chunk(index);
+        let Some(chunk) = voxel_data.chunks.get(&index) else {
+            return;
+        };
+        if !light::has_lights(chunk) {
+            return;
+        }
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let neighbour = ChunkIndex {
+                        x: index.x + x,
+                        y: index.y + y,
+                        z: index.z + z,
+                    };
+                    if neighbour != index && voxel_data.is_loaded(neighbour) {
+                        self.relight.insert(neighbour);
+                    }
+                }
             }
         }
-        if (t_max_x > 1.0 && t_max_y > 1.0 && t_max_z > 1.0) {
+    }
+
+    /// Remesh every loaded column, e.g. once blocks are drawn differently
+    pub fn remesh_all(&mut self, voxel_data: &VoxelData) {
+        self.queue
+            .extend(voxel_data.chunks.keys().map(|index| ChunkColumn {
+                x: index.x,
+                z: index.z,
+            }));
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len() + self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty() && self.chunks.is_empty()
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct VoxelModifyQueue {
+    pub queue: Vec<(VoxelPos, BlockId)>,
+    pub guarded: Vec<(VoxelPos, BlockId, Editor)>, // dropped where the claims don't allow them
+    pub states: Vec<(VoxelPos, BlockState)>, // applied after the blocks, which clear the state
+    // applied after the blocks too, in place of the new block entity of the block placed
+    pub entities: Vec<(VoxelPos, BlockEntity)>,
+}
+
+/// Voxels a ray passes through within `range`, in order, starting with the one it starts in.
+/// Amanatides & Woo's voxel traversal: step into whichever neighbour's boundary the ray
+/// crosses first
+pub fn get_intersected_voxels(start_point: &Vec3, direction: &Vec3, range: f32) -> Vec<VoxelPos> {
+    let direction = direction.normalize_or_zero();
+    let mut current_voxel = VoxelPos::from_world(*start_point);
+    let mut intersected = vec![current_voxel];
+    if direction == Vec3::ZERO {
+        return intersected;
+    }
+
+    let moving = direction.cmpne(Vec3::ZERO);
+    let step = Vec3::select(moving, direction.signum(), Vec3::ZERO).as_ivec3();
+    // distance along the ray to cross one voxel, on each axis
+    let t_delta = (1.0 / direction).abs();
+    // distance along the ray to the first voxel boundary, on each axis
+    let start_voxel = start_point.floor();
+    let boundary = start_voxel + step.max(IVec3::ZERO).as_vec3();
+    let mut t_max = Vec3::select(
+        moving,
+        (boundary - *start_point) / direction,
+        Vec3::splat(f32::INFINITY),
+    );
+
+    loop {
+        let axis = if t_max.x < t_max.y && t_max.x < t_max.z {
+            0
+        } else if t_max.y < t_max.z {
+            1
+        } else {
+            2
+        };
+        if t_max[axis] > range {
             break;
         }
+        current_voxel.0[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
         intersected.push(current_voxel);
     }
     intersected
 }
 
-pub struct VoxelLocalIndex {
-    pub x: u8,
-    pub y: u8,
-    pub z: u8,
+#[derive(Reflect, Resource, Clone, InspectorOptions, Serialize, Deserialize)]
+#[reflect(Resource, InspectorOptions)]
+#[serde(default)]
+pub struct VoxelSettings {
+    pub sight_range: u8,          // in chunk
+    pub vertical_sight_range: u8, // chunks loaded above and below the camera chunk
+    pub unload_margin: u8,        // chunks past the sight range before a chunk unloads
+    pub chunk_cache_size: u16,    // unloaded chunks kept for an instant reload
+    pub memory_budget: u16,       // MB for chunks and meshes, the chunk cache shrinks to stay under
+    pub interact_distance: f32,
+    pub break_cooldown: f32,     // seconds before another block can be mined
+    pub place_cooldown: f32,     // seconds before another block can be placed
+    pub use_cooldown: f32,       // seconds before another block can be used
+    pub chunk_gen_budget: u16,   // max chunks generated per frame
+    pub column_mesh_budget: u16, // max column meshes rebuilt per frame
+    pub relight_budget: u16,     // max chunks relit per frame after edits
+    pub bulk_edit_budget: u16,   // max blocks of bulk edits applied per frame
 }
 
-pub fn pos_to_voxel(pos: &Vec3) -> (ChunkIndex, VoxelLocalIndex) {
-    let chunk_index = get_chunk_index(pos);
-    (
-        chunk_index,
-        VoxelLocalIndex {
-            x: (pos.x - chunk_index.x as f32 * CHUNK_SIZE as f32).floor() as u8,
-            y: (pos.y - chunk_index.y as f32 * CHUNK_SIZE as f32).floor() as u8,
-            z: (pos.z - chunk_index.z as f32 * CHUNK_SIZE as f32).floor() as u8,
-        },
-    )
+impl Default for VoxelSettings {
+    fn default() -> Self {
+        VoxelSettings {
+            sight_range: 8,
+            vertical_sight_range: 4,
+            unload_margin: 2,
+            chunk_cache_size: 1024,
+            memory_budget: 512,
+            interact_distance: 10.0,
+            break_cooldown: 0.2,
+            place_cooldown: 0.2,
+            use_cooldown: 0.25,
+            chunk_gen_budget: 64,
+            column_mesh_budget: 4,
+            relight_budget: 8,
+            bulk_edit_budget: 4096,
+        }
+    }
+}
+
+/// Chunk heights within `vertical_sight_range` of the camera chunk, inside the world
+pub fn vertical_chunks(camera_chunk: &ChunkIndex, vertical_sight_range: u8) -> RangeInclusive<i32> {
+    let range = vertical_sight_range as i32;
+    (camera_chunk.y - range).max(0)..=(camera_chunk.y + range).min(CHUNK_LIMIT_Y as i32 - 1)
 }
 
-#[derive(Reflect, Resource, Default, InspectorOptions)]
+/// Which generator makes the world's chunks
+#[derive(Reflect, Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WorldPreset {
+    #[default]
+    Terrain,
+    Flat, // the flat layers, then air
+    Void, // nothing but a platform to spawn on
+}
+
+impl WorldPreset {
+    pub const ALL: [WorldPreset; 3] = [WorldPreset::Terrain, WorldPreset::Flat, WorldPreset::Void];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WorldPreset::Terrain => "terrain",
+            WorldPreset::Flat => "flat",
+            WorldPreset::Void => "void",
+        }
+    }
+}
+
+/// A point of the spline from terrain noise to land height
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HeightPoint {
+    pub noise: f32,
+    pub height: f32,
+}
+
+/// Shown as a button in the inspector, pressing it regenerates the world with the settings
+#[derive(Reflect, Default, Debug, Clone, Copy)]
+pub struct RegenerateButton {
+    pub pressed: bool,
+}
+
+/// A layer of the flat world, stacked from the bottom of the world up
+#[derive(Reflect, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlatLayer {
+    pub block: BlockId,
+    pub thickness: u8,
+}
+
+#[derive(Reflect, Resource, InspectorOptions, Clone, Serialize, Deserialize)]
 #[reflect(Resource, InspectorOptions)]
-pub struct VoxelSettings {
-    pub sight_range: u8, // in chunk
-    pub interact_distance: f32,
+#[serde(default)]
+pub struct WorldGenSettings {
+    pub seed: u32,
+    pub preset: WorldPreset,
+    pub wave_length: f32, // in voxels, of the first octave of the terrain
+    #[inspector(min = 1, max = 8)]
+    pub octaves: u8,
+    pub lacunarity: f32,  // how much finer each octave is
+    pub persistence: f32, // how much weaker each octave is
+    #[inspector(min = 0.0, max = 1.0)]
+    pub ridges: f32, // blend of the octaves towards sharp ridges
+    pub warp_strength: f32, // in voxels, how far domain warping moves a column
+    pub warp_wave_length: f32,
+    pub height_spline: Vec<HeightPoint>, // noise to land height, by ascending noise
+    pub sea_level: i32,                  // terrain below it is under water
+    pub erosion: bool,                   // smooth the heightmap before voxelization
+    pub erosion_iterations: u8,          // more iterations give longer talus slopes
+    pub talus: f32,                      // steepest height difference left untouched by erosion
+    pub flat_layers: Vec<FlatLayer>,     // bottom up, for `WorldPreset::Flat`
+    #[serde(skip)]
+    pub regenerate: RegenerateButton,
+}
+
+impl Default for WorldGenSettings {
+    fn default() -> Self {
+        WorldGenSettings {
+            seed: 123,
+            preset: WorldPreset::Terrain,
+            wave_length: WAVE_LENGTH as f32,
+            octaves: 1,
+            lacunarity: 2.0,
+            persistence: 0.5,
+            ridges: 0.0,
+            warp_strength: 0.0,
+            warp_wave_length: 128.0,
+            height_spline: vec![
+                HeightPoint {
+                    noise: -1.0,
+                    height: 48.0,
+                },
+                HeightPoint {
+                    noise: 1.0,
+                    height: 128.0,
+                },
+            ],
+            sea_level: 64,
+            erosion: false,
+            erosion_iterations: 8,
+            talus: 2.0,
+            flat_layers: vec![
+                FlatLayer {
+                    block: STONE,
+                    thickness: 60,
+                },
+                FlatLayer {
+                    block: DIRT,
+                    thickness: 3,
+                },
+                FlatLayer {
+                    block: GRASS,
+                    thickness: 1,
+                },
+            ],
+            regenerate: RegenerateButton::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intersected_voxels_start_in_the_negative_voxel() {
+        let voxels = get_intersected_voxels(&Vec3::new(-0.5, 10.5, -0.5), &Vec3::NEG_X, 4.0);
+        assert_eq!(voxels[0], VoxelPos::new(-1, 10, -1));
+        assert_eq!(voxels[1], VoxelPos::new(-2, 10, -1));
+    }
+
+    #[test]
+    fn block_ids_show_their_names() {
+        assert_eq!(STONE.to_string(), "Stone");
+        assert_eq!(WHEAT.offset(1).to_string(), "Seeds");
+        assert_eq!("4".parse::<BlockId>(), Ok(STONE));
+    }
+
+    #[test]
+    fn intersected_voxels_straight_up() {
+        let voxels = get_intersected_voxels(&Vec3::new(0.5, 0.5, 0.5), &Vec3::Y, 3.0);
+        let expected: Vec<_> = (0..=3).map(|y| VoxelPos::new(0, y, 0)).collect();
+        assert_eq!(voxels, expected);
+    }
+
+    #[test]
+    fn intersected_voxels_along_a_diagonal() {
+        let voxels =
+            get_intersected_voxels(&Vec3::new(0.5, 0.5, 0.2), &Vec3::new(1.0, 1.0, 0.0), 3.0);
+        // every step crosses one boundary, x and y alternate, ties go to y
+        assert_eq!(
+            voxels,
+            [
+                VoxelPos::new(0, 0, 0),
+                VoxelPos::new(0, 1, 0),
+                VoxelPos::new(1, 1, 0),
+                VoxelPos::new(1, 2, 0),
+                VoxelPos::new(2, 2, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn intersected_voxels_in_negative_directions() {
+        let voxels = get_intersected_voxels(&Vec3::new(0.5, 5.5, 0.5), &Vec3::NEG_Y, 2.0);
+        assert_eq!(
+            voxels,
+            [
+                VoxelPos::new(0, 5, 0),
+                VoxelPos::new(0, 4, 0),
+                VoxelPos::new(0, 3, 0)
+            ]
+        );
+        let voxels =
+            get_intersected_voxels(&Vec3::new(0.25, 0.5, 0.5), &Vec3::new(-1.0, 0.0, -1.0), 1.0);
+        assert_eq!(voxels[1], VoxelPos::new(-1, 0, 0));
+        assert_eq!(voxels.last(), Some(&VoxelPos::new(-1, 0, -1)));
+    }
+
+    #[test]
+    fn intersected_voxels_with_zero_components() {
+        // components of zero never step, a zero direction stays in the start voxel
+        let voxels = get_intersected_voxels(&Vec3::new(2.5, 2.5, 2.5), &Vec3::NEG_Z, 10.0);
+        assert_eq!(voxels.len(), 11);
+        assert!(voxels.iter().all(|voxel| voxel.0.x == 2 && voxel.0.y == 2));
+        let voxels = get_intersected_voxels(&Vec3::new(2.5, 2.5, 2.5), &Vec3::ZERO, 10.0);
+        assert_eq!(voxels, [VoxelPos::new(2, 2, 2)]);
+    }
+
+    #[test]
+    fn rays_pass_over_a_slab() {
+        let slab = VoxelPos::new(0, 0, 0);
+        let over = Vec3::new(-1.0, 0.75, 0.5);
+        assert!(!ray_hits_block(STONE_SLAB, 0, slab, over, Vec3::X));
+        assert!(ray_hits_block(STONE, 0, slab, over, Vec3::X));
+        assert!(ray_hits_block(
+            STONE_SLAB,
+            0,
+            slab,
+            Vec3::new(0.5, 2.0, 0.5),
+            Vec3::NEG_Y
+        ));
+        // the upper step of the stairs is on the -z half
+        assert!(ray_hits_block(
+            STONE_STAIRS,
+            0,
+            slab,
+            Vec3::new(-1.0, 0.75, 0.25),
+            Vec3::X
+        ));
+        assert!(!ray_hits_block(
+            STONE_STAIRS,
+            0,
+            slab,
+            Vec3::new(-1.0, 0.75, 0.75),
+            Vec3::X
+        ));
+    }
+
+    #[test]
+    fn doors_open_and_close_again() {
+        for closed in [DOOR, DOOR_TOP, TRAPDOOR] {
+            let open = match on_use(closed) {
+                Some(OnUse::Door(open) | OnUse::Toggle(open)) => open,
+                other => panic!("{closed} is used as {other:?}"),
+            };
+            assert!(is_solid(closed) && !is_solid(open));
+            assert!(matches!(
+                on_use(open),
+                Some(OnUse::Door(back) | OnUse::Toggle(back)) if back == closed
+            ));
+        }
+    }
+
+    #[test]
+    fn placed_stairs_step_up_away_from_the_player() {
+        let state = placed_state(STONE_STAIRS, IVec3::Y, Vec3::new(0.9, -0.3, 0.2));
+        assert_eq!(state, 3);
+        let stairs = VoxelPos::new(0, 0, 0);
+        let over = |x| Vec3::new(x, 0.75, -1.0);
+        assert!(ray_hits_block(
+            STONE_STAIRS,
+            state,
+            stairs,
+            over(0.75),
+            Vec3::Z
+        ));
+        assert!(!ray_hits_block(
+            STONE_STAIRS,
+            state,
+            stairs,
+            over(0.25),
+            Vec3::Z
+        ));
+        assert_eq!(placed_state(LOG, IVec3::NEG_X, Vec3::Z), 1 << AXIS_SHIFT);
+        assert_eq!(placed_state(STONE, IVec3::Y, Vec3::Z), 0);
+    }
+
+    #[test]
+    fn states_survive_encoding_and_go_with_the_block() {
+        let mut voxel_data = VoxelData::default();
+        let index = ChunkIndex { x: 0, y: 0, z: 0 };
+        voxel_data.chunks.insert(
+            index,
+            ChunkData {
+                index,
+                ..Default::default()
+            },
+        );
+        let position = VoxelPos::new(3, 4, 5);
+        voxel_data.set_block(position, STONE_STAIRS);
+        voxel_data.set_state(position, 2);
+
+        let chunk = codec::decode_chunk(&codec::encode_chunk(&voxel_data.chunks[&index])).unwrap();
+        assert_eq!(chunk.state(position.local()), 2);
+        assert_eq!(chunk.states.len(), 1);
+
+        voxel_data.set_block(position, STONE);
+        assert_eq!(voxel_data.get_state(position), 0);
+    }
+
+    #[test]
+    fn height_spline_interpolates_and_clamps() {
+        let spline = WorldGenSettings::default().height_spline;
+        assert_eq!(spline_height(&spline, -1.0), 48.0);
+        assert_eq!(spline_height(&spline, 0.5), 108.0);
+        assert_eq!(spline_height(&spline, 2.0), 128.0);
+        assert_eq!(spline_height(&[], 0.0), 88.0);
+    }
+
+    #[test]
+    fn sea_covers_sand_and_gravel() {
+        let flat_at = |height| WorldGenSettings {
+            height_spline: vec![HeightPoint { noise: 0.0, height }],
+            ..Default::default()
+        };
+        let index = ChunkIndex { x: 2, y: 3, z: -1 };
+        // land at 60 is shallow, sand under water from 61 to the sea level at 64
+        let shallow = ChunkData::new(index, &flat_at(60.0));
+        assert_eq!(shallow.voxels[4][11][4], SAND);
+        assert_eq!(shallow.voxels[4][12][4], SAND);
+        assert_eq!(shallow.voxels[4][13][4], WATER);
+        assert_eq!(shallow.voxels[4][15][4], WATER);
+        let deep = ChunkData::new(index, &flat_at(52.0));
+        assert_eq!(deep.voxels[4][4][4], GRAVEL);
+        assert_eq!(deep.voxels[4][5][4], WATER);
+    }
+
+    #[test]
+    fn presets_generate_flat_layers_and_a_platform() {
+        let mut settings = WorldGenSettings {
+            preset: WorldPreset::Flat,
+            ..Default::default()
+        };
+        let ground = ChunkData::new(ChunkIndex { x: -3, y: 3, z: 7 }, &settings);
+        // 60 stone, 3 dirt and grass on top, from y 48 in this chunk
+        assert_eq!(ground.voxels[5][11][5], STONE);
+        assert_eq!(ground.voxels[5][14][5], DIRT);
+        assert_eq!(ground.voxels[5][15][5], GRASS);
+        assert_eq!(surface_height(100, -100, &settings), 63);
+        let sky = ChunkData::new(ChunkIndex { x: 0, y: 4, z: 0 }, &settings);
+        assert!(sky
+            .voxels
+            .iter()
+            .flatten()
+            .flatten()
+            .all(|block| *block == AIR));
+
+        settings.preset = WorldPreset::Void;
+        let platform = ChunkData::new(ChunkIndex { x: -1, y: 4, z: 0 }, &settings);
+        assert_eq!(platform.voxels[15][0][0], STONE);
+        assert_eq!(platform.voxels[15][1][0], AIR);
+        let far = ChunkData::new(ChunkIndex { x: 1, y: 4, z: 0 }, &settings);
+        assert!(far
+            .voxels
+            .iter()
+            .flatten()
+            .flatten()
+            .all(|block| *block == AIR));
+    }
+
+    #[test]
+    fn packed_vertices_keep_grid_positions() {
+        let position = Vec3::new(-0.5, 255.0 + 3.0 / 16.0, 16.0);
+        let [position_word, attributes] = pack_vertex(position, NORMALS[4], UVS[2], 5 | GLOW_BIT);
+        let x = (position_word & 0x1ff) as f32 / POSITION_STEPS - POSITION_BIAS;
+        let z = (position_word >> 9 & 0x1ff) as f32 / POSITION_STEPS - POSITION_BIAS;
+        let y = (position_word >> 18) as f32 / POSITION_STEPS - POSITION_BIAS;
+        assert_eq!(Vec3::new(x, y, z), position);
+        assert_eq!(attributes & 0x1ffff, 5 | GLOW_BIT);
+        assert_eq!(attributes >> 17 & 0x7, 4);
+        assert_eq!(attributes >> 20, 2);
+    }
+
+    /// Area of the faces of a mesh, in block faces
+    fn face_area(mesh: &MeshData) -> f32 {
+        mesh.positions
+            .chunks(4)
+            .map(|quad| {
+                let (min, max) = quad.iter().fold((quad[0], quad[0]), |(min, max), p| {
+                    (min.min(*p), max.max(*p))
+                });
+                let extent = max - min; // flat along the face's normal
+                extent.x * extent.y + extent.y * extent.z + extent.z * extent.x
+            })
+            .sum()
+    }
+
+    fn patterned_chunk(pattern: impl Fn(usize, usize, usize) -> BlockId) -> ChunkData {
+        let mut voxels = [[[AIR; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+        for (x, plane) in voxels.iter_mut().enumerate() {
+            for (y, row) in plane.iter_mut().enumerate() {
+                for (z, voxel) in row.iter_mut().enumerate() {
+                    *voxel = pattern(x, y, z);
+                }
+            }
+        }
+        ChunkData {
+            voxels,
+            states: BTreeMap::new(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn greedy_meshing_covers_the_faces_of_default_mesh() {
+        let solid = patterned_chunk(|_, _, _| STONE);
+        let greedy = greedy_meshing(
+            &solid,
+            &ChunkLight::of(&ChunkNeighbourhood::lone(&solid)),
+            false,
+        );
+        assert_eq!(greedy.positions.len(), 6 * 4);
+        assert_eq!(face_area(&greedy), 6.0 * 256.0);
+
+        let patterns: [fn(usize, usize, usize) -> BlockId; 3] = [
+            |x, y, z| if (x + y + z) % 2 == 0 { STONE } else { AIR },
+            |x, y, _| if y < 5 + x % 3 { DIRT } else { AIR },
+            |x, y, z| [AIR, STONE, DIRT, STONE][(x * 7 + y * 3 + z * 5) % 4],
+        ];
+        for pattern in patterns {
+            let chunk = patterned_chunk(pattern);
+            let default = default_mesh(chunk.clone());
+            let greedy = greedy_meshing(
+                &chunk,
+                &ChunkLight::of(&ChunkNeighbourhood::lone(&chunk)),
+                false,
+            );
+            assert_eq!(face_area(&greedy), (default.positions.len() / 4) as f32);
+            assert!(greedy.positions.len() <= default.positions.len());
+        }
+    }
+
+    #[test]
+    fn patched_column_meshes_match_combining_them_again() {
+        let sub_mesh = |pattern: fn(usize, usize, usize) -> BlockId| {
+            let chunk = patterned_chunk(pattern);
+            ChunkSubMesh::new(&chunk, &ChunkNeighbourhood::lone(&chunk))
+        };
+        let mut sub_meshes = BTreeMap::from([
+            (0, sub_mesh(|_, _, _| STONE)),
+            (
+                1,
+                sub_mesh(|x, y, _| if y < 5 + x % 3 { DIRT } else { AIR }),
+            ),
+            (2, sub_mesh(|_, _, _| STONE)),
+        ]);
+        let mut hidden = HashSet::new();
+        let (opaque, _) = combine_sub_meshes(&mut sub_meshes, &hidden);
+        let mut mesh = Mesh::from(opaque);
+
+        // the middle chunk gets more faces, the top one is hidden and one is added above
+        let changed = [1, 2, 3];
+        let replaced = sub_mesh_ranges(&sub_meshes, changed, false);
+        sub_meshes.insert(
+            1,
+            sub_mesh(|x, y, z| if (x + y + z) % 2 == 0 { STONE } else { AIR }),
+        );
+        sub_meshes.insert(3, sub_mesh(|_, y, _| if y < 2 { DIRT } else { AIR }));
+        hidden.insert(2);
+        assert!(patch_sub_meshes(
+            &mut sub_meshes,
+            &hidden,
+            replaced,
+            false,
+            &mut mesh
+        ));
+
+        let patched_ranges: Vec<MeshRange> = sub_meshes
+            .values()
+            .map(|s| s.opaque_range.clone())
+            .collect();
+        let (opaque, _) = combine_sub_meshes(&mut sub_meshes, &hidden);
+        let combined = Mesh::from(opaque);
+        let ranges: Vec<MeshRange> = sub_meshes
+            .values()
+            .map(|s| s.opaque_range.clone())
+            .collect();
+        assert_eq!(patched_ranges, ranges);
+        assert_eq!(
+            mesh.indices().unwrap().iter().collect::<Vec<_>>(),
+            combined.indices().unwrap().iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            mesh.attribute(ATTRIBUTE_PACKED_VERTEX).unwrap().get_bytes(),
+            combined
+                .attribute(ATTRIBUTE_PACKED_VERTEX)
+                .unwrap()
+                .get_bytes()
+        );
+        assert_eq!(
+            mesh.attribute(ATTRIBUTE_VOXEL_LIGHT).unwrap().get_bytes(),
+            combined
+                .attribute(ATTRIBUTE_VOXEL_LIGHT)
+                .unwrap()
+                .get_bytes()
+        );
+    }
 }
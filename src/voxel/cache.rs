@@ -0,0 +1,194 @@
+//! Chunks unloaded a moment ago, kept around so walking back and forth over
+//! the unload border brings them back without generating them again. Edited
+//! chunks that couldn't be saved stay until they are, so edits aren't lost.
+
+use std::collections::{HashMap, VecDeque};
+
+use bevy::prelude::*;
+
+use super::{ChunkData, ChunkIndex};
+
+struct CachedChunk {
+    data: ChunkData,
+    modified: bool, // edited before it was unloaded
+    stamp: u64,
+}
+
+/// Least recently unloaded chunks are dropped first once the cache is full, edited ones only
+/// once they're written
+#[derive(Resource, Default)]
+pub struct ChunkCache {
+    chunks: HashMap<ChunkIndex, CachedChunk>,
+    order: VecDeque<(ChunkIndex, u64)>, // oldest first, may hold stale stamps of taken chunks
+    next_stamp: u64,
+}
+
+impl ChunkCache {
+    /// Cache an unloaded chunk, `modified` if it's edited and not saved yet. Past `capacity`
+    /// the oldest chunks are dropped, an edited one only once `write` saved it
+    pub fn insert(
+        &mut self,
+        data: ChunkData,
+        modified: bool,
+        capacity: usize,
+        mut write: impl FnMut(&ChunkData) -> bool,
+    ) {
+        let index = data.index;
+        let stamp = self.next_stamp;
+        self.next_stamp += 1;
+        self.chunks.insert(
+            index,
+            CachedChunk {
+                data,
+                modified,
+                stamp,
+            },
+        );
+        self.order.push_back((index, stamp));
+
+        let mut unsaved = Vec::new();
+        while self.chunks.len() > capacity {
+            let Some((oldest, stamp)) = self.order.pop_front() else {
+                break;
+            };
+            let Some(cached) = self.chunks.get(&oldest).filter(|c| c.stamp == stamp) else {
+                continue;
+            };
+            if cached.modified && !write(&cached.data) {
+                unsaved.push((oldest, stamp));
+                continue;
+            }
+            self.chunks.remove(&oldest);
+        }
+        // still the oldest, they're tried first next time
+        for entry in unsaved.into_iter().rev() {
+            self.order.push_front(entry);
+        }
+        // forget the stamps of chunks taken back, so the queue doesn't grow without bound
+        if self.order.len() > self.chunks.len() * 2 {
+            let chunks = &self.chunks;
+            self.order
+                .retain(|(index, stamp)| chunks.get(index).is_some_and(|c| c.stamp == *stamp));
+        }
+    }
+
+    /// Take a chunk back out of the cache, with whether it had been edited
+    pub fn take(&mut self, index: &ChunkIndex) -> Option<(ChunkData, bool)> {
+        self.chunks
+            .remove(index)
+            .map(|cached| (cached.data, cached.modified))
+    }
+
+    /// Write the edited chunks that couldn't be saved when they unloaded, the ones `write`
+    /// saves are clean from then on
+    pub fn write_modified(&mut self, mut write: impl FnMut(&ChunkData) -> bool) {
+        for cached in self.chunks.values_mut() {
+            if cached.modified && write(&cached.data) {
+                cached.modified = false;
+            }
+        }
+    }
+
+    /// Forget the chunks that weren't edited, so they are generated again
+    pub fn clear_unmodified(&mut self) {
+        self.chunks.retain(|_, cached| cached.modified);
+    }
+
+    /// Drop the cached chunk farthest from `center`, edited ones are kept. Returns the bytes
+    /// freed, none once only edited chunks are left
+    pub fn evict_farthest(&mut self, center: &ChunkIndex) -> Option<usize> {
+        let distance = |index: &ChunkIndex| {
+            (index.x - center.x)
+                .abs()
+                .max((index.y - center.y).abs())
+                .max((index.z - center.z).abs())
+        };
+        let farthest = *self
+            .chunks
+            .iter()
+            .filter(|(_, cached)| !cached.modified)
+            .max_by_key(|(index, _)| distance(index))?
+            .0;
+        self.chunks
+            .remove(&farthest)
+            .map(|cached| cached.data.memory_bytes())
+    }
+
+    /// Bytes taken up by the cached chunks
+    pub fn memory_bytes(&self) -> usize {
+        self.chunks
+            .values()
+            .map(|cached| cached.data.memory_bytes())
+            .sum()
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(x: i32) -> ChunkData {
+        ChunkData::air(ChunkIndex { x, y: 0, z: 0 })
+    }
+
+    fn saved(_: &ChunkData) -> bool {
+        true
+    }
+
+    #[test]
+    fn evicts_the_least_recently_unloaded_chunk() {
+        let mut cache = ChunkCache::default();
+        cache.insert(chunk(0), false, 2, saved);
+        cache.insert(chunk(1), true, 2, saved);
+        assert!(cache.take(&chunk(0).index).is_some());
+        cache.insert(chunk(0), false, 2, saved);
+        cache.insert(chunk(2), false, 2, saved);
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.take(&chunk(1).index).is_none());
+        assert!(cache.take(&chunk(0).index).is_some());
+        assert!(cache.take(&chunk(2).index).is_some());
+    }
+
+    #[test]
+    fn evicts_the_farthest_unedited_chunk_for_the_budget() {
+        let mut cache = ChunkCache::default();
+        cache.insert(chunk(-5), true, 8, saved);
+        cache.insert(chunk(3), false, 8, saved);
+        cache.insert(chunk(-1), false, 8, saved);
+
+        let center = chunk(0).index;
+        assert_eq!(cache.evict_farthest(&center), Some(chunk(3).memory_bytes()));
+        assert!(cache.evict_farthest(&center).is_some());
+        assert_eq!(cache.evict_farthest(&center), None);
+        assert!(cache.take(&chunk(-5).index).is_some());
+    }
+
+    #[test]
+    fn keeps_edited_chunks_until_they_are_written() {
+        let mut cache = ChunkCache::default();
+        let mut written = Vec::new();
+        cache.insert(chunk(0), true, 1, saved);
+        cache.insert(chunk(1), false, 1, |chunk| {
+            written.push(chunk.index.x);
+            false
+        });
+        // the edited chunk couldn't be saved, so it stays and the next oldest goes instead
+        assert_eq!(written, [0]);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.take(&chunk(1).index).is_none());
+
+        cache.write_modified(saved);
+        cache.insert(chunk(2), false, 1, |_| panic!("nothing is edited"));
+        assert_eq!(cache.len(), 1);
+        assert!(cache.take(&chunk(2).index).is_some());
+    }
+}
@@ -0,0 +1,183 @@
+//! Conversions between world space, voxel positions and chunk indices.
+//! Everything goes through euclidean division, so negative coordinates land
+//! in the chunk below zero instead of wrapping.
+
+use bevy::prelude::*;
+
+use super::{ChunkIndex, CHUNK_SIZE};
+
+const SIZE: i32 = CHUNK_SIZE as i32;
+
+/// Position of a voxel inside its chunk, each axis in `0..CHUNK_SIZE`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VoxelLocalIndex {
+    pub x: u8,
+    pub y: u8,
+    pub z: u8,
+}
+
+impl VoxelLocalIndex {
+    /// The index as one number, x-major then y then z like the voxel arrays
+    pub fn packed(self) -> u16 {
+        (self.x as u16 * CHUNK_SIZE as u16 + self.y as u16) * CHUNK_SIZE as u16 + self.z as u16
+    }
+
+    /// Inverse of `packed`, `None` past the end of a chunk
+    pub fn from_packed(packed: u16) -> Option<Self> {
+        let size = CHUNK_SIZE as u16;
+        if packed >= size * size * size {
+            return None;
+        }
+        Some(VoxelLocalIndex {
+            x: (packed / (size * size)) as u8,
+            y: (packed / size % size) as u8,
+            z: (packed % size) as u8,
+        })
+    }
+}
+
+/// Integer position of a voxel in the world
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VoxelPos(pub IVec3);
+
+impl VoxelPos {
+    pub fn new(x: i32, y: i32, z: i32) -> Self {
+        VoxelPos(IVec3::new(x, y, z))
+    }
+
+    /// Voxel containing a point in world space
+    pub fn from_world(pos: Vec3) -> Self {
+        VoxelPos(pos.floor().as_ivec3())
+    }
+
+    /// Voxel at `local` inside the chunk `chunk`, the inverse of `split`
+    pub fn from_parts(chunk: ChunkIndex, local: VoxelLocalIndex) -> Self {
+        VoxelPos(chunk.origin() + IVec3::new(local.x as i32, local.y as i32, local.z as i32))
+    }
+
+    /// World space position of the voxel's lowest corner
+    pub fn to_world(self) -> Vec3 {
+        self.0.as_vec3()
+    }
+
+    pub fn chunk(self) -> ChunkIndex {
+        ChunkIndex {
+            x: self.0.x.div_euclid(SIZE),
+            y: self.0.y.div_euclid(SIZE),
+            z: self.0.z.div_euclid(SIZE),
+        }
+    }
+
+    pub fn local(self) -> VoxelLocalIndex {
+        VoxelLocalIndex {
+            x: self.0.x.rem_euclid(SIZE) as u8,
+            y: self.0.y.rem_euclid(SIZE) as u8,
+            z: self.0.z.rem_euclid(SIZE) as u8,
+        }
+    }
+
+    /// Chunk holding the voxel, and its position inside that chunk
+    pub fn split(self) -> (ChunkIndex, VoxelLocalIndex) {
+        (self.chunk(), self.local())
+    }
+}
+
+impl From<IVec3> for VoxelPos {
+    fn from(position: IVec3) -> Self {
+        VoxelPos(position)
+    }
+}
+
+impl ChunkIndex {
+    /// Position of the chunk's lowest corner voxel
+    pub fn origin(self) -> IVec3 {
+        IVec3::new(self.x, self.y, self.z) * SIZE
+    }
+}
+
+/// Chunk containing a point in world space
+pub fn get_chunk_index(pos: &Vec3) -> ChunkIndex {
+    VoxelPos::from_world(*pos).chunk()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_world_floors_towards_negative_infinity() {
+        assert_eq!(
+            VoxelPos::from_world(Vec3::new(0.5, 1.0, 1.9)),
+            VoxelPos::new(0, 1, 1)
+        );
+        assert_eq!(
+            VoxelPos::from_world(Vec3::new(-0.5, -1.0, -1.1)),
+            VoxelPos::new(-1, -1, -2)
+        );
+    }
+
+    #[test]
+    fn chunk_index_at_borders() {
+        let chunk = |x: f32, z: f32| get_chunk_index(&Vec3::new(x, 0.0, z));
+        assert_eq!(chunk(0.0, -0.001), ChunkIndex { x: 0, y: 0, z: -1 });
+        assert_eq!(chunk(15.999, -16.0), ChunkIndex { x: 0, y: 0, z: -1 });
+        assert_eq!(chunk(16.0, -16.001), ChunkIndex { x: 1, y: 0, z: -2 });
+    }
+
+    #[test]
+    fn split_negative_coordinates() {
+        let (chunk, local) = VoxelPos::new(-1, -16, -17).split();
+        assert_eq!(
+            chunk,
+            ChunkIndex {
+                x: -1,
+                y: -1,
+                z: -2
+            }
+        );
+        assert_eq!(local, VoxelLocalIndex { x: 15, y: 0, z: 15 });
+    }
+
+    #[test]
+    fn position_round_trips_through_chunk_and_local() {
+        for x in -4 * SIZE..4 * SIZE {
+            for y in -2 * SIZE..2 * SIZE {
+                for z in -4 * SIZE..4 * SIZE {
+                    let position = VoxelPos::new(x, y, z);
+                    let (chunk, local) = position.split();
+                    assert!(local.x < CHUNK_SIZE as u8);
+                    assert!(local.y < CHUNK_SIZE as u8);
+                    assert!(local.z < CHUNK_SIZE as u8);
+                    assert_eq!(VoxelPos::from_parts(chunk, local), position);
+                    assert_eq!(VoxelLocalIndex::from_packed(local.packed()), Some(local));
+                    assert_eq!(get_chunk_index(&position.to_world()), chunk);
+                    // any point inside the voxel maps back to it
+                    let inside = position.to_world() + Vec3::splat(0.5);
+                    assert_eq!(VoxelPos::from_world(inside), position);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn chunk_and_local_round_trip_through_position() {
+        for chunk_x in -3..3 {
+            for chunk_z in -3..3 {
+                let chunk = ChunkIndex {
+                    x: chunk_x,
+                    y: 1,
+                    z: chunk_z,
+                };
+                for x in 0..CHUNK_SIZE as u8 {
+                    for y in 0..CHUNK_SIZE as u8 {
+                        for z in 0..CHUNK_SIZE as u8 {
+                            let local = VoxelLocalIndex { x, y, z };
+                            let position = VoxelPos::from_parts(chunk, local);
+                            assert_eq!(position.split(), (chunk, local));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
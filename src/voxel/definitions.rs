@@ -0,0 +1,266 @@
+//! Block definitions loaded from `blocks/default.blocks.ron` on top of the built-in
+//! registry: a definition changes the name, look, mining time or drops of a block,
+//! or adds a plain block after the last one. Gameplay still refers to the built-in
+//! blocks by id. A block's texture is picked by layer or by the name of a texture
+//! in the pack, see `texture_pack`. The file is reloaded along with the other assets
+//! and the world is remeshed with the new definitions.
+
+use std::sync::RwLock;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use super::{BlockId, BlockProperties, BlockShape, BlockTag, LightColor, AIR, BLOCKS, MAX_LIGHT};
+use crate::world::VoxelWorld;
+
+const DEFINITIONS_FILE: &str = "blocks/default.blocks.ron";
+
+/// A block as the definitions file describes it, fields left out keep the built-in value
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct BlockDefinition {
+    pub id: BlockId,
+    pub name: Option<String>,
+    pub layer: Option<u32>,      // texture layer
+    pub texture: Option<String>, // texture by name, over `layer`
+    pub transparent: Option<bool>,
+    pub shape: Option<BlockShape>,
+    pub glows: Option<bool>,
+    pub light: Option<LightColor>, // given off, 0 to `MAX_LIGHT` per channel
+    pub passable: Option<bool>,
+    pub falls: Option<bool>,
+    pub tags: Option<Vec<String>>, // e.g. `#flammable`
+    pub mining_time: Option<f32>,  // seconds by hand
+    pub drops: Option<Vec<BlockId>>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, TypeUuid, TypePath)]
+#[uuid = "6d3a2f0e-51c4-4b8e-9a77-2b1f8c0d4e95"]
+pub struct BlockDefinitions {
+    pub blocks: Vec<BlockDefinition>,
+}
+
+#[derive(Default)]
+pub struct BlockDefinitionsLoader;
+
+impl AssetLoader for BlockDefinitionsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let definitions: BlockDefinitions = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(definitions));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["blocks.ron"]
+    }
+}
+
+/// A registry entry with what only definitions carry
+#[derive(Debug, Clone)]
+pub(super) struct DefinedBlock {
+    pub properties: BlockProperties,
+    pub texture: Option<String>,
+    pub name: Option<String>,
+    pub mining_time: Option<f32>,
+    pub drops: Option<Vec<BlockId>>,
+}
+
+/// The registry with the definitions applied, indexed like `BLOCKS`. Empty until the
+/// definitions are loaded, the built-in registry is used until then. Meshing tasks read
+/// it off the main thread, so it lives outside the ECS
+pub(super) static DEFINED_BLOCKS: RwLock<Vec<DefinedBlock>> = RwLock::new(Vec::new());
+
+/// Texture names of the layers of the array texture in use, indexed by layer
+static TEXTURE_LAYERS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Point the blocks picking their texture by name at its layer in `layers`, whether
+/// any block now has a different layer
+fn resolve_textures(blocks: &mut [DefinedBlock], layers: &[String]) -> bool {
+    let mut changed = false;
+    for block in blocks.iter_mut() {
+        let Some(texture) = block.texture.as_ref() else {
+            continue;
+        };
+        match layers.iter().position(|layer| layer == texture) {
+            Some(layer) if block.properties.layer != layer as u32 => {
+                block.properties.layer = layer as u32;
+                changed = true;
+            }
+            Some(_) => {}
+            // until a pack is loaded the texture names aren't known
+            None if !layers.is_empty() => warn!("The texture pack has no texture '{}'", texture),
+            None => {}
+        }
+    }
+    changed
+}
+
+/// Tell the registry the texture names of the array texture's layers, once a texture
+/// pack is stacked. Whether blocks are now drawn with different layers
+pub fn set_texture_layers(layers: Vec<String>) -> bool {
+    let changed = resolve_textures(&mut DEFINED_BLOCKS.write().unwrap(), &layers);
+    *TEXTURE_LAYERS.write().unwrap() = layers;
+    changed
+}
+
+/// Built-in registry with `definitions` applied over it
+fn define(definitions: &BlockDefinitions) -> Result<Vec<DefinedBlock>, String> {
+    let mut blocks: Vec<DefinedBlock> = BLOCKS
+        .iter()
+        .map(|&properties| DefinedBlock {
+            properties,
+            texture: None,
+            name: None,
+            mining_time: None,
+            drops: None,
+        })
+        .collect();
+    for definition in definitions.blocks.iter() {
+        if definition.id == AIR {
+            return Err("block 0 is air and can't be defined".to_string());
+        }
+        let index = definition.id.registry_index();
+        if index == blocks.len() {
+            blocks.push(DefinedBlock {
+                properties: BlockProperties::cube(0),
+                texture: None,
+                name: None,
+                mining_time: None,
+                drops: None,
+            });
+        }
+        let Some(block) = blocks.get_mut(index) else {
+            return Err(format!(
+                "block {} leaves a gap after block {}",
+                definition.id.0,
+                blocks.len()
+            ));
+        };
+        let properties = &mut block.properties;
+        if let Some(layer) = definition.layer {
+            properties.layer = layer;
+        }
+        if let Some(transparent) = definition.transparent {
+            properties.transparent = transparent;
+        }
+        if let Some(shape) = definition.shape {
+            properties.shape = shape;
+        }
+        if let Some(glows) = definition.glows {
+            properties.glows = glows;
+        }
+        if let Some(light) = definition.light {
+            properties.light = light.map(|level| level.min(MAX_LIGHT));
+        }
+        if let Some(passable) = definition.passable {
+            properties.passable = passable;
+        }
+        if let Some(falls) = definition.falls {
+            properties.falls = falls;
+        }
+        if let Some(tags) = definition.tags.as_ref() {
+            properties.tags = 0;
+            for name in tags {
+                let tag = BlockTag::from_name(name).ok_or(format!(
+                    "unknown block tag '{}' on block {}",
+                    name, definition.id.0
+                ))?;
+                *properties = properties.tagged(tag);
+            }
+        }
+        if definition.texture.is_some() {
+            block.texture = definition.texture.clone();
+        }
+        if definition.name.is_some() {
+            block.name = definition.name.clone();
+        }
+        if definition.mining_time.is_some() {
+            block.mining_time = definition.mining_time;
+        }
+        if definition.drops.is_some() {
+            block.drops = definition.drops.clone();
+        }
+    }
+    resolve_textures(&mut blocks, &TEXTURE_LAYERS.read().unwrap());
+    Ok(blocks)
+}
+
+#[derive(Resource)]
+pub struct BlockDefinitionsFile {
+    handle: Handle<BlockDefinitions>,
+}
+
+pub fn load_block_definitions(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(BlockDefinitionsFile {
+        handle: asset_server.load(DEFINITIONS_FILE),
+    });
+}
+
+/// Put the definitions in the registry once they're loaded and whenever they're reloaded,
+/// then remesh the world with them
+pub fn apply_block_definitions(
+    file: Res<BlockDefinitionsFile>,
+    definitions: Res<Assets<BlockDefinitions>>,
+    mut definition_events: EventReader<AssetEvent<BlockDefinitions>>,
+    mut world: VoxelWorld,
+) {
+    let loaded = definition_events.iter().any(|event| match event {
+        AssetEvent::Created { handle } | AssetEvent::Modified { handle } => *handle == file.handle,
+        AssetEvent::Removed { .. } => false,
+    });
+    let Some(definitions) = definitions.get(&file.handle).filter(|_| loaded) else {
+        return;
+    };
+    let blocks = match define(definitions) {
+        Ok(blocks) => blocks,
+        Err(err) => {
+            warn!("Ignoring block definitions {}: {}", DEFINITIONS_FILE, err);
+            return;
+        }
+    };
+    info!(
+        "Loaded {} block definitions from {}",
+        definitions.blocks.len(),
+        DEFINITIONS_FILE
+    );
+    *DEFINED_BLOCKS.write().unwrap() = blocks;
+    world.remesh_all();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::{GLASS, LAST_BUILT_IN_BLOCK, STONE};
+
+    #[test]
+    fn definitions_override_and_extend_the_registry() {
+        let definitions: BlockDefinitions = ron::from_str(
+            r##"(blocks: [
+                (id: 4, name: Some("Granite"), mining_time: Some(3.0)),
+                (id: 37, name: Some("Bricks"), layer: Some(3), tags: Some(["#flammable"])),
+            ])"##,
+        )
+        .unwrap();
+        let blocks = define(&definitions).unwrap();
+        assert_eq!(blocks.len(), LAST_BUILT_IN_BLOCK.0 as usize + 1);
+        let stone = &blocks[STONE.registry_index()];
+        assert_eq!(stone.name.as_deref(), Some("Granite"));
+        assert_eq!(stone.properties.layer, BLOCKS[STONE.registry_index()].layer);
+        assert!(blocks[36].properties.has_tag(BlockTag::Flammable));
+        assert!(blocks[GLASS.registry_index()].properties.transparent);
+
+        let gap: BlockDefinitions = ron::from_str("(blocks: [(id: 40)])").unwrap();
+        assert!(define(&gap).is_err());
+    }
+}
@@ -0,0 +1,329 @@
+//! Block light: blocks that give off light, like lamps and fire, light up the
+//! voxels around them with their own color. Each channel spreads on its own,
+//! one level dimmer per block and stopped by opaque cubes, and a voxel keeps
+//! the brightest of each channel that reaches it, so a red and a blue lamp
+//! side by side light the space between them purple. The light is worked out
+//! when a chunk is meshed and baked into its vertices, see `ATTRIBUTE_VOXEL_LIGHT`:
+//! each corner of a face gets the average of the voxels in front of the face
+//! around it, like Minecraft's smooth lighting, and the shader blends between them.
+//! Lights in the chunks around count too, see `ChunkNeighbourhood`. After an
+//! edit the chunks around it are relit a few a frame, and only the ones whose
+//! light changed are meshed again, see `relight_chunks`.
+//!
+//! With light bounce on, each face also gets the light bounced onto it by the
+//! surfaces around, traced through the light in a coarse cone, see
+//! `ChunkLight::bounce`.
+
+use std::{
+    collections::{hash_map::DefaultHasher, VecDeque},
+    hash::{Hash, Hasher},
+};
+
+use bevy::prelude::*;
+
+use super::{
+    block_properties, last_block, occludes, BlockId, ChunkData, ChunkNeighbourhood, AIR, CHUNK_SIZE,
+};
+
+pub const MAX_LIGHT: u8 = 15; // brightest level of a channel
+const BOUNCE_REACH: i32 = 6; // voxels out from a face its bounce cone looks for surfaces
+const BOUNCE_SHIFT: u32 = 12; // bits of `ATTRIBUTE_VOXEL_LIGHT` below the bounced light
+
+/// Voxels around the chunk the light is worked out for, as far as a light can be from a
+/// voxel next to the chunk and still reach it
+const MARGIN: i32 = MAX_LIGHT as i32;
+const SIDE: i32 = CHUNK_SIZE as i32 + 2 * MARGIN;
+
+/// Light color of a voxel, a level from 0 to `MAX_LIGHT` per channel
+pub type LightColor = [u8; 3];
+
+/// Pack a light color into `ATTRIBUTE_VOXEL_LIGHT`, 4 bits per channel
+pub fn pack_light(color: LightColor) -> u32 {
+    color[0] as u32 | (color[1] as u32) << 4 | (color[2] as u32) << 8
+}
+
+/// Pack the direct and the bounced light of a vertex into `ATTRIBUTE_VOXEL_LIGHT`
+pub fn pack_lights(direct: LightColor, bounced: LightColor) -> u32 {
+    pack_light(direct) | pack_light(bounced) << BOUNCE_SHIFT
+}
+
+/// Whether any block of the chunk gives off light
+pub(super) fn has_lights(chunk: &ChunkData) -> bool {
+    chunk
+        .voxels
+        .iter()
+        .flatten()
+        .flatten()
+        .any(|&block| block != AIR && block_properties(block).light != [0; 3])
+}
+
+/// Block light of every voxel of a chunk and of the voxels around it up to `MARGIN` away,
+/// empty when no light reaches them
+#[derive(Debug, Clone)]
+pub struct ChunkLight {
+    levels: Vec<LightColor>, // x, then y, then z
+    opaque: Vec<bool>,       // stops light, left out of the smooth light of corners
+}
+
+fn cell(p: IVec3) -> usize {
+    let p = p + IVec3::splat(MARGIN);
+    ((p.x * SIDE + p.y) * SIDE + p.z) as usize
+}
+
+fn inside(p: IVec3) -> bool {
+    p.cmpge(IVec3::splat(-MARGIN)).all() && p.cmplt(IVec3::splat(SIDE - MARGIN)).all()
+}
+
+impl ChunkLight {
+    pub fn of(neighbourhood: &ChunkNeighbourhood) -> Self {
+        // by block id, the light it gives off and whether it stops light
+        let blocks: Vec<(LightColor, bool)> = (0..=last_block().0)
+            .map(|block| {
+                let block = BlockId(block);
+                (block_properties(block).light, occludes(block))
+            })
+            .collect();
+        let block = |p: IVec3| {
+            let block = neighbourhood.block(p);
+            blocks
+                .get(block.0 as usize)
+                .copied()
+                .unwrap_or(([0; 3], true))
+        };
+
+        let cells = (SIDE * SIDE * SIDE) as usize;
+        let mut levels = vec![[0; 3]; cells];
+        let mut opaque = vec![false; cells];
+        let mut queue = VecDeque::new();
+        for x in -MARGIN..SIDE - MARGIN {
+            for y in -MARGIN..SIDE - MARGIN {
+                for z in -MARGIN..SIDE - MARGIN {
+                    let p = IVec3::new(x, y, z);
+                    let (light, stops_light) = block(p);
+                    opaque[cell(p)] = stops_light;
+                    if light != [0; 3] {
+                        levels[cell(p)] = light;
+                        queue.push_back(p);
+                    }
+                }
+            }
+        }
+        if queue.is_empty() {
+            return ChunkLight {
+                levels: Vec::new(),
+                opaque: Vec::new(),
+            };
+        }
+        // breadth first, a voxel is queued again whenever one of its channels gets brighter
+        while let Some(p) = queue.pop_front() {
+            let spread = levels[cell(p)].map(|level| level.saturating_sub(1));
+            if spread == [0; 3] {
+                continue;
+            }
+            for step in [
+                IVec3::X,
+                IVec3::Y,
+                IVec3::Z,
+                IVec3::NEG_X,
+                IVec3::NEG_Y,
+                IVec3::NEG_Z,
+            ] {
+                let next = p + step;
+                if !inside(next) || opaque[cell(next)] {
+                    continue;
+                }
+                let level = &mut levels[cell(next)];
+                let blended = [0, 1, 2].map(|i| level[i].max(spread[i]));
+                if blended != *level {
+                    *level = blended;
+                    queue.push_back(next);
+                }
+            }
+        }
+        ChunkLight { levels, opaque }
+    }
+
+    /// Light at a position relative to the chunk's origin, right up to a voxel past its
+    /// borders, dark farther out
+    pub fn at(&self, p: IVec3) -> LightColor {
+        if !self.levels.is_empty() && inside(p) {
+            self.levels[cell(p)]
+        } else {
+            [0; 3]
+        }
+    }
+
+    /// Hash of the light of the chunk and of the voxels around it its mesh is lit by, as far
+    /// as the light bounces from, to tell whether its light changed since it was meshed. 0
+    /// when it's all dark
+    pub fn fingerprint(&self) -> u64 {
+        if self.levels.is_empty() {
+            return 0;
+        }
+        let mut hasher = DefaultHasher::new();
+        let mut lit = false;
+        let range = -1 - BOUNCE_REACH..=CHUNK_SIZE as i32 + BOUNCE_REACH;
+        for x in range.clone() {
+            for y in range.clone() {
+                for z in range.clone() {
+                    let level = self.levels[cell(IVec3::new(x, y, z))];
+                    lit |= level != [0; 3];
+                    level.hash(&mut hasher);
+                }
+            }
+        }
+        if lit {
+            hasher.finish()
+        } else {
+            0
+        }
+    }
+
+    /// Smooth light at a corner of a face: the average of the voxels that touch the corner
+    /// on the side the face looks at, `front` being the one right in front of it and
+    /// `corner` which corner of the block's cell, 0 or 1 on each axis. Opaque voxels are
+    /// left out, and so is the diagonal one when both voxels beside it are opaque, like
+    /// Minecraft does
+    pub fn corner(&self, front: IVec3, normal: IVec3, corner: IVec3) -> LightColor {
+        if self.levels.is_empty() {
+            return [0; 3];
+        }
+        let open = |p: IVec3| inside(p) && !self.opaque[cell(p)];
+        // a step along each axis of the face, towards the corner
+        let mut steps = [0, 1, 2]
+            .into_iter()
+            .filter(|&axis| normal[axis] == 0)
+            .map(|axis| {
+                let mut step = IVec3::ZERO;
+                step[axis] = if corner[axis] == 1 { 1 } else { -1 };
+                step
+            });
+        let (u, v) = (steps.next().unwrap(), steps.next().unwrap());
+        let samples = [front, front + u, front + v, front + u + v];
+        // the diagonal is hidden behind two opaque voxels
+        let seen = 3 + (open(front + u) || open(front + v)) as usize;
+
+        let mut sum = [0u32; 3];
+        let mut count = 0;
+        for &p in samples[..seen].iter().filter(|&&p| open(p)) {
+            for (total, level) in sum.iter_mut().zip(self.levels[cell(p)]) {
+                *total += level as u32;
+            }
+            count += 1;
+        }
+        if count == 0 {
+            return [0; 3];
+        }
+        sum.map(|total| ((total + count / 2) / count) as u8)
+    }
+
+    /// Light bounced once onto a face by the surfaces it looks at, `front` being the voxel
+    /// right in front of it. Rays go out straight and tilted along each axis of the face, a
+    /// coarse cone, and each one that hits an opaque voxel within `BOUNCE_REACH` brings back
+    /// the light falling on that surface, less the farther it is
+    pub fn bounce(&self, front: IVec3, normal: IVec3) -> LightColor {
+        if self.levels.is_empty() {
+            return [0; 3];
+        }
+        let mut rays = vec![normal];
+        for axis in (0..3).filter(|&axis| normal[axis] == 0) {
+            let mut tilt = IVec3::ZERO;
+            tilt[axis] = 1;
+            rays.extend([normal + tilt, normal - tilt]);
+        }
+
+        let mut sum = [0.0; 3];
+        for &ray in &rays {
+            let mut p = front;
+            for distance in 1..=BOUNCE_REACH {
+                let next = p + ray;
+                if !inside(next) {
+                    break;
+                }
+                if self.opaque[cell(next)] {
+                    for (total, level) in sum.iter_mut().zip(self.levels[cell(p)]) {
+                        *total += level as f32 / distance as f32;
+                    }
+                    break;
+                }
+                p = next;
+            }
+        }
+        sum.map(|total| (total / rays.len() as f32).round() as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::{ChunkData, ChunkIndex, VoxelLocalIndex, FIRE, LAMP_ON, STONE, WIRE_ON};
+
+    #[test]
+    fn light_fades_and_blends_its_colors() {
+        let mut chunk = ChunkData::air(ChunkIndex { x: 0, y: 0, z: 0 });
+        let (wire, lamp) = (
+            block_properties(WIRE_ON).light,
+            block_properties(LAMP_ON).light,
+        );
+        chunk.set_block(VoxelLocalIndex { x: 5, y: 8, z: 8 }, WIRE_ON);
+        chunk.set_block(VoxelLocalIndex { x: 12, y: 8, z: 8 }, LAMP_ON);
+        chunk.set_block(VoxelLocalIndex { x: 12, y: 8, z: 9 }, STONE);
+
+        let light = ChunkLight::of(&ChunkNeighbourhood::lone(&chunk));
+        assert_eq!(light.at(IVec3::new(12, 8, 8)), lamp);
+        assert_eq!(light.at(IVec3::new(9, 8, 8)), lamp.map(|level| level - 3));
+        // a channel at a time, each from the light it's brightest from
+        let blended = [0, 1, 2].map(|i| wire[i].saturating_sub(1).max(lamp[i].saturating_sub(8)));
+        assert_eq!(light.at(IVec3::new(4, 8, 8)), blended);
+        // nothing gets into the stone
+        assert_eq!(light.at(IVec3::new(12, 8, 9)), [0; 3]);
+        // nothing's loaded around the chunk, the light goes on out of it as through air
+        assert_eq!(light.at(IVec3::new(-1, 8, 8)), [wire[0] - 6, 0, 0]);
+    }
+
+    #[test]
+    fn corners_average_the_voxels_around_them() {
+        let mut chunk = ChunkData::air(ChunkIndex { x: 0, y: 0, z: 0 });
+        let fire = block_properties(FIRE).light;
+        chunk.set_block(VoxelLocalIndex { x: 8, y: 9, z: 8 }, FIRE);
+        chunk.set_block(VoxelLocalIndex { x: 7, y: 9, z: 8 }, STONE);
+        chunk.set_block(VoxelLocalIndex { x: 8, y: 9, z: 7 }, STONE);
+
+        // the top face of the block under the fire
+        let light = ChunkLight::of(&ChunkNeighbourhood::lone(&chunk));
+        let (front, up) = (IVec3::new(8, 9, 8), IVec3::Y);
+        // the fire, two voxels a level dimmer and one two levels dimmer
+        let open = light.corner(front, up, IVec3::new(1, 1, 1));
+        assert_eq!(open, fire.map(|level| level - 1));
+        // only the fire, the stones beside it hide the diagonal
+        let walled = light.corner(front, up, IVec3::new(0, 1, 0));
+        assert_eq!(walled, fire);
+    }
+
+    #[test]
+    fn light_bounces_off_the_surfaces_a_face_looks_at() {
+        let mut chunk = ChunkData::air(ChunkIndex { x: 0, y: 0, z: 0 });
+        let lamp = block_properties(LAMP_ON).light;
+        // a lamp hanging from a ceiling, lighting it from below
+        chunk.set_block(VoxelLocalIndex { x: 6, y: 11, z: 8 }, LAMP_ON);
+        for x in 0..16 {
+            for z in 0..16 {
+                chunk.set_block(VoxelLocalIndex { x, y: 12, z }, STONE);
+            }
+        }
+        let light = ChunkLight::of(&ChunkNeighbourhood::lone(&chunk));
+        // the top of a floor under it gets some of the light off the ceiling back
+        let up = IVec3::Y;
+        let bounced = light.bounce(IVec3::new(8, 9, 8), up);
+        assert!(bounced
+            .iter()
+            .zip(lamp)
+            .any(|(&level, lit)| level > 0 && level < lit));
+        assert!(bounced
+            .iter()
+            .zip(lamp)
+            .all(|(&level, lit)| level < lit || lit == 0));
+        // the ceiling's out of reach of a floor lower down
+        assert_eq!(light.bounce(IVec3::new(8, 1, 8), up), [0; 3]);
+    }
+}
@@ -0,0 +1,100 @@
+//! Snapshots of a chunk along with the chunks around it, so the mesher can
+//! look past the chunk's borders. Light spreads across them: a lamp by the
+//! border lights both chunks, and since each is meshed from a snapshot of the
+//! same voxels, the light on either side of the border comes out the same in
+//! both meshes instead of leaving a seam.
+
+use bevy::prelude::*;
+
+use super::{BlockId, ChunkData, ChunkIndex, VoxelData, AIR, CHUNK_SIZE};
+
+type Voxels = [[[BlockId; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+
+/// Voxels of a chunk and of the 26 chunks around it, as they were when it was taken
+#[derive(Clone)]
+pub struct ChunkNeighbourhood {
+    chunks: Vec<Option<Box<Voxels>>>, // by offset from the chunk, x then y then z
+}
+
+/// Which of the 27 chunks an offset of -1, 0 or 1 on each axis is
+fn slot(offset: IVec3) -> usize {
+    let offset = offset + IVec3::ONE;
+    (offset.x * 9 + offset.y * 3 + offset.z) as usize
+}
+
+impl ChunkNeighbourhood {
+    pub fn snapshot(voxel_data: &VoxelData, index: ChunkIndex) -> Self {
+        let mut chunks = vec![None; 27];
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let neighbour = ChunkIndex {
+                        x: index.x + x,
+                        y: index.y + y,
+                        z: index.z + z,
+                    };
+                    chunks[slot(IVec3::new(x, y, z))] = voxel_data
+                        .chunks
+                        .get(&neighbour)
+                        .map(|chunk| Box::new(chunk.voxels));
+                }
+            }
+        }
+        ChunkNeighbourhood { chunks }
+    }
+
+    /// A chunk on its own, as if nothing was loaded around it
+    pub fn lone(chunk: &ChunkData) -> Self {
+        let mut chunks = vec![None; 27];
+        chunks[slot(IVec3::ZERO)] = Some(Box::new(chunk.voxels));
+        ChunkNeighbourhood { chunks }
+    }
+
+    /// Block at a position relative to the chunk's origin, up to a chunk past its borders.
+    /// Air where no chunk was loaded
+    pub fn block(&self, p: IVec3) -> BlockId {
+        let size = CHUNK_SIZE as i32;
+        let offset = IVec3::new(
+            p.x.div_euclid(size),
+            p.y.div_euclid(size),
+            p.z.div_euclid(size),
+        );
+        if offset.abs().max_element() > 1 {
+            return AIR;
+        }
+        let local = p - offset * size;
+        self.chunks[slot(offset)].as_ref().map_or(AIR, |voxels| {
+            voxels[local.x as usize][local.y as usize][local.z as usize]
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::{block_properties, ChunkLight, VoxelPos, LAMP_ON};
+
+    #[test]
+    fn light_matches_on_both_sides_of_a_border() {
+        let (left, right) = (
+            ChunkIndex { x: 0, y: 0, z: 0 },
+            ChunkIndex { x: 1, y: 0, z: 0 },
+        );
+        let mut voxel_data = VoxelData::air([left, right]);
+        voxel_data.set_block(VoxelPos::new(15, 8, 8), LAMP_ON);
+        let lamp = block_properties(LAMP_ON).light;
+
+        let left_light = ChunkLight::of(&ChunkNeighbourhood::snapshot(&voxel_data, left));
+        let right_light = ChunkLight::of(&ChunkNeighbourhood::snapshot(&voxel_data, right));
+        // the first voxel of the right chunk, seen from either chunk
+        let beside = lamp.map(|level| level - 1);
+        assert_eq!(left_light.at(IVec3::new(16, 8, 8)), beside);
+        assert_eq!(right_light.at(IVec3::new(0, 8, 8)), beside);
+        // and the light of the corners of faces on the border
+        let (front, normal, corner) = (IVec3::new(0, 9, 8), IVec3::Y, IVec3::ZERO);
+        assert_eq!(
+            right_light.corner(front, normal, corner),
+            left_light.corner(front + IVec3::X * 16, normal, corner)
+        );
+    }
+}
@@ -0,0 +1,179 @@
+//! Cave culling, like Minecraft's: which faces of a chunk see each other through its
+//! see-through voxels, and the chunks the camera can look into by walking from chunk to
+//! chunk through those faces. Chunks it can't, like caves deep under the ground, are left
+//! out of their column's mesh.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use bevy::prelude::*;
+
+use super::{
+    block_properties, last_block, BlockId, BlockShape, ChunkData, ChunkIndex, AIR, CHUNK_SIZE,
+};
+
+/// Directions of the faces of a chunk, in the order of `NORMALS`
+const FACES: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::Y,
+    IVec3::Z,
+    IVec3::NEG_X,
+    IVec3::NEG_Y,
+    IVec3::NEG_Z,
+];
+
+fn opposite(face: usize) -> usize {
+    (face + 3) % 6
+}
+
+/// Which faces of a chunk see each other, a bit per pair of faces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkConnectivity(u64);
+
+impl ChunkConnectivity {
+    const OPEN: ChunkConnectivity = ChunkConnectivity(u64::MAX); // every face sees every other
+
+    pub fn of(chunk: &ChunkData) -> Self {
+        // by block id, whether the block hides what's behind it
+        let blocks_sight: Vec<bool> = (0..=last_block().0)
+            .map(|block| {
+                let block = BlockId(block);
+                let properties = block_properties(block);
+                block != AIR && !properties.transparent && properties.shape == BlockShape::Cube
+            })
+            .collect();
+        let solid = |p: IVec3| {
+            let block = chunk.voxels[p.x as usize][p.y as usize][p.z as usize];
+            blocks_sight.get(block.0 as usize).copied().unwrap_or(true)
+        };
+        let cells = || {
+            (0..CHUNK_SIZE as i32).flat_map(|x| {
+                (0..CHUNK_SIZE as i32)
+                    .flat_map(move |y| (0..CHUNK_SIZE as i32).map(move |z| IVec3::new(x, y, z)))
+            })
+        };
+        if !cells().any(solid) {
+            return Self::OPEN;
+        }
+
+        // flood fill each pocket of see-through voxels, its faces all see each other
+        let inside =
+            |p: IVec3| p.cmpge(IVec3::ZERO).all() && p.cmplt(IVec3::splat(CHUNK_SIZE as i32)).all();
+        let mut visited = [[[false; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+        let mut connectivity = 0;
+        let mut stack = Vec::new();
+        for start in cells() {
+            if visited[start.x as usize][start.y as usize][start.z as usize] || solid(start) {
+                continue;
+            }
+            visited[start.x as usize][start.y as usize][start.z as usize] = true;
+            stack.push(start);
+            let mut faces = 0u64;
+            while let Some(p) = stack.pop() {
+                for (face, step) in FACES.iter().enumerate() {
+                    let next = p + *step;
+                    if !inside(next) {
+                        faces |= 1 << face;
+                        continue;
+                    }
+                    let seen = &mut visited[next.x as usize][next.y as usize][next.z as usize];
+                    if !*seen && !solid(next) {
+                        *seen = true;
+                        stack.push(next);
+                    }
+                }
+            }
+            for from in 0..6 {
+                if faces & 1 << from != 0 {
+                    connectivity |= faces << (from * 6);
+                }
+            }
+        }
+        ChunkConnectivity(connectivity)
+    }
+
+    /// Whether looking in through face `from` can see out through face `to`
+    pub fn connects(self, from: usize, to: usize) -> bool {
+        self.0 & 1 << (from * 6 + to) != 0
+    }
+}
+
+/// Chunks the camera in chunk `camera` can see into: walking out from it through the
+/// faces each chunk connects, never back towards the camera. Only the chunks in
+/// `connectivity` are walked into
+pub fn visible_chunks(
+    camera: ChunkIndex,
+    connectivity: &HashMap<ChunkIndex, ChunkConnectivity>,
+) -> HashSet<ChunkIndex> {
+    let mut visible = HashSet::from([camera]);
+    // chunk, the face it was entered through and the directions walked to get there
+    let mut queue = VecDeque::from([(camera, None, 0u8)]);
+    while let Some((index, entered, walked)) = queue.pop_front() {
+        let chunk = connectivity
+            .get(&index)
+            .copied()
+            .unwrap_or(ChunkConnectivity::OPEN);
+        for (face, step) in FACES.iter().enumerate() {
+            if walked & 1 << opposite(face) != 0
+                || entered.is_some_and(|entered| !chunk.connects(entered, face))
+            {
+                continue;
+            }
+            let next = ChunkIndex {
+                x: index.x + step.x,
+                y: index.y + step.y,
+                z: index.z + step.z,
+            };
+            if !connectivity.contains_key(&next) || !visible.insert(next) {
+                continue;
+            }
+            queue.push_back((next, Some(opposite(face)), walked | 1 << face));
+        }
+    }
+    visible
+}
+
+/// When the hidden chunks were last worked out
+#[derive(Resource, Default)]
+pub struct ChunkOcclusion {
+    pub camera_chunk: Option<ChunkIndex>,
+    pub stale: bool, // chunks were remeshed since
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::STONE;
+
+    #[test]
+    fn solid_chunks_hide_what_is_behind_them() {
+        let index = |x| ChunkIndex { x, y: 0, z: 0 };
+        let mut tunnel = ChunkData {
+            index: index(1),
+            ..Default::default()
+        };
+        for column in tunnel.voxels.iter_mut() {
+            for (y, row) in column.iter_mut().enumerate() {
+                for (z, voxel) in row.iter_mut().enumerate() {
+                    *voxel = if y == 8 && z == 8 { AIR } else { STONE };
+                }
+            }
+        }
+        let tunnel = ChunkConnectivity::of(&tunnel);
+        assert!(tunnel.connects(0, 3));
+        assert!(!tunnel.connects(3, 1));
+
+        let solid = ChunkConnectivity::of(&ChunkData {
+            voxels: [[[STONE; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
+            ..Default::default()
+        });
+        let connectivity = HashMap::from([
+            (index(0), ChunkConnectivity::OPEN),
+            (index(1), tunnel),
+            (index(2), solid),
+            (index(3), ChunkConnectivity::OPEN),
+        ]);
+        let visible = visible_chunks(index(0), &connectivity);
+        assert!(visible.contains(&index(2)));
+        assert!(!visible.contains(&index(3)));
+    }
+}
@@ -0,0 +1,146 @@
+use bevy::prelude::*;
+use smooth_bevy_cameras::controllers::fps::FpsCameraController;
+
+use crate::input::{InputAction, PlayerInput};
+
+#[derive(Debug, Clone)]
+pub struct Waypoint {
+    pub name: String,
+    pub position: Vec3,
+    pub owner: Option<String>, // player who shared it, none for our own waypoints
+}
+
+#[derive(Resource, Default)]
+pub struct Waypoints {
+    pub waypoints: Vec<Waypoint>,
+    pub compass_target: Option<usize>, // index into `waypoints` the compass points at
+}
+
+/// A waypoint we share with the other players, to be broadcast by the network layer
+#[derive(Event, Debug, Clone)]
+pub struct WaypointShared {
+    pub waypoint: Waypoint,
+}
+
+/// A waypoint shared by another player, sent in by the network layer
+#[derive(Event, Debug, Clone)]
+pub struct WaypointReceived {
+    pub player: String,
+    pub waypoint: Waypoint,
+}
+
+#[derive(Component)]
+pub struct CompassText;
+
+pub fn setup_compass(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 20.0,
+                color: Color::GOLD,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(5.0),
+            right: Val::Px(5.0),
+            ..default()
+        }),
+        CompassText,
+    ));
+}
+
+/// B drops a waypoint at the camera, N cycles the compass target, V shares the compass target
+pub fn waypoint_input(
+    player_input: Res<PlayerInput>,
+    fps_camera_query: Query<&GlobalTransform, With<FpsCameraController>>,
+    mut waypoints: ResMut<Waypoints>,
+    mut shared_events: EventWriter<WaypointShared>,
+) {
+    let actions = &player_input.actions;
+    if actions.just_pressed(InputAction::DropWaypoint) {
+        let Ok(transform) = fps_camera_query.get_single() else {
+            return;
+        };
+        let name = format!("Waypoint {}", waypoints.waypoints.len() + 1);
+        waypoints.waypoints.push(Waypoint {
+            name,
+            position: transform.translation(),
+            owner: None,
+        });
+        waypoints.compass_target = Some(waypoints.waypoints.len() - 1);
+    }
+
+    if actions.just_pressed(InputAction::NextWaypoint) && !waypoints.waypoints.is_empty() {
+        waypoints.compass_target = Some(match waypoints.compass_target {
+            Some(target) => (target + 1) % waypoints.waypoints.len(),
+            None => 0,
+        });
+    }
+
+    if actions.just_pressed(InputAction::ShareWaypoint) {
+        if let Some(waypoint) = waypoints
+            .compass_target
+            .and_then(|target| waypoints.waypoints.get(target))
+        {
+            shared_events.send(WaypointShared {
+                waypoint: waypoint.clone(),
+            });
+        }
+    }
+}
+
+pub fn receive_waypoints(
+    mut received_events: EventReader<WaypointReceived>,
+    mut waypoints: ResMut<Waypoints>,
+) {
+    for event in received_events.iter() {
+        waypoints.waypoints.push(Waypoint {
+            owner: Some(event.player.clone()),
+            ..event.waypoint.clone()
+        });
+    }
+}
+
+/// Show the direction and distance to the compass target
+pub fn update_compass(
+    waypoints: Res<Waypoints>,
+    fps_camera_query: Query<&GlobalTransform, With<FpsCameraController>>,
+    mut text_query: Query<&mut Text, With<CompassText>>,
+) {
+    let Ok(transform) = fps_camera_query.get_single() else {
+        return;
+    };
+    let Ok(mut text) = text_query.get_single_mut() else {
+        return;
+    };
+
+    let Some(waypoint) = waypoints
+        .compass_target
+        .and_then(|target| waypoints.waypoints.get(target))
+    else {
+        text.sections[0].value.clear();
+        return;
+    };
+
+    let offset = waypoint.position - transform.translation();
+    let forward = transform.forward();
+    // angle of the target relative to where the camera looks, clockwise from straight ahead
+    let angle = offset.z.atan2(offset.x) - forward.z.atan2(forward.x);
+    let angle = (angle.to_degrees() + 360.0) % 360.0;
+    let arrow = ["^", "/", ">", "\\", "v", "/", "<", "\\"][((angle + 22.5) / 45.0) as usize % 8];
+
+    let owner = match &waypoint.owner {
+        Some(player) => format!(" ({})", player),
+        None => String::new(),
+    };
+    text.sections[0].value = format!(
+        "{} {}{} {:.0}m",
+        arrow,
+        waypoint.name,
+        owner,
+        offset.length()
+    );
+}
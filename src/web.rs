@@ -0,0 +1,75 @@
+//! Running in the browser. A page can only lock the cursor right after a
+//! click, and Esc unlocks it without the game ever seeing the key: so a click
+//! on the canvas starts playing, and losing the lock opens the pause menu.
+//! Files go to local storage, see `storage`, and task pools run everything on
+//! the one thread a page has, chunk generation included.
+
+use bevy::prelude::*;
+
+/// Adds the browser specific systems, nothing on desktop
+#[derive(Default)]
+pub struct WebPlugin;
+
+impl Plugin for WebPlugin {
+    fn build(&self, _app: &mut App) {
+        #[cfg(target_arch = "wasm32")]
+        _app.add_systems(Update, pointer_lock::pointer_lock);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod pointer_lock {
+    use bevy::{prelude::*, window::PrimaryWindow};
+    use bevy_egui::EguiContexts;
+    use smooth_bevy_cameras::controllers::fps::FpsCameraController;
+
+    use crate::{apply_input_mode, console::Console, menu::PauseMenu, MouseSettings};
+
+    fn is_pointer_locked() -> bool {
+        web_sys::window()
+            .and_then(|window| window.document())
+            .and_then(|document| document.pointer_lock_element())
+            .is_some()
+    }
+
+    pub fn pointer_lock(
+        mut contexts: EguiContexts,
+        mouse_input: Res<Input<MouseButton>>,
+        console: Res<Console>,
+        mut pause_menu: ResMut<PauseMenu>,
+        mut ms: ResMut<MouseSettings>,
+        mut primary_query: Query<&mut Window, With<PrimaryWindow>>,
+        mut fps_camera_query: Query<&mut FpsCameraController>,
+        mut granted: Local<bool>, // the browser locked the cursor since the game asked for it
+    ) {
+        let locked = is_pointer_locked();
+        if ms.ui_mode {
+            *granted = false;
+            let play = mouse_input.just_pressed(MouseButton::Left)
+                && !pause_menu.open
+                && !console.open
+                && !contexts.ctx_mut().is_pointer_over_area();
+            if !play {
+                return;
+            }
+            ms.ui_mode = false;
+        } else if locked {
+            *granted = true;
+            return;
+        } else if *granted {
+            // unlocked by the browser, most likely Esc
+            *granted = false;
+            pause_menu.open = true;
+            ms.ui_mode = true;
+        } else {
+            return;
+        }
+        if let Ok(mut fps_camera) = fps_camera_query.get_single_mut() {
+            apply_input_mode(
+                &ms,
+                &mut fps_camera,
+                primary_query.get_single_mut().ok().as_deref_mut(),
+            );
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! The loaded world as systems see it: blocks, states and whole chunks read
+//! and written through [`VoxelWorld`], which queues the remeshing and
+//! relighting each write needs, so a caller can't forget it.
+//!
+//! The chunk data itself stays in [`VoxelData`], whose chunk map is private to
+//! the voxel module.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    block_entity::BlockEntity,
+    voxel::{
+        BlockId, BlockState, ChunkData, ChunkIndex, ChunkMeshesUpdateQueue, VoxelData, VoxelPos,
+    },
+};
+
+/// Read and edit the loaded world from a system, without reaching into the chunk maps
+#[derive(SystemParam)]
+pub struct VoxelWorld<'w> {
+    voxel_data: ResMut<'w, VoxelData>,
+    chunk_meshes_update_queue: ResMut<'w, ChunkMeshesUpdateQueue>,
+}
+
+impl VoxelWorld<'_> {
+    /// The chunk data, for what reads more than single blocks
+    pub fn data(&self) -> &VoxelData {
+        &self.voxel_data
+    }
+
+    /// Block at a world position, air where the chunk isn't loaded
+    pub fn get_block(&self, position: VoxelPos) -> BlockId {
+        self.voxel_data.get_block(position)
+    }
+
+    /// Set a block and remesh the chunks whose light it changes, returns false where the chunk
+    /// isn't loaded
+    pub fn set_block(&mut self, position: VoxelPos, block: BlockId) -> bool {
+        self.replace_block(position, block).is_some()
+    }
+
+    /// Set a block like `set_block` and return the block entity of the block it replaced,
+    /// None where the chunk isn't loaded
+    pub fn replace_block(
+        &mut self,
+        position: VoxelPos,
+        block: BlockId,
+    ) -> Option<Option<BlockEntity>> {
+        let removed = self.voxel_data.replace_block(position, block)?;
+        self.chunk_meshes_update_queue
+            .queue_block(&self.voxel_data, position);
+        Some(removed)
+    }
+
+    /// State of the voxel at a world position, 0 where the chunk isn't loaded
+    pub fn get_state(&self, position: VoxelPos) -> BlockState {
+        self.voxel_data.get_state(position)
+    }
+
+    /// Set the state of a voxel and remesh its chunk, returns false where the chunk isn't loaded
+    pub fn set_state(&mut self, position: VoxelPos, state: BlockState) -> bool {
+        if !self.voxel_data.set_state(position, state) {
+            return false;
+        }
+        self.chunk_meshes_update_queue.queue_chunk(position.chunk());
+        true
+    }
+
+    /// Block entity at a world position, if its block has one and the chunk is loaded
+    pub fn get_entity(&self, position: VoxelPos) -> Option<&BlockEntity> {
+        self.voxel_data.get_entity(position)
+    }
+
+    /// Put a block entity at a world position, see `VoxelData::set_entity`
+    pub fn set_entity(&mut self, position: VoxelPos, entity: Option<BlockEntity>) -> bool {
+        self.voxel_data.set_entity(position, entity)
+    }
+
+    pub fn is_loaded(&self, index: ChunkIndex) -> bool {
+        self.voxel_data.is_loaded(index)
+    }
+
+    pub fn chunk(&self, index: ChunkIndex) -> Option<&ChunkData> {
+        self.voxel_data.chunk(index)
+    }
+
+    pub fn iter_chunks(&self) -> impl Iterator<Item = &ChunkData> {
+        self.voxel_data.iter_chunks()
+    }
+
+    /// Add a chunk come back from the store or the generator and mesh it with its neighbours,
+    /// `modified` if it's edited and not saved yet
+    pub fn load_chunk(&mut self, chunk: ChunkData, modified: bool) {
+        let index = chunk.index;
+        self.voxel_data.insert_chunk(chunk);
+        if modified {
+            self.voxel_data.mark_modified(index);
+        }
+        self.chunk_meshes_update_queue
+            .queue_loaded(&self.voxel_data, index);
+    }
+
+    /// Take a chunk out of the world as it unloads, with whether it's edited, see
+    /// `VoxelData::remove_chunk`
+    pub fn remove_chunk(&mut self, index: ChunkIndex) -> Option<(ChunkData, bool)> {
+        self.voxel_data.remove_chunk(index)
+    }
+
+    /// Remesh a chunk whose column changed around it
+    pub fn remesh_chunk(&mut self, index: ChunkIndex) {
+        self.chunk_meshes_update_queue.queue_chunk(index);
+    }
+
+    /// Put back a loaded chunk as it was at an earlier time and remesh it, see
+    /// `VoxelData::restore_chunk`
+    pub fn restore_chunk(&mut self, chunk: ChunkData) {
+        let index = chunk.index;
+        self.voxel_data.restore_chunk(chunk);
+        self.chunk_meshes_update_queue.queue_chunk(index);
+    }
+
+    /// Unload the chunks `keep` returns false for, see `VoxelData::retain_chunks`. The
+    /// generator fills their place again
+    pub fn retain_chunks(&mut self, keep: impl FnMut(ChunkIndex, &ChunkData, bool) -> bool) {
+        self.voxel_data.retain_chunks(keep);
+    }
+
+    /// Leave a chunk's edits out of the save, they're lost when it unloads
+    pub fn forget_edits(&mut self, index: ChunkIndex) {
+        self.voxel_data.forget_edits(index);
+    }
+
+    pub fn is_modified(&self, index: ChunkIndex) -> bool {
+        self.voxel_data.is_modified(index)
+    }
+
+    /// Remesh every loaded column, after what blocks look like changed
+    pub fn remesh_all(&mut self) {
+        self.chunk_meshes_update_queue.remesh_all(&self.voxel_data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+
+    use super::*;
+    use crate::voxel::{AIR, STONE, STONE_STAIRS};
+
+    const HOME: ChunkIndex = ChunkIndex { x: 0, y: 0, z: 0 };
+    const AWAY: ChunkIndex = ChunkIndex { x: 5, y: 0, z: 0 };
+
+    fn world_with(indices: &[ChunkIndex]) -> World {
+        let mut world = World::new();
+        world.insert_resource(VoxelData::air(indices.iter().copied()));
+        world.init_resource::<ChunkMeshesUpdateQueue>();
+        world
+    }
+
+    #[test]
+    fn writes_queue_their_chunk_for_remeshing() {
+        let mut world = world_with(&[HOME]);
+        let mut state = SystemState::<VoxelWorld>::new(&mut world);
+        let mut voxel_world = state.get_mut(&mut world);
+        let position = VoxelPos::new(3, 4, 5);
+        assert!(voxel_world.set_block(position, STONE_STAIRS));
+        assert!(voxel_world.set_state(position, 2));
+        assert_eq!(voxel_world.get_block(position), STONE_STAIRS);
+        assert_eq!(voxel_world.get_state(position), 2);
+        assert!(voxel_world.is_modified(HOME));
+
+        let queue = world.resource::<ChunkMeshesUpdateQueue>();
+        assert!(queue.chunks.contains(&HOME));
+    }
+
+    #[test]
+    fn writes_outside_the_loaded_world_are_dropped() {
+        let mut world = world_with(&[HOME]);
+        let mut state = SystemState::<VoxelWorld>::new(&mut world);
+        let mut voxel_world = state.get_mut(&mut world);
+        let away = VoxelPos(AWAY.origin());
+        assert!(!voxel_world.is_loaded(AWAY));
+        assert!(!voxel_world.set_block(away, STONE));
+        assert!(!voxel_world.set_state(away, 1));
+        assert_eq!(voxel_world.get_block(away), AIR);
+        assert!(world.resource::<ChunkMeshesUpdateQueue>().is_empty());
+    }
+
+    #[test]
+    fn unloaded_chunks_lose_their_edits() {
+        let mut world = world_with(&[HOME, AWAY]);
+        let mut state = SystemState::<VoxelWorld>::new(&mut world);
+        let mut voxel_world = state.get_mut(&mut world);
+        voxel_world.set_block(VoxelPos(AWAY.origin()), STONE);
+        voxel_world.retain_chunks(|index, _, _| index == HOME);
+        assert_eq!(voxel_world.iter_chunks().count(), 1);
+        assert!(voxel_world.chunk(AWAY).is_none());
+        assert!(!voxel_world.is_modified(AWAY));
+    }
+}
@@ -0,0 +1,105 @@
+//! World generation snapshots: chunks generated from a fixed seed must keep the voxels they
+//! had when the snapshots were recorded, so existing worlds look the same after a refactor.
+//!
+//! Run with `UPDATE_SNAPSHOTS=1` to record them again after an intended change.
+
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use mcrs::{ChunkData, ChunkIndex, WorldGenSettings, CHUNK_SIZE};
+
+const SEED: u32 = 20230917;
+
+fn snapshot_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots/worldgen.txt")
+}
+
+/// FNV-1a over the voxels, stable across platforms and compiler versions
+fn voxel_hash(chunk: &ChunkData) -> u64 {
+    chunk
+        .voxels
+        .iter()
+        .flatten()
+        .flatten()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, &voxel| {
+            (hash ^ voxel.0 as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+/// Chunks around the origin, on both sides of it and across the surface heights
+fn indices() -> impl Iterator<Item = ChunkIndex> {
+    let surface = 48 / CHUNK_SIZE as i32..=128 / CHUNK_SIZE as i32;
+    (-1..=1).flat_map(move |x| {
+        let surface = surface.clone();
+        (-1..=1).flat_map(move |z| surface.clone().map(move |y| ChunkIndex { x, y, z }))
+    })
+}
+
+/// Voxel hashes by chunk, a line each
+fn snapshot() -> String {
+    let mut hashes = BTreeMap::new();
+    for erosion in [false, true] {
+        let settings = WorldGenSettings {
+            seed: SEED,
+            erosion,
+            ..Default::default()
+        };
+        for index in indices() {
+            let key = (erosion, index.x, index.y, index.z);
+            hashes.insert(key, voxel_hash(&ChunkData::new(index, &settings)));
+        }
+    }
+    hashes
+        .into_iter()
+        .map(|((erosion, x, y, z), hash)| {
+            let erosion = if erosion { "eroded" } else { "plain" };
+            format!("{erosion} {x} {y} {z} {hash:016x}\n")
+        })
+        .collect()
+}
+
+#[test]
+fn generated_chunks_match_the_snapshots() {
+    let path = snapshot_path();
+    let actual = snapshot();
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|err| {
+        panic!(
+            "no world generation snapshots at {} ({err}), record them with UPDATE_SNAPSHOTS=1",
+            path.display()
+        )
+    });
+
+    let changed: Vec<_> = expected
+        .lines()
+        .zip(actual.lines())
+        .filter(|(expected, actual)| expected != actual)
+        .map(|(expected, _)| {
+            expected
+                .rsplit_once(' ')
+                .map_or(expected, |(chunk, _)| chunk)
+        })
+        .collect();
+    assert!(
+        changed.is_empty() && expected.lines().count() == actual.lines().count(),
+        "world generation changed for {} chunks: {changed:?}, \
+         rerun with UPDATE_SNAPSHOTS=1 if that was intended",
+        changed.len()
+    );
+}
+
+#[test]
+fn generation_is_deterministic() {
+    let settings = WorldGenSettings {
+        seed: SEED,
+        ..Default::default()
+    };
+    for index in indices().take(4) {
+        let first = ChunkData::new(index, &settings);
+        let second = ChunkData::new(index, &settings);
+        assert_eq!(voxel_hash(&first), voxel_hash(&second));
+    }
+}